@@ -0,0 +1,154 @@
+//! Running a command on behalf of `mountaineer run`.
+//!
+//! The child stays in our process group, so a Ctrl+C at the terminal already
+//! reaches it. Signals sent to Mountaineer alone (`kill <pid>`, launchd
+//! stopping a job) are caught and passed on to the child instead of killing
+//! us, so the lease held for the child is released when it actually exits.
+
+use std::io;
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use nix::libc::{self, c_int};
+
+/// Signals passed on to the child.
+const FORWARDED: [c_int; 6] = [
+    libc::SIGHUP,
+    libc::SIGINT,
+    libc::SIGQUIT,
+    libc::SIGTERM,
+    libc::SIGUSR1,
+    libc::SIGUSR2,
+];
+
+/// How often the child is checked for exit (and `tick` called).
+const POLL: Duration = Duration::from_millis(100);
+
+/// Bit `n` set when signal `n` arrived and has not been forwarded yet.
+static PENDING: AtomicU32 = AtomicU32::new(0);
+
+extern "C" fn remember_signal(signal: c_int) {
+    PENDING.fetch_or(1 << signal, Ordering::SeqCst);
+}
+
+/// Spawn `command`, forward signals to it until it exits, and call `tick`
+/// about every [`POLL`] meanwhile. Signal handlers are restored afterwards.
+pub fn run_forwarding_signals(
+    command: &mut Command,
+    mut tick: impl FnMut(),
+) -> io::Result<ExitStatus> {
+    // Caught from before the spawn, so a signal in between is forwarded
+    // once the child exists instead of killing us and orphaning it.
+    PENDING.store(0, Ordering::SeqCst);
+    let previous = install_handlers();
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            restore_handlers(previous);
+            return Err(e);
+        }
+    };
+    let pid = child.id() as libc::pid_t;
+
+    let status = loop {
+        let pending = PENDING.swap(0, Ordering::SeqCst);
+        for signal in FORWARDED {
+            // SIGINT from the terminal went to the whole foreground group,
+            // child included; only forward one sent to us directly.
+            if pending & (1 << signal) != 0 && !(signal == libc::SIGINT && in_terminal_foreground())
+            {
+                log::info!("run: forwarding signal {} to pid {}", signal, pid);
+                // SAFETY: plain syscall; `pid` is our child, not yet reaped.
+                unsafe { libc::kill(pid, signal) };
+            }
+        }
+        match child.try_wait() {
+            Ok(Some(status)) => break Ok(status),
+            Ok(None) => {
+                tick();
+                std::thread::sleep(POLL);
+            }
+            Err(e) => break Err(e),
+        }
+    };
+
+    restore_handlers(previous);
+    status
+}
+
+/// Catch every [`FORWARDED`] signal; returns the handlers they replaced.
+fn install_handlers() -> Vec<libc::sighandler_t> {
+    FORWARDED
+        .iter()
+        .map(|&signal| {
+            // SAFETY: `remember_signal` only touches an atomic, which is
+            // async-signal-safe.
+            unsafe {
+                libc::signal(
+                    signal,
+                    remember_signal as extern "C" fn(c_int) as libc::sighandler_t,
+                )
+            }
+        })
+        .collect()
+}
+
+fn restore_handlers(previous: Vec<libc::sighandler_t>) {
+    for (signal, handler) in FORWARDED.iter().zip(previous) {
+        // SAFETY: `handler` is what `signal` returned for this signal.
+        unsafe { libc::signal(*signal, handler) };
+    }
+}
+
+/// Shell convention: the exit code, or 128 + signal for a killed child.
+pub fn exit_code(status: ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+
+    match (status.code(), status.signal()) {
+        (Some(code), _) => code,
+        (None, Some(signal)) => 128 + signal,
+        (None, None) => 1,
+    }
+}
+
+fn in_terminal_foreground() -> bool {
+    // SAFETY: plain syscall on a descriptor number; no memory is passed.
+    let foreground = unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) };
+    // SAFETY: plain syscall without arguments.
+    let group = unsafe { libc::getpgrp() };
+    foreground != -1 && foreground == group
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(script: &str) -> ExitStatus {
+        run_forwarding_signals(Command::new("sh").args(["-c", script]), || {}).unwrap()
+    }
+
+    #[test]
+    fn exit_code_propagates_child_code() {
+        assert_eq!(exit_code(run("exit 0")), 0);
+        assert_eq!(exit_code(run("exit 3")), 3);
+    }
+
+    #[test]
+    fn exit_code_for_killed_child_is_128_plus_signal() {
+        assert_eq!(exit_code(run("kill -TERM $$")), 128 + libc::SIGTERM);
+    }
+
+    #[test]
+    fn tick_runs_while_child_is_alive() {
+        let mut ticks = 0;
+        run_forwarding_signals(Command::new("sh").args(["-c", "sleep 0.3"]), || ticks += 1)
+            .unwrap();
+        assert!(ticks >= 1);
+    }
+
+    #[test]
+    fn spawn_failure_is_an_error() {
+        assert!(run_forwarding_signals(&mut Command::new("/nonexistent/command"), || {}).is_err());
+    }
+}
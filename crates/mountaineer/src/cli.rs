@@ -1,4 +1,6 @@
-use clap::{Args, Parser, Subcommand};
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 use crate::config::Backend;
 
@@ -36,11 +38,17 @@ pub enum Command {
     Switch {
         #[arg(long)]
         share: String,
-        #[arg(long)]
-        to: Backend,
+        #[arg(long, required_unless_present = "cancel")]
+        to: Option<Backend>,
         /// Force switch even if files are open on the current mount
         #[arg(long)]
         force: bool,
+        /// Wait up to this long (e.g. 30s, 10m, 1h) for open files to close
+        #[arg(long, value_parser = parse_duration, conflicts_with = "force")]
+        drain: Option<Duration>,
+        /// Cancel a pending drained switch
+        #[arg(long, conflicts_with_all = ["to", "force", "drain"])]
+        cancel: bool,
     },
     /// Health and mountpoint checks only
     Verify {
@@ -62,6 +70,24 @@ pub enum Command {
         #[arg(long)]
         force: bool,
     },
+    /// Make the running tray or monitor re-read config.toml and reconcile now
+    Reload,
+    /// Find extra mounts of managed shares (e.g. /Volumes/CORE-1) and unmount
+    /// those without open files
+    Dedupe {
+        /// Only report duplicates; do not unmount anything
+        #[arg(long)]
+        dry_run: bool,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show which processes have files open on a share
+    OpenFiles {
+        #[arg(long)]
+        share: String,
+        #[arg(long)]
+        json: bool,
+    },
     /// List folders via stable share path
     Folders {
         #[arg(long)]
@@ -84,6 +110,55 @@ pub enum Command {
         #[command(subcommand)]
         command: ConfigCommand,
     },
+    /// Store, check or delete SMB passwords in the configured credential store
+    Credentials {
+        #[command(subcommand)]
+        command: CredentialsCommand,
+    },
+    /// Hold a share's current mount against backend switches
+    Lease {
+        #[command(subcommand)]
+        command: LeaseCommand,
+    },
+    /// Run a command once a share is mounted, holding a lease so the share
+    /// is not switched while it runs
+    Run {
+        #[arg(long)]
+        share: String,
+        /// Wait until the share is on this backend
+        #[arg(long)]
+        backend: Option<Backend>,
+        /// Give up if the share is not ready within this long
+        #[arg(long, value_parser = parse_duration, default_value = "60s")]
+        timeout: Duration,
+        /// Command and arguments, after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Block until a share (or every favorite) reaches a state. Exits 0 when
+    /// it does, 124 on timeout and 3 for an unknown share
+    Wait {
+        #[arg(long, required_unless_present = "all")]
+        share: Option<String>,
+        #[arg(long, conflicts_with = "share")]
+        all: bool,
+        /// With `--state ready`, also require this active backend
+        #[arg(long)]
+        backend: Option<Backend>,
+        #[arg(long, value_enum, default_value = "ready")]
+        state: WaitState,
+        #[arg(long, value_parser = parse_duration, default_value = "60s")]
+        timeout: Duration,
+        /// Only watch status; do not mount (the tray or `monitor` does)
+        #[arg(long)]
+        observe: bool,
+    },
+    /// Re-enter the password for a share after an authentication failure
+    /// and resume automatic mounting
+    Reauth {
+        #[arg(long)]
+        share: String,
+    },
     /// Install LaunchAgent to start Mountaineer at login
     Install,
     /// Remove LaunchAgent
@@ -94,7 +169,8 @@ pub enum Command {
 pub enum ConfigCommand {
     /// Set a configuration value
     Set {
-        /// Configuration key (lsof-recheck, auto-failback, check-interval, connect-timeout)
+        /// Configuration key (lsof-recheck, auto-failback, check-interval, connect-timeout,
+        /// reclaim-foreign-mounts, credential-store, on-share-removed)
         key: String,
         /// Configuration value (on/off for toggles, number for intervals)
         value: String,
@@ -103,6 +179,65 @@ pub enum ConfigCommand {
     Show,
 }
 
+/// Passwords are always prompted for (or read from stdin), never taken as
+/// arguments, so they do not end up in shell history or the process list.
+#[derive(Debug, Subcommand)]
+pub enum CredentialsCommand {
+    /// Save the password for a share's servers
+    Set {
+        #[arg(long)]
+        share: String,
+    },
+    /// Check that each server accepts the stored password
+    Test {
+        #[arg(long)]
+        share: String,
+    },
+    /// Delete the stored password for a share's servers
+    Remove {
+        #[arg(long)]
+        share: String,
+    },
+}
+
+/// Condition `wait` blocks on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum WaitState {
+    /// Mounted and responding
+    Ready,
+    /// Not mounted on either backend
+    Unmounted,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum LeaseCommand {
+    /// Take a lease and print its token
+    Acquire {
+        #[arg(long)]
+        share: String,
+        /// How long the lease lasts unless released (e.g. 30m, 2h)
+        #[arg(long, value_parser = parse_duration)]
+        ttl: Duration,
+        /// Who holds the lease, shown in status
+        #[arg(long)]
+        holder: Option<String>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Release a lease before it expires
+    Release {
+        #[arg(long)]
+        token: String,
+    },
+    /// List live leases
+    List {
+        #[arg(long)]
+        share: Option<String>,
+        #[arg(long)]
+        json: bool,
+    },
+}
+
 #[derive(Debug, Clone, Args)]
 pub struct MultiShareTarget {
     #[arg(long, conflicts_with = "share")]
@@ -168,6 +303,71 @@ pub enum FavoritesCommand {
         #[arg(long)]
         json: bool,
     },
+    /// List SMB mounts Mountaineer does not manage, or take one over as a
+    /// favorite without unmounting it
+    Adopt {
+        /// Mount point of the mount to adopt (omit to list candidates)
+        #[arg(long)]
+        mount: Option<String>,
+        /// Favorite name (defaults to the remote share name)
+        #[arg(long)]
+        share: Option<String>,
+        /// Thunderbolt host; the existing mount is then the fallback
+        #[arg(long = "tb-host", conflicts_with = "fallback_host")]
+        tb_host: Option<String>,
+        /// Fallback host; the existing mount is then the Thunderbolt one
+        #[arg(long = "fallback-host")]
+        fallback_host: Option<String>,
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Parse a duration such as `90` (seconds), `30s`, `10m`, `2h`, `1d` or
+/// `1h30m`.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    if let Ok(secs) = input.parse::<u64>() {
+        return positive(Duration::from_secs(secs), input);
+    }
+    let invalid = || {
+        format!(
+            "invalid duration '{}' (expected e.g. 30s, 10m, 1h30m)",
+            input
+        )
+    };
+    let mut total = 0u64;
+    let mut digits = String::new();
+    for c in input.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            _ => return Err(invalid()),
+        };
+        let n: u64 = digits.parse().map_err(|_| invalid())?;
+        total = n
+            .checked_mul(unit)
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(invalid)?;
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        return Err(invalid());
+    }
+    positive(Duration::from_secs(total), input)
+}
+
+fn positive(duration: Duration, input: &str) -> Result<Duration, String> {
+    if duration.is_zero() {
+        return Err(format!("duration '{}' must be greater than zero", input));
+    }
+    Ok(duration)
 }
 
 #[cfg(test)]
@@ -259,9 +459,11 @@ mod tests {
     fn switch_to_tb() {
         let cli = parse(&["switch", "--share", "CORE", "--to", "tb"]);
         match cli.command.unwrap() {
-            Command::Switch { share, to, force } => {
+            Command::Switch {
+                share, to, force, ..
+            } => {
                 assert_eq!(share, "CORE");
-                assert_eq!(to, Backend::Tb);
+                assert_eq!(to, Some(Backend::Tb));
                 assert!(!force);
             }
             other => panic!("expected Switch, got {:?}", other),
@@ -272,9 +474,11 @@ mod tests {
     fn switch_to_fallback_force() {
         let cli = parse(&["switch", "--share", "DATA", "--to", "fallback", "--force"]);
         match cli.command.unwrap() {
-            Command::Switch { share, to, force } => {
+            Command::Switch {
+                share, to, force, ..
+            } => {
                 assert_eq!(share, "DATA");
-                assert_eq!(to, Backend::Fallback);
+                assert_eq!(to, Some(Backend::Fallback));
                 assert!(force);
             }
             other => panic!("expected Switch, got {:?}", other),
@@ -285,6 +489,182 @@ mod tests {
     fn switch_requires_share_and_to() {
         // Missing --share should fail
         let _ = parse_err(&["switch", "--to", "tb"]);
+        let _ = parse_err(&["switch", "--share", "CORE"]);
+    }
+
+    #[test]
+    fn switch_with_drain() {
+        let cli = parse(&["switch", "--share", "CORE", "--to", "tb", "--drain", "10m"]);
+        match cli.command.unwrap() {
+            Command::Switch { drain, cancel, .. } => {
+                assert_eq!(drain, Some(Duration::from_secs(600)));
+                assert!(!cancel);
+            }
+            other => panic!("expected Switch, got {:?}", other),
+        }
+        let _ = parse_err(&[
+            "switch", "--share", "CORE", "--to", "tb", "--drain", "10m", "--force",
+        ]);
+    }
+
+    #[test]
+    fn switch_cancel_needs_no_target() {
+        let cli = parse(&["switch", "--share", "CORE", "--cancel"]);
+        match cli.command.unwrap() {
+            Command::Switch { to, cancel, .. } => {
+                assert_eq!(to, None);
+                assert!(cancel);
+            }
+            other => panic!("expected Switch, got {:?}", other),
+        }
+        let _ = parse_err(&["switch", "--share", "CORE", "--to", "tb", "--cancel"]);
+    }
+
+    // --- Lease ---
+
+    #[test]
+    fn lease_acquire_parses_ttl() {
+        let cli = parse(&[
+            "lease", "acquire", "--share", "CORE", "--ttl", "2h", "--holder", "rsync",
+        ]);
+        match cli.command.unwrap() {
+            Command::Lease {
+                command:
+                    LeaseCommand::Acquire {
+                        share, ttl, holder, ..
+                    },
+            } => {
+                assert_eq!(share, "CORE");
+                assert_eq!(ttl, Duration::from_secs(7200));
+                assert_eq!(holder.as_deref(), Some("rsync"));
+            }
+            other => panic!("expected Lease Acquire, got {:?}", other),
+        }
+        let _ = parse_err(&["lease", "acquire", "--share", "CORE"]);
+        let _ = parse_err(&["lease", "acquire", "--share", "CORE", "--ttl", "soon"]);
+    }
+
+    #[test]
+    fn lease_release_and_list() {
+        let cli = parse(&["lease", "release", "--token", "lease-00ff"]);
+        match cli.command.unwrap() {
+            Command::Lease {
+                command: LeaseCommand::Release { token },
+            } => assert_eq!(token, "lease-00ff"),
+            other => panic!("expected Lease Release, got {:?}", other),
+        }
+        let cli = parse(&["lease", "list", "--json"]);
+        match cli.command.unwrap() {
+            Command::Lease {
+                command: LeaseCommand::List { share, json },
+            } => {
+                assert_eq!(share, None);
+                assert!(json);
+            }
+            other => panic!("expected Lease List, got {:?}", other),
+        }
+    }
+
+    // --- Run ---
+
+    #[test]
+    fn run_takes_command_after_separator() {
+        let cli = parse(&[
+            "run",
+            "--share",
+            "CORE",
+            "--backend",
+            "tb",
+            "--",
+            "rsync",
+            "-a",
+            "--delete",
+            "src/",
+        ]);
+        match cli.command.unwrap() {
+            Command::Run {
+                share,
+                backend,
+                timeout,
+                command,
+            } => {
+                assert_eq!(share, "CORE");
+                assert_eq!(backend, Some(Backend::Tb));
+                assert_eq!(timeout, Duration::from_secs(60));
+                assert_eq!(command, ["rsync", "-a", "--delete", "src/"]);
+            }
+            other => panic!("expected Run, got {:?}", other),
+        }
+        let _ = parse_err(&["run", "--share", "CORE"]);
+        let _ = parse_err(&["run", "--share", "CORE", "make"]);
+    }
+
+    // --- Wait ---
+
+    #[test]
+    fn wait_defaults_to_ready_within_a_minute() {
+        let cli = parse(&["wait", "--share", "CORE"]);
+        match cli.command.unwrap() {
+            Command::Wait {
+                share,
+                all,
+                backend,
+                state,
+                timeout,
+                observe,
+            } => {
+                assert_eq!(share.as_deref(), Some("CORE"));
+                assert!(!all);
+                assert_eq!(backend, None);
+                assert_eq!(state, WaitState::Ready);
+                assert_eq!(timeout, Duration::from_secs(60));
+                assert!(!observe);
+            }
+            other => panic!("expected Wait, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn wait_all_unmounted_with_timeout() {
+        let cli = parse(&[
+            "wait",
+            "--all",
+            "--state",
+            "unmounted",
+            "--timeout",
+            "2m",
+            "--observe",
+        ]);
+        match cli.command.unwrap() {
+            Command::Wait {
+                all,
+                state,
+                timeout,
+                observe,
+                ..
+            } => {
+                assert!(all);
+                assert_eq!(state, WaitState::Unmounted);
+                assert_eq!(timeout, Duration::from_secs(120));
+                assert!(observe);
+            }
+            other => panic!("expected Wait, got {:?}", other),
+        }
+        let _ = parse_err(&["wait"]);
+        let _ = parse_err(&["wait", "--all", "--share", "CORE"]);
+    }
+
+    #[test]
+    fn parse_duration_accepts_units() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86400)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        for bad in ["", "m", "5x", "1.5h", "-3s", "0"] {
+            assert!(parse_duration(bad).is_err(), "{}", bad);
+        }
     }
 
     // --- Verify ---
@@ -358,6 +738,49 @@ mod tests {
         }
     }
 
+    #[test]
+    fn reload_takes_no_arguments() {
+        assert!(matches!(parse(&["reload"]).command, Some(Command::Reload)));
+    }
+
+    // --- Dedupe ---
+
+    #[test]
+    fn dedupe_flags() {
+        let cli = parse(&["dedupe", "--dry-run", "--json"]);
+        match cli.command.unwrap() {
+            Command::Dedupe { dry_run, json } => {
+                assert!(dry_run);
+                assert!(json);
+            }
+            other => panic!("expected Dedupe, got {:?}", other),
+        }
+
+        let cli = parse(&["dedupe"]);
+        match cli.command.unwrap() {
+            Command::Dedupe { dry_run, json } => {
+                assert!(!dry_run);
+                assert!(!json);
+            }
+            other => panic!("expected Dedupe, got {:?}", other),
+        }
+    }
+
+    // --- Open files ---
+
+    #[test]
+    fn open_files_requires_share() {
+        let cli = parse(&["open-files", "--share", "CORE", "--json"]);
+        match cli.command.unwrap() {
+            Command::OpenFiles { share, json } => {
+                assert_eq!(share, "CORE");
+                assert!(json);
+            }
+            other => panic!("expected OpenFiles, got {:?}", other),
+        }
+        let _ = parse_err(&["open-files"]);
+    }
+
     // --- Folders ---
 
     #[test]
@@ -561,6 +984,63 @@ mod tests {
         }
     }
 
+    #[test]
+    fn favorites_adopt_list_and_select() {
+        let cli = parse(&["favorites", "adopt", "--json"]);
+        match cli.command.unwrap() {
+            Command::Favorites {
+                command: FavoritesCommand::Adopt { mount, json, .. },
+            } => {
+                assert_eq!(mount, None);
+                assert!(json);
+            }
+            other => panic!("expected Favorites Adopt, got {:?}", other),
+        }
+
+        let cli = parse(&[
+            "favorites",
+            "adopt",
+            "--mount",
+            "/Volumes/CORE-1",
+            "--share",
+            "CORE",
+            "--tb-host",
+            "10.10.10.1",
+        ]);
+        match cli.command.unwrap() {
+            Command::Favorites {
+                command:
+                    FavoritesCommand::Adopt {
+                        mount,
+                        share,
+                        tb_host,
+                        fallback_host,
+                        ..
+                    },
+            } => {
+                assert_eq!(mount.as_deref(), Some("/Volumes/CORE-1"));
+                assert_eq!(share.as_deref(), Some("CORE"));
+                assert_eq!(tb_host.as_deref(), Some("10.10.10.1"));
+                assert_eq!(fallback_host, None);
+            }
+            other => panic!("expected Favorites Adopt, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn favorites_adopt_rejects_both_hosts() {
+        parse_err(&[
+            "favorites",
+            "adopt",
+            "--mount",
+            "/Volumes/CORE",
+            "--tb-host",
+            "10.10.10.1",
+            "--fallback-host",
+            "nas.local",
+        ]);
+    }
+
     #[test]
     fn favorites_list_json() {
         let cli = parse(&["favorites", "list", "--json"]);
@@ -599,6 +1079,56 @@ mod tests {
         }
     }
 
+    // --- Credentials subcommands ---
+
+    #[test]
+    fn credentials_set_test_remove() {
+        match parse(&["credentials", "set", "--share", "CORE"])
+            .command
+            .unwrap()
+        {
+            Command::Credentials {
+                command: CredentialsCommand::Set { share },
+            } => assert_eq!(share, "CORE"),
+            other => panic!("expected Credentials Set, got {:?}", other),
+        }
+        match parse(&["credentials", "test", "--share", "CORE"])
+            .command
+            .unwrap()
+        {
+            Command::Credentials {
+                command: CredentialsCommand::Test { share },
+            } => assert_eq!(share, "CORE"),
+            other => panic!("expected Credentials Test, got {:?}", other),
+        }
+        match parse(&["credentials", "remove", "--share", "CORE"])
+            .command
+            .unwrap()
+        {
+            Command::Credentials {
+                command: CredentialsCommand::Remove { share },
+            } => assert_eq!(share, "CORE"),
+            other => panic!("expected Credentials Remove, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn credentials_set_rejects_password_argument() {
+        let _ = parse_err(&["credentials", "set", "--share", "CORE", "--password", "x"]);
+    }
+
+    // --- Reauth ---
+
+    #[test]
+    fn reauth_requires_share() {
+        let cli = parse(&["reauth", "--share", "CORE"]);
+        match cli.command.unwrap() {
+            Command::Reauth { share } => assert_eq!(share, "CORE"),
+            other => panic!("expected Reauth, got {:?}", other),
+        }
+        let _ = parse_err(&["reauth"]);
+    }
+
     // --- Install / Uninstall ---
 
     #[test]
@@ -4,6 +4,10 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use crate::events::ShareEventKind;
+use crate::fslock;
+use crate::migrate;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
 #[serde(rename_all = "snake_case")]
 pub enum Backend {
@@ -20,6 +24,46 @@ impl Backend {
     }
 }
 
+/// Where SMB passwords are stored (see `credentials`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialStore {
+    /// macOS login Keychain.
+    #[default]
+    Keychain,
+    /// Passphrase-encrypted file at `~/.mountaineer/credentials.enc`.
+    File,
+}
+
+impl CredentialStore {
+    pub fn label(self) -> &'static str {
+        match self {
+            CredentialStore::Keychain => "keychain",
+            CredentialStore::File => "file",
+        }
+    }
+}
+
+/// What happens to a share removed from config.toml while it is running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemovedSharePolicy {
+    /// Leave it mounted, like `favorites remove` without `--cleanup`.
+    #[default]
+    Keep,
+    /// Unmount it once idle and remove its stable symlink.
+    Cleanup,
+}
+
+impl RemovedSharePolicy {
+    pub fn label(self) -> &'static str {
+        match self {
+            RemovedSharePolicy::Keep => "keep",
+            RemovedSharePolicy::Cleanup => "cleanup",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GlobalConfig {
     #[serde(default = "default_shares_root")]
@@ -34,6 +78,15 @@ pub struct GlobalConfig {
     pub connect_timeout_ms: u64,
     #[serde(default = "default_lsof_recheck")]
     pub lsof_recheck: bool,
+    #[serde(default)]
+    pub credential_store: CredentialStore,
+    /// Unmount idle foreign copies of managed shares during reconcile.
+    #[serde(default)]
+    pub reclaim_foreign_mounts: bool,
+    /// Applied when a running tray or `monitor` sees a share disappear from
+    /// config.toml.
+    #[serde(default)]
+    pub on_share_removed: RemovedSharePolicy,
 }
 
 impl Default for GlobalConfig {
@@ -45,17 +98,59 @@ impl Default for GlobalConfig {
             auto_failback_stable_secs: default_auto_failback_stable_secs(),
             connect_timeout_ms: default_connect_timeout_ms(),
             lsof_recheck: default_lsof_recheck(),
+            credential_store: CredentialStore::default(),
+            reclaim_foreign_mounts: false,
+            on_share_removed: RemovedSharePolicy::default(),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ShareConfig {
     pub name: String,
     pub username: String,
     pub thunderbolt_host: String,
     pub fallback_host: String,
     pub share_name: String,
+    /// Active Directory / workgroup domain, sent as `DOMAIN;user`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    /// SMB port, when the server does not listen on 445.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Connect as guest; `username` and stored passwords are ignored.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub guest: bool,
+    /// Extra `mount_smbfs -o` options such as `nobrowse`, `soft` or `rdonly`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mount_options: Vec<String>,
+    /// Directory to mount at instead of `/Volumes/<share_name>`, e.g.
+    /// `~/.mountaineer/mnt/CORE`. Mountaineer creates and owns it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mount_point: Option<String>,
+    /// Per-share hook commands; each event set here replaces the global one.
+    #[serde(default, skip_serializing_if = "HooksConfig::is_empty")]
+    pub hooks: HooksConfig,
+}
+
+impl ShareConfig {
+    /// TCP port used for reachability probes and mounts.
+    pub fn smb_port(&self) -> u16 {
+        self.port.unwrap_or(DEFAULT_SMB_PORT)
+    }
+
+    /// Whether the share mounts at its own `mount_point` rather than `/Volumes`.
+    pub fn owns_mount_point(&self) -> bool {
+        self.mount_point
+            .as_deref()
+            .is_some_and(|path| !path.trim().is_empty())
+    }
+}
+
+pub const DEFAULT_SMB_PORT: u16 = 445;
+
+fn is_false(value: &bool) -> bool {
+    !*value
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,14 +162,141 @@ pub struct AliasConfig {
     pub target_subpath: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+/// Shell commands run on share lifecycle events (see `hooks`). Used both as
+/// the global `[hooks]` table and per share as `[shares.hooks]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HooksConfig {
+    /// Before a backend switch; a non-zero exit vetoes the switch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_switch: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_unmount: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_mount: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failover: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failback: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub switch_blocked: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub both_backends_down: Option<String>,
+    /// After a switch that got past `pre_switch` fails, rolled back or not.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub switch_failed: Option<String>,
+    /// Seconds a hook may run before it is killed (default 10).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+impl HooksConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// Schema version (see `migrate`); older files are upgraded on load.
+    #[serde(default)]
+    pub version: u32,
     #[serde(default)]
     pub global: GlobalConfig,
     #[serde(default)]
     pub shares: Vec<ShareConfig>,
     #[serde(default)]
     pub aliases: Vec<AliasConfig>,
+    #[serde(default, skip_serializing_if = "HooksConfig::is_empty")]
+    pub hooks: HooksConfig,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<WebhookConfig>,
+    #[serde(default, skip_serializing_if = "NotificationsConfig::is_default")]
+    pub notifications: NotificationsConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: migrate::CONFIG_VERSION,
+            global: GlobalConfig::default(),
+            shares: Vec::new(),
+            aliases: Vec::new(),
+            hooks: HooksConfig::default(),
+            webhooks: Vec::new(),
+            notifications: NotificationsConfig::default(),
+        }
+    }
+}
+
+/// An HTTP endpoint that receives share events as JSON POSTs (see `webhooks`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Events to send; empty sends all of them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<ShareEventKind>,
+}
+
+/// Desktop notifications (see `notify`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotificationsConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Shell command that shows the notification instead of macOS
+    /// Notification Center, e.g. `terminal-notifier` or `notify-send`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default = "default_true")]
+    pub failover: bool,
+    #[serde(default = "default_true")]
+    pub recovery_pending: bool,
+    #[serde(default = "default_true")]
+    pub both_down: bool,
+    #[serde(default)]
+    pub error: bool,
+    /// Minimum time between two notifications for the same share and event.
+    #[serde(default = "default_notification_interval_secs")]
+    pub min_interval_secs: u64,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            command: None,
+            failover: true,
+            recovery_pending: true,
+            both_down: true,
+            error: false,
+            min_interval_secs: default_notification_interval_secs(),
+        }
+    }
+}
+
+impl NotificationsConfig {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether `event` should notify at all.
+    pub fn wants(&self, event: ShareEventKind) -> bool {
+        self.enabled
+            && match event {
+                ShareEventKind::Failover => self.failover,
+                ShareEventKind::RecoveryPending => self.recovery_pending,
+                ShareEventKind::BothDown => self.both_down,
+                ShareEventKind::Error => self.error,
+                ShareEventKind::Switch => false,
+            }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_notification_interval_secs() -> u64 {
+    300
 }
 
 fn default_shares_root() -> String {
@@ -108,6 +330,13 @@ pub fn config_path() -> PathBuf {
         .join("config.toml")
 }
 
+pub fn credentials_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("/"))
+        .join(".mountaineer")
+        .join("credentials.enc")
+}
+
 pub fn state_path() -> PathBuf {
     dirs::home_dir()
         .unwrap_or_else(|| PathBuf::from("/"))
@@ -115,24 +344,113 @@ pub fn state_path() -> PathBuf {
         .join("state.json")
 }
 
+/// Unix socket the running agent (tray or `monitor`) serves commands on.
+pub fn control_socket_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("/"))
+        .join(".mountaineer")
+        .join("control.sock")
+}
+
+/// Held by the process that owns reconciliation (see `ownership`).
+pub fn reconciler_lock_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("/"))
+        .join(".mountaineer")
+        .join("reconciler.lock")
+}
+
+/// Switches in progress, for recovery after a crash (see `journal`).
+pub fn switch_journal_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("/"))
+        .join(".mountaineer")
+        .join("switch-journal.json")
+}
+
+/// Webhook deliveries not yet accepted by their endpoint.
+pub fn webhook_outbox_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("/"))
+        .join(".mountaineer")
+        .join("webhook-outbox.json")
+}
+
 pub fn load() -> Result<Config> {
-    let path = config_path();
+    load_at(&config_path())
+}
+
+/// Load the config file at `path`. One written by an older version is
+/// upgraded in place, after a backup of it is written (see `migrate`).
+pub fn load_at(path: &Path) -> Result<Config> {
     if !path.exists() {
         return Ok(Config::default());
     }
 
-    let contents = fs::read_to_string(&path)
-        .with_context(|| format!("failed reading config {}", path.display()))?;
-    let config: Config = toml::from_str(&contents)
+    let contents = read_config(path)?;
+    let (config, _, found) = parse_versioned(&contents, path)?;
+    if found < migrate::CONFIG_VERSION {
+        // Upgrading rewrites the file, so it takes the lock like any write.
+        let _lock = fslock::lock_exclusive(&config_lock_path(path))?;
+        return load_locked(path);
+    }
+    Ok(config)
+}
+
+/// Load the config file while holding its lock, upgrading it in place if
+/// another process has not already. Only the `version` line changes when
+/// no step touched the document, so comments and key order survive.
+fn load_locked(path: &Path) -> Result<Config> {
+    let contents = read_config(path)?;
+    let (config, doc, found) = parse_versioned(&contents, path)?;
+    if found < migrate::CONFIG_VERSION {
+        let backup = migrate::backup(path, found)?;
+        write_config(path, &migrate::upgraded_config_text(&contents, &doc)?)?;
+        log::info!(
+            "config: upgraded {} from version {} to {} (previous file kept at {})",
+            path.display(),
+            found,
+            migrate::CONFIG_VERSION,
+            backup.display()
+        );
+    }
+    Ok(config)
+}
+
+fn read_config(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("failed reading config {}", path.display()))
+}
+
+fn config_lock_path(path: &Path) -> PathBuf {
+    path.with_extension("lock")
+}
+
+/// Parse and validate the contents of the config file at `path`, upgrading
+/// them in memory if an older version wrote them.
+pub fn parse(contents: &str, path: &Path) -> Result<Config> {
+    Ok(parse_versioned(contents, path)?.0)
+}
+
+/// [`parse`], also returning the upgraded document and the version the
+/// contents had.
+fn parse_versioned(contents: &str, path: &Path) -> Result<(Config, toml::Table, u32)> {
+    let mut doc: toml::Table = toml::from_str(contents)
+        .with_context(|| format!("failed parsing TOML {}", path.display()))?;
+    let found = migrate::upgrade_config(&mut doc)
+        .with_context(|| format!("cannot load {}", path.display()))?;
+    let config: Config = doc
+        .clone()
+        .try_into()
         .with_context(|| format!("failed parsing TOML {}", path.display()))?;
     validate(&config)?;
-    Ok(config)
+    Ok((config, doc, found))
 }
 
 /// Validate config on load per spec 02: reject duplicate share names,
 /// empty required fields, and duplicate alias names.
 fn validate(config: &Config) -> Result<()> {
     let mut seen_shares = std::collections::HashSet::new();
+    let mut seen_mount_points = std::collections::HashSet::new();
     for share in &config.shares {
         if share.name.trim().is_empty() {
             anyhow::bail!("config error: share has empty name");
@@ -149,12 +467,59 @@ fn validate(config: &Config) -> Result<()> {
                 share.name
             );
         }
+        for (field, host) in [
+            ("thunderbolt_host", &share.thunderbolt_host),
+            ("fallback_host", &share.fallback_host),
+        ] {
+            if let Err(reason) = crate::network::host::validate(host) {
+                anyhow::bail!("config error: share '{}' {}: {}", share.name, field, reason);
+            }
+        }
+        if share.port == Some(0) {
+            anyhow::bail!("config error: share '{}' has invalid port 0", share.name);
+        }
+        if let Some(option) = share
+            .mount_options
+            .iter()
+            .find(|o| o.is_empty() || o.contains(|c: char| c == ',' || c.is_whitespace()))
+        {
+            anyhow::bail!(
+                "config error: share '{}' has invalid mount option '{}' (one option per entry, no commas or spaces)",
+                share.name,
+                option
+            );
+        }
+        if share.owns_mount_point() {
+            let path = share_mount_path(share);
+            if !path.is_absolute() {
+                anyhow::bail!(
+                    "config error: share '{}' mount_point must be an absolute or ~/ path",
+                    share.name
+                );
+            }
+            if !seen_mount_points.insert(path.clone()) {
+                anyhow::bail!(
+                    "config error: share '{}' mount_point {} is used by another share",
+                    share.name,
+                    path.display()
+                );
+            }
+        }
         let key = share.name.to_ascii_lowercase();
         if !seen_shares.insert(key) {
             anyhow::bail!("config error: duplicate share name '{}'", share.name);
         }
     }
 
+    for webhook in &config.webhooks {
+        if !webhook.url.starts_with("http://") && !webhook.url.starts_with("https://") {
+            anyhow::bail!(
+                "config error: webhook url '{}' must start with http:// or https://",
+                webhook.url
+            );
+        }
+    }
+
     let mut seen_aliases = std::collections::HashSet::new();
     for alias in &config.aliases {
         if alias.name.trim().is_empty() {
@@ -170,18 +535,29 @@ fn validate(config: &Config) -> Result<()> {
 }
 
 pub fn save(config: &Config) -> Result<()> {
-    let path = config_path();
+    save_at(&config_path(), config)
+}
+
+pub fn save_at(path: &Path, config: &Config) -> Result<()> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)
             .with_context(|| format!("failed creating {}", parent.display()))?;
     }
-    let toml = toml::to_string_pretty(config)?;
+    // Whatever was loaded, the file written is in the current schema.
+    let toml = toml::to_string_pretty(&Config {
+        version: migrate::CONFIG_VERSION,
+        ..config.clone()
+    })?;
+    let _lock = fslock::lock_exclusive(&config_lock_path(path))?;
+    write_config(path, &toml)
+}
 
-    // Atomic write: write to .tmp then rename, so a crash mid-write won't corrupt config.toml
+/// Atomic write: write to .tmp then rename, so a crash mid-write won't corrupt config.toml
+fn write_config(path: &Path, toml: &str) -> Result<()> {
     let tmp_path = path.with_extension("toml.tmp");
-    fs::write(&tmp_path, &toml)
+    fs::write(&tmp_path, toml)
         .with_context(|| format!("failed writing temp config {}", tmp_path.display()))?;
-    fs::rename(&tmp_path, &path)
+    fs::rename(&tmp_path, path)
         .with_context(|| format!("failed renaming temp config to {}", path.display()))?;
     Ok(())
 }
@@ -215,6 +591,15 @@ pub fn volume_mount_path(share_name: &str) -> PathBuf {
     PathBuf::from("/Volumes").join(share_name)
 }
 
+/// Where `share` is mounted: its configured `mount_point` if set, otherwise
+/// [`volume_mount_path`].
+pub fn share_mount_path(share: &ShareConfig) -> PathBuf {
+    match share.mount_point.as_deref().map(str::trim) {
+        Some(path) if !path.is_empty() => expand_path(path),
+        _ => volume_mount_path(&share.share_name),
+    }
+}
+
 pub fn default_alias_path(config: &Config, alias_name: &str) -> PathBuf {
     shares_root_path(config).join("Links").join(alias_name)
 }
@@ -273,6 +658,7 @@ mod tests {
             thunderbolt_host: "10.0.0.1".to_string(),
             fallback_host: "192.168.1.1".to_string(),
             share_name: name.to_string(),
+            ..ShareConfig::default()
         }
     }
 
@@ -383,6 +769,127 @@ mod tests {
         assert_eq!(parsed.shares[0].name, "CORE");
     }
 
+    #[test]
+    fn share_connection_options_roundtrip_and_default() {
+        let plain = make_share("CORE");
+        let toml_str = toml::to_string_pretty(&plain).unwrap();
+        assert!(!toml_str.contains("domain"));
+        assert!(!toml_str.contains("guest"));
+        assert!(!toml_str.contains("mount_options"));
+        assert_eq!(plain.smb_port(), 445);
+
+        let share = ShareConfig {
+            domain: Some("CORP".to_string()),
+            port: Some(1445),
+            guest: true,
+            mount_options: vec!["nobrowse".to_string(), "rdonly".to_string()],
+            ..make_share("DATA")
+        };
+        let parsed: ShareConfig = toml::from_str(&toml::to_string_pretty(&share).unwrap()).unwrap();
+        assert_eq!(parsed.domain.as_deref(), Some("CORP"));
+        assert_eq!(parsed.smb_port(), 1445);
+        assert!(parsed.guest);
+        assert_eq!(parsed.mount_options, ["nobrowse", "rdonly"]);
+    }
+
+    #[test]
+    fn validate_accepts_ipv6_hosts_and_rejects_host_with_port() {
+        let mut share = make_share("CORE");
+        share.thunderbolt_host = "fe80::1%bridge0".to_string();
+        share.fallback_host = "[2001:db8::1]".to_string();
+        let cfg = Config {
+            shares: vec![share],
+            ..Config::default()
+        };
+        assert!(validate(&cfg).is_ok());
+
+        let mut share = make_share("CORE");
+        share.fallback_host = "nas.local:445".to_string();
+        let cfg = Config {
+            shares: vec![share],
+            ..Config::default()
+        };
+        let err = validate(&cfg).unwrap_err().to_string();
+        assert!(err.contains("fallback_host"), "{}", err);
+    }
+
+    #[test]
+    fn share_mount_path_defaults_to_volumes_and_expands_custom() {
+        let share = make_share("CORE");
+        assert!(!share.owns_mount_point());
+        assert_eq!(share_mount_path(&share), PathBuf::from("/Volumes/CORE"));
+
+        let share = ShareConfig {
+            mount_point: Some("~/.mountaineer/mnt/CORE".to_string()),
+            ..make_share("CORE")
+        };
+        assert!(share.owns_mount_point());
+        let path = share_mount_path(&share);
+        assert!(path.is_absolute());
+        assert!(path.ends_with(".mountaineer/mnt/CORE"));
+
+        let blank = ShareConfig {
+            mount_point: Some(" ".to_string()),
+            ..make_share("CORE")
+        };
+        assert!(!blank.owns_mount_point());
+        assert_eq!(share_mount_path(&blank), PathBuf::from("/Volumes/CORE"));
+    }
+
+    #[test]
+    fn validate_rejects_relative_and_shared_mount_points() {
+        let relative = ShareConfig {
+            mount_point: Some("mnt/CORE".to_string()),
+            ..make_share("CORE")
+        };
+        let cfg = Config {
+            shares: vec![relative],
+            ..Config::default()
+        };
+        assert!(validate(&cfg).unwrap_err().to_string().contains("absolute"));
+
+        let a = ShareConfig {
+            mount_point: Some("/tmp/mnt/CORE".to_string()),
+            ..make_share("CORE")
+        };
+        let b = ShareConfig {
+            mount_point: Some("/tmp/mnt/CORE".to_string()),
+            ..make_share("DATA")
+        };
+        let cfg = Config {
+            shares: vec![a, b],
+            ..Config::default()
+        };
+        assert!(
+            validate(&cfg)
+                .unwrap_err()
+                .to_string()
+                .contains("used by another share")
+        );
+    }
+
+    #[test]
+    fn validate_rejects_bad_port_and_mount_options() {
+        let mut share = make_share("CORE");
+        share.port = Some(0);
+        let cfg = Config {
+            shares: vec![share],
+            ..Config::default()
+        };
+        assert!(validate(&cfg).unwrap_err().to_string().contains("port"));
+
+        for bad in ["", "soft,rdonly", "no browse"] {
+            let mut share = make_share("CORE");
+            share.mount_options = vec![bad.to_string()];
+            let cfg = Config {
+                shares: vec![share],
+                ..Config::default()
+            };
+            let err = validate(&cfg).unwrap_err();
+            assert!(err.to_string().contains("invalid mount option"), "{}", bad);
+        }
+    }
+
     // --- P7.1: Config load/save round-trip with filesystem ---
 
     #[test]
@@ -392,6 +899,7 @@ mod tests {
         let config_file = dir.path().join("config.toml");
 
         let cfg = Config {
+            version: migrate::CONFIG_VERSION,
             global: GlobalConfig {
                 lsof_recheck: false,
                 auto_failback: true,
@@ -399,6 +907,9 @@ mod tests {
                 connect_timeout_ms: 2000,
                 auto_failback_stable_secs: 60,
                 shares_root: "~/MyShares".to_string(),
+                credential_store: CredentialStore::File,
+                reclaim_foreign_mounts: true,
+                on_share_removed: RemovedSharePolicy::Cleanup,
             },
             shares: vec![make_share("CORE"), make_share("DATA")],
             aliases: vec![AliasConfig {
@@ -407,6 +918,20 @@ mod tests {
                 share: "CORE".to_string(),
                 target_subpath: "dev/projects".to_string(),
             }],
+            hooks: HooksConfig {
+                pre_switch: Some("~/bin/pause-sync".to_string()),
+                timeout_secs: Some(5),
+                ..HooksConfig::default()
+            },
+            webhooks: vec![WebhookConfig {
+                url: "https://dashboard.example/mountaineer".to_string(),
+                events: vec![ShareEventKind::Failover],
+            }],
+            notifications: NotificationsConfig {
+                error: true,
+                min_interval_secs: 60,
+                ..NotificationsConfig::default()
+            },
         };
 
         // Write config to the temp file
@@ -424,6 +949,9 @@ mod tests {
         assert_eq!(parsed.global.auto_failback_stable_secs, 60);
         assert_eq!(parsed.global.connect_timeout_ms, 2000);
         assert!(!parsed.global.lsof_recheck);
+        assert_eq!(parsed.global.credential_store, CredentialStore::File);
+        assert!(parsed.global.reclaim_foreign_mounts);
+        assert_eq!(parsed.global.on_share_removed, RemovedSharePolicy::Cleanup);
         assert_eq!(parsed.shares.len(), 2);
         assert_eq!(parsed.shares[0].name, "CORE");
         assert_eq!(parsed.shares[1].name, "DATA");
@@ -434,6 +962,108 @@ mod tests {
         assert_eq!(parsed.aliases[0].name, "projects");
         assert_eq!(parsed.aliases[0].share, "CORE");
         assert_eq!(parsed.aliases[0].target_subpath, "dev/projects");
+        assert_eq!(parsed.hooks.pre_switch.as_deref(), Some("~/bin/pause-sync"));
+        assert_eq!(parsed.hooks.timeout_secs, Some(5));
+        assert!(parsed.shares[0].hooks.is_empty());
+        assert_eq!(parsed.webhooks, cfg.webhooks);
+        assert_eq!(parsed.notifications, cfg.notifications);
+    }
+
+    #[test]
+    fn notifications_default_on_with_per_event_flags() {
+        let cfg: Config = toml::from_str("").unwrap();
+        assert!(cfg.notifications.wants(ShareEventKind::RecoveryPending));
+        assert!(cfg.notifications.wants(ShareEventKind::Failover));
+        assert!(cfg.notifications.wants(ShareEventKind::BothDown));
+        assert!(!cfg.notifications.wants(ShareEventKind::Error));
+        assert!(!cfg.notifications.wants(ShareEventKind::Switch));
+        assert!(!toml::to_string(&cfg).unwrap().contains("notifications"));
+
+        let cfg: Config = toml::from_str(
+            r#"
+[notifications]
+command = "notify-send \"$MOUNTAINEER_TITLE\" \"$MOUNTAINEER_BODY\""
+failover = false
+min_interval_secs = 60
+"#,
+        )
+        .unwrap();
+        assert!(!cfg.notifications.wants(ShareEventKind::Failover));
+        assert!(cfg.notifications.wants(ShareEventKind::RecoveryPending));
+        assert_eq!(cfg.notifications.min_interval_secs, 60);
+        assert!(cfg.notifications.command.is_some());
+
+        let off: Config = toml::from_str("[notifications]\nenabled = false\n").unwrap();
+        assert!(!off.notifications.wants(ShareEventKind::RecoveryPending));
+    }
+
+    #[test]
+    fn webhooks_parse_with_event_filter() {
+        let cfg: Config = toml::from_str(
+            r#"
+[[webhooks]]
+url = "https://dashboard.example/hooks/mountaineer"
+events = ["failover", "recovery-pending"]
+
+[[webhooks]]
+url = "http://127.0.0.1:8080/all"
+"#,
+        )
+        .unwrap();
+        assert_eq!(cfg.webhooks.len(), 2);
+        assert_eq!(
+            cfg.webhooks[0].events,
+            vec![ShareEventKind::Failover, ShareEventKind::RecoveryPending]
+        );
+        assert!(cfg.webhooks[1].events.is_empty());
+        assert!(validate(&cfg).is_ok());
+
+        assert!(
+            toml::from_str::<Config>("[[webhooks]]\nurl = \"http://x\"\nevents = [\"reboot\"]\n")
+                .is_err()
+        );
+        let bad = Config {
+            webhooks: vec![WebhookConfig {
+                url: "dashboard.example".to_string(),
+                events: Vec::new(),
+            }],
+            ..Config::default()
+        };
+        assert!(validate(&bad).is_err());
+    }
+
+    #[test]
+    fn hooks_parse_globally_and_per_share() {
+        let cfg: Config = toml::from_str(
+            r#"
+[hooks]
+pre_switch = "pause-sync"
+post_mount = "resume-sync"
+
+[[shares]]
+name = "CORE"
+username = "admin"
+thunderbolt_host = "10.10.10.1"
+fallback_host = "nas.local"
+share_name = "CORE"
+
+[shares.hooks]
+post_mount = "resume-sync --share CORE"
+timeout_secs = 30
+"#,
+        )
+        .unwrap();
+        assert_eq!(cfg.hooks.pre_switch.as_deref(), Some("pause-sync"));
+        assert_eq!(
+            cfg.shares[0].hooks.post_mount.as_deref(),
+            Some("resume-sync --share CORE")
+        );
+        assert_eq!(cfg.shares[0].hooks.timeout_secs, Some(30));
+
+        let text = toml::to_string_pretty(&cfg).unwrap();
+        let reparsed: Config = toml::from_str(&text).unwrap();
+        assert_eq!(reparsed.hooks, cfg.hooks);
+        assert_eq!(reparsed.shares[0].hooks, cfg.shares[0].hooks);
     }
 
     #[test]
@@ -477,6 +1107,9 @@ mod tests {
         assert_eq!(cfg.global.auto_failback_stable_secs, 30);
         assert_eq!(cfg.global.connect_timeout_ms, 800);
         assert!(cfg.global.lsof_recheck); // spec 02: lsof_recheck defaults to true
+        assert_eq!(cfg.global.credential_store, CredentialStore::Keychain);
+        assert!(!cfg.global.reclaim_foreign_mounts);
+        assert_eq!(cfg.global.on_share_removed, RemovedSharePolicy::Keep);
         assert!(cfg.shares.is_empty());
         assert!(cfg.aliases.is_empty());
     }
@@ -575,6 +1208,13 @@ share = "CORE"
         assert!(path.to_string_lossy().ends_with("state.json"));
     }
 
+    #[test]
+    fn switch_journal_path_under_mountaineer_dir() {
+        let path = switch_journal_path();
+        assert!(path.to_string_lossy().contains(".mountaineer"));
+        assert!(path.to_string_lossy().ends_with("switch-journal.json"));
+    }
+
     #[test]
     fn shares_root_path_expands_tilde() {
         let cfg = Config::default(); // shares_root = "~/Shares"
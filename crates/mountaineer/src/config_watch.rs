@@ -0,0 +1,807 @@
+//! Watching `config.toml` and applying edits to running shares: added shares
+//! mount on the next pass, removed ones follow `global.on_share_removed`, and
+//! changed ones are remounted once idle. An invalid edit keeps the last good
+//! config in use.
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::{self, Config, RemovedSharePolicy, ShareConfig};
+use crate::engine::{self, RuntimeState, UnmountResult};
+
+/// Editors save in steps (truncate then write, or write a temporary file and
+/// rename it); wait this long after a change before reading the file.
+const SETTLE: Duration = Duration::from_millis(300);
+/// How often the file is checked where no watcher could be set up.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How the shares in two configs differ.
+#[derive(Debug, Default)]
+pub struct ConfigDiff {
+    /// Names of shares that are new.
+    pub added: Vec<String>,
+    /// Shares that are gone, as they were configured.
+    pub removed: Vec<ShareConfig>,
+    /// Shares whose hosts or mount settings changed, as they were before.
+    pub changed: Vec<ShareConfig>,
+}
+
+impl ConfigDiff {
+    pub fn between(old: &Config, new: &Config) -> Self {
+        let mut diff = Self::default();
+        for share in &new.shares {
+            if config::find_share(old, &share.name).is_none() {
+                diff.added.push(share.name.clone());
+            }
+        }
+        for share in &old.shares {
+            match config::find_share(new, &share.name) {
+                None => diff.removed.push(share.clone()),
+                Some(updated) if connection_changed(share, updated) => {
+                    diff.changed.push(share.clone())
+                }
+                Some(_) => {}
+            }
+        }
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Whether a share mounted with `old` has to be remounted to use `new`.
+fn connection_changed(old: &ShareConfig, new: &ShareConfig) -> bool {
+    old.thunderbolt_host != new.thunderbolt_host
+        || old.fallback_host != new.fallback_host
+        || old.share_name != new.share_name
+        || old.username != new.username
+        || old.domain != new.domain
+        || old.port != new.port
+        || old.guest != new.guest
+        || old.mount_options != new.mount_options
+        || config::share_mount_path(old) != config::share_mount_path(new)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PendingAction {
+    Remount,
+    Cleanup,
+}
+
+/// A share still mounted with settings config.toml no longer has.
+#[derive(Debug)]
+struct Pending {
+    /// The share as it was mounted.
+    share: ShareConfig,
+    action: PendingAction,
+    /// Whether a deferral was already reported, so it is not every pass.
+    reported: bool,
+}
+
+/// The config in effect for a long-running loop, and the changes to running
+/// shares that edits to it still require.
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    path: PathBuf,
+    /// The last valid config read from the file.
+    loaded: Config,
+    /// `loaded` with shares still waiting to remount at their old settings.
+    in_use: Config,
+    /// The config running shares were last planned against.
+    applied: Config,
+    /// The file as last read, valid or not. `None` when it is missing.
+    contents: Option<String>,
+    error: Option<String>,
+    /// By lowercase share name.
+    pending: BTreeMap<String, Pending>,
+}
+
+impl ConfigWatcher {
+    /// Load the default config file.
+    pub fn open() -> Self {
+        Self::open_at(config::config_path())
+    }
+
+    /// Load `path` with [`config::load_at`], upgrading an older file in
+    /// place. An invalid file leaves the default config in use and sets
+    /// [`error`](Self::error).
+    pub fn open_at(path: PathBuf) -> Self {
+        let (config, error) = match config::load_at(&path) {
+            Ok(config) => (config, None),
+            Err(e) => {
+                log::error!("config: {:#}", e);
+                (Config::default(), Some(format!("{:#}", e)))
+            }
+        };
+        let contents = fs::read_to_string(&path).ok();
+        Self {
+            path,
+            in_use: config.clone(),
+            applied: config.clone(),
+            loaded: config,
+            contents,
+            error,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.in_use
+    }
+
+    /// Why the file on disk is not the config in use, if it is not.
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Re-read the file. Returns whether it changed to a valid config. The
+    /// changes to running shares are left for [`update`](Self::update).
+    pub fn reload(&mut self) -> bool {
+        let contents = fs::read_to_string(&self.path).ok();
+        if contents == self.contents {
+            return false;
+        }
+        self.contents = contents;
+
+        let parsed = match &self.contents {
+            Some(text) => config::parse(text, &self.path),
+            None => Err(anyhow::anyhow!("{} was removed", self.path.display())),
+        };
+        match parsed {
+            Ok(config) => {
+                let diff = ConfigDiff::between(&self.loaded, &config);
+                log::info!(
+                    "config: reloaded {} ({} added, {} removed, {} changed)",
+                    self.path.display(),
+                    diff.added.len(),
+                    diff.removed.len(),
+                    diff.changed.len()
+                );
+                self.loaded = config;
+                self.error = None;
+                self.refresh_in_use();
+                true
+            }
+            Err(e) => {
+                log::error!("config: keeping the previous config: {:#}", e);
+                self.error = Some(format!("{:#}", e));
+                false
+            }
+        }
+    }
+
+    /// Re-read the file and carry out what its changes require of running
+    /// shares. Returns a line for each thing done, for the caller to show.
+    /// Call before each reconcile pass; work deferred by open files or leases
+    /// is retried on the next call.
+    pub fn update(&mut self, state: &mut RuntimeState) -> Vec<String> {
+        let mut messages = Vec::new();
+        let previous_error = self.error.clone();
+        if !self.reload()
+            && let Some(error) = &self.error
+            && previous_error.as_ref() != Some(error)
+        {
+            messages.push(format!(
+                "config.toml is invalid, keeping the previous config: {}",
+                error
+            ));
+        }
+        // Also covers edits reloaded while another process was the owner.
+        let diff = ConfigDiff::between(&self.applied, &self.loaded);
+        if !diff.is_empty() {
+            messages.extend(self.plan(&diff));
+            self.applied = self.loaded.clone();
+        }
+        messages.extend(self.apply_pending(state));
+        self.refresh_in_use();
+        messages
+    }
+
+    /// Rebuild [`config`](Self::config): shares waiting to remount keep the
+    /// settings they are mounted with.
+    fn refresh_in_use(&mut self) {
+        let mut in_use = self.loaded.clone();
+        for pending in self.pending.values() {
+            if pending.action != PendingAction::Remount {
+                continue;
+            }
+            if let Some(share) = in_use
+                .shares
+                .iter_mut()
+                .find(|share| share.name.eq_ignore_ascii_case(&pending.share.name))
+            {
+                *share = pending.share.clone();
+            }
+        }
+        self.in_use = in_use;
+    }
+
+    /// Queue the work `diff` needs and describe it.
+    fn plan(&mut self, diff: &ConfigDiff) -> Vec<String> {
+        let mut messages = Vec::new();
+        for name in &diff.added {
+            messages.push(format!("{}: added", name));
+            // Re-added before its cleanup finished: remount only if what is
+            // mounted differs from the new settings.
+            let key = name.to_ascii_lowercase();
+            if let Some(pending) = self.pending.get_mut(&key) {
+                match config::find_share(&self.loaded, name) {
+                    Some(share) if connection_changed(&pending.share, share) => {
+                        pending.action = PendingAction::Remount;
+                    }
+                    _ => {
+                        self.pending.remove(&key);
+                    }
+                }
+            }
+        }
+        for share in &diff.removed {
+            let key = share.name.to_ascii_lowercase();
+            match self.loaded.global.on_share_removed {
+                RemovedSharePolicy::Keep => {
+                    self.pending.remove(&key);
+                    messages.push(format!("{}: removed, left as it is", share.name));
+                }
+                RemovedSharePolicy::Cleanup => {
+                    self.queue(share, PendingAction::Cleanup);
+                    messages.push(format!("{}: removed, cleaning up", share.name));
+                }
+            }
+        }
+        for share in &diff.changed {
+            self.queue(share, PendingAction::Remount);
+            messages.push(format!(
+                "{}: connection settings changed, remounting",
+                share.name
+            ));
+        }
+        messages
+    }
+
+    fn queue(&mut self, share: &ShareConfig, action: PendingAction) {
+        let pending = self
+            .pending
+            .entry(share.name.to_ascii_lowercase())
+            .or_insert_with(|| Pending {
+                share: share.clone(),
+                action,
+                reported: false,
+            });
+        pending.action = action;
+        pending.reported = false;
+    }
+
+    fn apply_pending(&mut self, state: &mut RuntimeState) -> Vec<String> {
+        let mut messages = Vec::new();
+        let config = &self.loaded;
+        self.pending.retain(|key, pending| {
+            let name = &pending.share.name;
+            let result = match pending.action {
+                PendingAction::Remount => {
+                    engine::unmount_for_remount(config, state, &pending.share)
+                }
+                PendingAction::Cleanup => {
+                    match engine::cleanup_removed_share(config, state, &pending.share) {
+                        Ok((_, result)) => result,
+                        Err(e) => {
+                            messages.push(format!("{}: cleanup failed: {:#}", name, e));
+                            return false;
+                        }
+                    }
+                }
+            };
+
+            if result.unmounted || !result.attempted {
+                match pending.action {
+                    PendingAction::Remount if result.unmounted => {
+                        messages.push(format!("{}: unmounted to remount", name));
+                    }
+                    PendingAction::Remount => {}
+                    PendingAction::Cleanup => {
+                        state.shares.remove(key);
+                        messages.push(format!("{}: cleaned up", name));
+                    }
+                }
+                return false;
+            }
+            if !pending.reported {
+                pending.reported = true;
+                messages.push(deferral(&result));
+            }
+            true
+        });
+        messages
+    }
+}
+
+fn deferral(result: &UnmountResult) -> String {
+    format!(
+        "{}: {}, will retry",
+        result.share,
+        result.message.as_deref().unwrap_or("not unmounted")
+    )
+}
+
+/// Call `wake` from a background thread whenever the file at `path` changes.
+pub fn watch(path: PathBuf, wake: impl Fn() + Send + 'static) -> Result<()> {
+    std::thread::Builder::new()
+        .name("config-watch".into())
+        .spawn(move || {
+            let mut last = fs::read_to_string(&path).ok();
+            let mut check = || {
+                std::thread::sleep(SETTLE);
+                let now = fs::read_to_string(&path).ok();
+                if now != last {
+                    last = now;
+                    wake();
+                }
+            };
+            if let Err(e) = os::wait_for_changes(&path, &mut check) {
+                log::warn!(
+                    "config: cannot watch {} ({:#}), polling instead",
+                    path.display(),
+                    e
+                );
+                loop {
+                    std::thread::sleep(POLL_INTERVAL);
+                    check();
+                }
+            }
+        })?;
+    Ok(())
+}
+
+/// The directory holding the config file. Watching it, rather than the file,
+/// also catches saves that replace the file.
+fn watched_dir(path: &Path) -> Result<&Path> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("{} has no parent directory", path.display()))?;
+    fs::create_dir_all(dir)?;
+    Ok(dir)
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    use anyhow::{Context, Result};
+    use nix::libc;
+    use std::ffi::CString;
+    use std::fs::File;
+    use std::io::{ErrorKind, Read};
+    use std::os::fd::FromRawFd;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    /// Call `on_event` for every inotify event in the config directory.
+    /// Returns only on error.
+    pub fn wait_for_changes(path: &Path, on_event: &mut dyn FnMut()) -> Result<()> {
+        let dir = super::watched_dir(path)?;
+        let c_dir = CString::new(dir.as_os_str().as_bytes())?;
+
+        // SAFETY: plain syscall; the descriptor is owned by `events` below.
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error()).context("inotify_init1 failed");
+        }
+        // SAFETY: `fd` is a fresh descriptor nothing else owns.
+        let mut events = unsafe { File::from_raw_fd(fd) };
+        let mask = libc::IN_CLOSE_WRITE
+            | libc::IN_MOVED_TO
+            | libc::IN_MOVED_FROM
+            | libc::IN_CREATE
+            | libc::IN_DELETE;
+        // SAFETY: `c_dir` is a valid NUL-terminated path.
+        if unsafe { libc::inotify_add_watch(fd, c_dir.as_ptr(), mask) } < 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("cannot watch {}", dir.display()));
+        }
+
+        let mut buf = [0u8; 4096];
+        loop {
+            match events.read(&mut buf) {
+                Ok(_) => on_event(),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e).context("reading inotify events failed"),
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use anyhow::{Context, Result};
+    use nix::libc;
+    use std::fs::{File, OpenOptions};
+    use std::io::ErrorKind;
+    use std::os::fd::{AsRawFd, FromRawFd, RawFd};
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::path::Path;
+
+    /// Call `on_event` for every kqueue vnode event on the config directory
+    /// or the file itself. Returns only on error.
+    pub fn wait_for_changes(path: &Path, on_event: &mut dyn FnMut()) -> Result<()> {
+        let dir = open_for_events(super::watched_dir(path)?)?;
+
+        // SAFETY: plain syscall; the descriptor is owned by `queue` below.
+        let kq = unsafe { libc::kqueue() };
+        if kq < 0 {
+            return Err(std::io::Error::last_os_error()).context("kqueue failed");
+        }
+        // SAFETY: `kq` is a fresh descriptor nothing else owns.
+        let queue = unsafe { File::from_raw_fd(kq) };
+
+        loop {
+            // Saves that replace the file leave the old one watched, so open
+            // it again each time. Closing it removes its event.
+            let file = open_for_events(path).ok();
+            let mut changes = vec![vnode_event(dir.as_raw_fd(), libc::NOTE_WRITE)];
+            if let Some(file) = &file {
+                changes.push(vnode_event(
+                    file.as_raw_fd(),
+                    libc::NOTE_WRITE
+                        | libc::NOTE_EXTEND
+                        | libc::NOTE_DELETE
+                        | libc::NOTE_RENAME
+                        | libc::NOTE_ATTRIB,
+                ));
+            }
+            // SAFETY: an all-zero kevent is a valid value to be overwritten.
+            let mut fired: [libc::kevent; 2] = unsafe { std::mem::zeroed() };
+            // SAFETY: both buffers outlive the call and their lengths are
+            // passed with them.
+            let n = unsafe {
+                libc::kevent(
+                    queue.as_raw_fd(),
+                    changes.as_ptr(),
+                    changes.len() as libc::c_int,
+                    fired.as_mut_ptr(),
+                    fired.len() as libc::c_int,
+                    std::ptr::null(),
+                )
+            };
+            if n < 0 {
+                let err = std::io::Error::last_os_error();
+                if err.kind() == ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err).context("kevent failed");
+            }
+            on_event();
+        }
+    }
+
+    fn open_for_events(path: &Path) -> Result<File> {
+        OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_EVTONLY)
+            .open(path)
+            .with_context(|| format!("cannot open {}", path.display()))
+    }
+
+    fn vnode_event(fd: RawFd, fflags: u32) -> libc::kevent {
+        libc::kevent {
+            ident: fd as libc::uintptr_t,
+            filter: libc::EVFILT_VNODE,
+            flags: libc::EV_ADD | libc::EV_CLEAR,
+            fflags,
+            data: 0,
+            udata: std::ptr::null_mut(),
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod os {
+    use anyhow::{Result, bail};
+    use std::path::Path;
+
+    pub fn wait_for_changes(_path: &Path, _on_event: &mut dyn FnMut()) -> Result<()> {
+        bail!("no file watcher on this platform")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Backend;
+    use crate::runner;
+
+    const CORE: &str = r#"
+[[shares]]
+name = "CORE"
+username = "admin"
+thunderbolt_host = "10.10.10.1"
+fallback_host = "nas.old.local"
+share_name = "CORE"
+"#;
+
+    fn share(name: &str, fallback_host: &str) -> ShareConfig {
+        ShareConfig {
+            name: name.to_string(),
+            username: "admin".to_string(),
+            thunderbolt_host: "10.10.10.1".to_string(),
+            fallback_host: fallback_host.to_string(),
+            share_name: name.to_string(),
+            ..ShareConfig::default()
+        }
+    }
+
+    fn on_fallback(share: &str) -> RuntimeState {
+        let mut state = RuntimeState::default();
+        state
+            .shares
+            .entry(share.to_ascii_lowercase())
+            .or_default()
+            .active_backend = Some(Backend::Fallback);
+        state
+    }
+
+    fn replay_fixture(name: &str) -> runner::replay::Guard {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures/replay")
+            .join(name);
+        let text = fs::read_to_string(&path).unwrap();
+        runner::replay::install(runner::parse_fixture(&text).unwrap())
+    }
+
+    #[test]
+    fn diff_separates_added_removed_and_reconnected_shares() {
+        let old = Config {
+            shares: vec![share("CORE", "nas.local"), share("DATA", "nas.local")],
+            ..Config::default()
+        };
+        let mut hooked = share("CORE", "nas.local");
+        hooked.hooks.post_mount = Some("~/bin/reindex".to_string());
+        let new = Config {
+            shares: vec![
+                hooked,
+                share("data", "nas2.local"),
+                share("MEDIA", "nas.local"),
+            ],
+            ..Config::default()
+        };
+
+        let diff = ConfigDiff::between(&old, &new);
+        assert_eq!(diff.added, vec!["MEDIA".to_string()]);
+        assert!(diff.removed.is_empty());
+        // A new hook needs no remount; a new host does.
+        let changed: Vec<&str> = diff
+            .changed
+            .iter()
+            .map(|s| s.fallback_host.as_str())
+            .collect();
+        assert_eq!(changed, vec!["nas.local"]);
+
+        let diff = ConfigDiff::between(&new, &Config::default());
+        assert_eq!(diff.removed.len(), 3);
+        let diff = ConfigDiff::between(&old, &old);
+        assert!(diff.added.is_empty() && diff.removed.is_empty() && diff.changed.is_empty());
+    }
+
+    #[test]
+    fn invalid_edit_keeps_the_previous_config_until_fixed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, CORE).unwrap();
+        let mut watcher = ConfigWatcher::open_at(path.clone());
+        let mut state = RuntimeState::default();
+        assert_eq!(watcher.config().shares.len(), 1);
+        assert!(watcher.update(&mut state).is_empty());
+
+        fs::write(&path, CORE.replace("name = \"CORE\"", "name = \"\"")).unwrap();
+        let messages = watcher.update(&mut state);
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].starts_with("config.toml is invalid"));
+        assert!(watcher.error().unwrap().contains("empty name"));
+        assert_eq!(watcher.config().shares[0].name, "CORE");
+        // Reported once, not on every pass.
+        assert!(watcher.update(&mut state).is_empty());
+
+        fs::write(
+            &path,
+            format!("{}\n[global]\ncheck_interval_secs = 5\n", CORE),
+        )
+        .unwrap();
+        assert!(watcher.update(&mut state).is_empty());
+        assert_eq!(watcher.error(), None);
+        assert_eq!(watcher.config().global.check_interval_secs, 5);
+    }
+
+    #[test]
+    fn host_change_remounts_once_the_share_is_idle() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, CORE).unwrap();
+        let mut watcher = ConfigWatcher::open_at(path.clone());
+        let mut state = on_fallback("CORE");
+
+        let guard = replay_fixture("config_change_remount.jsonl");
+        fs::write(&path, CORE.replace("nas.old.local", "nas.local")).unwrap();
+        assert_eq!(
+            watcher.update(&mut state),
+            vec![
+                "CORE: connection settings changed, remounting".to_string(),
+                "CORE: deferred: open files detected, will retry".to_string(),
+            ]
+        );
+        assert_eq!(state.shares["core"].active_backend, Some(Backend::Fallback));
+
+        assert_eq!(
+            watcher.update(&mut state),
+            vec!["CORE: unmounted to remount".to_string()]
+        );
+        assert_eq!(state.shares["core"].active_backend, None);
+        assert!(watcher.update(&mut state).is_empty());
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        assert_eq!(guard.unconsumed(), 0);
+    }
+
+    #[test]
+    fn moved_mount_point_waits_for_the_old_one_even_if_reloaded_elsewhere() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, CORE).unwrap();
+        let mut watcher = ConfigWatcher::open_at(path.clone());
+        let mut state = on_fallback("CORE");
+        let old_mount = config::share_mount_path(&watcher.config().shares[0]);
+        let moved = format!("{}mount_point = \"/Volumes/CORE-2\"\n", CORE);
+
+        // Read while another process owned the shares: nothing is carried out.
+        let guard = replay_fixture("config_change_remount.jsonl");
+        fs::write(&path, &moved).unwrap();
+        assert!(watcher.reload());
+        assert_eq!(
+            watcher.update(&mut state),
+            vec![
+                "CORE: connection settings changed, remounting".to_string(),
+                "CORE: deferred: open files detected, will retry".to_string(),
+            ]
+        );
+        // Still in use at the old mount point, so not mounted at the new one.
+        assert_eq!(
+            config::share_mount_path(&watcher.config().shares[0]),
+            old_mount
+        );
+
+        assert_eq!(
+            watcher.update(&mut state),
+            vec!["CORE: unmounted to remount".to_string()]
+        );
+        assert_eq!(
+            watcher.config().shares[0].mount_point.as_deref(),
+            Some("/Volumes/CORE-2")
+        );
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        assert_eq!(guard.unconsumed(), 0);
+    }
+
+    #[test]
+    fn removed_share_follows_the_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let shares_root = dir.path().join("Shares");
+        let with_policy = |policy: &str, shares: &str| {
+            format!(
+                "[global]\nshares_root = \"{}\"\non_share_removed = \"{}\"\n{}",
+                shares_root.display(),
+                policy,
+                shares
+            )
+        };
+
+        // keep: nothing is touched, so no command runs.
+        let guard = runner::replay::install(Vec::new());
+        fs::write(&path, with_policy("keep", CORE)).unwrap();
+        let mut watcher = ConfigWatcher::open_at(path.clone());
+        let mut state = on_fallback("CORE");
+        fs::write(&path, with_policy("keep", "")).unwrap();
+        assert_eq!(
+            watcher.update(&mut state),
+            vec!["CORE: removed, left as it is".to_string()]
+        );
+        assert_eq!(state.shares["core"].active_backend, Some(Backend::Fallback));
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        drop(guard);
+
+        // cleanup: the share is not mounted, so its symlink and state go.
+        let _guard = runner::replay::install(
+            runner::parse_fixture(
+                r#"{"kind":"command","program":"mount","args":["-t","smbfs"],"output":{"code":0,"stdout":"","stderr":""}}"#,
+            )
+            .unwrap(),
+        );
+        fs::create_dir_all(&shares_root).unwrap();
+        std::os::unix::fs::symlink("/Volumes/CORE", shares_root.join("CORE")).unwrap();
+        fs::write(&path, with_policy("cleanup", CORE)).unwrap();
+        let mut watcher = ConfigWatcher::open_at(path.clone());
+        fs::write(&path, with_policy("cleanup", "")).unwrap();
+        assert_eq!(
+            watcher.update(&mut state),
+            vec![
+                "CORE: removed, cleaning up".to_string(),
+                "CORE: cleaned up".to_string(),
+            ]
+        );
+        assert!(!state.shares.contains_key("core"));
+        assert!(fs::symlink_metadata(shares_root.join("CORE")).is_err());
+    }
+
+    #[test]
+    fn removed_share_in_use_keeps_its_stable_path_until_unmounted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let shares_root = dir.path().join("Shares");
+        let with_cleanup = |shares: &str| {
+            format!(
+                "[global]\nshares_root = \"{}\"\non_share_removed = \"cleanup\"\n{}",
+                shares_root.display(),
+                shares
+            )
+        };
+        let _guard = runner::replay::install(
+            runner::parse_fixture(
+                r#"{"kind":"command","program":"mount","args":["-t","smbfs"],"output":{"code":0,"stdout":"//admin@nas.old.local/CORE on /Volumes/CORE (smbfs, nodev, nosuid, mounted by admin)\n","stderr":""}}
+{"kind":"open_files","path":"/Volumes/CORE","files":[{"pid":933,"command":"TextEdit","user":"admin","fd":"5r","access":"read","path":"/Volumes/CORE/notes.txt"}]}
+{"kind":"command","program":"mount","args":["-t","smbfs"],"output":{"code":0,"stdout":"//admin@nas.old.local/CORE on /Volumes/CORE (smbfs, nodev, nosuid, mounted by admin)\n","stderr":""}}
+{"kind":"open_files","path":"/Volumes/CORE","files":[]}
+{"kind":"command","program":"diskutil","args":["unmount","/Volumes/CORE"],"output":{"code":0,"stdout":"Volume CORE on /Volumes/CORE unmounted\n","stderr":""}}"#,
+            )
+            .unwrap(),
+        );
+        fs::create_dir_all(&shares_root).unwrap();
+        std::os::unix::fs::symlink("/Volumes/CORE", shares_root.join("CORE")).unwrap();
+        fs::write(&path, with_cleanup(CORE)).unwrap();
+        let mut watcher = ConfigWatcher::open_at(path.clone());
+        let mut state = on_fallback("CORE");
+
+        // TextEdit still has a file open: the mount and its stable path stay.
+        fs::write(&path, with_cleanup("")).unwrap();
+        assert_eq!(
+            watcher.update(&mut state),
+            vec![
+                "CORE: removed, cleaning up".to_string(),
+                "CORE: deferred: open files detected, will retry".to_string(),
+            ]
+        );
+        assert_eq!(state.shares["core"].active_backend, Some(Backend::Fallback));
+        assert!(fs::symlink_metadata(shares_root.join("CORE")).is_ok());
+
+        // Closed by the next pass: unmounted, then the symlink and state go.
+        assert_eq!(
+            watcher.update(&mut state),
+            vec!["CORE: cleaned up".to_string()]
+        );
+        assert!(!state.shares.contains_key("core"));
+        assert!(fs::symlink_metadata(shares_root.join("CORE")).is_err());
+    }
+
+    #[test]
+    fn watch_wakes_on_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, CORE).unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        watch(path.clone(), move || {
+            let _ = tx.send(());
+        })
+        .unwrap();
+        // Let the watcher set up before editing.
+        std::thread::sleep(Duration::from_millis(100));
+
+        // Another file in the directory changing is not an edit.
+        fs::write(dir.path().join("state.json"), "{}").unwrap();
+        assert!(rx.recv_timeout(Duration::from_millis(800)).is_err());
+
+        let tmp = dir.path().join("config.toml.tmp");
+        fs::write(&tmp, CORE.replace("nas.old.local", "nas.local")).unwrap();
+        fs::rename(&tmp, &path).unwrap();
+        rx.recv_timeout(Duration::from_secs(5))
+            .expect("no wake after config.toml was replaced");
+    }
+}
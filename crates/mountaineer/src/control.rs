@@ -0,0 +1,587 @@
+//! Control socket: CLI commands served by the running agent.
+//!
+//! The tray and `monitor` keep runtime state in memory and save it after
+//! every pass, so a CLI command that loads, changes and saves `state.json` on
+//! its own races them and can undo a switch. While an agent runs it listens
+//! on `~/.mountaineer/control.sock`, and `status`, `switch`, `mount`,
+//! `unmount`, `reload`, `dedupe`, `reauth` and `favorites remove --cleanup`
+//! send it JSON-RPC 2.0 requests instead, one JSON object per line. So does
+//! the menu of a tray that does not own reconciliation. The agent runs them
+//! against its own state between passes. Without an agent the CLI claims
+//! reconciliation and runs the command itself.
+
+use anyhow::{Context, Result, anyhow};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::mpsc::{self, Sender};
+use std::time::Duration;
+
+use crate::config::{self, Backend, Config, ShareConfig};
+use crate::engine::{self, RuntimeState, ShareStatus, StatusOutput};
+use crate::events::{self, ShareEventKind};
+use crate::ownership;
+
+pub const JSONRPC_VERSION: &str = "2.0";
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+/// The command ran in the agent and failed; `message` is the CLI error.
+pub const COMMAND_FAILED: i64 = 1;
+
+/// How long a client waits for the agent to answer. A switch unmounts and
+/// mounts, so this is generous.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// A command the agent can run, as JSON-RPC `method` and `params`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum Call {
+    Status,
+    Switch {
+        share: String,
+        to: Backend,
+        #[serde(default)]
+        force: bool,
+    },
+    Mount,
+    Unmount {
+        #[serde(default)]
+        force: bool,
+    },
+    Reload,
+    /// Unmount idle duplicate mounts of managed shares.
+    Dedupe,
+    /// Credentials for `share` were re-entered: resume and remount it.
+    Reauth {
+        share: String,
+    },
+    /// `share` was removed from config.toml: unmount it and drop its symlink.
+    Cleanup {
+        share: Box<ShareConfig>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Request {
+    jsonrpc: String,
+    /// Any JSON-RPC id, number or string; answered unchanged.
+    id: Value,
+    #[serde(flatten)]
+    call: Call,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Response {
+    jsonrpc: String,
+    id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+/// Result of [`switch_share`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwitchReply {
+    /// The share was already on the requested backend; nothing was done.
+    #[serde(default)]
+    pub already: bool,
+    #[serde(default)]
+    pub statuses: Vec<ShareStatus>,
+}
+
+/// Result of [`reauth_share`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReauthReply {
+    /// The share's automatic mounts were suspended for rejected credentials.
+    #[serde(default)]
+    pub was_suspended: bool,
+    #[serde(default)]
+    pub statuses: Vec<ShareStatus>,
+}
+
+/// A request waiting for the agent. Answer it with [`Incoming::respond`].
+pub struct Incoming {
+    call: Call,
+    reply: Sender<Result<Value, RpcError>>,
+}
+
+impl Incoming {
+    pub fn call(&self) -> &Call {
+        &self.call
+    }
+
+    pub fn respond(self, result: Result<Value, RpcError>) {
+        let _ = self.reply.send(result);
+    }
+}
+
+/// Listen on the control socket at `path`, handing each request to
+/// `dispatch`. Fails when another agent is already listening there.
+pub fn serve(path: &Path, dispatch: impl Fn(Incoming) + Send + Sync + 'static) -> Result<()> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(anyhow!(
+                "another agent is already listening on {}",
+                path.display()
+            ));
+        }
+        // Left behind by an agent that did not shut down cleanly.
+        std::fs::remove_file(path)
+            .with_context(|| format!("failed removing stale socket {}", path.display()))?;
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed creating {}", parent.display()))?;
+    }
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("failed listening on {}", path.display()))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("failed restricting {}", path.display()))?;
+    log::info!("control: listening on {}", path.display());
+
+    let dispatch = std::sync::Arc::new(dispatch);
+    std::thread::Builder::new()
+        .name("control-accept".into())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let dispatch = std::sync::Arc::clone(&dispatch);
+                        std::thread::spawn(move || serve_connection(stream, &*dispatch));
+                    }
+                    Err(e) => log::warn!("control: accept failed: {}", e),
+                }
+            }
+        })
+        .context("failed to spawn control socket thread")?;
+    Ok(())
+}
+
+/// [`serve`] on the default socket path.
+pub fn serve_default(dispatch: impl Fn(Incoming) + Send + Sync + 'static) -> Result<()> {
+    serve(&config::control_socket_path(), dispatch)
+}
+
+fn serve_connection(stream: UnixStream, dispatch: &(impl Fn(Incoming) + ?Sized)) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = answer(&line, dispatch);
+        let Ok(mut text) = serde_json::to_string(&response) else {
+            return;
+        };
+        text.push('\n');
+        if writer.write_all(text.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+fn answer(line: &str, dispatch: &(impl Fn(Incoming) + ?Sized)) -> Response {
+    let failure = |id: Value, code: i64, message: String| Response {
+        jsonrpc: JSONRPC_VERSION.to_string(),
+        id,
+        result: None,
+        error: Some(RpcError { code, message }),
+    };
+    let value: Value = match serde_json::from_str(line) {
+        Ok(value) => value,
+        Err(e) => return failure(Value::Null, PARSE_ERROR, format!("parse error: {}", e)),
+    };
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    let request: Request = match serde_json::from_value(value) {
+        Ok(request) => request,
+        Err(e) => return failure(id, INVALID_REQUEST, format!("invalid request: {}", e)),
+    };
+    if request.jsonrpc != JSONRPC_VERSION {
+        return failure(
+            id,
+            INVALID_REQUEST,
+            format!("unsupported jsonrpc version '{}'", request.jsonrpc),
+        );
+    }
+
+    log::info!("control: {:?}", request.call);
+    let (reply, result) = mpsc::channel();
+    dispatch(Incoming {
+        call: request.call,
+        reply,
+    });
+    match result.recv() {
+        Ok(Ok(result)) => Response {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        },
+        Ok(Err(error)) => Response {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: None,
+            error: Some(error),
+        },
+        Err(_) => failure(id, COMMAND_FAILED, "agent is shutting down".to_string()),
+    }
+}
+
+/// Send `call` to the agent listening at `path`. `None` when no agent is
+/// listening, so the caller runs the command itself.
+pub fn request_at<T: DeserializeOwned>(path: &Path, call: &Call) -> Option<Result<T>> {
+    let stream = match UnixStream::connect(path) {
+        Ok(stream) => stream,
+        Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::ConnectionRefused) => {
+            return None;
+        }
+        Err(e) => {
+            log::warn!("control: cannot reach agent at {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    log::info!("control: sending {:?} to the running agent", call);
+    Some(exchange(stream, call))
+}
+
+/// [`request_at`] on the default socket path.
+pub fn request<T: DeserializeOwned>(call: &Call) -> Option<Result<T>> {
+    request_at(&config::control_socket_path(), call)
+}
+
+fn exchange<T: DeserializeOwned>(stream: UnixStream, call: &Call) -> Result<T> {
+    stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    let mut text = serde_json::to_string(&Request {
+        jsonrpc: JSONRPC_VERSION.to_string(),
+        id: 1.into(),
+        call: call.clone(),
+    })?;
+    text.push('\n');
+    writer.write_all(text.as_bytes())?;
+
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .context("no answer from the running agent")?;
+    let response: Response = serde_json::from_str(&line)
+        .with_context(|| format!("unreadable answer from the running agent: {}", line.trim()))?;
+    if let Some(error) = response.error {
+        return Err(anyhow!(error.message));
+    }
+    serde_json::from_value(response.result.unwrap_or(Value::Null))
+        .context("unexpected answer from the running agent")
+}
+
+/// Run `call` against the agent's `state` and save it. `cfg` is the config
+/// the agent is using, so commands see the same one as its passes.
+pub fn execute(cfg: &Config, call: &Call, state: &mut RuntimeState) -> Result<Value, RpcError> {
+    run(cfg, call, state).map_err(|e| RpcError {
+        code: COMMAND_FAILED,
+        message: format!("{:#}", e),
+    })
+}
+
+fn run(cfg: &Config, call: &Call, state: &mut RuntimeState) -> Result<Value> {
+    let value = match call {
+        Call::Status => {
+            let shares = engine::verify_all(cfg, state);
+            serde_json::to_value(StatusOutput {
+                lsof_recheck: cfg.global.lsof_recheck,
+                shares,
+                reconciler: ownership::owner(),
+            })?
+        }
+        Call::Switch { share, to, force } => {
+            serde_json::to_value(switch_share(cfg, state, share, *to, *force)?)?
+        }
+        Call::Mount => serde_json::to_value(engine::mount_all(cfg, state))?,
+        Call::Unmount { force } => serde_json::to_value(engine::unmount_all(cfg, state, *force))?,
+        Call::Reload => serde_json::to_value(engine::reconcile_all(cfg, state))?,
+        Call::Dedupe => serde_json::to_value(engine::dedupe_mounts(cfg, true))?,
+        Call::Reauth { share } => serde_json::to_value(reauth_share(cfg, state, share)?)?,
+        Call::Cleanup { share } => {
+            serde_json::to_value(engine::cleanup_removed_share(cfg, state, share)?)?
+        }
+    };
+    engine::save_runtime_state(state)?;
+    Ok(value)
+}
+
+/// Resume `share_name` after its credentials were re-entered and remount it,
+/// shared by `reauth`, the tray and the agent.
+pub fn reauth_share(
+    cfg: &Config,
+    state: &mut RuntimeState,
+    share_name: &str,
+) -> Result<ReauthReply> {
+    let share = config::find_share(cfg, share_name)
+        .ok_or_else(|| anyhow!("share '{}' is not configured", share_name))?;
+    let was_suspended = engine::resume_after_credentials(state, &share.name);
+    let statuses = engine::reconcile_selected(cfg, state, std::slice::from_ref(&share.name))?;
+    Ok(ReauthReply {
+        was_suspended,
+        statuses,
+    })
+}
+
+/// Manual switch of one share, shared by `switch` and the agent. Saves state
+/// and publishes the switch event when it happened.
+pub fn switch_share(
+    cfg: &Config,
+    state: &mut RuntimeState,
+    share_name: &str,
+    to: Backend,
+    force: bool,
+) -> Result<SwitchReply> {
+    let share = config::find_share(cfg, share_name)
+        .ok_or_else(|| anyhow!("share '{}' is not configured", share_name))?
+        .clone();
+
+    let from = state
+        .shares
+        .get(&share_name.to_ascii_lowercase())
+        .and_then(|e| e.active_backend)
+        .ok_or_else(|| {
+            anyhow!(
+                "share '{}' has no active backend to switch from",
+                share_name
+            )
+        })?;
+
+    if from == to {
+        return Ok(SwitchReply {
+            already: true,
+            statuses: Vec::new(),
+        });
+    }
+
+    match engine::switch_backend_single_mount(
+        cfg,
+        state,
+        &share,
+        from,
+        to,
+        force,
+        engine::SwitchReason::Manual,
+    ) {
+        engine::SwitchResult::Success => {
+            engine::save_runtime_state(state)?;
+            let statuses = engine::verify_all(cfg, state);
+            events::publish_for(cfg, ShareEventKind::Switch, &statuses, &share.name);
+            Ok(SwitchReply {
+                already: false,
+                statuses,
+            })
+        }
+        engine::SwitchResult::BusyOpenFiles => {
+            let leases = engine::live_leases(state, &share.name, chrono::Utc::now());
+            if leases.is_empty() {
+                Err(anyhow!(
+                    "cannot switch '{}': open files detected. Close files and retry, or use --force",
+                    share_name
+                ))
+            } else {
+                Err(anyhow!(
+                    "cannot switch '{}': held by {} lease(s) ({}). Release them, or use --force",
+                    share_name,
+                    leases.len(),
+                    leases
+                        .iter()
+                        .map(|l| l.token.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            }
+        }
+        engine::SwitchResult::Vetoed(e) => Err(anyhow!(
+            "cannot switch '{}': vetoed by pre-switch hook: {}",
+            share_name,
+            e
+        )),
+        engine::SwitchResult::UnmountFailed(e) => Err(anyhow!(
+            "cannot switch '{}': unmount failed: {}",
+            share_name,
+            e
+        )),
+        engine::SwitchResult::MountFailed {
+            error,
+            rolled_back,
+            kind,
+        } => {
+            if rolled_back {
+                engine::save_runtime_state(state)?;
+            }
+            Err(anyhow!(
+                "cannot switch '{}': mount failed: {} (rolled back: {})\nhint: {}",
+                share_name,
+                error,
+                rolled_back,
+                kind.hint()
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_exchange(path: &Path, line: &str) -> Value {
+        let mut stream = UnixStream::connect(path).unwrap();
+        stream.write_all(format!("{}\n", line).as_bytes()).unwrap();
+        let mut answer = String::new();
+        BufReader::new(stream).read_line(&mut answer).unwrap();
+        serde_json::from_str(&answer).unwrap()
+    }
+
+    #[test]
+    fn call_encodes_as_jsonrpc_method_and_params() {
+        let request = Request {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id: 7.into(),
+            call: Call::Switch {
+                share: "CORE".to_string(),
+                to: Backend::Tb,
+                force: false,
+            },
+        };
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 7,
+                "method": "switch",
+                "params": {"share": "CORE", "to": "tb", "force": false},
+            })
+        );
+        let status: Request =
+            serde_json::from_str(r#"{"jsonrpc":"2.0","id":1,"method":"status"}"#).unwrap();
+        assert_eq!(status.call, Call::Status);
+        let unmount: Request =
+            serde_json::from_str(r#"{"jsonrpc":"2.0","id":2,"method":"unmount","params":{}}"#)
+                .unwrap();
+        assert_eq!(unmount.call, Call::Unmount { force: false });
+        let cleanup: Request = serde_json::from_str(
+            r#"{"jsonrpc":"2.0","id":3,"method":"cleanup","params":{"share":{
+                "name":"CORE","username":"admin","thunderbolt_host":"10.10.10.1",
+                "fallback_host":"nas.local","share_name":"CORE"}}}"#,
+        )
+        .unwrap();
+        match cleanup.call {
+            Call::Cleanup { share } => assert_eq!(share.fallback_host, "nas.local"),
+            other => panic!("expected Cleanup, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn agent_answers_requests_over_the_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("control.sock");
+        serve(&path, |incoming: Incoming| {
+            let result = match incoming.call() {
+                Call::Switch { share, .. } if share == "CORE" => Ok(serde_json::json!({
+                    "already": true,
+                })),
+                Call::Switch { share, .. } => Err(RpcError {
+                    code: COMMAND_FAILED,
+                    message: format!("share '{}' is not configured", share),
+                }),
+                _ => Ok(Value::Null),
+            };
+            incoming.respond(result);
+        })
+        .unwrap();
+
+        let reply: SwitchReply = request_at(
+            &path,
+            &Call::Switch {
+                share: "CORE".to_string(),
+                to: Backend::Tb,
+                force: false,
+            },
+        )
+        .unwrap()
+        .unwrap();
+        assert!(reply.already);
+
+        let err = request_at::<SwitchReply>(
+            &path,
+            &Call::Switch {
+                share: "NOPE".to_string(),
+                to: Backend::Tb,
+                force: false,
+            },
+        )
+        .unwrap()
+        .unwrap_err();
+        assert_eq!(err.to_string(), "share 'NOPE' is not configured");
+
+        // A second agent cannot take the socket over.
+        assert!(
+            serve(&path, |incoming: Incoming| incoming
+                .respond(Ok(Value::Null)))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn malformed_requests_get_jsonrpc_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("control.sock");
+        serve(&path, |incoming: Incoming| {
+            incoming.respond(Ok(Value::Null))
+        })
+        .unwrap();
+
+        let answer = raw_exchange(&path, "{not json");
+        assert_eq!(answer["error"]["code"], PARSE_ERROR);
+        assert_eq!(answer["id"], Value::Null);
+
+        let answer = raw_exchange(&path, r#"{"jsonrpc":"2.0","id":4,"method":"reboot"}"#);
+        assert_eq!(answer["error"]["code"], INVALID_REQUEST);
+        assert_eq!(answer["id"], 4);
+
+        let answer = raw_exchange(&path, r#"{"jsonrpc":"2.0","id":5,"method":"reload"}"#);
+        assert_eq!(answer["id"], 5);
+        assert!(answer.get("error").is_none());
+
+        // String ids are answered unchanged.
+        let answer = raw_exchange(&path, r#"{"jsonrpc":"2.0","id":"a-1","method":"status"}"#);
+        assert_eq!(answer["id"], "a-1");
+        assert!(answer.get("error").is_none());
+    }
+
+    #[test]
+    fn no_agent_means_run_directly() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("control.sock");
+        assert!(request_at::<Value>(&path, &Call::Status).is_none());
+
+        // A socket file left by a dead agent is treated the same, and the
+        // next agent replaces it.
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(request_at::<Value>(&path, &Call::Status).is_none());
+        serve(&path, |incoming: Incoming| {
+            incoming.respond(Ok(Value::Null))
+        })
+        .unwrap();
+        assert!(request_at::<Value>(&path, &Call::Status).is_some());
+    }
+}
@@ -0,0 +1,318 @@
+//! Passphrase-encrypted credential file for hosts without a Keychain.
+//!
+//! The file holds a JSON envelope with the Argon2id parameters, salt and nonce
+//! alongside a ChaCha20-Poly1305 ciphertext of the `user@host -> password` map.
+//! Every write re-encrypts with a fresh salt and nonce.
+
+use anyhow::{Context, Result, anyhow, bail};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::CredentialProvider;
+use crate::network::host as net_host;
+
+const FORMAT_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+
+type Entries = BTreeMap<String, String>;
+
+/// Argon2id cost parameters, stored in the file so they can be raised later
+/// without breaking existing files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct KdfParams {
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Envelope {
+    version: u32,
+    kdf: KdfParams,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+pub struct EncryptedFileProvider {
+    path: PathBuf,
+    passphrase: String,
+    params: KdfParams,
+}
+
+impl EncryptedFileProvider {
+    pub fn new(path: PathBuf, passphrase: String) -> Result<Self> {
+        if passphrase.is_empty() {
+            bail!("credential file passphrase must not be empty");
+        }
+        Ok(Self {
+            path,
+            passphrase,
+            params: KdfParams::default(),
+        })
+    }
+
+    /// Cheap KDF parameters so tests do not spend seconds in Argon2.
+    #[cfg(test)]
+    fn for_tests(path: PathBuf, passphrase: &str) -> Self {
+        Self {
+            path,
+            passphrase: passphrase.to_string(),
+            params: KdfParams {
+                m_cost: 8,
+                t_cost: 1,
+                p_cost: 1,
+            },
+        }
+    }
+
+    fn load(&self) -> Result<Entries> {
+        if !self.path.exists() {
+            return Ok(Entries::new());
+        }
+        let text = fs::read_to_string(&self.path)
+            .with_context(|| format!("failed reading {}", self.path.display()))?;
+        let envelope: Envelope = serde_json::from_str(&text)
+            .with_context(|| format!("failed parsing {}", self.path.display()))?;
+        if envelope.version != FORMAT_VERSION {
+            bail!(
+                "unsupported credential file version {} in {}",
+                envelope.version,
+                self.path.display()
+            );
+        }
+
+        let salt = hex_decode(&envelope.salt)?;
+        let nonce = hex_decode(&envelope.nonce)?;
+        let ciphertext = hex_decode(&envelope.ciphertext)?;
+        if nonce.len() != 12 {
+            bail!("corrupted credential file {}", self.path.display());
+        }
+
+        let cipher = self.cipher(&salt, envelope.kdf)?;
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+            .map_err(|_| {
+                anyhow!(
+                    "cannot decrypt {}: wrong passphrase or corrupted file",
+                    self.path.display()
+                )
+            })?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    fn store(&self, entries: &Entries) -> Result<()> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+        let cipher = self.cipher(&salt, self.params)?;
+        let plaintext = serde_json::to_vec(entries)?;
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext.as_ref())
+            .map_err(|_| anyhow!("failed to encrypt credentials"))?;
+
+        let envelope = Envelope {
+            version: FORMAT_VERSION,
+            kdf: self.params,
+            salt: hex_encode(&salt),
+            nonce: hex_encode(&nonce),
+            ciphertext: hex_encode(&ciphertext),
+        };
+        write_private(&self.path, &serde_json::to_string_pretty(&envelope)?)
+    }
+
+    fn cipher(&self, salt: &[u8], kdf: KdfParams) -> Result<ChaCha20Poly1305> {
+        let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(32))
+            .map_err(|e| anyhow!("invalid credential file KDF parameters: {}", e))?;
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(self.passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| anyhow!("failed to derive credential file key: {}", e))?;
+        Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+}
+
+impl CredentialProvider for EncryptedFileProvider {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn get(&self, host: &str, username: &str) -> Result<Option<String>> {
+        Ok(self.load()?.remove(&entry_key(host, username)))
+    }
+
+    fn set(&self, host: &str, username: &str, password: &str) -> Result<()> {
+        let mut entries = self.load()?;
+        entries.insert(entry_key(host, username), password.to_string());
+        self.store(&entries)
+    }
+
+    fn remove(&self, host: &str, username: &str) -> Result<bool> {
+        let mut entries = self.load()?;
+        if entries.remove(&entry_key(host, username)).is_none() {
+            return Ok(false);
+        }
+        self.store(&entries)?;
+        Ok(true)
+    }
+}
+
+/// `user@host` with the host in the canonical form [`net_host::same`] compares,
+/// so every spelling of a server finds the same entry.
+fn entry_key(host: &str, username: &str) -> String {
+    let host = net_host::display(host).to_ascii_lowercase();
+    format!("{}@{}", username, host)
+}
+
+/// Atomic tmp+rename write with owner-only permissions.
+fn write_private(path: &Path, contents: &str) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed creating {}", parent.display()))?;
+    }
+    let tmp_path = path.with_extension("enc.tmp");
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp_path)
+        .with_context(|| format!("failed writing {}", tmp_path.display()))?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path).with_context(|| {
+        format!(
+            "failed renaming {} to {}",
+            tmp_path.display(),
+            path.display()
+        )
+    })?;
+    Ok(())
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hex_decode(text: &str) -> Result<Vec<u8>> {
+    if !text.is_ascii() || !text.len().is_multiple_of(2) {
+        bail!("invalid hex in credential file");
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&text[i..i + 2], 16)
+                .map_err(|_| anyhow!("invalid hex in credential file"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_get_remove_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let provider = EncryptedFileProvider::for_tests(dir.path().join("c.enc"), "pass");
+
+        assert_eq!(provider.get("10.0.0.1", "admin").unwrap(), None);
+        provider.set("10.0.0.1", "admin", "s3cret").unwrap();
+        provider.set("NAS.local", "admin", "other").unwrap();
+
+        assert_eq!(
+            provider.get("10.0.0.1", "admin").unwrap().as_deref(),
+            Some("s3cret")
+        );
+        assert_eq!(
+            provider.get("nas.local", "admin").unwrap().as_deref(),
+            Some("other")
+        );
+        assert_eq!(provider.get("10.0.0.1", "guest").unwrap(), None);
+
+        assert!(provider.remove("10.0.0.1", "admin").unwrap());
+        assert!(!provider.remove("10.0.0.1", "admin").unwrap());
+        assert_eq!(provider.get("10.0.0.1", "admin").unwrap(), None);
+    }
+
+    #[test]
+    fn host_spellings_share_an_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let provider = EncryptedFileProvider::for_tests(dir.path().join("c.enc"), "pass");
+
+        provider.set("[fe80::1%en0]", "admin", "s3cret").unwrap();
+        assert_eq!(
+            provider.get("fe80::1%en0", "admin").unwrap().as_deref(),
+            Some("s3cret")
+        );
+        assert_eq!(
+            provider.get("FE80:0::1%en0", "admin").unwrap().as_deref(),
+            Some("s3cret")
+        );
+        assert!(provider.remove("fe80::1%25en0", "admin").unwrap());
+    }
+
+    #[test]
+    fn file_does_not_contain_plaintext_and_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("c.enc");
+        let provider = EncryptedFileProvider::for_tests(path.clone(), "pass");
+        provider.set("10.0.0.1", "admin", "s3cret").unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("s3cret"));
+        assert!(!text.contains("admin"));
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn wrong_passphrase_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("c.enc");
+        EncryptedFileProvider::for_tests(path.clone(), "right")
+            .set("10.0.0.1", "admin", "s3cret")
+            .unwrap();
+
+        let err = EncryptedFileProvider::for_tests(path, "wrong")
+            .get("10.0.0.1", "admin")
+            .unwrap_err();
+        assert!(err.to_string().contains("wrong passphrase"));
+    }
+
+    #[test]
+    fn empty_passphrase_is_rejected() {
+        assert!(EncryptedFileProvider::new(PathBuf::from("/tmp/x.enc"), String::new()).is_err());
+    }
+
+    #[test]
+    fn hex_roundtrip() {
+        let bytes = [0u8, 1, 0xab, 0xff];
+        assert_eq!(hex_encode(&bytes), "0001abff");
+        assert_eq!(hex_decode("0001abff").unwrap(), bytes);
+        assert!(hex_decode("abc").is_err());
+        assert!(hex_decode("zz").is_err());
+    }
+}
@@ -0,0 +1,204 @@
+//! SMB passwords in the user's login Keychain.
+//!
+//! Finder and `mount_smbfs` look SMB credentials up as internet passwords keyed
+//! by server and account, so saving the item here is all a later mount needs.
+//! The password is passed to `security` on stdin (interactive mode) so it never
+//! appears in the process list.
+
+use anyhow::{Result, anyhow};
+
+use super::CredentialProvider;
+use crate::runner;
+
+/// Keychain protocol code for SMB internet passwords (four characters, padded).
+const SMB_PROTOCOL: &str = "smb ";
+
+/// `security` exit status when no matching item exists.
+const ITEM_NOT_FOUND: i32 = 44;
+
+pub struct KeychainProvider;
+
+impl CredentialProvider for KeychainProvider {
+    fn name(&self) -> &'static str {
+        "keychain"
+    }
+
+    fn get(&self, host: &str, username: &str) -> Result<Option<String>> {
+        // `-w` prints the password itself; keep it out of recordings.
+        let output = runner::run_secret(
+            "security",
+            &[
+                "find-internet-password",
+                "-a",
+                username,
+                "-s",
+                host,
+                "-r",
+                SMB_PROTOCOL,
+                "-w",
+            ],
+        )
+        .map_err(|e| anyhow!("failed to run security: {}", e))?;
+        if output.code == Some(ITEM_NOT_FOUND) {
+            return Ok(None);
+        }
+        if !output.success() {
+            return Err(anyhow!(
+                "failed to read Keychain password for {}@{}: {}",
+                username,
+                host,
+                output.stderr.trim()
+            ));
+        }
+        Ok(Some(output.stdout.trim_end_matches('\n').to_string()))
+    }
+
+    fn set(&self, host: &str, username: &str, password: &str) -> Result<()> {
+        let script = add_password_command(host, username, password);
+        let output = runner::run_with_input("security", &["-i"], &script)
+            .map_err(|e| anyhow!("failed to run security: {}", e))?;
+        let stderr = output.stderr.trim();
+        if !output.success() || !stderr.is_empty() {
+            return Err(anyhow!(
+                "failed to save Keychain password for {}@{}: {}",
+                username,
+                host,
+                stderr
+            ));
+        }
+        Ok(())
+    }
+
+    fn remove(&self, host: &str, username: &str) -> Result<bool> {
+        let output = runner::run(
+            "security",
+            &[
+                "delete-internet-password",
+                "-a",
+                username,
+                "-s",
+                host,
+                "-r",
+                SMB_PROTOCOL,
+            ],
+        )
+        .map_err(|e| anyhow!("failed to run security: {}", e))?;
+        match output.code {
+            Some(0) => Ok(true),
+            Some(ITEM_NOT_FOUND) => Ok(false),
+            _ => Err(anyhow!(
+                "failed to remove Keychain password for {}@{}: {}",
+                username,
+                host,
+                output.stderr.trim()
+            )),
+        }
+    }
+}
+
+/// Build the `security -i` command line that adds or updates the item.
+fn add_password_command(host: &str, account: &str, password: &str) -> String {
+    format!(
+        "add-internet-password -U -a {} -s {} -r {} -w {}\n",
+        quote(account),
+        quote(host),
+        quote(SMB_PROTOCOL),
+        quote(password)
+    )
+}
+
+/// Double-quote an argument for `security -i`, escaping `\` and `"`.
+fn quote(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{CommandOutput, Interaction};
+
+    fn security(args: &[&str], code: i32, stdout: &str) -> Interaction {
+        Interaction::Command {
+            program: "security".to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            output: Some(CommandOutput {
+                code: Some(code),
+                stdout: stdout.to_string(),
+                stderr: String::new(),
+            }),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn add_password_command_quotes_every_argument() {
+        assert_eq!(
+            add_password_command("10.0.0.1", "admin", "hunter2"),
+            "add-internet-password -U -a \"admin\" -s \"10.0.0.1\" -r \"smb \" -w \"hunter2\"\n"
+        );
+    }
+
+    #[test]
+    fn quote_escapes_quotes_and_backslashes() {
+        assert_eq!(quote(r#"p"a\ss word"#), r#""p\"a\\ss word""#);
+    }
+
+    #[test]
+    fn get_reads_password_and_treats_missing_item_as_none() {
+        let find = |host| {
+            [
+                "find-internet-password",
+                "-a",
+                "admin",
+                "-s",
+                host,
+                "-r",
+                "smb ",
+                "-w",
+            ]
+        };
+        let _guard = runner::replay::install(vec![
+            security(&find("10.0.0.1"), 0, "s3cret\n"),
+            security(&find("nas.local"), ITEM_NOT_FOUND, ""),
+        ]);
+
+        let provider = KeychainProvider;
+        assert_eq!(
+            provider.get("10.0.0.1", "admin").unwrap().as_deref(),
+            Some("s3cret")
+        );
+        assert_eq!(provider.get("nas.local", "admin").unwrap(), None);
+    }
+
+    #[test]
+    fn remove_reports_whether_an_item_existed() {
+        let delete = |host| {
+            [
+                "delete-internet-password",
+                "-a",
+                "admin",
+                "-s",
+                host,
+                "-r",
+                "smb ",
+            ]
+        };
+        let _guard = runner::replay::install(vec![
+            security(&delete("10.0.0.1"), 0, ""),
+            security(&delete("nas.local"), ITEM_NOT_FOUND, ""),
+        ]);
+
+        let provider = KeychainProvider;
+        assert!(provider.remove("10.0.0.1", "admin").unwrap());
+        assert!(!provider.remove("nas.local", "admin").unwrap());
+    }
+}
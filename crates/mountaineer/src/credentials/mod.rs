@@ -0,0 +1,125 @@
+//! Where SMB passwords are stored and how mounts obtain them.
+//!
+//! `global.credential_store` selects the provider:
+//! - `keychain` (default): the macOS login Keychain. Finder and `mount_smbfs`
+//!   read it themselves, so mounts do not need the password passed in.
+//! - `file`: a passphrase-encrypted file at `~/.mountaineer/credentials.enc`,
+//!   for machines without a usable Keychain (headless or Linux). The password
+//!   is handed to the mount commands on stdin, never in argv.
+
+mod file;
+mod keychain;
+
+use anyhow::{Result, anyhow};
+
+use crate::config::{self, CredentialStore, GlobalConfig, ShareConfig};
+use crate::network::host;
+
+pub use file::EncryptedFileProvider;
+pub use keychain::KeychainProvider;
+
+/// Environment variable holding the passphrase for the `file` store.
+pub const PASSPHRASE_ENV: &str = "MOUNTAINEER_CREDENTIALS_PASSPHRASE";
+
+/// A store of SMB passwords keyed by server host and account name.
+pub trait CredentialProvider {
+    fn name(&self) -> &'static str;
+    fn get(&self, host: &str, username: &str) -> Result<Option<String>>;
+    fn set(&self, host: &str, username: &str, password: &str) -> Result<()>;
+    /// Returns whether a stored credential was removed.
+    fn remove(&self, host: &str, username: &str) -> Result<bool>;
+}
+
+/// Open the configured store. `passphrase` overrides [`PASSPHRASE_ENV`] for
+/// the `file` store and is ignored for the Keychain.
+pub fn provider(
+    global: &GlobalConfig,
+    passphrase: Option<String>,
+) -> Result<Box<dyn CredentialProvider>> {
+    match global.credential_store {
+        CredentialStore::Keychain => Ok(Box::new(KeychainProvider)),
+        CredentialStore::File => {
+            let passphrase = passphrase
+                .or_else(|| std::env::var(PASSPHRASE_ENV).ok())
+                .ok_or_else(|| {
+                    anyhow!(
+                        "the file credential store needs a passphrase; set {}",
+                        PASSPHRASE_ENV
+                    )
+                })?;
+            Ok(Box::new(EncryptedFileProvider::new(
+                config::credentials_path(),
+                passphrase,
+            )?))
+        }
+    }
+}
+
+/// Distinct hosts a share mounts from (TB first), for storing one credential
+/// per server.
+pub fn share_hosts(share: &ShareConfig) -> Vec<&str> {
+    let mut hosts = vec![share.thunderbolt_host.as_str()];
+    if !host::same(&share.fallback_host, &share.thunderbolt_host) {
+        hosts.push(share.fallback_host.as_str());
+    }
+    hosts
+}
+
+/// Password to pass explicitly to a mount of `share` from `host`.
+///
+/// `None` for the Keychain store (the system looks it up) and when the file
+/// store has no entry or cannot be opened; the mount then proceeds without one.
+pub fn mount_password(global: &GlobalConfig, share: &ShareConfig, host: &str) -> Option<String> {
+    if global.credential_store == CredentialStore::Keychain {
+        return None;
+    }
+    let lookup = provider(global, None).and_then(|p| p.get(host, &share.username));
+    match lookup {
+        Ok(password) => password,
+        Err(e) => {
+            log::warn!("{}: cannot read stored credentials: {}", share.name, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn share(tb: &str, fb: &str) -> ShareConfig {
+        ShareConfig {
+            name: "CORE".to_string(),
+            username: "admin".to_string(),
+            thunderbolt_host: tb.to_string(),
+            fallback_host: fb.to_string(),
+            share_name: "CORE".to_string(),
+            ..ShareConfig::default()
+        }
+    }
+
+    #[test]
+    fn share_hosts_deduplicates_same_server() {
+        assert_eq!(
+            share_hosts(&share("10.0.0.1", "nas.local")),
+            vec!["10.0.0.1", "nas.local"]
+        );
+        assert_eq!(
+            share_hosts(&share("nas.local", "NAS.local")),
+            vec!["nas.local"]
+        );
+        assert_eq!(
+            share_hosts(&share("fe80::1%bridge0", "[FE80::1%bridge0]")),
+            vec!["fe80::1%bridge0"]
+        );
+    }
+
+    #[test]
+    fn keychain_store_never_passes_password() {
+        let global = GlobalConfig::default();
+        assert_eq!(
+            mount_password(&global, &share("10.0.0.1", "nas.local"), "10.0.0.1"),
+            None
+        );
+    }
+}
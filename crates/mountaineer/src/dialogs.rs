@@ -10,6 +10,7 @@ use std::ffi::CStr;
 use std::path::{Path, PathBuf};
 
 use crate::config::Backend;
+use crate::open_files::{self, OpenFile};
 
 /// Result from the "Add Favorite" dialog.
 pub struct AddFavoriteInput {
@@ -27,6 +28,14 @@ pub struct RemoveFavoriteChoice {
     pub cleanup: bool,
 }
 
+/// Result from the "Adopt Existing Mount" dialog.
+pub struct AdoptMountInput {
+    pub share_name: String,
+    /// Which role the already-mounted host plays in the new favorite.
+    pub mounted_as: Backend,
+    pub alternate_host: String,
+}
+
 /// Result from the "Add Alias" dialog.
 pub struct AddAliasInput {
     pub alias_name: String,
@@ -92,6 +101,14 @@ unsafe fn make_text_field(placeholder: &str, frame: NSRect) -> *mut Object {
     field
 }
 
+/// Create an NSSecureTextField that masks its input.
+unsafe fn make_secure_field(placeholder: &str, frame: NSRect) -> *mut Object {
+    let field: *mut Object = msg_send![class!(NSSecureTextField), alloc];
+    let field: *mut Object = msg_send![field, initWithFrame: frame];
+    let _: () = msg_send![field, setPlaceholderString: unsafe { nsstring(placeholder) }];
+    field
+}
+
 /// Show a native macOS form dialog to collect "Add Favorite" fields.
 ///
 /// Uses NSAlert with an accessory view containing labeled text fields.
@@ -218,6 +235,123 @@ pub fn show_add_favorite_dialog() -> Option<AddFavoriteInput> {
     }
 }
 
+/// Show a form to turn an existing SMB mount into a favorite.
+///
+/// `suggestion` prefills the role of the mounted host and the other host,
+/// e.g. from a favorite that already pairs them. Returns `None` if cancelled.
+///
+/// # Safety
+/// Must be called from the main thread (AppKit requirement).
+pub fn show_adopt_mount_dialog(
+    description: &str,
+    default_name: &str,
+    suggestion: Option<(Backend, String)>,
+) -> Option<AdoptMountInput> {
+    unsafe {
+        let alert: *mut Object = msg_send![class!(NSAlert), new];
+        let _: () = msg_send![alert, setMessageText: nsstring("Adopt Existing Mount")];
+        let _: () = msg_send![alert, setInformativeText: nsstring(description)];
+        // NSAlertStyleInformational = 1
+        let _: () = msg_send![alert, setAlertStyle: 1i64];
+
+        let _: () = msg_send![alert, addButtonWithTitle: nsstring("Adopt")];
+        let _: () = msg_send![alert, addButtonWithTitle: nsstring("Cancel")];
+
+        let width: f64 = 300.0;
+        let field_height: f64 = 24.0;
+        let label_height: f64 = 17.0;
+        let gap: f64 = 2.0;
+        let spacing: f64 = 8.0;
+        let pair_height = label_height + gap + field_height;
+        let total_height = (pair_height + spacing) * 3.0;
+
+        let frame = NSRect {
+            origin: NSPoint { x: 0.0, y: 0.0 },
+            size: NSSize {
+                width,
+                height: total_height,
+            },
+        };
+        let container: *mut Object = msg_send![class!(NSView), alloc];
+        let container: *mut Object = msg_send![container, initWithFrame: frame];
+
+        let row_y =
+            |row: usize| total_height - (row as f64 + 1.0) * (pair_height + spacing) + spacing;
+        let add_label = |text: &str, y: f64| {
+            let label = make_label(
+                text,
+                NSRect {
+                    origin: NSPoint {
+                        x: 0.0,
+                        y: y + field_height + gap,
+                    },
+                    size: NSSize {
+                        width,
+                        height: label_height,
+                    },
+                },
+            );
+            let _: () = msg_send![container, addSubview: label];
+        };
+        let row_frame = |y: f64| NSRect {
+            origin: NSPoint { x: 0.0, y },
+            size: NSSize {
+                width,
+                height: field_height,
+            },
+        };
+
+        add_label("Favorite Name:", row_y(0));
+        let name_field = make_text_field("e.g. CORE", row_frame(row_y(0)));
+        let _: () = msg_send![name_field, setStringValue: nsstring(default_name)];
+        let _: () = msg_send![container, addSubview: name_field];
+
+        add_label("The mounted host is the:", row_y(1));
+        let popup: *mut Object = msg_send![class!(NSPopUpButton), alloc];
+        let popup_frame = row_frame(row_y(1));
+        let popup: *mut Object = msg_send![popup, initWithFrame:popup_frame pullsDown:false];
+        let _: () = msg_send![popup, addItemWithTitle: nsstring("Fallback host")];
+        let _: () = msg_send![popup, addItemWithTitle: nsstring("Thunderbolt host")];
+        let _: () = msg_send![container, addSubview: popup];
+
+        add_label("Other Host:", row_y(2));
+        let host_field = make_text_field("e.g. 10.0.0.1", row_frame(row_y(2)));
+        let _: () = msg_send![container, addSubview: host_field];
+
+        if let Some((mounted_as, alternate)) = &suggestion {
+            let index: i64 = match mounted_as {
+                Backend::Fallback => 0,
+                Backend::Tb => 1,
+            };
+            let _: () = msg_send![popup, selectItemAtIndex: index];
+            let _: () = msg_send![host_field, setStringValue: nsstring(alternate)];
+        }
+
+        let _: () = msg_send![alert, setAccessoryView: container];
+        let _: () = msg_send![alert, layout];
+
+        let window: *mut Object = msg_send![alert, window];
+        let _: () = msg_send![window, makeFirstResponder: host_field];
+
+        let response: i64 = msg_send![alert, runModal];
+        // NSAlertFirstButtonReturn = 1000
+        if response != 1000 {
+            return None;
+        }
+
+        let idx: i64 = msg_send![popup, indexOfSelectedItem];
+        Some(AdoptMountInput {
+            share_name: get_field_string(name_field),
+            mounted_as: if idx == 1 {
+                Backend::Tb
+            } else {
+                Backend::Fallback
+            },
+            alternate_host: get_field_string(host_field),
+        })
+    }
+}
+
 /// Show a native macOS confirmation dialog for removing a favorite.
 ///
 /// Displays the share name, affected alias names, and offers
@@ -282,6 +416,55 @@ pub fn show_remove_favorite_dialog(
     }
 }
 
+/// Ask for the SMB password after the server rejected the saved credentials.
+///
+/// Returns `None` if cancelled, or `Some("")` when the user chose to retry
+/// with the password already saved.
+pub fn show_password_dialog(share_name: &str, username: &str) -> Option<String> {
+    unsafe {
+        let alert: *mut Object = msg_send![class!(NSAlert), new];
+        let _: () = msg_send![alert, setMessageText:
+            nsstring(&format!("Password for '{}'", share_name))];
+        let _: () = msg_send![alert, setInformativeText:
+        nsstring(&format!(
+            "The server rejected the password for '{}'. Automatic mounts are \
+             paused so the account is not locked.\n\n\
+             Enter the password to save it, or leave it empty to retry \
+             with the saved one.",
+            username
+        ))];
+        // NSAlertStyleWarning = 0
+        let _: () = msg_send![alert, setAlertStyle: 0i64];
+
+        let _: () = msg_send![alert, addButtonWithTitle: nsstring("Save & Mount")];
+        let _: () = msg_send![alert, addButtonWithTitle: nsstring("Cancel")];
+
+        let field = make_secure_field(
+            "Password",
+            NSRect {
+                origin: NSPoint { x: 0.0, y: 0.0 },
+                size: NSSize {
+                    width: 300.0,
+                    height: 24.0,
+                },
+            },
+        );
+        let _: () = msg_send![alert, setAccessoryView: field];
+        let _: () = msg_send![alert, layout];
+
+        let window: *mut Object = msg_send![alert, window];
+        let _: () = msg_send![window, makeFirstResponder: field];
+
+        let response: i64 = msg_send![alert, runModal];
+        // NSAlertFirstButtonReturn = 1000
+        if response != 1000 {
+            return None;
+        }
+
+        Some(get_field_string(field))
+    }
+}
+
 /// Show a simple error alert with OK button.
 pub fn show_error_dialog(title: &str, message: &str) {
     unsafe {
@@ -501,20 +684,54 @@ pub fn show_remove_alias_dialog(alias_name: &str, target_path: &str) -> bool {
 
 /// Show a warning dialog about open files before switching backends (spec 14 AC 2).
 ///
-/// Displays the file count and offers "Force Switch" / "Cancel" buttons.
+/// Lists the processes holding files open so the user knows which apps to
+/// close, and offers "Force Switch" / "Cancel" buttons.
 /// Returns `true` if the user chose to proceed with force switch.
-pub fn show_open_files_warning(share_name: &str, open_count: usize, to: Backend) -> bool {
+pub fn show_open_files_warning(share_name: &str, files: &[OpenFile], to: Backend) -> bool {
+    const MAX_PROCESSES: usize = 8;
+
+    let processes = open_files::by_process(files);
+    let mut lines: Vec<String> = processes
+        .iter()
+        .take(MAX_PROCESSES)
+        .map(|process| {
+            let first = process.paths[0].rsplit('/').next().unwrap_or_default();
+            match process.paths.len() {
+                1 => format!(
+                    "• {} (pid {}): {}",
+                    process.command.trim(),
+                    process.pid,
+                    first
+                ),
+                n => format!(
+                    "• {} (pid {}): {} and {} more",
+                    process.command.trim(),
+                    process.pid,
+                    first,
+                    n - 1
+                ),
+            }
+        })
+        .collect();
+    if processes.len() > MAX_PROCESSES {
+        lines.push(format!(
+            "• ...and {} more app(s)",
+            processes.len() - MAX_PROCESSES
+        ));
+    }
+
     unsafe {
         let alert: *mut Object = msg_send![class!(NSAlert), new];
         let _: () = msg_send![alert, setMessageText:
             nsstring(&format!("Open files on '{}'", share_name))];
         let _: () = msg_send![alert, setInformativeText:
         nsstring(&format!(
-            "{} file(s) are currently open on this share.\n\n\
+            "{} file(s) are currently open on this share:\n\n{}\n\n\
              Switching to {} while files are open may cause data loss \
              or application errors.\n\n\
-             Close the files first, or force the switch.",
-            open_count,
+             Close these apps first, or force the switch.",
+            files.len(),
+            lines.join("\n"),
             to.short_label()
         ))];
         // NSAlertStyleCritical = 2
@@ -1,20 +1,47 @@
-use std::io::Read;
-use std::net::{TcpStream, ToSocketAddrs};
-use std::process::{Command, ExitStatus, Stdio};
-use std::time::{Duration, Instant};
+use std::net::TcpStream;
+use std::time::Duration;
 
-pub fn is_smb_reachable_with_timeout(server: &str, timeout: Duration) -> bool {
-    let addr = format!("{}:445", server);
-    let addrs: Vec<_> = match addr.to_socket_addrs() {
-        Ok(a) => a.collect(),
-        Err(_) => return false,
-    };
-    for sock_addr in addrs {
-        if TcpStream::connect_timeout(&sock_addr, timeout).is_ok() {
-            return true;
+use crate::mount;
+use crate::mount::smb::{MountError, SmbOptions};
+use crate::network::host;
+use crate::runner;
+
+pub fn is_smb_reachable_with_timeout(server: &str, port: u16, timeout: Duration) -> bool {
+    let addr = host::socket_addr_string(server, port);
+    runner::probe(&addr, || {
+        for sock_addr in host::resolve(server, port) {
+            if TcpStream::connect_timeout(&sock_addr, timeout).is_ok() {
+                return true;
+            }
         }
+        false
+    })
+}
+
+/// Check that `server` accepts the account in `options` and `password` by
+/// listing its shares. The password goes to `smbutil view` on stdin.
+pub fn verify_credentials(
+    server: &str,
+    options: &SmbOptions,
+    password: &str,
+    timeout: Duration,
+) -> Result<(), MountError> {
+    let server_url = mount::smb::build_server_url(server, options);
+    let output = runner::run_with_input_timeout(
+        "smbutil",
+        &["view", &server_url],
+        &format!("{}\n", password),
+        timeout,
+    )
+    .map_err(|e| MountError::mount_failed(format!("smbutil view failed: {}", e), None))?;
+    if output.success() {
+        Ok(())
+    } else {
+        Err(MountError::mount_failed(
+            output.stderr.trim().to_string(),
+            output.code,
+        ))
     }
-    false
 }
 
 // check_share_available and its supporting types are candidates for future
@@ -36,9 +63,11 @@ pub enum ShareCheckResult {
 /// - [`ShareCheckResult::Unknown`] for timeout/spawn/command failures
 #[allow(dead_code)]
 pub fn check_share_available(server: &str, share: &str, timeout: Duration) -> ShareCheckResult {
-    let output = match run_smbutil_view(server, timeout) {
+    let server_url = format!("//{}", host::url_authority(server, None));
+    let output = match runner::run_with_timeout("smbutil", &["view", &server_url], timeout) {
         Ok(o) => o,
-        Err(reason) => {
+        Err(e) => {
+            let reason = format!("smbutil view failed: {}", e);
             log::debug!(
                 "smbutil view preflight unavailable for {}: {}",
                 server,
@@ -48,12 +77,11 @@ pub fn check_share_available(server: &str, share: &str, timeout: Duration) -> Sh
         }
     };
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if !output.success() {
         let reason = format!(
             "smbutil view exited with {:?}: {}",
-            output.status.code(),
-            stderr
+            output.code,
+            output.stderr.trim()
         );
         log::debug!(
             "smbutil view preflight unavailable for {}: {}",
@@ -63,68 +91,13 @@ pub fn check_share_available(server: &str, share: &str, timeout: Duration) -> Sh
         return ShareCheckResult::Unknown { reason };
     }
 
-    if parse_smbutil_view_contains_share(&output.stdout, share) {
+    if parse_smbutil_view_contains_share(output.stdout.as_bytes(), share) {
         ShareCheckResult::Available
     } else {
         ShareCheckResult::NotFound
     }
 }
 
-#[allow(dead_code)]
-struct CommandOutput {
-    status: ExitStatus,
-    stdout: Vec<u8>,
-    stderr: Vec<u8>,
-}
-
-#[allow(dead_code)]
-fn run_smbutil_view(server: &str, timeout: Duration) -> Result<CommandOutput, String> {
-    let mut child = Command::new("smbutil")
-        .args(["view", &format!("//{}", server)])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(|e| format!("failed to spawn smbutil view: {}", e))?;
-
-    let deadline = Instant::now() + timeout;
-    let status = loop {
-        match child.try_wait() {
-            Ok(Some(status)) => break status,
-            Ok(None) => {
-                if Instant::now() >= deadline {
-                    let _ = child.kill();
-                    let _ = child.wait();
-                    return Err(format!(
-                        "smbutil view timed out after {}ms",
-                        timeout.as_millis()
-                    ));
-                }
-                std::thread::sleep(Duration::from_millis(25));
-            }
-            Err(e) => {
-                let _ = child.kill();
-                let _ = child.wait();
-                return Err(format!("failed while waiting for smbutil view: {}", e));
-            }
-        }
-    };
-
-    let mut stdout = Vec::new();
-    if let Some(mut out) = child.stdout.take() {
-        let _ = out.read_to_end(&mut stdout);
-    }
-    let mut stderr = Vec::new();
-    if let Some(mut err) = child.stderr.take() {
-        let _ = err.read_to_end(&mut stderr);
-    }
-
-    Ok(CommandOutput {
-        status,
-        stdout,
-        stderr,
-    })
-}
-
 #[allow(dead_code)]
 fn parse_smbutil_view_contains_share(stdout: &[u8], share: &str) -> bool {
     let text = String::from_utf8_lossy(stdout);
@@ -164,4 +137,19 @@ CORE-01                       Disk
 "#;
         assert!(!parse_smbutil_view_contains_share(sample, "VAULT-R1"));
     }
+
+    #[test]
+    fn check_share_available_brackets_ipv6_hosts() {
+        let guard = runner::replay::install(
+            runner::parse_fixture(
+                r#"{"kind":"command","program":"smbutil","args":["view","//[fe80::1%25en5]"],"output":{"code":0,"stdout":"Share  Type\n-----  ----\nCORE   Disk\n","stderr":""}}"#,
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            check_share_available("fe80::1%en5", "CORE", Duration::from_secs(5)),
+            ShareCheckResult::Available
+        );
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+    }
 }
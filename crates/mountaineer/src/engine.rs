@@ -1,36 +1,125 @@
 use anyhow::{Context, Result, anyhow};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
 use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fs;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 use std::time::Duration;
 
 use crate::config::{self, AliasConfig, Backend, Config, ShareConfig};
-use crate::{discovery, mount};
-
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+use crate::events::{self, ShareEventKind};
+use crate::fslock;
+use crate::hooks::{self, HookContext, HookEvent};
+use crate::journal::{self, SwitchIntent, SwitchPhase};
+use crate::migrate;
+use crate::mount::smb::MountFailureKind;
+use crate::open_files::{self, OpenFile};
+use crate::{credentials, discovery, mount, network};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RuntimeState {
+    /// Schema version (see `migrate`); older files are upgraded on load.
+    #[serde(default)]
+    pub version: u32,
     #[serde(default)]
     pub shares: HashMap<String, ShareRuntimeState>,
+    /// Share entries as last read from or written to disk: what this
+    /// process's changes are measured against when merging on save.
+    #[serde(skip)]
+    base: HashMap<String, ShareRuntimeState>,
+    /// The switch journal that goes with state.json (see `journal`). Set by
+    /// [`load_runtime_state`]; state that did not come from state.json has
+    /// nothing a crash could leave behind, so its switches are not journaled.
+    #[serde(skip)]
+    journal: Option<PathBuf>,
+}
+
+impl Default for RuntimeState {
+    fn default() -> Self {
+        Self {
+            version: migrate::STATE_VERSION,
+            shares: HashMap::new(),
+            base: HashMap::new(),
+            journal: None,
+        }
+    }
+}
+
+impl RuntimeState {
+    /// Record `intent` in this state's switch journal, if it has one.
+    fn journal_record(&self, intent: &SwitchIntent) {
+        if let Some(path) = &self.journal {
+            journal::record(path, intent);
+        }
+    }
+
+    /// Drop `share`'s intent from this state's switch journal.
+    fn journal_clear(&self, share: &str) {
+        if let Some(path) = &self.journal {
+            journal::clear(path, share);
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct ShareRuntimeState {
+    /// Bumped by every save that changes the entry, so a writer can tell
+    /// whether someone else changed it since it was loaded.
+    #[serde(default)]
+    pub revision: u64,
     pub active_backend: Option<Backend>,
     pub last_switch_at: Option<DateTime<Utc>>,
     pub tb_reachable_since: Option<DateTime<Utc>>,
     pub tb_healthy_since: Option<DateTime<Utc>>,
     pub last_error: Option<String>,
+    /// Category of `last_error` when it came from a failed mount.
+    #[serde(default)]
+    pub last_error_kind: Option<MountFailureKind>,
+    /// Backend whose server rejected the share's credentials. While set,
+    /// automatic mounts for the share are suspended so repeated failed logins
+    /// cannot lock the account; cleared when credentials are re-entered.
+    #[serde(default)]
+    pub needs_credentials: Option<Backend>,
     /// TB became available while on Fallback (awaiting user confirmation to switch).
     /// With auto_failback=false, the user must explicitly trigger the switch.
     #[serde(default)]
     pub tb_recovery_pending: bool,
+    /// The pre-switch hook vetoed the automatic failback for this recovery.
+    /// It is not asked again until recovery ends or the user switches.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub failback_vetoed: bool,
+    /// Switch waiting for open files to close (`switch --drain`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_switch: Option<PendingSwitch>,
+    /// Switch leases held by tools (`lease acquire`); expired ones are
+    /// pruned on the next reconcile.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub leases: Vec<Lease>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// A hold on a share's current mount. While any lease is live, switches
+/// are refused as if files were open.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lease {
+    pub token: String,
+    /// Free-form description of who holds it (`--holder`, or `run`'s command).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub holder: Option<String>,
+    pub acquired_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A switch deferred until the mount has no open files, or the deadline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingSwitch {
+    pub to: Backend,
+    pub requested_at: DateTime<Utc>,
+    pub deadline: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BackendStatus {
     pub host: String,
     pub mount_point: String,
@@ -39,31 +128,123 @@ pub struct BackendStatus {
     pub alive: bool,
     pub ready: bool,
     pub last_error: Option<String>,
+    pub last_error_kind: Option<MountFailureKind>,
 }
 
 /// Wrapper for JSON `status --all --json` output that includes global config fields
 /// alongside per-share status, per spec 09 ("Include lsof_recheck current setting").
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StatusOutput {
     pub lsof_recheck: bool,
     pub shares: Vec<ShareStatus>,
+    /// The process keeping shares mounted, if one is running.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reconciler: Option<crate::ownership::Owner>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShareStatus {
     pub name: String,
     pub stable_path: String,
     pub active_backend: Option<Backend>,
     pub desired_backend: Option<Backend>,
     pub tb_recovery_pending: bool,
+    /// Why the switch back to TB waits, while `tb_recovery_pending`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recovery_hold: Option<RecoveryHold>,
     pub tb: BackendStatus,
     pub fallback: BackendStatus,
     pub last_switch_at: Option<DateTime<Utc>>,
     pub last_error: Option<String>,
+    /// Category of `last_error` when it came from a failed mount.
+    pub last_error_kind: Option<MountFailureKind>,
+    /// Remediation hint for `last_error_kind`.
+    pub last_error_hint: Option<String>,
+    /// Backend that rejected the credentials; automatic mounts are suspended.
+    pub needs_credentials: Option<Backend>,
     /// When TB first became reachable (TCP 445 probe success). Per spec 09.
     pub tb_reachable_since: Option<DateTime<Utc>>,
     /// When TB was first both reachable AND successfully mounted. Per spec 09.
     pub tb_healthy_since: Option<DateTime<Utc>>,
+    /// SMB mounts Mountaineer did not make of this share or its servers.
+    pub foreign_mounts: Vec<ForeignMount>,
+    /// Drained switch waiting for open files to close.
+    pub pending_switch: Option<PendingSwitch>,
+    /// Live switch leases.
+    pub leases: Vec<Lease>,
+}
+
+/// What keeps a share on Fallback once TB is back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RecoveryHold {
+    /// `auto_failback` is off; the user switches.
+    Manual,
+    /// Files are open on the fallback mount, or a lease is held.
+    Busy,
+    /// The pre-switch hook refused the switch.
+    Vetoed,
+}
+
+impl RecoveryHold {
+    fn of(config: &Config, entry: &ShareRuntimeState) -> Option<Self> {
+        if !entry.tb_recovery_pending {
+            None
+        } else if entry.failback_vetoed {
+            Some(Self::Vetoed)
+        } else if !config.global.auto_failback {
+            Some(Self::Manual)
+        } else {
+            Some(Self::Busy)
+        }
+    }
+}
+
+impl ShareStatus {
+    /// Mounted and responding on the active backend, which must be
+    /// `backend` when one is given.
+    pub fn is_ready_on(&self, backend: Option<Backend>) -> bool {
+        let Some(active) = self.active_backend else {
+            return false;
+        };
+        if backend.is_some_and(|wanted| wanted != active) {
+            return false;
+        }
+        let status = match active {
+            Backend::Tb => &self.tb,
+            Backend::Fallback => &self.fallback,
+        };
+        status.mounted && status.alive
+    }
+
+    /// Not mounted through either backend.
+    pub fn is_unmounted(&self) -> bool {
+        !self.tb.mounted && !self.fallback.mounted
+    }
+}
+
+/// How a foreign mount collides with a managed share.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForeignConflict {
+    /// Another mount of the managed share's remote, e.g. `//nas.local/CORE`
+    /// at `/Volumes/CORE-1`. Breaks single-mount; can be reclaimed when it
+    /// comes from one of the share's hosts. A same-named share from another
+    /// server at a suffixed path is reported only.
+    SameShare,
+    /// A different share on one of the managed share's servers. Reported
+    /// only: it shares the SMB session but is not ours to unmount.
+    SameServer,
+}
+
+/// An SMB mount that conflicts with a managed share, from the mount table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForeignMount {
+    pub mount_point: String,
+    pub host: String,
+    pub share: String,
+    pub user: Option<String>,
+    pub conflict: ForeignConflict,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -85,7 +266,7 @@ pub struct FolderEntry {
     pub path: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UnmountResult {
     pub share: String,
     pub backend: Backend,
@@ -96,25 +277,268 @@ pub struct UnmountResult {
     pub message: Option<String>,
 }
 
+/// A second mount of a managed share's remote, e.g. `/Volumes/CORE-1` left
+/// behind by a Finder race.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateMount {
+    pub mount_point: String,
+    pub host: String,
+    pub busy: bool,
+    pub unmounted: bool,
+    pub message: Option<String>,
+}
+
+/// An SMB mount that no configured share manages, e.g. one made in Finder.
+#[derive(Debug, Clone, Serialize)]
+pub struct UnmanagedMount {
+    pub host: String,
+    pub share: String,
+    pub user: Option<String>,
+    pub mount_point: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupeResult {
+    pub share: String,
+    /// The copy Mountaineer manages, when it is mounted.
+    pub managed: Option<String>,
+    pub duplicates: Vec<DuplicateMount>,
+}
+
 #[derive(Debug, Clone)]
 struct BackendProbe {
     status: BackendStatus,
 }
 
+// --- Runtime state persistence ---
+//
+// The tray, `monitor` and CLI commands all write state.json. Every write
+// happens under an exclusive lock on state.lock and merges with what is on
+// disk instead of overwriting it: per share, an entry this process did not
+// change since it loaded it is taken from disk, and one changed on both
+// sides is merged field by field, this process winning only the fields it
+// changed.
+
 pub fn load_runtime_state() -> Result<RuntimeState> {
-    let path = config::state_path();
+    let mut state = load_runtime_state_at(&config::state_path())?;
+    state.journal = Some(config::switch_journal_path());
+    Ok(state)
+}
+
+pub fn load_runtime_state_at(path: &Path) -> Result<RuntimeState> {
+    let (state, found) = read_runtime_state(path)?;
+    if found < migrate::STATE_VERSION {
+        // Upgrading rewrites the file, so it takes the lock like any write.
+        let _lock = fslock::lock_exclusive(&state_lock_path(path))?;
+        return load_locked_runtime_state(path);
+    }
+    Ok(state)
+}
+
+/// Load state.json while holding its lock. A file written by an older
+/// version is upgraded in place, after a backup of it is written (see
+/// `migrate`).
+fn load_locked_runtime_state(path: &Path) -> Result<RuntimeState> {
+    let (mut state, found) = read_runtime_state(path)?;
+    if found < migrate::STATE_VERSION {
+        let backup = migrate::backup(path, found)?;
+        write_runtime_state(path, &mut state)?;
+        log::info!(
+            "state: upgraded {} from version {} to {} (previous file kept at {})",
+            path.display(),
+            found,
+            migrate::STATE_VERSION,
+            backup.display()
+        );
+    }
+    Ok(state)
+}
+
+/// Parse state.json, upgrading it in memory. Also returns the version the
+/// file had; a missing file is current.
+fn read_runtime_state(path: &Path) -> Result<(RuntimeState, u32)> {
     if !path.exists() {
-        return Ok(RuntimeState::default());
+        return Ok((RuntimeState::default(), migrate::STATE_VERSION));
     }
-    let text = fs::read_to_string(&path)
+    let text = fs::read_to_string(path)
         .with_context(|| format!("failed reading runtime state {}", path.display()))?;
-    let state: RuntimeState = serde_json::from_str(&text)
+    let mut doc: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&text)
         .with_context(|| format!("failed parsing runtime state {}", path.display()))?;
-    Ok(state)
+    let found = migrate::upgrade_state(&mut doc)
+        .with_context(|| format!("cannot load {}", path.display()))?;
+    let mut state: RuntimeState = serde_json::from_value(serde_json::Value::Object(doc))
+        .with_context(|| format!("failed parsing runtime state {}", path.display()))?;
+    state.base = state.shares.clone();
+    Ok((state, found))
+}
+
+/// Take the leases and pending switches other processes wrote to state.json
+/// since `state` was loaded, keeping those made here since. Long-running
+/// loops (monitor, tray) keep state in memory and call this before each pass
+/// so `lease` and `switch --drain` from the CLI take effect, and are not
+/// overwritten by the next save.
+pub fn merge_external_holds(state: &mut RuntimeState, on_disk: &RuntimeState) {
+    for (name, entry) in state.shares.iter_mut() {
+        let base = state.base.get(name);
+        let disk = on_disk.shares.get(name);
+        let base_leases = base.map(|e| e.leases.as_slice()).unwrap_or_default();
+        let disk_leases = disk.map(|e| e.leases.as_slice()).unwrap_or_default();
+        entry.leases = merge_leases(base_leases, &entry.leases, disk_leases);
+        if entry.pending_switch == base.and_then(|e| e.pending_switch.clone()) {
+            entry.pending_switch = disk.and_then(|e| e.pending_switch.clone());
+        }
+    }
+}
+
+/// Merge `state` with state.json and write the result. Afterwards `state`
+/// holds the merged state, including other writers' changes.
+pub fn save_runtime_state(state: &mut RuntimeState) -> Result<()> {
+    save_runtime_state_at(&config::state_path(), state)?;
+    // Switches this process finished are now on disk.
+    if let Some(path) = &state.journal {
+        journal::clear_switched(path);
+    }
+    Ok(())
+}
+
+pub fn save_runtime_state_at(path: &Path, state: &mut RuntimeState) -> Result<()> {
+    let _lock = fslock::lock_exclusive(&state_lock_path(path))?;
+    let on_disk = load_locked_runtime_state(path)?;
+    rebase_runtime_state(state, on_disk);
+    write_runtime_state(path, state)
+}
+
+/// Take other writers' changes from state.json into `state`, keeping the
+/// changes not saved yet. Long-running loops call this before each pass so
+/// they act on what the CLI did in between.
+pub fn refresh_runtime_state(state: &mut RuntimeState) -> Result<()> {
+    let on_disk = load_runtime_state()?;
+    merge_external_holds(state, &on_disk);
+    rebase_runtime_state(state, on_disk);
+    Ok(())
+}
+
+/// Load, change and save state.json while holding its lock, for edits
+/// that must not interleave with any other writer.
+pub fn update_runtime_state<T>(f: impl FnOnce(&mut RuntimeState) -> T) -> Result<T> {
+    update_runtime_state_at(&config::state_path(), f)
+}
+
+pub fn update_runtime_state_at<T>(
+    path: &Path,
+    f: impl FnOnce(&mut RuntimeState) -> T,
+) -> Result<T> {
+    let _lock = fslock::lock_exclusive(&state_lock_path(path))?;
+    let mut state = load_locked_runtime_state(path)?;
+    let result = f(&mut state);
+    write_runtime_state(path, &mut state)?;
+    Ok(result)
 }
 
-pub fn save_runtime_state(state: &RuntimeState) -> Result<()> {
-    let path = config::state_path();
+fn state_lock_path(path: &Path) -> PathBuf {
+    path.with_extension("lock")
+}
+
+/// Three-way merge of `state` (changed since `state.base`) with `on_disk`.
+/// `on_disk` becomes the new base.
+fn rebase_runtime_state(state: &mut RuntimeState, on_disk: RuntimeState) {
+    let mut names: Vec<String> = state.shares.keys().cloned().collect();
+    names.extend(on_disk.shares.keys().cloned());
+    names.sort();
+    names.dedup();
+
+    let mut merged = HashMap::new();
+    for name in names {
+        let base = state.base.get(&name);
+        let ours = state.shares.get(&name);
+        let theirs = on_disk.shares.get(&name);
+        let entry = if ours == base {
+            theirs.cloned()
+        } else if theirs.map(|e| e.revision) == base.map(|e| e.revision) {
+            ours.cloned()
+        } else {
+            match (ours, theirs) {
+                (Some(ours), Some(theirs)) => Some(merge_share_entry(base, ours, theirs)),
+                // Removed here but changed elsewhere, or the other way
+                // round: keep the entry that still exists.
+                (ours, theirs) => ours.or(theirs).cloned(),
+            }
+        };
+        if let Some(entry) = entry {
+            merged.insert(name, entry);
+        }
+    }
+    state.shares = merged;
+    state.base = on_disk.shares;
+}
+
+/// Fields of `ours` that differ from `base`, over `theirs`.
+fn merge_share_entry(
+    base: Option<&ShareRuntimeState>,
+    ours: &ShareRuntimeState,
+    theirs: &ShareRuntimeState,
+) -> ShareRuntimeState {
+    let fields = |entry: Option<&ShareRuntimeState>| match entry.map(serde_json::to_value) {
+        Some(Ok(serde_json::Value::Object(map))) => map,
+        _ => serde_json::Map::new(),
+    };
+    let base_fields = fields(base);
+    let our_fields = fields(Some(ours));
+    let mut merged = fields(Some(theirs));
+    for (key, value) in our_fields.iter() {
+        if base_fields.get(key) != Some(value) {
+            merged.insert(key.clone(), value.clone());
+        }
+    }
+    // Fields left out when empty (leases, pending_switch) that we cleared.
+    for key in base_fields.keys() {
+        if !our_fields.contains_key(key) {
+            merged.remove(key);
+        }
+    }
+    merged.insert("revision".to_string(), theirs.revision.into());
+    let mut entry: ShareRuntimeState = serde_json::from_value(serde_json::Value::Object(merged))
+        .unwrap_or_else(|e| {
+            log::warn!("state: cannot merge share entry, keeping ours: {}", e);
+            ours.clone()
+        });
+    let base_leases = base.map(|e| e.leases.as_slice()).unwrap_or_default();
+    entry.leases = merge_leases(base_leases, &ours.leases, &theirs.leases);
+    entry
+}
+
+/// Leases of `theirs`, less those `ours` dropped since `base` and with those
+/// `ours` acquired or renewed since. A lease is identified by its token.
+fn merge_leases(base: &[Lease], ours: &[Lease], theirs: &[Lease]) -> Vec<Lease> {
+    let has = |leases: &[Lease], token: &str| leases.iter().any(|l| l.token == token);
+    let mut merged: Vec<Lease> = theirs
+        .iter()
+        .filter(|lease| !has(base, &lease.token) || has(ours, &lease.token))
+        .map(|lease| {
+            ours.iter()
+                .find(|o| o.token == lease.token && !base.contains(o))
+                .unwrap_or(lease)
+                .clone()
+        })
+        .collect();
+    for lease in ours {
+        if !has(base, &lease.token) && !has(&merged, &lease.token) {
+            merged.push(lease.clone());
+        }
+    }
+    merged
+}
+
+/// Bump the revision of every entry that differs from `state.base` and
+/// write atomically. `state.base` then matches the file.
+fn write_runtime_state(path: &Path, state: &mut RuntimeState) -> Result<()> {
+    for (name, entry) in state.shares.iter_mut() {
+        let base = state.base.get(name);
+        if base != Some(entry) {
+            entry.revision = base.map(|e| e.revision).unwrap_or(0) + 1;
+        }
+    }
+    state.version = migrate::STATE_VERSION;
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)
             .with_context(|| format!("failed creating {}", parent.display()))?;
@@ -125,41 +549,75 @@ pub fn save_runtime_state(state: &RuntimeState) -> Result<()> {
     let tmp_path = path.with_extension("json.tmp");
     fs::write(&tmp_path, text)
         .with_context(|| format!("failed writing temp state {}", tmp_path.display()))?;
-    fs::rename(&tmp_path, &path)
+    fs::rename(&tmp_path, path)
         .with_context(|| format!("failed renaming temp state to {}", path.display()))?;
+    state.base = state.shares.clone();
     Ok(())
 }
 
 pub fn verify_all(config: &Config, state: &mut RuntimeState) -> Vec<ShareStatus> {
     let now = Utc::now();
-    config
+    let mut statuses: Vec<ShareStatus> = config
         .shares
         .iter()
         .map(|share| reconcile_share(config, state, share, false, false, now))
-        .collect()
+        .collect();
+    attach_foreign_mounts(config, &mut statuses);
+    statuses
 }
 
 pub fn reconcile_all(config: &Config, state: &mut RuntimeState) -> Vec<ShareStatus> {
     let now = Utc::now();
-    let statuses: Vec<ShareStatus> = config
+    let _scan = open_files::cycle();
+    let mut statuses: Vec<ShareStatus> = config
         .shares
         .iter()
-        .map(|share| reconcile_share(config, state, share, true, true, now))
+        .map(|share| reconcile_with_pending_switch(config, state, share, now))
         .collect();
+    if config.global.reclaim_foreign_mounts {
+        for result in dedupe_mounts(config, true) {
+            for duplicate in &result.duplicates {
+                log::warn!(
+                    "{}: duplicate mount {} ({})",
+                    result.share,
+                    duplicate.mount_point,
+                    duplicate.message.as_deref().unwrap_or("")
+                );
+            }
+        }
+    }
+    attach_foreign_mounts(config, &mut statuses);
     let _ = reconcile_aliases(config);
     statuses
 }
 
+/// Run `share`'s drained switch if it is due, then a full reconcile pass.
+fn reconcile_with_pending_switch(
+    config: &Config,
+    state: &mut RuntimeState,
+    share: &ShareConfig,
+    now: DateTime<Utc>,
+) -> ShareStatus {
+    let drained = process_pending_switch(config, state, share, now);
+    let status = reconcile_share(config, state, share, true, true, now);
+    if matches!(drained, Some(DrainOutcome::Switched)) {
+        events::publish(config, &[ShareEventKind::Switch], &status);
+    }
+    status
+}
+
 /// Mount-only reconciliation: attempts to mount unmounted shares but does NOT
 /// trigger failover or recovery on already-mounted shares (auto_switch=false).
 /// Per spec 08: "Skip shares that are already mounted — do not unmount and remount."
 pub fn mount_all(config: &Config, state: &mut RuntimeState) -> Vec<ShareStatus> {
     let now = Utc::now();
-    let statuses: Vec<ShareStatus> = config
+    let _scan = open_files::cycle();
+    let mut statuses: Vec<ShareStatus> = config
         .shares
         .iter()
         .map(|share| reconcile_share(config, state, share, true, false, now))
         .collect();
+    attach_foreign_mounts(config, &mut statuses);
     let _ = reconcile_aliases(config);
     statuses
 }
@@ -170,11 +628,13 @@ pub fn reconcile_selected(
     share_names: &[String],
 ) -> Result<Vec<ShareStatus>> {
     let now = Utc::now();
+    let _scan = open_files::cycle();
     let shares = select_shares(config, share_names)?;
-    let statuses = shares
+    let mut statuses: Vec<ShareStatus> = shares
         .iter()
-        .map(|share| reconcile_share(config, state, share, true, true, now))
+        .map(|share| reconcile_with_pending_switch(config, state, share, now))
         .collect();
+    attach_foreign_mounts(config, &mut statuses);
     Ok(statuses)
 }
 
@@ -185,13 +645,38 @@ pub fn verify_selected(
 ) -> Result<Vec<ShareStatus>> {
     let now = Utc::now();
     let shares = select_shares(config, share_names)?;
-    let statuses = shares
+    let mut statuses: Vec<ShareStatus> = shares
         .iter()
         .map(|share| reconcile_share(config, state, share, false, false, now))
         .collect();
+    attach_foreign_mounts(config, &mut statuses);
     Ok(statuses)
 }
 
+/// Why a switch is happening; hooks see it as `MOUNTAINEER_REASON`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwitchReason {
+    /// `switch` or a tray switch item.
+    Manual,
+    /// A drained switch whose files closed.
+    Drain,
+    /// The active backend went offline.
+    Failover,
+    /// Back to Thunderbolt (auto-failback or `lsof_recheck`).
+    Failback,
+}
+
+impl SwitchReason {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Manual => "manual",
+            Self::Drain => "drain",
+            Self::Failover => "failover",
+            Self::Failback => "failback",
+        }
+    }
+}
+
 /// Result of a backend switch operation.
 #[derive(Debug, Clone)]
 pub enum SwitchResult {
@@ -199,6 +684,8 @@ pub enum SwitchResult {
     Success,
     /// Cannot switch: open files detected on current mount.
     BusyOpenFiles,
+    /// The pre-switch hook refused the switch.
+    Vetoed(String),
     /// Failed to unmount the current backend.
     UnmountFailed(String),
     /// Failed to mount the new backend.
@@ -207,9 +694,205 @@ pub enum SwitchResult {
         rolled_back: bool,
         /// The mount error message.
         error: String,
+        /// Category of the mount failure.
+        kind: MountFailureKind,
     },
 }
 
+/// Take a lease on `share_name` for `ttl`. The token is needed to release it.
+pub fn acquire_lease(
+    state: &mut RuntimeState,
+    share_name: &str,
+    ttl: Duration,
+    holder: Option<String>,
+    now: DateTime<Utc>,
+) -> Lease {
+    let expires_at =
+        now + chrono::Duration::from_std(ttl).unwrap_or_else(|_| chrono::Duration::days(365));
+    let lease = Lease {
+        token: new_lease_token(now),
+        holder,
+        acquired_at: now,
+        expires_at,
+    };
+    state_entry_mut(state, share_name)
+        .leases
+        .push(lease.clone());
+    lease
+}
+
+/// Release the lease with `token`, whichever share holds it. Returns the
+/// share's state key and the lease.
+pub fn release_lease(state: &mut RuntimeState, token: &str) -> Option<(String, Lease)> {
+    state.shares.iter_mut().find_map(|(name, entry)| {
+        let index = entry.leases.iter().position(|l| l.token == token)?;
+        Some((name.clone(), entry.leases.remove(index)))
+    })
+}
+
+/// Push back the expiry of the lease with `token` to `now + ttl`. False if
+/// it was released or has already expired.
+pub fn renew_lease(
+    state: &mut RuntimeState,
+    token: &str,
+    ttl: Duration,
+    now: DateTime<Utc>,
+) -> bool {
+    let expires_at =
+        now + chrono::Duration::from_std(ttl).unwrap_or_else(|_| chrono::Duration::days(365));
+    state
+        .shares
+        .values_mut()
+        .flat_map(|entry| entry.leases.iter_mut())
+        .find(|lease| lease.token == token && lease.expires_at > now)
+        .map(|lease| lease.expires_at = expires_at)
+        .is_some()
+}
+
+/// Leases on `share_name` that have not expired at `now`.
+pub fn live_leases(state: &RuntimeState, share_name: &str, now: DateTime<Utc>) -> Vec<Lease> {
+    state
+        .shares
+        .get(&share_name.to_ascii_lowercase())
+        .map(|entry| {
+            entry
+                .leases
+                .iter()
+                .filter(|l| l.expires_at > now)
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn prune_expired_leases(state: &mut RuntimeState, share_name: &str, now: DateTime<Utc>) {
+    if let Some(entry) = state.shares.get_mut(&share_name.to_ascii_lowercase()) {
+        entry.leases.retain(|lease| {
+            let live = lease.expires_at > now;
+            if !live {
+                log::info!("{}: lease {} expired", share_name, lease.token);
+            }
+            live
+        });
+    }
+}
+
+/// Random enough to not collide between concurrent `lease acquire` calls:
+/// `RandomState` is seeded per process.
+fn new_lease_token(now: DateTime<Utc>) -> String {
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_i64(now.timestamp_nanos_opt().unwrap_or_default());
+    hasher.write_u32(std::process::id());
+    format!("lease-{:016x}", hasher.finish())
+}
+
+/// What [`process_pending_switch`] did with a share's drained switch.
+#[derive(Debug, Clone)]
+pub enum DrainOutcome {
+    /// Files are still open; the switch stays pending.
+    Waiting,
+    /// The switch ran.
+    Switched,
+    /// The share was already on the target backend, e.g. after a failover;
+    /// the switch was dropped without running.
+    AlreadyOnTarget,
+    /// The deadline passed with files still open; the switch was dropped.
+    Expired,
+    /// The switch ran and failed; it is not retried.
+    Failed(SwitchResult),
+}
+
+/// Defer a switch of `share_name` to `to` until its mount is idle, for at
+/// most `drain`. Replaces any earlier pending switch.
+pub fn request_drained_switch(
+    state: &mut RuntimeState,
+    share_name: &str,
+    to: Backend,
+    drain: Duration,
+    now: DateTime<Utc>,
+) -> PendingSwitch {
+    let deadline =
+        now + chrono::Duration::from_std(drain).unwrap_or_else(|_| chrono::Duration::days(365));
+    let pending = PendingSwitch {
+        to,
+        requested_at: now,
+        deadline,
+    };
+    state_entry_mut(state, share_name).pending_switch = Some(pending.clone());
+    pending
+}
+
+/// Drop a share's pending drained switch. Returns it if there was one.
+pub fn cancel_pending_switch(state: &mut RuntimeState, share_name: &str) -> Option<PendingSwitch> {
+    state
+        .shares
+        .get_mut(&share_name.to_ascii_lowercase())
+        .and_then(|entry| entry.pending_switch.take())
+}
+
+/// Run a share's pending drained switch if its mount has gone idle, or drop
+/// it once the deadline has passed. `None` when nothing is pending.
+pub fn process_pending_switch(
+    config: &Config,
+    state: &mut RuntimeState,
+    share: &ShareConfig,
+    now: DateTime<Utc>,
+) -> Option<DrainOutcome> {
+    let pending = state_entry_mut(state, &share.name).pending_switch.clone()?;
+    let from = current_active_backend(config, state, share);
+    if from == Some(pending.to) {
+        cancel_pending_switch(state, &share.name);
+        return Some(DrainOutcome::AlreadyOnTarget);
+    }
+    let from = from.unwrap_or(match pending.to {
+        Backend::Tb => Backend::Fallback,
+        Backend::Fallback => Backend::Tb,
+    });
+
+    // The switch itself refuses to unmount while files are open.
+    match switch_backend_single_mount(
+        config,
+        state,
+        share,
+        from,
+        pending.to,
+        false,
+        SwitchReason::Drain,
+    ) {
+        SwitchResult::BusyOpenFiles if now < pending.deadline => Some(DrainOutcome::Waiting),
+        SwitchResult::BusyOpenFiles => {
+            cancel_pending_switch(state, &share.name);
+            let entry = state_entry_mut(state, &share.name);
+            entry.last_error = Some(format!(
+                "drained switch to {} gave up: files still open at the deadline",
+                pending.to.short_label()
+            ));
+            entry.last_error_kind = None;
+            log::warn!(
+                "{}: drained switch to {} expired with files open",
+                share.name,
+                pending.to.short_label()
+            );
+            Some(DrainOutcome::Expired)
+        }
+        SwitchResult::Success => {
+            log::info!(
+                "{}: mount idle, drained switch to {} done",
+                share.name,
+                pending.to.short_label()
+            );
+            cancel_pending_switch(state, &share.name);
+            Some(DrainOutcome::Switched)
+        }
+        other => {
+            cancel_pending_switch(state, &share.name);
+            Some(DrainOutcome::Failed(other))
+        }
+    }
+}
+
 /// Switch backends: unmount old → mount new → update symlink.
 /// Both backends mount at the same `/Volumes/<SHARE>` path under single-mount architecture.
 /// Attempts rollback if the new mount fails.
@@ -220,18 +903,37 @@ pub fn switch_backend_single_mount(
     from: Backend,
     to: Backend,
     force: bool,
+    reason: SwitchReason,
 ) -> SwitchResult {
-    let mount_point = config::volume_mount_path(&share.share_name);
+    let mount_point = config::share_mount_path(share);
     let to_host = backend_host(share, to);
     let stable_path = config::share_stable_path(config, &share.name);
+    let hook_context = HookContext {
+        from: Some(from),
+        to: Some(to),
+        mount_point: &mount_point,
+        reason: reason.label(),
+    };
 
-    // Step 1: Check for open files (unless force)
+    // Step 1: Check for leases and open files (unless force)
+    if !force && !live_leases(state, &share.name, Utc::now()).is_empty() {
+        log::info!("{}: switch refused, share is leased", share.name);
+        return SwitchResult::BusyOpenFiles;
+    }
     if !force && mount::smb::is_mounted(&mount_point) && has_open_handles(&mount_point) {
         return SwitchResult::BusyOpenFiles;
     }
 
+    // Step 1.5: The pre-switch hook may veto (e.g. a sync it could not pause)
+    if let Err(e) = hooks::run(config, share, HookEvent::PreSwitch, &hook_context) {
+        return SwitchResult::Vetoed(e);
+    }
+
     // Step 2: Unmount old backend (if mounted)
+    let intent = |phase| SwitchIntent::new(&share.name, from, to, phase);
+    state.journal_record(&intent(SwitchPhase::Unmounting));
     if mount::smb::is_mounted(&mount_point) {
+        let _ = hooks::run(config, share, HookEvent::PreUnmount, &hook_context);
         let unmount_result = if force {
             mount::smb::unmount(&mount_point)
         } else {
@@ -239,6 +941,8 @@ pub fn switch_backend_single_mount(
         };
 
         if let Err(e) = unmount_result {
+            state.journal_clear(&share.name);
+            let _ = hooks::run(config, share, HookEvent::SwitchFailed, &hook_context);
             return SwitchResult::UnmountFailed(e.to_string());
         }
         log::info!(
@@ -270,27 +974,27 @@ pub fn switch_backend_single_mount(
 
     // Step 3: Mount new backend at the same /Volumes/<SHARE> path
     // Per spec 03: if mount fails, retry once before rolling back.
-    let mount_result =
-        mount::smb::mount_share(to_host, &share.share_name, &share.username, &mount_point);
+    state.journal_record(&intent(SwitchPhase::Mounting));
+    let mount_result = mount_from(config, share, to_host, &mount_point);
 
     let mount_result = match mount_result {
-        Err(first_err) => {
+        // Retrying with a rejected password only adds another failed login.
+        Err(first_err) if first_err.kind() != MountFailureKind::AuthFailed => {
             log::warn!(
                 "{}: first mount attempt for {} failed: {}, retrying once",
                 share.name,
                 to.short_label(),
                 first_err
             );
-            mount::smb::mount_share(to_host, &share.share_name, &share.username, &mount_point)
-                .map_err(|retry_err| {
-                    log::error!(
-                        "{}: retry mount for {} also failed: {}",
-                        share.name,
-                        to.short_label(),
-                        retry_err
-                    );
+            mount_from(config, share, to_host, &mount_point).map_err(|retry_err| {
+                log::error!(
+                    "{}: retry mount for {} also failed: {}",
+                    share.name,
+                    to.short_label(),
                     retry_err
-                })
+                );
+                retry_err
+            })
         }
         ok => ok,
     };
@@ -316,7 +1020,11 @@ pub fn switch_backend_single_mount(
             entry.active_backend = Some(to);
             entry.last_switch_at = Some(Utc::now());
             entry.tb_recovery_pending = false;
+            entry.failback_vetoed = false;
             entry.last_error = None;
+            entry.last_error_kind = None;
+            // Cleared once the caller has saved state.
+            state.journal_record(&intent(SwitchPhase::Switched));
 
             log::info!(
                 "{}: switched {} -> {}",
@@ -324,11 +1032,13 @@ pub fn switch_backend_single_mount(
                 from.short_label(),
                 to.short_label()
             );
+            let _ = hooks::run(config, share, HookEvent::PostMount, &hook_context);
 
             SwitchResult::Success
         }
         Err(e) => {
             let error_msg = e.to_string();
+            let kind = e.kind();
             log::error!(
                 "{}: failed to mount {} at {}: {}",
                 share.name,
@@ -336,15 +1046,14 @@ pub fn switch_backend_single_mount(
                 mount_point.display(),
                 error_msg
             );
+            note_auth_failure(state, &share.name, to, Some(kind));
 
             // Step 5: Rollback - try to remount old backend
+            state.journal_record(&intent(SwitchPhase::RollingBack));
             let from_host = backend_host(share, from);
-            let rollback_result = mount::smb::mount_share(
-                from_host,
-                &share.share_name,
-                &share.username,
-                &mount_point,
-            );
+            let rollback_result = mount_from(config, share, from_host, &mount_point);
+            // State never left `from`; if it is not mounted, reconcile remounts it.
+            state.journal_clear(&share.name);
 
             let rolled_back = rollback_result.is_ok();
             if rolled_back {
@@ -362,62 +1071,248 @@ pub fn switch_backend_single_mount(
                     from.short_label()
                 );
             }
+            let _ = hooks::run(config, share, HookEvent::SwitchFailed, &hook_context);
 
             SwitchResult::MountFailed {
                 rolled_back,
                 error: error_msg,
+                kind,
             }
         }
     }
 }
 
-pub fn unmount_all(config: &Config, state: &mut RuntimeState, force: bool) -> Vec<UnmountResult> {
-    let mut results = Vec::new();
+/// A journal entry still in progress is left alone for this long, in case
+/// the process that wrote it is still switching.
+const SWITCH_INTENT_IN_FLIGHT: ChronoDuration = ChronoDuration::minutes(10);
 
-    for share in &config.shares {
-        let active_backend = current_active_backend(config, state, share);
-        let mount_point = config::volume_mount_path(&share.share_name);
-        let mounted = mount::smb::is_mounted(&mount_point);
-        let mut result = UnmountResult {
-            share: share.name.clone(),
-            backend: active_backend.unwrap_or(Backend::Tb),
-            mount_point: mount_point.display().to_string(),
-            attempted: mounted,
-            unmounted: false,
-            busy: false,
-            message: None,
-        };
+/// A switch found unfinished in the journal, and where it ended up.
+#[derive(Debug, Clone)]
+pub struct RecoveredSwitch {
+    pub intent: SwitchIntent,
+    /// Backend the share is mounted from now; `None` if neither mounted.
+    pub mounted: Option<Backend>,
+}
 
-        if !mounted {
-            // not mounted, nothing to do
-        } else if !force && has_open_handles(&mount_point) {
-            result.busy = true;
-            result.message = Some("deferred: open files detected".to_string());
-        } else {
-            let unmount_result = if force {
-                mount::smb::unmount(&mount_point)
-            } else {
-                mount::smb::unmount_graceful(&mount_point)
-            };
-            match unmount_result {
-                Ok(()) => {
-                    result.unmounted = true;
-                    let method = if force { "forcefully" } else { "gracefully" };
-                    result.message = Some(format!("unmounted {}", method));
-                }
-                Err(err) => {
-                    result.message = Some(format!("unmount failed: {}", err));
-                }
+impl RecoveredSwitch {
+    pub fn describe(&self) -> String {
+        let outcome = match self.mounted {
+            Some(backend) if backend == self.intent.to => {
+                format!("finished on {}", backend.short_label())
             }
-        }
-
-        // Stable symlinks are preserved across unmount per spec 05/08.
-        // They are only removed on explicit `favorites remove --cleanup`.
+            Some(backend) => format!("rolled back to {}", backend.short_label()),
+            None => "could not mount either backend".to_string(),
+        };
+        format!(
+            "{}: switch {} -> {} interrupted while {}, {}",
+            self.intent.share,
+            self.intent.from.short_label(),
+            self.intent.to.short_label(),
+            self.intent.phase.label(),
+            outcome
+        )
+    }
+}
+
+/// Finish or roll back the switches a dead process left in the journal.
+/// Run by whoever just took ownership of reconciliation, before its first
+/// pass. Which way each goes depends only on the recorded phase: up to the
+/// unmount, and while rolling back, the share returns to `from`; once the
+/// new backend was being mounted, the switch is finished, falling back to
+/// `from` if `to` will not mount.
+pub fn recover_interrupted_switches(
+    config: &Config,
+    state: &mut RuntimeState,
+) -> Vec<RecoveredSwitch> {
+    let Some(journal_path) = state.journal.clone() else {
+        return Vec::new();
+    };
+    let intents = match journal::intents_at(&journal_path) {
+        Ok(intents) => intents,
+        Err(e) => {
+            log::warn!("cannot read switch journal: {:#}", e);
+            return Vec::new();
+        }
+    };
+    let now = Utc::now();
+    let mut recovered = Vec::new();
+    for intent in intents {
+        if intent.pid != std::process::id()
+            && process_alive(intent.pid)
+            && now - intent.updated_at < SWITCH_INTENT_IN_FLIGHT
+        {
+            log::info!(
+                "{}: switch by pid {} still in progress, not recovering",
+                intent.share,
+                intent.pid
+            );
+            continue;
+        }
+        let Some(share) = config::find_share(config, &intent.share) else {
+            journal::clear(&journal_path, &intent.share);
+            continue;
+        };
+        let mounted = recover_switch(config, state, share, &intent);
+        match mounted {
+            // Dropped from the journal once the caller saves state.
+            Some(backend) => journal::record(
+                &journal_path,
+                &SwitchIntent::new(&share.name, intent.from, backend, SwitchPhase::Switched),
+            ),
+            None => journal::clear(&journal_path, &share.name),
+        }
+        let result = RecoveredSwitch { intent, mounted };
+        log::warn!("{}", result.describe());
+        recovered.push(result);
+    }
+    recovered
+}
+
+fn recover_switch(
+    config: &Config,
+    state: &mut RuntimeState,
+    share: &ShareConfig,
+    intent: &SwitchIntent,
+) -> Option<Backend> {
+    let mount_point = config::share_mount_path(share);
+    let stable_path = config::share_stable_path(config, &share.name);
+    let target = intent.recovery_target();
+
+    let current = mounted_backend(share, &mount_point);
+    let mounted = if current == Some(target) && mount::smb::is_mount_alive(&mount_point) {
+        Some(target)
+    } else {
+        if (current.is_some() || mount::smb::is_mounted(&mount_point))
+            && let Err(e) = mount::smb::unmount(&mount_point)
+        {
+            log::error!(
+                "{}: cannot clear {}: {}",
+                share.name,
+                mount_point.display(),
+                e
+            );
+        }
+        let mut candidates = vec![target];
+        if intent.phase == SwitchPhase::Mounting {
+            candidates.push(intent.from);
+        }
+        candidates.into_iter().find(|&backend| {
+            match mount_from(config, share, backend_host(share, backend), &mount_point) {
+                Ok(()) => true,
+                Err(e) => {
+                    log::error!(
+                        "{}: recovery mount of {} failed: {}",
+                        share.name,
+                        backend.short_label(),
+                        e
+                    );
+                    note_auth_failure(state, &share.name, backend, Some(e.kind()));
+                    false
+                }
+            }
+        })
+    };
+
+    let entry = state_entry_mut(state, &share.name);
+    match mounted {
+        Some(backend) => {
+            if entry.active_backend != Some(backend) {
+                entry.last_switch_at = Some(Utc::now());
+            }
+            entry.active_backend = Some(backend);
+            entry.last_error = None;
+            entry.last_error_kind = None;
+            if let Err(e) = set_symlink_atomically(&mount_point, &stable_path) {
+                log::error!("{}: recovered mount but symlink failed: {}", share.name, e);
+            }
+        }
+        None => {
+            entry.last_error = Some(format!(
+                "interrupted switch to {} could not be recovered",
+                intent.to.short_label()
+            ));
+        }
+    }
+    mounted
+}
+
+/// Which of the share's backends is mounted at `mount_point`, by host.
+fn mounted_backend(share: &ShareConfig, mount_point: &Path) -> Option<Backend> {
+    let mount = mount::smb::list_mounts()
+        .into_iter()
+        .find(|mount| path_eq(&mount.mount_path, mount_point))?;
+    [Backend::Tb, Backend::Fallback]
+        .into_iter()
+        .find(|&backend| network::host::same(&mount.host, backend_host(share, backend)))
+}
+
+fn process_alive(pid: u32) -> bool {
+    let Ok(pid) = nix::libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: signal 0 only checks that the process exists.
+    if unsafe { nix::libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    std::io::Error::last_os_error().raw_os_error() == Some(nix::libc::EPERM)
+}
+
+pub fn unmount_all(config: &Config, state: &mut RuntimeState, force: bool) -> Vec<UnmountResult> {
+    let _scan = open_files::cycle();
+    let mut results = Vec::new();
+
+    for share in &config.shares {
+        let active_backend = current_active_backend(config, state, share);
+        let mount_point = config::share_mount_path(share);
+        let mounted = mount::smb::is_mounted(&mount_point);
+        let mut result = UnmountResult {
+            share: share.name.clone(),
+            backend: active_backend.unwrap_or(Backend::Tb),
+            mount_point: mount_point.display().to_string(),
+            attempted: mounted,
+            unmounted: false,
+            busy: false,
+            message: None,
+        };
+
+        if !mounted {
+            // not mounted, nothing to do
+        } else if !force && has_open_handles(&mount_point) {
+            result.busy = true;
+            result.message = Some("deferred: open files detected".to_string());
+        } else {
+            let context = HookContext {
+                from: active_backend,
+                to: None,
+                mount_point: &mount_point,
+                reason: "unmount",
+            };
+            let _ = hooks::run(config, share, HookEvent::PreUnmount, &context);
+            let unmount_result = if force {
+                mount::smb::unmount(&mount_point)
+            } else {
+                mount::smb::unmount_graceful(&mount_point)
+            };
+            match unmount_result {
+                Ok(()) => {
+                    result.unmounted = true;
+                    let method = if force { "forcefully" } else { "gracefully" };
+                    result.message = Some(format!("unmounted {}", method));
+                }
+                Err(err) => {
+                    result.message = Some(format!("unmount failed: {}", err));
+                }
+            }
+        }
+
+        // Stable symlinks are preserved across unmount per spec 05/08.
+        // They are only removed on explicit `favorites remove --cleanup`.
 
         if result.unmounted {
             let entry = state_entry_mut(state, &share.name);
             entry.active_backend = None;
             entry.last_error = None;
+            entry.last_error_kind = None;
         }
 
         results.push(result);
@@ -544,6 +1439,104 @@ pub fn add_share(config: &mut Config, share: ShareConfig) -> Result<()> {
     Ok(())
 }
 
+/// SMB mounts that are neither a managed share's mount nor another copy of a
+/// managed share's remote (those are reported by [`dedupe_mounts`]).
+pub fn unmanaged_mounts(config: &Config) -> Vec<UnmanagedMount> {
+    let managed_paths: Vec<Vec<PathBuf>> = config.shares.iter().map(managed_mount_paths).collect();
+    mount::smb::list_mounts()
+        .into_iter()
+        .filter(|mount| {
+            let is_managed_path = managed_paths
+                .iter()
+                .flatten()
+                .any(|path| path_eq(&mount.mount_path, path));
+            let is_managed_remote = config.shares.iter().any(|share| {
+                mount.share.eq_ignore_ascii_case(&share.share_name)
+                    && (network::host::same(&mount.host, &share.thunderbolt_host)
+                        || network::host::same(&mount.host, &share.fallback_host))
+            });
+            !is_managed_path && !is_managed_remote
+        })
+        .map(|mount| UnmanagedMount {
+            host: mount.host,
+            share: mount.share,
+            user: mount.user,
+            mount_point: mount.mount_path.display().to_string(),
+        })
+        .collect()
+}
+
+/// Guess the other host for a mount of `host` from favorites on the same
+/// server. Returns the role `host` plays there and the alternate host.
+pub fn suggest_alternate_host(config: &Config, host: &str) -> Option<(Backend, String)> {
+    config.shares.iter().find_map(|share| {
+        if network::host::same(host, &share.thunderbolt_host) {
+            Some((Backend::Tb, share.fallback_host.clone()))
+        } else if network::host::same(host, &share.fallback_host) {
+            Some((Backend::Fallback, share.thunderbolt_host.clone()))
+        } else {
+            None
+        }
+    })
+}
+
+/// Add a favorite for an existing unmanaged mount and take it over in place:
+/// the share's mount point and stable path are pointed at the mount, and
+/// `mounted_as` is recorded as the active backend, so nothing is remounted.
+///
+/// The caller saves the config and runtime state.
+pub fn adopt_mount(
+    config: &mut Config,
+    state: &mut RuntimeState,
+    mount: &UnmanagedMount,
+    name: &str,
+    mounted_as: Backend,
+    alternate_host: &str,
+) -> Result<ShareConfig> {
+    let (username, domain, guest) = match mount.user.as_deref() {
+        Some(user) if user.eq_ignore_ascii_case("guest") => (String::new(), None, true),
+        Some(user) => match user.split_once(';') {
+            Some((domain, user)) => (user.to_string(), Some(domain.to_string()), false),
+            None => (user.to_string(), None, false),
+        },
+        None => (String::new(), None, false),
+    };
+    let (thunderbolt_host, fallback_host) = match mounted_as {
+        Backend::Tb => (mount.host.clone(), alternate_host.trim().to_string()),
+        Backend::Fallback => (alternate_host.trim().to_string(), mount.host.clone()),
+    };
+    if alternate_host.trim().is_empty() {
+        return Err(anyhow!("an alternate host is required"));
+    }
+    if let Err(reason) = network::host::validate(alternate_host) {
+        return Err(anyhow!("alternate host {}", reason));
+    }
+
+    let share = ShareConfig {
+        name: name.trim().to_string(),
+        username,
+        thunderbolt_host,
+        fallback_host,
+        share_name: mount.share.clone(),
+        domain,
+        guest,
+        ..ShareConfig::default()
+    };
+    add_share(config, share.clone())?;
+
+    let existing = PathBuf::from(&mount.mount_point);
+    let mount_point = config::share_mount_path(&share);
+    mount::smb::adopt_mount_point(&mount_point, &existing)
+        .with_context(|| format!("failed to take over {}", existing.display()))?;
+    let stable_path = config::share_stable_path(config, &share.name);
+    set_symlink_atomically(&mount_point, &stable_path)?;
+
+    let entry = state_entry_mut(state, &share.name);
+    entry.active_backend = Some(mounted_as);
+    entry.last_switch_at = Some(Utc::now());
+    Ok(share)
+}
+
 pub fn remove_share(config: &mut Config, share_name: &str) -> Option<ShareConfig> {
     let idx = config
         .shares
@@ -555,52 +1548,55 @@ pub fn remove_share(config: &mut Config, share_name: &str) -> Option<ShareConfig
 pub fn cleanup_removed_share(
     config: &Config,
     state: &mut RuntimeState,
-    removed_share_name: &str,
-) -> Result<(usize, Vec<UnmountResult>)> {
-    let temp_share = ShareConfig {
-        name: removed_share_name.to_string(),
-        username: String::new(),
-        thunderbolt_host: String::new(),
-        fallback_host: String::new(),
-        share_name: String::new(),
-    };
-
-    let mut temp_cfg = config.clone();
-    temp_cfg.shares.push(temp_share);
-
-    let unmount_results = unmount_all_for_share(&temp_cfg, state, removed_share_name);
-
-    let stable = config::share_stable_path(&temp_cfg, removed_share_name);
-    if is_symlink(&stable) {
-        let _ = fs::remove_file(&stable);
+    removed: &ShareConfig,
+) -> Result<(usize, UnmountResult)> {
+    let unmount_result = unmount_share_when_idle(config, state, removed, "remove");
+    // Still in use: keep the stable path until the deferred unmount succeeds.
+    if unmount_result.unmounted || !unmount_result.attempted {
+        state_entry_mut(state, &removed.name).active_backend = None;
+        let stable = config::share_stable_path(config, &removed.name);
+        if is_symlink(&stable) {
+            let _ = fs::remove_file(&stable);
+        }
     }
 
     let affected_aliases = config
         .aliases
         .iter()
-        .filter(|alias| alias.share.eq_ignore_ascii_case(removed_share_name))
+        .filter(|alias| alias.share.eq_ignore_ascii_case(&removed.name))
         .count();
 
-    Ok((affected_aliases, unmount_results))
+    Ok((affected_aliases, unmount_result))
 }
 
-fn unmount_all_for_share(
+/// Unmount a share whose hosts or mount settings changed in config.toml, so
+/// the next reconcile mounts it with the new ones. `old` is the share as it
+/// was mounted. Deferred (`busy`) while files are open or it is leased.
+pub fn unmount_for_remount(
     config: &Config,
     state: &mut RuntimeState,
-    share_name: &str,
-) -> Vec<UnmountResult> {
-    let active_backend = config::find_share(config, share_name)
-        .and_then(|share| current_active_backend(config, state, share));
-
-    // Find the share_name on the remote to determine the volume mount path.
-    // Use the share's `share_name` field if available, otherwise fall back to `share_name` param.
-    let remote_name = config::find_share(config, share_name)
-        .map(|s| s.share_name.as_str())
-        .unwrap_or(share_name);
-    let mount_point = config::volume_mount_path(remote_name);
+    old: &ShareConfig,
+) -> UnmountResult {
+    let result = unmount_share_when_idle(config, state, old, "config-change");
+    if result.unmounted || !result.attempted {
+        state_entry_mut(state, &old.name).active_backend = None;
+    }
+    result
+}
+
+/// Gracefully unmount `share` unless it is in use. Leaves runtime state to
+/// the caller.
+fn unmount_share_when_idle(
+    config: &Config,
+    state: &mut RuntimeState,
+    share: &ShareConfig,
+    reason: &str,
+) -> UnmountResult {
+    let active_backend = current_active_backend(config, state, share);
+    let mount_point = config::share_mount_path(share);
     let mounted = mount::smb::is_mounted(&mount_point);
     let mut result = UnmountResult {
-        share: share_name.to_string(),
+        share: share.name.clone(),
         backend: active_backend.unwrap_or(Backend::Tb),
         mount_point: mount_point.display().to_string(),
         attempted: mounted,
@@ -609,25 +1605,241 @@ fn unmount_all_for_share(
         message: None,
     };
 
-    if mounted {
-        if has_open_handles(&mount_point) {
-            result.busy = true;
-            result.message = Some("deferred: open files detected".to_string());
-        } else {
-            match mount::smb::unmount_graceful(&mount_point) {
-                Ok(()) => {
-                    result.unmounted = true;
-                    result.message = Some("unmounted gracefully".to_string());
+    if !mounted {
+        return result;
+    }
+    if !live_leases(state, &share.name, Utc::now()).is_empty() {
+        result.busy = true;
+        result.message = Some("deferred: share is leased".to_string());
+    } else if has_open_handles(&mount_point) {
+        result.busy = true;
+        result.message = Some("deferred: open files detected".to_string());
+    } else {
+        let context = HookContext {
+            from: active_backend,
+            to: None,
+            mount_point: &mount_point,
+            reason,
+        };
+        let _ = hooks::run(config, share, HookEvent::PreUnmount, &context);
+        match mount::smb::unmount_graceful(&mount_point) {
+            Ok(()) => {
+                result.unmounted = true;
+                result.message = Some("unmounted gracefully".to_string());
+            }
+            Err(err) => {
+                result.message = Some(format!("unmount failed: {}", err));
+            }
+        }
+    }
+    result
+}
+
+/// Find extra mounts of each managed share and, with `apply`, unmount those
+/// without open files. Only shares with duplicates are returned.
+///
+/// A mount is a duplicate of a share when it exports the same remote share
+/// name from one of the share's hosts and is not a managed mount of any
+/// configured share. A same-named share from another server is never touched,
+/// even at a suffixed Finder path (`/Volumes/CORE-1`).
+/// Extras are only unmounted when the managed copy is mounted, so the share
+/// is never left without a mount.
+pub fn dedupe_mounts(config: &Config, apply: bool) -> Vec<DedupeResult> {
+    let _scan = open_files::cycle();
+    let mounts = mount::smb::list_mounts();
+    let managed_paths: Vec<Vec<PathBuf>> = config.shares.iter().map(managed_mount_paths).collect();
+
+    let mut results = Vec::new();
+    for (share, own_paths) in config.shares.iter().zip(&managed_paths) {
+        let (managed, extras) = find_duplicate_mounts(share, own_paths, &managed_paths, &mounts);
+        if extras.is_empty() {
+            continue;
+        }
+
+        let duplicates = extras
+            .into_iter()
+            .map(|extra| {
+                let mut duplicate = DuplicateMount {
+                    mount_point: extra.mount_path.display().to_string(),
+                    host: extra.host.clone(),
+                    busy: false,
+                    unmounted: false,
+                    message: None,
+                };
+                if !apply {
+                    return duplicate;
                 }
-                Err(err) => {
-                    result.message = Some(format!("unmount failed: {}", err));
+                if managed.is_none() {
+                    duplicate.message =
+                        Some("left in place: the managed mount is not mounted".to_string());
+                } else if has_open_handles(&extra.mount_path) {
+                    duplicate.busy = true;
+                    duplicate.message = Some("left in place: open files detected".to_string());
+                } else {
+                    match mount::smb::unmount_graceful(&extra.mount_path) {
+                        Ok(()) => {
+                            duplicate.unmounted = true;
+                            duplicate.message = Some("unmounted".to_string());
+                        }
+                        Err(err) => {
+                            duplicate.message = Some(format!("unmount failed: {}", err));
+                        }
+                    }
                 }
-            }
+                duplicate
+            })
+            .collect();
+
+        results.push(DedupeResult {
+            share: share.name.clone(),
+            managed: managed.map(|m| m.mount_path.display().to_string()),
+            duplicates,
+        });
+    }
+    results
+}
+
+/// Fill in each status's `foreign_mounts` from a single read of the mount
+/// table.
+fn attach_foreign_mounts(config: &Config, statuses: &mut [ShareStatus]) {
+    if statuses.is_empty() {
+        return;
+    }
+    let mounts = mount::smb::list_mounts();
+    let managed_paths: Vec<Vec<PathBuf>> = config.shares.iter().map(managed_mount_paths).collect();
+    for status in statuses.iter_mut() {
+        let Some(index) = config
+            .shares
+            .iter()
+            .position(|share| share.name.eq_ignore_ascii_case(&status.name))
+        else {
+            continue;
+        };
+        status.foreign_mounts = find_foreign_mounts(
+            &config.shares[index],
+            &managed_paths[index],
+            &managed_paths,
+            &mounts,
+        );
+    }
+}
+
+/// Mounts of `share`'s remote made outside Mountaineer (see
+/// [`find_duplicate_mounts`]), then same-named shares from other servers that
+/// took a suffixed Finder path (`/Volumes/CORE-1`), followed by unmanaged
+/// mounts of other shares on the same servers.
+fn find_foreign_mounts(
+    share: &ShareConfig,
+    own_paths: &[PathBuf],
+    all_managed_paths: &[Vec<PathBuf>],
+    mounts: &[mount::smb::SmbMount],
+) -> Vec<ForeignMount> {
+    let foreign = |mount: &mount::smb::SmbMount, conflict| ForeignMount {
+        mount_point: mount.mount_path.display().to_string(),
+        host: mount.host.clone(),
+        share: mount.share.clone(),
+        user: mount.user.clone(),
+        conflict,
+    };
+
+    let managed = |mount: &mount::smb::SmbMount| {
+        all_managed_paths
+            .iter()
+            .flatten()
+            .any(|path| path_eq(&mount.mount_path, path))
+    };
+
+    let (_, duplicates) = find_duplicate_mounts(share, own_paths, all_managed_paths, mounts);
+    let mut found: Vec<ForeignMount> = duplicates
+        .into_iter()
+        .map(|mount| foreign(mount, ForeignConflict::SameShare))
+        .collect();
+    // Reported so the Finder name clash is visible, but never reclaimed:
+    // nothing says the other server's share is ours.
+    for mount in mounts {
+        if mount.share.eq_ignore_ascii_case(&share.share_name)
+            && !from_share_host(mount, share)
+            && is_suffixed_volume(&mount.mount_path, &share.share_name)
+            && !managed(mount)
+        {
+            found.push(foreign(mount, ForeignConflict::SameShare));
+        }
+    }
+    for mount in mounts {
+        if mount.share.eq_ignore_ascii_case(&share.share_name) {
+            continue;
+        }
+        if from_share_host(mount, share) && !managed(mount) {
+            found.push(foreign(mount, ForeignConflict::SameServer));
+        }
+    }
+    found
+}
+
+/// The share's mount path plus, when it is an adopted symlink, its target.
+fn managed_mount_paths(share: &ShareConfig) -> Vec<PathBuf> {
+    let mount_path = config::share_mount_path(share);
+    let target = resolve_symlink_target(&mount_path);
+    std::iter::once(mount_path).chain(target).collect()
+}
+
+/// Split the mounts of `share`'s remote into its managed mount and the extras.
+fn find_duplicate_mounts<'a>(
+    share: &ShareConfig,
+    own_paths: &[PathBuf],
+    all_managed_paths: &[Vec<PathBuf>],
+    mounts: &'a [mount::smb::SmbMount],
+) -> (
+    Option<&'a mount::smb::SmbMount>,
+    Vec<&'a mount::smb::SmbMount>,
+) {
+    let is_at = |mount: &mount::smb::SmbMount, paths: &[PathBuf]| {
+        paths.iter().any(|path| path_eq(&mount.mount_path, path))
+    };
+
+    let mut managed = None;
+    let mut extras = Vec::new();
+    for mount in mounts {
+        if !mount.share.eq_ignore_ascii_case(&share.share_name) {
+            continue;
+        }
+        if is_at(mount, own_paths) {
+            managed = Some(mount);
+            continue;
+        }
+        if !from_share_host(mount, share) {
+            continue;
         }
+        if all_managed_paths.iter().any(|paths| is_at(mount, paths)) {
+            // Another configured share's own mount (same remote name, other server).
+            continue;
+        }
+        extras.push(mount);
     }
+    (managed, extras)
+}
+
+/// Whether `mount` comes from `share`'s Thunderbolt or fallback host.
+fn from_share_host(mount: &mount::smb::SmbMount, share: &ShareConfig) -> bool {
+    network::host::same(&mount.host, &share.thunderbolt_host)
+        || network::host::same(&mount.host, &share.fallback_host)
+}
 
-    state_entry_mut(state, share_name).active_backend = None;
-    vec![result]
+/// `/Volumes/<share_name>-<n>`, the name Finder picks when `/Volumes/<share_name>`
+/// is taken.
+fn is_suffixed_volume(path: &Path, share_name: &str) -> bool {
+    if path.parent() != Some(Path::new("/Volumes")) {
+        return false;
+    }
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    let Some((base, suffix)) = name.rsplit_once('-') else {
+        return false;
+    };
+    base.eq_ignore_ascii_case(share_name)
+        && !suffix.is_empty()
+        && suffix.bytes().all(|b| b.is_ascii_digit())
 }
 
 fn reconcile_share(
@@ -638,6 +1850,24 @@ fn reconcile_share(
     auto_switch: bool,
     now: DateTime<Utc>,
 ) -> ShareStatus {
+    let (status, share_events) =
+        reconcile_share_events(config, state, share, attempt_mount, auto_switch, now);
+    if !share_events.is_empty() {
+        events::publish(config, &share_events, &status);
+    }
+    status
+}
+
+/// [`reconcile_share`] without publishing: the status and the events the
+/// pass raised.
+fn reconcile_share_events(
+    config: &Config,
+    state: &mut RuntimeState,
+    share: &ShareConfig,
+    attempt_mount: bool,
+    auto_switch: bool,
+    now: DateTime<Utc>,
+) -> (ShareStatus, Vec<ShareEventKind>) {
     let timeout = Duration::from_millis(config.global.connect_timeout_ms);
 
     let stable_path = config::share_stable_path(config, &share.name);
@@ -648,19 +1878,78 @@ fn reconcile_share(
         .and_then(|entry| entry.active_backend);
     let active_hint = detected_active.or(remembered_active);
 
+    // After an authentication failure, keep probing for status but do not mount
+    // or switch until the user re-enters credentials.
+    let suspended = state
+        .shares
+        .get(&share.name.to_ascii_lowercase())
+        .is_some_and(|entry| entry.needs_credentials.is_some());
+    if suspended {
+        log::debug!(
+            "{}: automatic mounts suspended until credentials are re-entered",
+            share.name
+        );
+    }
+    let attempt_mount = attempt_mount && !suspended;
+    let auto_switch = auto_switch && !suspended;
+    prune_expired_leases(state, &share.name, now);
+    // Event hooks fire on changes, not on every pass that finds the same
+    // situation.
+    let (previous_error, was_recovery_pending) = state
+        .shares
+        .get(&share.name.to_ascii_lowercase())
+        .map(|entry| (entry.last_error.clone(), entry.tb_recovery_pending))
+        .unwrap_or_default();
+    let mount_point = config::share_mount_path(share);
+    // Failover, failback and both-down are also published as share events
+    // once the final status is known.
+    let switch_events = RefCell::new(Vec::new());
+    let event_hook = |event: HookEvent, from: Option<Backend>, to: Option<Backend>, reason| {
+        match event {
+            HookEvent::Failover => switch_events.borrow_mut().push(ShareEventKind::Failover),
+            HookEvent::Failback => switch_events.borrow_mut().push(ShareEventKind::Switch),
+            HookEvent::BothBackendsDown => {
+                switch_events.borrow_mut().push(ShareEventKind::BothDown)
+            }
+            _ => {}
+        }
+        let context = HookContext {
+            from,
+            to,
+            mount_point: &mount_point,
+            reason,
+        };
+        let _ = hooks::run(config, share, event, &context);
+    };
+
     // Probe both backends (always check reachability for status display)
     // Only the active backend will attempt to mount
-    let tb = probe_backend(share, Backend::Tb, timeout, attempt_mount, active_hint);
+    let tb = probe_backend(
+        config,
+        share,
+        Backend::Tb,
+        timeout,
+        attempt_mount,
+        active_hint,
+    );
     let fb = probe_backend(
+        config,
         share,
         Backend::Fallback,
         timeout,
         attempt_mount,
         active_hint,
     );
+    note_auth_failure(state, &share.name, Backend::Tb, tb.status.last_error_kind);
+    note_auth_failure(
+        state,
+        &share.name,
+        Backend::Fallback,
+        fb.status.last_error_kind,
+    );
 
     // Update TB reachability/health tracking (scoped borrow)
-    let (active_backend, tb_stability_since) = {
+    let (active_backend, tb_stability_since, failback_vetoed) = {
         let entry = state_entry_mut(state, &share.name);
         if tb.status.reachable {
             if entry.tb_reachable_since.is_none() {
@@ -669,6 +1958,7 @@ fn reconcile_share(
         } else {
             entry.tb_reachable_since = None;
             entry.tb_recovery_pending = false;
+            entry.failback_vetoed = false;
         }
 
         if tb.status.ready {
@@ -686,7 +1976,7 @@ fn reconcile_share(
             (None, Some(healthy)) => Some(healthy),
             (None, None) => None,
         };
-        (active_backend, tb_stability_since)
+        (active_backend, tb_stability_since, entry.failback_vetoed)
     };
 
     let desired_backend = choose_desired_backend(
@@ -700,6 +1990,7 @@ fn reconcile_share(
     );
 
     let mut last_error = None;
+    let mut last_error_kind = None;
 
     if auto_switch {
         if let Some(active) = active_backend {
@@ -724,31 +2015,62 @@ fn reconcile_share(
                         other.short_label()
                     );
                     // switch_backend_single_mount updates state internally
-                    match switch_backend_single_mount(config, state, share, active, other, false) {
+                    match switch_backend_single_mount(
+                        config,
+                        state,
+                        share,
+                        active,
+                        other,
+                        false,
+                        SwitchReason::Failover,
+                    ) {
                         SwitchResult::Success => {
                             // State already updated by switch function
+                            event_hook(HookEvent::Failover, Some(active), Some(other), "failover");
                         }
                         SwitchResult::BusyOpenFiles => {
+                            let msg = if live_leases(state, &share.name, now).is_empty() {
+                                format!(
+                                    "{}: failover blocked - open files on {}",
+                                    share.name,
+                                    active.short_label()
+                                )
+                            } else {
+                                format!("{}: failover blocked - share is leased", share.name)
+                            };
+                            log::warn!("{}", msg);
+                            if previous_error.as_deref() != Some(msg.as_str()) {
+                                event_hook(
+                                    HookEvent::SwitchBlocked,
+                                    Some(active),
+                                    Some(other),
+                                    "failover",
+                                );
+                            }
+                            last_error = Some(msg.clone());
+                            set_share_error(state, &share.name, msg, None);
+                        }
+                        SwitchResult::Vetoed(e) => {
                             let msg = format!(
-                                "{}: failover blocked - open files on {}",
-                                share.name,
-                                active.short_label()
+                                "{}: failover vetoed by pre-switch hook: {}",
+                                share.name, e
                             );
                             log::warn!("{}", msg);
                             last_error = Some(msg.clone());
-                            state_entry_mut(state, &share.name).last_error = Some(msg);
+                            set_share_error(state, &share.name, msg, None);
                         }
                         SwitchResult::UnmountFailed(e) => {
                             let msg = format!("{}: failover unmount failed: {}", share.name, e);
                             log::error!("{}", msg);
                             last_error = Some(msg.clone());
-                            state_entry_mut(state, &share.name).last_error = Some(msg);
+                            set_share_error(state, &share.name, msg, None);
                         }
-                        SwitchResult::MountFailed { error, .. } => {
+                        SwitchResult::MountFailed { error, kind, .. } => {
                             let msg = format!("{}: failover mount failed: {}", share.name, error);
                             log::error!("{}", msg);
                             last_error = Some(msg.clone());
-                            state_entry_mut(state, &share.name).last_error = Some(msg);
+                            last_error_kind = Some(kind);
+                            set_share_error(state, &share.name, msg, Some(kind));
                         }
                     }
                 } else {
@@ -760,8 +2082,16 @@ fn reconcile_share(
                         other.short_label()
                     );
                     log::warn!("{}", msg);
+                    if previous_error.as_deref() != Some(msg.as_str()) {
+                        event_hook(
+                            HookEvent::BothBackendsDown,
+                            Some(active),
+                            None,
+                            "both-backends-down",
+                        );
+                    }
                     last_error = Some(msg.clone());
-                    state_entry_mut(state, &share.name).last_error = Some(msg);
+                    set_share_error(state, &share.name, msg, None);
                 }
             } else if active == Backend::Fallback && tb.status.reachable {
                 // On Fallback but TB is reachable
@@ -782,6 +2112,7 @@ fn reconcile_share(
                     // This is independent of auto_failback per spec 04:
                     //   "lsof_recheck is a separate toggle from auto_failback"
                     if config.global.lsof_recheck
+                        && !failback_vetoed
                         && let Some(since) = tb_stability_since
                     {
                         let stable_for = (now - since).num_seconds().max(0) as u64;
@@ -798,15 +2129,38 @@ fn reconcile_share(
                                 Backend::Fallback,
                                 Backend::Tb,
                                 false,
+                                SwitchReason::Failback,
                             ) {
                                 SwitchResult::Success => {
                                     // State already updated by switch function
+                                    event_hook(
+                                        HookEvent::Failback,
+                                        Some(Backend::Fallback),
+                                        Some(Backend::Tb),
+                                        "failback",
+                                    );
                                 }
                                 SwitchResult::BusyOpenFiles => {
                                     log::debug!(
                                         "{}: lsof_recheck: open files still present, deferring",
                                         share.name
                                     );
+                                    if !was_recovery_pending {
+                                        event_hook(
+                                            HookEvent::SwitchBlocked,
+                                            Some(Backend::Fallback),
+                                            Some(Backend::Tb),
+                                            "failback",
+                                        );
+                                    }
+                                }
+                                SwitchResult::Vetoed(e) => {
+                                    log::info!(
+                                        "{}: lsof_recheck: switch vetoed by pre-switch hook: {}",
+                                        share.name,
+                                        e
+                                    );
+                                    state_entry_mut(state, &share.name).failback_vetoed = true;
                                 }
                                 SwitchResult::UnmountFailed(e) => {
                                     let msg = format!(
@@ -815,23 +2169,24 @@ fn reconcile_share(
                                     );
                                     log::error!("{}", msg);
                                     last_error = Some(msg.clone());
-                                    state_entry_mut(state, &share.name).last_error = Some(msg);
+                                    set_share_error(state, &share.name, msg, None);
                                 }
-                                SwitchResult::MountFailed { error, .. } => {
+                                SwitchResult::MountFailed { error, kind, .. } => {
                                     let msg = format!(
                                         "{}: lsof_recheck switch mount failed: {}",
                                         share.name, error
                                     );
                                     log::error!("{}", msg);
                                     last_error = Some(msg.clone());
-                                    state_entry_mut(state, &share.name).last_error = Some(msg);
+                                    last_error_kind = Some(kind);
+                                    set_share_error(state, &share.name, msg, Some(kind));
                                 }
                             }
                         }
                     }
                 } else {
                     // Auto-failback is enabled - check stability window
-                    if let Some(since) = tb_stability_since {
+                    if !failback_vetoed && let Some(since) = tb_stability_since {
                         let stable_for = (now - since).num_seconds().max(0) as u64;
                         if stable_for >= config.global.auto_failback_stable_secs {
                             log::info!(
@@ -841,27 +2196,59 @@ fn reconcile_share(
                             );
                             // When lsof_recheck is disabled, skip open-file checks
                             // during auto-failback per spec 04
+                            // Skipping lsof does not skip leases.
                             let skip_lsof = !config.global.lsof_recheck;
-                            match switch_backend_single_mount(
-                                config,
-                                state,
-                                share,
-                                Backend::Fallback,
-                                Backend::Tb,
-                                skip_lsof,
-                            ) {
+                            let result =
+                                if skip_lsof && !live_leases(state, &share.name, now).is_empty() {
+                                    SwitchResult::BusyOpenFiles
+                                } else {
+                                    switch_backend_single_mount(
+                                        config,
+                                        state,
+                                        share,
+                                        Backend::Fallback,
+                                        Backend::Tb,
+                                        skip_lsof,
+                                        SwitchReason::Failback,
+                                    )
+                                };
+                            match result {
                                 SwitchResult::Success => {
                                     // State already updated by switch function
+                                    event_hook(
+                                        HookEvent::Failback,
+                                        Some(Backend::Fallback),
+                                        Some(Backend::Tb),
+                                        "failback",
+                                    );
                                 }
                                 SwitchResult::BusyOpenFiles => {
                                     let msg = format!(
-                                        "{}: auto-failback blocked - open files",
+                                        "{}: auto-failback blocked - open files or lease",
                                         share.name
                                     );
                                     log::warn!("{}", msg);
+                                    if !was_recovery_pending {
+                                        event_hook(
+                                            HookEvent::SwitchBlocked,
+                                            Some(Backend::Fallback),
+                                            Some(Backend::Tb),
+                                            "failback",
+                                        );
+                                    }
                                     // Don't set as error - just defer
                                     state_entry_mut(state, &share.name).tb_recovery_pending = true;
                                 }
+                                SwitchResult::Vetoed(e) => {
+                                    log::info!(
+                                        "{}: auto-failback vetoed by pre-switch hook: {}",
+                                        share.name,
+                                        e
+                                    );
+                                    let entry = state_entry_mut(state, &share.name);
+                                    entry.tb_recovery_pending = true;
+                                    entry.failback_vetoed = true;
+                                }
                                 SwitchResult::UnmountFailed(e) => {
                                     let msg = format!(
                                         "{}: auto-failback unmount failed: {}",
@@ -869,16 +2256,17 @@ fn reconcile_share(
                                     );
                                     log::error!("{}", msg);
                                     last_error = Some(msg.clone());
-                                    state_entry_mut(state, &share.name).last_error = Some(msg);
+                                    set_share_error(state, &share.name, msg, None);
                                 }
-                                SwitchResult::MountFailed { error, .. } => {
+                                SwitchResult::MountFailed { error, kind, .. } => {
                                     let msg = format!(
                                         "{}: auto-failback mount failed: {}",
                                         share.name, error
                                     );
                                     log::error!("{}", msg);
                                     last_error = Some(msg.clone());
-                                    state_entry_mut(state, &share.name).last_error = Some(msg);
+                                    last_error_kind = Some(kind);
+                                    set_share_error(state, &share.name, msg, Some(kind));
                                 }
                             }
                         }
@@ -886,19 +2274,21 @@ fn reconcile_share(
                 }
             } else if active == Backend::Tb {
                 // On TB and it's working - clear any pending flags
-                state_entry_mut(state, &share.name).tb_recovery_pending = false;
+                let entry = state_entry_mut(state, &share.name);
+                entry.tb_recovery_pending = false;
+                entry.failback_vetoed = false;
             }
         } else if let Some(desired) = desired_backend {
             // No active backend - do initial mount at /Volumes/<SHARE>
             let host = backend_host(share, desired);
-            let mount_path = config::volume_mount_path(&share.share_name);
+            let mount_path = config::share_mount_path(share);
             log::info!(
                 "{}: initial mount to {} at {}",
                 share.name,
                 desired.short_label(),
                 mount_path.display()
             );
-            match mount::smb::mount_share(host, &share.share_name, &share.username, &mount_path) {
+            match mount_from(config, share, host, &mount_path) {
                 Ok(()) => {
                     if let Err(e) = set_symlink_atomically(&mount_path, &stable_path) {
                         log::error!("{}: symlink failed: {}", share.name, e);
@@ -906,12 +2296,15 @@ fn reconcile_share(
                     let entry = state_entry_mut(state, &share.name);
                     entry.active_backend = Some(desired);
                     entry.last_switch_at = Some(now);
+                    event_hook(HookEvent::PostMount, None, Some(desired), "initial-mount");
                 }
                 Err(e) => {
                     let msg = format!("{}: initial mount failed: {}", share.name, e);
                     log::error!("{}", msg);
                     last_error = Some(msg.clone());
-                    state_entry_mut(state, &share.name).last_error = Some(msg);
+                    last_error_kind = Some(e.kind());
+                    set_share_error(state, &share.name, msg, Some(e.kind()));
+                    note_auth_failure(state, &share.name, desired, Some(e.kind()));
                 }
             }
         }
@@ -936,7 +2329,7 @@ fn reconcile_share(
                 new_backend.short_label()
             );
             // Create stable symlink for newly-mounted share
-            let mount_point = config::volume_mount_path(&share.share_name);
+            let mount_point = config::share_mount_path(share);
             if let Err(e) = set_symlink_atomically(&mount_point, &stable_path) {
                 log::error!(
                     "{}: symlink creation after mount_all failed: {}",
@@ -957,7 +2350,7 @@ fn reconcile_share(
             let needs_repair = if stable_path.symlink_metadata().is_ok() {
                 // Symlink exists — check if it points to the right place
                 match std::fs::read_link(&stable_path) {
-                    Ok(target) => target != config::volume_mount_path(&share.share_name),
+                    Ok(target) => target != config::share_mount_path(share),
                     Err(_) => true,
                 }
             } else {
@@ -965,7 +2358,7 @@ fn reconcile_share(
                 true
             };
             if needs_repair {
-                let mount_point = config::volume_mount_path(&share.share_name);
+                let mount_point = config::share_mount_path(share);
                 log::info!(
                     "{}: recreating missing/broken stable symlink {} -> {}",
                     share.name,
@@ -982,27 +2375,49 @@ fn reconcile_share(
     if last_error.is_none() {
         if tb.status.last_error.is_some() {
             last_error = tb.status.last_error.clone();
+            last_error_kind = tb.status.last_error_kind;
         }
         if last_error.is_none() && fb.status.last_error.is_some() {
             last_error = fb.status.last_error.clone();
+            last_error_kind = fb.status.last_error_kind;
         }
     }
-
-    // Build final status
+    if last_error.is_none() {
+        let entry = state_entry_mut(state, &share.name);
+        last_error = entry.last_error.clone();
+        last_error_kind = entry.last_error_kind;
+    }
+
+    // Build final status
     let entry = state_entry_mut(state, &share.name);
-    ShareStatus {
+    let mut share_events = switch_events.into_inner();
+    if entry.tb_recovery_pending && !was_recovery_pending {
+        share_events.push(ShareEventKind::RecoveryPending);
+    }
+    if entry.last_error.is_some() && entry.last_error != previous_error {
+        share_events.push(ShareEventKind::Error);
+    }
+    let status = ShareStatus {
         name: share.name.clone(),
         stable_path: stable_path.display().to_string(),
         active_backend: entry.active_backend.or(active_backend),
         desired_backend,
         tb_recovery_pending: entry.tb_recovery_pending,
+        recovery_hold: RecoveryHold::of(config, entry),
         tb: tb.status,
         fallback: fb.status,
         last_switch_at: entry.last_switch_at,
-        last_error: last_error.or_else(|| entry.last_error.clone()),
+        last_error,
+        last_error_kind,
+        last_error_hint: last_error_kind.map(|kind| kind.hint().to_string()),
+        needs_credentials: entry.needs_credentials,
         tb_reachable_since: entry.tb_reachable_since,
         tb_healthy_since: entry.tb_healthy_since,
-    }
+        foreign_mounts: Vec::new(),
+        pending_switch: entry.pending_switch.clone(),
+        leases: entry.leases.clone(),
+    };
+    (status, share_events)
 }
 
 /// Choose desired backend based on reachability (since only the active backend is mounted).
@@ -1059,6 +2474,7 @@ fn choose_desired_backend(
 }
 
 fn probe_backend(
+    config: &Config,
     share: &ShareConfig,
     backend: Backend,
     timeout: Duration,
@@ -1066,10 +2482,11 @@ fn probe_backend(
     active_backend: Option<Backend>,
 ) -> BackendProbe {
     let host = backend_host(share, backend).to_string();
-    let mount_path = config::volume_mount_path(&share.share_name);
+    let mount_path = config::share_mount_path(share);
 
     let mut last_error = None;
-    let reachable = discovery::is_smb_reachable_with_timeout(&host, timeout);
+    let mut last_error_kind = None;
+    let reachable = discovery::is_smb_reachable_with_timeout(&host, share.smb_port(), timeout);
 
     let mut mounted = mount::smb::is_mounted(&mount_path);
     let mut alive = mounted && mount::smb::is_mount_alive(&mount_path);
@@ -1120,7 +2537,7 @@ fn probe_backend(
             host,
             mount_path.display()
         );
-        match mount::smb::mount_share(&host, &share.share_name, &share.username, &mount_path) {
+        match mount_from(config, share, &host, &mount_path) {
             Ok(()) => {
                 mounted = mount::smb::is_mounted(&mount_path);
                 alive = mounted && mount::smb::is_mount_alive(&mount_path);
@@ -1132,6 +2549,13 @@ fn probe_backend(
                         host,
                         mount_path.display()
                     );
+                    let context = HookContext {
+                        from: None,
+                        to: Some(backend),
+                        mount_point: &mount_path,
+                        reason: "remount",
+                    };
+                    let _ = hooks::run(config, share, HookEvent::PostMount, &context);
                 } else if mounted {
                     log::info!(
                         "{} {}: mounted but not yet alive host={} path={}",
@@ -1144,7 +2568,7 @@ fn probe_backend(
             }
             Err(err) => {
                 let message = err.to_string();
-                if is_benign_mount_collision(&message) {
+                if err.kind() == MountFailureKind::FinderCollision {
                     log::info!(
                         "{} {}: mount collision (non-fatal): {}",
                         share.name,
@@ -1160,6 +2584,7 @@ fn probe_backend(
                     );
                     log::warn!("{}", msg);
                     last_error = Some(msg);
+                    last_error_kind = Some(err.kind());
                 }
             }
         }
@@ -1172,17 +2597,52 @@ fn probe_backend(
     let ready = reachable && mounted && alive;
     BackendProbe {
         status: BackendStatus {
-            host,
+            host: network::host::display(&host),
             mount_point: mount_path.display().to_string(),
             reachable,
             mounted,
             alive,
             ready,
             last_error,
+            last_error_kind,
         },
     }
 }
 
+/// Mount `share` from `host`, passing stored credentials when the configured
+/// store requires them (see `credentials::mount_password`).
+fn mount_from(
+    config: &Config,
+    share: &ShareConfig,
+    host: &str,
+    mount_point: &Path,
+) -> Result<(), mount::smb::MountError> {
+    let password = if share.guest {
+        None
+    } else {
+        credentials::mount_password(&config.global, share, host)
+    };
+    mount::smb::mount_share(
+        host,
+        &share.share_name,
+        &smb_options(share),
+        mount_point,
+        password.as_deref(),
+    )
+}
+
+/// How `share` connects: account, domain, port and mount flags.
+pub fn smb_options(share: &ShareConfig) -> mount::smb::SmbOptions {
+    mount::smb::SmbOptions {
+        username: share.username.clone(),
+        domain: share.domain.clone(),
+        port: share.port,
+        guest: share.guest,
+        mount_options: share.mount_options.clone(),
+        own_mount_point: share.owns_mount_point(),
+    }
+}
+
 fn select_shares<'a>(config: &'a Config, share_names: &[String]) -> Result<Vec<&'a ShareConfig>> {
     if share_names.is_empty() {
         return Ok(config.shares.iter().collect());
@@ -1211,12 +2671,52 @@ fn backend_ready(desired: Backend, tb: &BackendStatus, fb: &BackendStatus) -> bo
     }
 }
 
-fn is_benign_mount_collision(message: &str) -> bool {
-    let lower = message.to_ascii_lowercase();
-    lower.contains("file exists")
-        && (lower.contains("no detectable share path")
-            || lower.contains("an error of type -5014")
-            || lower.contains("execution error"))
+/// Suspend automatic mounts for a share when `kind` is an authentication failure.
+fn note_auth_failure(
+    state: &mut RuntimeState,
+    share_name: &str,
+    backend: Backend,
+    kind: Option<MountFailureKind>,
+) {
+    if kind != Some(MountFailureKind::AuthFailed) {
+        return;
+    }
+    let entry = state_entry_mut(state, share_name);
+    if entry.needs_credentials.is_none() {
+        log::warn!(
+            "{}: {} rejected the credentials; automatic mounts suspended until they are re-entered",
+            share_name,
+            backend.short_label()
+        );
+    }
+    entry.needs_credentials = Some(backend);
+}
+
+/// Clear the needs-credentials state after the user re-entered credentials so
+/// the next reconcile mounts the share again. Returns whether it was set.
+pub fn resume_after_credentials(state: &mut RuntimeState, share_name: &str) -> bool {
+    let entry = state_entry_mut(state, share_name);
+    let was_suspended = entry.needs_credentials.take().is_some();
+    if entry.last_error_kind == Some(MountFailureKind::AuthFailed) {
+        entry.last_error = None;
+        entry.last_error_kind = None;
+    }
+    if was_suspended {
+        log::info!("{}: credentials re-entered, resuming mounts", share_name);
+    }
+    was_suspended
+}
+
+/// Record a share-level error in runtime state along with its category.
+fn set_share_error(
+    state: &mut RuntimeState,
+    share_name: &str,
+    message: String,
+    kind: Option<MountFailureKind>,
+) {
+    let entry = state_entry_mut(state, share_name);
+    entry.last_error = Some(message);
+    entry.last_error_kind = kind;
 }
 
 fn state_entry_mut<'a>(state: &'a mut RuntimeState, share_name: &str) -> &'a mut ShareRuntimeState {
@@ -1361,36 +2861,22 @@ fn path_eq(a: &Path, b: &Path) -> bool {
     }
 }
 
+/// Whether any file is open under `path`, by the native libproc scan or, when
+/// that is unavailable, `lsof`. False if neither could tell.
 fn has_open_handles(path: &Path) -> bool {
-    open_handle_count(path) > 0
-}
-
-/// Count the number of open file handles on the given path using `lsof`.
-/// Returns 0 if no handles are open or if lsof fails.
-/// Used by the tray UI to show file count before switch (spec 14 AC 2).
-pub fn open_handle_count(path: &Path) -> usize {
-    let output = Command::new("lsof").arg("+D").arg(path).output();
-    match output {
-        Ok(output) => {
-            if output.stdout.is_empty() {
-                0
-            } else {
-                // lsof header is 1 line; remaining lines are open handles
-                let count = output
-                    .stdout
-                    .iter()
-                    .filter(|&&b| b == b'\n')
-                    .count()
-                    .saturating_sub(1);
-                log::info!("lsof: {} open handle(s) on {}", count, path.display());
-                count
-            }
-        }
-        Err(e) => {
-            log::warn!("lsof check failed on {}: {}", path.display(), e);
-            0
-        }
+    !open_files::list(path).is_empty()
+}
+
+/// Open files on a share's mount, with the processes holding them. Empty when
+/// the share is not mounted.
+pub fn share_open_files(config: &Config, share_name: &str) -> Result<Vec<OpenFile>> {
+    let share = config::find_share(config, share_name)
+        .ok_or_else(|| anyhow!("share '{}' is not configured", share_name))?;
+    let mount_point = config::share_mount_path(share);
+    if !mount::smb::is_mounted(&mount_point) {
+        return Ok(Vec::new());
     }
+    Ok(open_files::list(&mount_point))
 }
 
 fn is_symlink(path: &Path) -> bool {
@@ -1403,6 +2889,7 @@ fn is_symlink(path: &Path) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::runner;
     use chrono::Duration as ChronoDuration;
 
     #[test]
@@ -1476,10 +2963,16 @@ mod tests {
     #[test]
     fn benign_mount_collision_detection_matches_expected_patterns() {
         let benign = "mount_smbfs failed (exit 64): //u@macmini.local/CORE: File exists; osascript fallback mounted no detectable share path";
-        assert!(is_benign_mount_collision(benign));
+        assert_eq!(
+            MountFailureKind::classify(benign),
+            MountFailureKind::FinderCollision
+        );
 
         let fatal = "mount_smbfs failed (exit 64): permission denied";
-        assert!(!is_benign_mount_collision(fatal));
+        assert_ne!(
+            MountFailureKind::classify(fatal),
+            MountFailureKind::FinderCollision
+        );
     }
 
     // --- P7.3: Additional engine reconciliation tests ---
@@ -1590,27 +3083,39 @@ mod tests {
     #[test]
     fn benign_mount_collision_type_5014() {
         let msg = "file exists an error of type -5014 occurred";
-        assert!(is_benign_mount_collision(msg));
+        assert_eq!(
+            MountFailureKind::classify(msg),
+            MountFailureKind::FinderCollision
+        );
     }
 
     // Benign mount collision: test the execution error pattern
     #[test]
     fn benign_mount_collision_execution_error() {
         let msg = "File Exists execution error something";
-        assert!(is_benign_mount_collision(msg));
+        assert_eq!(
+            MountFailureKind::classify(msg),
+            MountFailureKind::FinderCollision
+        );
     }
 
     // Non-benign: "file exists" alone without the qualifying context
     #[test]
     fn not_benign_when_file_exists_alone() {
         let msg = "file exists";
-        assert!(!is_benign_mount_collision(msg));
+        assert_ne!(
+            MountFailureKind::classify(msg),
+            MountFailureKind::FinderCollision
+        );
     }
 
     // Non-benign: empty string
     #[test]
     fn not_benign_empty_string() {
-        assert!(!is_benign_mount_collision(""));
+        assert_ne!(
+            MountFailureKind::classify(""),
+            MountFailureKind::FinderCollision
+        );
     }
 
     // --- State entry helper ---
@@ -1673,6 +3178,7 @@ mod tests {
             thunderbolt_host: "10.0.0.1".to_string(),
             fallback_host: "192.168.1.1".to_string(),
             share_name: "CORE".to_string(),
+            ..ShareConfig::default()
         };
         assert_eq!(backend_host(&share, Backend::Tb), "10.0.0.1");
         assert_eq!(backend_host(&share, Backend::Fallback), "192.168.1.1");
@@ -1690,6 +3196,7 @@ mod tests {
             alive: true,
             ready: true,
             last_error: None,
+            last_error_kind: None,
         };
         let fb = BackendStatus {
             host: "192.168.1.1".to_string(),
@@ -1699,6 +3206,7 @@ mod tests {
             alive: false,
             ready: false,
             last_error: None,
+            last_error_kind: None,
         };
         assert!(backend_ready(Backend::Tb, &tb, &fb));
         assert!(!backend_ready(Backend::Fallback, &tb, &fb));
@@ -1716,6 +3224,7 @@ mod tests {
                     thunderbolt_host: "10.0.0.1".to_string(),
                     fallback_host: "192.168.1.1".to_string(),
                     share_name: "CORE".to_string(),
+                    ..ShareConfig::default()
                 },
                 ShareConfig {
                     name: "DATA".to_string(),
@@ -1723,6 +3232,7 @@ mod tests {
                     thunderbolt_host: "10.0.0.2".to_string(),
                     fallback_host: "192.168.1.2".to_string(),
                     share_name: "DATA".to_string(),
+                    ..ShareConfig::default()
                 },
             ],
             ..Config::default()
@@ -1741,6 +3251,7 @@ mod tests {
                     thunderbolt_host: "10.0.0.1".to_string(),
                     fallback_host: "192.168.1.1".to_string(),
                     share_name: "CORE".to_string(),
+                    ..ShareConfig::default()
                 },
                 ShareConfig {
                     name: "DATA".to_string(),
@@ -1748,6 +3259,7 @@ mod tests {
                     thunderbolt_host: "10.0.0.2".to_string(),
                     fallback_host: "192.168.1.2".to_string(),
                     share_name: "DATA".to_string(),
+                    ..ShareConfig::default()
                 },
             ],
             ..Config::default()
@@ -1799,6 +3311,7 @@ mod tests {
         let output = StatusOutput {
             lsof_recheck: true,
             shares: vec![],
+            reconciler: None,
         };
         let json = serde_json::to_string_pretty(&output).unwrap();
         assert!(json.contains("\"lsof_recheck\": true"));
@@ -1813,6 +3326,7 @@ mod tests {
             active_backend: Some(Backend::Tb),
             desired_backend: Some(Backend::Tb),
             tb_recovery_pending: false,
+            recovery_hold: None,
             tb: BackendStatus {
                 host: "10.0.0.1".to_string(),
                 mount_point: "/Volumes/core".to_string(),
@@ -1821,6 +3335,7 @@ mod tests {
                 alive: true,
                 ready: true,
                 last_error: None,
+                last_error_kind: None,
             },
             fallback: BackendStatus {
                 host: "10.0.1.1".to_string(),
@@ -1830,21 +3345,36 @@ mod tests {
                 alive: false,
                 ready: false,
                 last_error: None,
+                last_error_kind: None,
             },
             last_switch_at: None,
             last_error: None,
+            last_error_kind: None,
+            last_error_hint: None,
+            needs_credentials: None,
             tb_reachable_since: None,
             tb_healthy_since: None,
+            foreign_mounts: vec![ForeignMount {
+                mount_point: "/Volumes/core-1".to_string(),
+                host: "10.0.1.1".to_string(),
+                share: "core".to_string(),
+                user: Some("admin".to_string()),
+                conflict: ForeignConflict::SameShare,
+            }],
+            pending_switch: None,
+            leases: Vec::new(),
         };
         let output = StatusOutput {
             lsof_recheck: false,
             shares: vec![status],
+            reconciler: None,
         };
         let json = serde_json::to_string_pretty(&output).unwrap();
         assert!(json.contains("\"lsof_recheck\": false"));
         assert!(json.contains("\"core\""));
         assert!(json.contains("\"tb_reachable_since\""));
         assert!(json.contains("\"tb_healthy_since\""));
+        assert!(json.contains("\"conflict\": \"same_share\""));
     }
 
     // --- Symlink-related pure functions ---
@@ -1951,6 +3481,758 @@ mod tests {
         )));
     }
 
+    // --- Replayed sessions (fixtures/replay, captured with MOUNTAINEER_RECORD) ---
+
+    fn replay_fixture(name: &str) -> runner::replay::Guard {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures/replay")
+            .join(name);
+        let text = fs::read_to_string(&path).unwrap();
+        runner::replay::install(runner::parse_fixture(&text).unwrap())
+    }
+
+    fn replay_config(shares_root: &Path) -> Config {
+        Config {
+            global: config::GlobalConfig {
+                shares_root: shares_root.display().to_string(),
+                ..config::GlobalConfig::default()
+            },
+            // Replayed failovers must not reach the desktop.
+            notifications: config::NotificationsConfig {
+                enabled: false,
+                ..config::NotificationsConfig::default()
+            },
+            shares: vec![ShareConfig {
+                name: "CORE".to_string(),
+                username: "admin".to_string(),
+                thunderbolt_host: "10.10.10.1".to_string(),
+                fallback_host: "nas.local".to_string(),
+                share_name: "CORE".to_string(),
+                ..ShareConfig::default()
+            }],
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn replay_tb_drop_fails_over_to_fallback() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = replay_config(dir.path());
+        let mut state = RuntimeState::default();
+        state_entry_mut(&mut state, "CORE").active_backend = Some(Backend::Tb);
+
+        let guard = replay_fixture("tb_drop_failover.jsonl");
+        let now = Utc::now();
+        let status = reconcile_share(&cfg, &mut state, &cfg.shares[0], true, true, now);
+
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        assert_eq!(guard.unconsumed(), 0);
+        assert_eq!(status.active_backend, Some(Backend::Fallback));
+        assert!(!status.tb.reachable);
+        assert!(status.last_error.is_none());
+        let entry = state.shares.get("core").unwrap();
+        assert_eq!(entry.active_backend, Some(Backend::Fallback));
+        assert!(entry.last_switch_at.is_some());
+        assert_eq!(
+            fs::read_link(dir.path().join("CORE")).unwrap(),
+            PathBuf::from("/Volumes/CORE")
+        );
+    }
+
+    #[test]
+    fn replay_failover_runs_lifecycle_hooks() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("hooks.log");
+        let record = format!(
+            "echo \"$MOUNTAINEER_EVENT $MOUNTAINEER_FROM>$MOUNTAINEER_TO $MOUNTAINEER_REASON\" >> '{}'",
+            log.display()
+        );
+        let mut cfg = replay_config(dir.path());
+        cfg.hooks = config::HooksConfig {
+            pre_switch: Some(record.clone()),
+            pre_unmount: Some(record.clone()),
+            post_mount: Some(record.clone()),
+            failover: Some(record),
+            ..config::HooksConfig::default()
+        };
+        let mut state = RuntimeState::default();
+        state_entry_mut(&mut state, "CORE").active_backend = Some(Backend::Tb);
+
+        let guard = replay_fixture("tb_drop_failover.jsonl");
+        let status = reconcile_share(&cfg, &mut state, &cfg.shares[0], true, true, Utc::now());
+
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        assert_eq!(status.active_backend, Some(Backend::Fallback));
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            "pre-switch tb>fallback failover\n\
+             post-mount tb>fallback failover\n\
+             failover tb>fallback failover\n"
+        );
+    }
+
+    #[test]
+    fn replay_failover_raises_share_event_with_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = replay_config(dir.path());
+        let mut state = RuntimeState::default();
+        state_entry_mut(&mut state, "CORE").active_backend = Some(Backend::Tb);
+
+        let guard = replay_fixture("tb_drop_failover.jsonl");
+        let (status, kinds) =
+            reconcile_share_events(&cfg, &mut state, &cfg.shares[0], true, true, Utc::now());
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+
+        assert_eq!(kinds, vec![ShareEventKind::Failover]);
+        let event = events::ShareEvent {
+            event: kinds[0],
+            at: Utc::now(),
+            status,
+        };
+        let json: serde_json::Value = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "failover");
+        assert_eq!(json["name"], "CORE");
+        assert_eq!(json["active_backend"], "fallback");
+        assert_eq!(json["tb"]["host"], "10.10.10.1");
+        assert!(json["at"].is_string());
+    }
+
+    #[test]
+    fn pre_switch_hook_vetoes_before_touching_the_mount() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cfg = replay_config(dir.path());
+        cfg.shares[0].hooks.pre_switch = Some("echo 'render in progress' >&2; exit 1".to_string());
+        let mut state = RuntimeState::default();
+        state_entry_mut(&mut state, "CORE").active_backend = Some(Backend::Fallback);
+
+        let guard = runner::replay::install(Vec::new());
+        let result = switch_backend_single_mount(
+            &cfg,
+            &mut state,
+            &cfg.shares[0],
+            Backend::Fallback,
+            Backend::Tb,
+            true,
+            SwitchReason::Manual,
+        );
+
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        match result {
+            SwitchResult::Vetoed(e) => assert_eq!(e, "exited with 1: render in progress"),
+            other => panic!("expected Vetoed, got {:?}", other),
+        }
+        assert_eq!(state.shares["core"].active_backend, Some(Backend::Fallback));
+    }
+
+    #[test]
+    fn replay_failed_switch_runs_switch_failed_hook() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("hooks.log");
+        let record = format!(
+            "echo \"$MOUNTAINEER_EVENT $MOUNTAINEER_FROM>$MOUNTAINEER_TO\" >> '{}'",
+            log.display()
+        );
+        let mut cfg = replay_config(dir.path());
+        cfg.hooks = config::HooksConfig {
+            pre_switch: Some(record.clone()),
+            switch_failed: Some(record),
+            ..config::HooksConfig::default()
+        };
+        let mut state = RuntimeState::default();
+        state_entry_mut(&mut state, "CORE").active_backend = Some(Backend::Fallback);
+
+        let guard = replay_fixture("switch_unmount_fails.jsonl");
+        let result = switch_backend_single_mount(
+            &cfg,
+            &mut state,
+            &cfg.shares[0],
+            Backend::Fallback,
+            Backend::Tb,
+            false,
+            SwitchReason::Manual,
+        );
+
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        assert_eq!(guard.unconsumed(), 0);
+        assert!(
+            matches!(result, SwitchResult::UnmountFailed(_)),
+            "{:?}",
+            result
+        );
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            "pre-switch fallback>tb\nswitch-failed fallback>tb\n"
+        );
+    }
+
+    #[test]
+    fn replay_tb_recovery_deferred_by_open_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = replay_config(dir.path());
+        let now = Utc::now();
+        let mut state = RuntimeState::default();
+        {
+            let entry = state_entry_mut(&mut state, "CORE");
+            entry.active_backend = Some(Backend::Fallback);
+            entry.tb_reachable_since = Some(now - ChronoDuration::seconds(60));
+        }
+
+        let guard = replay_fixture("tb_recovery_busy.jsonl");
+        let status = reconcile_share(&cfg, &mut state, &cfg.shares[0], true, true, now);
+
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        assert_eq!(guard.unconsumed(), 0);
+        assert_eq!(status.active_backend, Some(Backend::Fallback));
+        assert!(status.tb_recovery_pending);
+        assert!(status.last_error.is_none());
+    }
+
+    #[test]
+    fn replay_vetoed_failback_asks_the_hook_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("hooks.log");
+        let mut cfg = replay_config(dir.path());
+        cfg.hooks.pre_switch = Some(format!(
+            "echo \"$MOUNTAINEER_EVENT\" >> '{}'; exit 1",
+            log.display()
+        ));
+        let now = Utc::now();
+        let mut state = RuntimeState::default();
+        {
+            let entry = state_entry_mut(&mut state, "CORE");
+            entry.active_backend = Some(Backend::Fallback);
+            entry.tb_reachable_since = Some(now - ChronoDuration::seconds(60));
+        }
+
+        let guard = replay_fixture("tb_recovery_vetoed.jsonl");
+        reconcile_share(&cfg, &mut state, &cfg.shares[0], true, true, now);
+        assert!(state.shares["core"].failback_vetoed);
+        let later = now + ChronoDuration::seconds(5);
+        let status = reconcile_share(&cfg, &mut state, &cfg.shares[0], true, true, later);
+
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        assert_eq!(guard.unconsumed(), 0);
+        assert_eq!(status.active_backend, Some(Backend::Fallback));
+        assert!(status.tb_recovery_pending);
+        assert_eq!(fs::read_to_string(&log).unwrap(), "pre-switch\n");
+    }
+
+    #[test]
+    fn replay_drained_switch_waits_for_files_to_close() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = replay_config(dir.path());
+        let now = Utc::now();
+        let journal_path = dir.path().join("switch-journal.json");
+        let mut state = journaled_state(&journal_path);
+        state_entry_mut(&mut state, "CORE").active_backend = Some(Backend::Fallback);
+        request_drained_switch(
+            &mut state,
+            "CORE",
+            Backend::Tb,
+            Duration::from_secs(600),
+            now,
+        );
+
+        let guard = replay_fixture("drain_switch_core.jsonl");
+        let outcome = process_pending_switch(&cfg, &mut state, &cfg.shares[0], now);
+        assert!(matches!(outcome, Some(DrainOutcome::Waiting)));
+        assert!(state.shares["core"].pending_switch.is_some());
+
+        let later = now + ChronoDuration::seconds(30);
+        let outcome = process_pending_switch(&cfg, &mut state, &cfg.shares[0], later);
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        assert_eq!(guard.unconsumed(), 0);
+        assert!(matches!(outcome, Some(DrainOutcome::Switched)));
+        let entry = &state.shares["core"];
+        assert_eq!(entry.active_backend, Some(Backend::Tb));
+        assert!(entry.pending_switch.is_none());
+        assert!(process_pending_switch(&cfg, &mut state, &cfg.shares[0], later).is_none());
+
+        // Journaled as done until the caller saves state.
+        let intents = journal::intents_at(&journal_path).unwrap();
+        assert_eq!(intents.len(), 1);
+        assert_eq!(intents[0].phase, SwitchPhase::Switched);
+        assert_eq!(intents[0].to, Backend::Tb);
+    }
+
+    /// Fresh state whose switches are journaled at `journal`.
+    fn journaled_state(journal: &Path) -> RuntimeState {
+        RuntimeState {
+            journal: Some(journal.to_path_buf()),
+            ..RuntimeState::default()
+        }
+    }
+
+    /// An intent written an hour ago by a process that no longer exists.
+    fn dead_intent(from: Backend, to: Backend, phase: SwitchPhase) -> SwitchIntent {
+        SwitchIntent {
+            pid: u32::MAX,
+            updated_at: Utc::now() - ChronoDuration::hours(1),
+            ..SwitchIntent::new("CORE", from, to, phase)
+        }
+    }
+
+    #[test]
+    fn replay_recovery_finishes_switch_interrupted_while_mounting() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = replay_config(dir.path());
+        let journal_path = dir.path().join("switch-journal.json");
+        let mut state = journaled_state(&journal_path);
+        state_entry_mut(&mut state, "CORE").active_backend = Some(Backend::Fallback);
+        journal::record(
+            &journal_path,
+            &dead_intent(Backend::Fallback, Backend::Tb, SwitchPhase::Mounting),
+        );
+
+        let guard = replay_fixture("interrupted_switch_mounting.jsonl");
+        let recovered = recover_interrupted_switches(&cfg, &mut state);
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        assert_eq!(guard.unconsumed(), 0);
+
+        assert_eq!(recovered.len(), 1);
+        assert_eq!(recovered[0].mounted, Some(Backend::Tb));
+        assert_eq!(
+            recovered[0].describe(),
+            "CORE: switch fallback -> tb interrupted while mounting, finished on tb"
+        );
+        assert_eq!(state.shares["core"].active_backend, Some(Backend::Tb));
+        let intents = journal::intents_at(&journal_path).unwrap();
+        assert_eq!(intents[0].phase, SwitchPhase::Switched);
+        assert_eq!(intents[0].pid, std::process::id());
+    }
+
+    #[test]
+    fn replay_recovery_rolls_back_switch_interrupted_before_unmount() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = replay_config(dir.path());
+        let journal_path = dir.path().join("switch-journal.json");
+        let mut state = journaled_state(&journal_path);
+        state_entry_mut(&mut state, "CORE").active_backend = Some(Backend::Tb);
+        journal::record(
+            &journal_path,
+            &dead_intent(Backend::Tb, Backend::Fallback, SwitchPhase::Unmounting),
+        );
+
+        let guard = replay_fixture("interrupted_switch_unmounting.jsonl");
+        let recovered = recover_interrupted_switches(&cfg, &mut state);
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        assert_eq!(guard.unconsumed(), 0);
+
+        assert_eq!(recovered[0].mounted, Some(Backend::Tb));
+        assert!(recovered[0].describe().ends_with("rolled back to tb"));
+        assert_eq!(state.shares["core"].active_backend, Some(Backend::Tb));
+    }
+
+    #[test]
+    fn recovery_leaves_switches_of_live_processes_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = replay_config(dir.path());
+        let journal_path = dir.path().join("switch-journal.json");
+        let mut state = journaled_state(&journal_path);
+        let in_flight = SwitchIntent {
+            pid: std::os::unix::process::parent_id(),
+            ..SwitchIntent::new(
+                "CORE",
+                Backend::Tb,
+                Backend::Fallback,
+                SwitchPhase::Mounting,
+            )
+        };
+        journal::record(&journal_path, &in_flight);
+
+        let guard = runner::replay::install(Vec::new());
+        assert!(recover_interrupted_switches(&cfg, &mut state).is_empty());
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        assert_eq!(journal::intents_at(&journal_path).unwrap(), vec![in_flight]);
+    }
+
+    #[test]
+    fn replay_drained_switch_expires_with_files_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = replay_config(dir.path());
+        let now = Utc::now();
+        let mut state = RuntimeState::default();
+        state_entry_mut(&mut state, "CORE").active_backend = Some(Backend::Fallback);
+        let pending = request_drained_switch(
+            &mut state,
+            "CORE",
+            Backend::Tb,
+            Duration::from_secs(60),
+            now,
+        );
+        assert_eq!(pending.deadline, now + ChronoDuration::seconds(60));
+
+        // Only the busy pass is consumed; the deadline has already passed.
+        let _guard = replay_fixture("drain_switch_core.jsonl");
+        let outcome = process_pending_switch(
+            &cfg,
+            &mut state,
+            &cfg.shares[0],
+            now + ChronoDuration::seconds(61),
+        );
+        assert!(matches!(outcome, Some(DrainOutcome::Expired)));
+        let entry = &state.shares["core"];
+        assert_eq!(entry.active_backend, Some(Backend::Fallback));
+        assert!(entry.pending_switch.is_none());
+        assert!(entry.last_error.as_deref().unwrap().contains("gave up"));
+    }
+
+    #[test]
+    fn cancel_pending_switch_clears_it_once() {
+        let mut state = RuntimeState::default();
+        let now = Utc::now();
+        request_drained_switch(
+            &mut state,
+            "CORE",
+            Backend::Tb,
+            Duration::from_secs(60),
+            now,
+        );
+        let status_json = serde_json::to_string(&state).unwrap();
+        assert!(status_json.contains("pending_switch"));
+
+        let cancelled = cancel_pending_switch(&mut state, "core").unwrap();
+        assert_eq!(cancelled.to, Backend::Tb);
+        assert!(cancel_pending_switch(&mut state, "CORE").is_none());
+        assert!(
+            !serde_json::to_string(&state)
+                .unwrap()
+                .contains("pending_switch")
+        );
+    }
+
+    #[test]
+    fn pending_switch_on_target_backend_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = replay_config(dir.path());
+        let mut state = RuntimeState::default();
+        state_entry_mut(&mut state, "CORE").active_backend = Some(Backend::Tb);
+        request_drained_switch(
+            &mut state,
+            "CORE",
+            Backend::Tb,
+            Duration::from_secs(60),
+            Utc::now(),
+        );
+        let outcome = process_pending_switch(&cfg, &mut state, &cfg.shares[0], Utc::now());
+        assert!(matches!(outcome, Some(DrainOutcome::AlreadyOnTarget)));
+        assert!(state.shares["core"].pending_switch.is_none());
+    }
+
+    #[test]
+    fn replay_lease_blocks_tb_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+        for (auto_failback, lsof_recheck) in [(false, true), (true, false)] {
+            let mut cfg = replay_config(dir.path());
+            cfg.global.auto_failback = auto_failback;
+            cfg.global.lsof_recheck = lsof_recheck;
+            let now = Utc::now();
+            let mut state = RuntimeState::default();
+            {
+                let entry = state_entry_mut(&mut state, "CORE");
+                entry.active_backend = Some(Backend::Fallback);
+                entry.tb_reachable_since = Some(now - ChronoDuration::seconds(60));
+            }
+            let lease = acquire_lease(
+                &mut state,
+                "CORE",
+                Duration::from_secs(3600),
+                Some("make".to_string()),
+                now,
+            );
+
+            let guard = replay_fixture("tb_recovery_leased.jsonl");
+            let status = reconcile_share(&cfg, &mut state, &cfg.shares[0], true, true, now);
+
+            assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+            assert_eq!(guard.unconsumed(), 0);
+            assert_eq!(status.active_backend, Some(Backend::Fallback));
+            assert!(status.tb_recovery_pending);
+            assert_eq!(status.leases, vec![lease]);
+        }
+    }
+
+    #[test]
+    fn leases_expire_and_release_by_token() {
+        let now = Utc::now();
+        let mut state = RuntimeState::default();
+        let short = acquire_lease(&mut state, "CORE", Duration::from_secs(60), None, now);
+        let long = acquire_lease(&mut state, "core", Duration::from_secs(7200), None, now);
+        assert_ne!(short.token, long.token);
+        assert_eq!(live_leases(&state, "CORE", now).len(), 2);
+
+        let later = now + ChronoDuration::seconds(61);
+        assert_eq!(live_leases(&state, "CORE", later), vec![long.clone()]);
+        prune_expired_leases(&mut state, "CORE", later);
+        assert_eq!(state.shares["core"].leases, vec![long.clone()]);
+
+        let (share, released) = release_lease(&mut state, &long.token).unwrap();
+        assert_eq!(share, "core");
+        assert_eq!(released, long);
+        assert!(release_lease(&mut state, &long.token).is_none());
+        assert!(!serde_json::to_string(&state).unwrap().contains("leases"));
+    }
+
+    #[test]
+    fn renew_lease_extends_only_live_leases() {
+        let now = Utc::now();
+        let mut state = RuntimeState::default();
+        let lease = acquire_lease(&mut state, "CORE", Duration::from_secs(60), None, now);
+        let later = now + ChronoDuration::seconds(30);
+        assert!(renew_lease(
+            &mut state,
+            &lease.token,
+            Duration::from_secs(60),
+            later
+        ));
+        assert_eq!(
+            state.shares["core"].leases[0].expires_at,
+            later + ChronoDuration::seconds(60)
+        );
+        let expired = later + ChronoDuration::seconds(61);
+        assert!(!renew_lease(
+            &mut state,
+            &lease.token,
+            Duration::from_secs(60),
+            expired
+        ));
+        assert!(!renew_lease(
+            &mut state,
+            "lease-unknown",
+            Duration::from_secs(60),
+            now
+        ));
+    }
+
+    #[test]
+    fn merge_external_holds_keeps_holds_made_since_the_last_load() {
+        let now = Utc::now();
+        let ttl = Duration::from_secs(60);
+        let mut disk = RuntimeState::default();
+        state_entry_mut(&mut disk, "CORE").active_backend = Some(Backend::Tb);
+        let released = acquire_lease(&mut disk, "CORE", ttl, None, now);
+        state_entry_mut(&mut disk, "DATA");
+        let mut memory = disk.clone();
+        memory.base = disk.shares.clone();
+
+        // Here: a lease on CORE and a drained switch of DATA, not saved yet.
+        let ours = acquire_lease(&mut memory, "CORE", ttl, None, now);
+        request_drained_switch(&mut memory, "DATA", Backend::Fallback, ttl, now);
+        // Elsewhere: the first lease released, another taken, CORE drained.
+        release_lease(&mut disk, &released.token);
+        let theirs = acquire_lease(&mut disk, "CORE", ttl, None, now);
+        request_drained_switch(&mut disk, "CORE", Backend::Fallback, ttl, now);
+
+        merge_external_holds(&mut memory, &disk);
+        let core = &memory.shares["core"];
+        assert_eq!(core.active_backend, Some(Backend::Tb));
+        assert_eq!(core.leases, vec![theirs, ours]);
+        assert_eq!(core.pending_switch.as_ref().unwrap().to, Backend::Fallback);
+        let data = &memory.shares["data"];
+        assert_eq!(data.pending_switch.as_ref().unwrap().to, Backend::Fallback);
+    }
+
+    // --- State persistence ---
+
+    #[test]
+    fn saves_from_stale_copies_keep_changes_to_different_shares() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+
+        let mut tray = load_runtime_state_at(&path).unwrap();
+        let mut cli = load_runtime_state_at(&path).unwrap();
+        state_entry_mut(&mut tray, "CORE").active_backend = Some(Backend::Tb);
+        state_entry_mut(&mut cli, "DATA").active_backend = Some(Backend::Fallback);
+        save_runtime_state_at(&path, &mut tray).unwrap();
+        save_runtime_state_at(&path, &mut cli).unwrap();
+
+        let saved = load_runtime_state_at(&path).unwrap();
+        assert_eq!(saved.shares["core"].active_backend, Some(Backend::Tb));
+        assert_eq!(saved.shares["data"].active_backend, Some(Backend::Fallback));
+        // The later writer also picked up the earlier one's change.
+        assert_eq!(cli.shares["core"].active_backend, Some(Backend::Tb));
+    }
+
+    #[test]
+    fn concurrent_changes_to_one_share_merge_by_field() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let mut setup = RuntimeState::default();
+        state_entry_mut(&mut setup, "CORE").active_backend = Some(Backend::Tb);
+        state_entry_mut(&mut setup, "CORE").last_error = Some("old".to_string());
+        save_runtime_state_at(&path, &mut setup).unwrap();
+
+        let mut tray = load_runtime_state_at(&path).unwrap();
+        let mut cli = load_runtime_state_at(&path).unwrap();
+        // The tray only clears the error; the CLI switches.
+        state_entry_mut(&mut tray, "CORE").last_error = None;
+        state_entry_mut(&mut cli, "CORE").active_backend = Some(Backend::Fallback);
+        save_runtime_state_at(&path, &mut cli).unwrap();
+        save_runtime_state_at(&path, &mut tray).unwrap();
+
+        let core = &load_runtime_state_at(&path).unwrap().shares["core"];
+        assert_eq!(core.active_backend, Some(Backend::Fallback));
+        assert_eq!(core.last_error, None);
+        assert_eq!(core.revision, 3);
+    }
+
+    #[test]
+    fn unchanged_entries_take_the_disk_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let mut setup = RuntimeState::default();
+        state_entry_mut(&mut setup, "CORE").active_backend = Some(Backend::Tb);
+        save_runtime_state_at(&path, &mut setup).unwrap();
+
+        let mut tray = load_runtime_state_at(&path).unwrap();
+        let mut cli = load_runtime_state_at(&path).unwrap();
+        state_entry_mut(&mut cli, "CORE").active_backend = Some(Backend::Fallback);
+        save_runtime_state_at(&path, &mut cli).unwrap();
+        // The tray changed nothing, so saving must not undo the switch.
+        save_runtime_state_at(&path, &mut tray).unwrap();
+
+        let core = &load_runtime_state_at(&path).unwrap().shares["core"];
+        assert_eq!(core.active_backend, Some(Backend::Fallback));
+        assert_eq!(core.revision, 2);
+        assert_eq!(tray.shares["core"].active_backend, Some(Backend::Fallback));
+    }
+
+    #[test]
+    fn parallel_writers_do_not_lose_updates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let rounds = 25;
+
+        let writers: Vec<_> = ["CORE", "DATA"]
+            .into_iter()
+            .map(|share| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for round in 0..rounds {
+                        // A whole-state writer on its own share...
+                        let mut state = load_runtime_state_at(&path).unwrap();
+                        state_entry_mut(&mut state, share).last_error = Some(round.to_string());
+                        save_runtime_state_at(&path, &mut state).unwrap();
+                        // ...and a locked edit of a list both writers touch.
+                        update_runtime_state_at(&path, |state| {
+                            acquire_lease(
+                                state,
+                                "SHARED",
+                                Duration::from_secs(60),
+                                Some(format!("{} {}", share, round)),
+                                Utc::now(),
+                            )
+                        })
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let saved = load_runtime_state_at(&path).unwrap();
+        let last = Some((rounds - 1).to_string());
+        assert_eq!(saved.shares["core"].last_error, last);
+        assert_eq!(saved.shares["data"].last_error, last);
+        assert_eq!(saved.shares["core"].revision, rounds as u64);
+        assert_eq!(saved.shares["shared"].leases.len(), 2 * rounds);
+    }
+
+    #[test]
+    fn pruning_an_expired_lease_keeps_one_acquired_elsewhere() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let now = Utc::now();
+        let mut setup = RuntimeState::default();
+        acquire_lease(
+            &mut setup,
+            "CORE",
+            Duration::from_secs(60),
+            None,
+            now - chrono::Duration::seconds(120),
+        );
+        save_runtime_state_at(&path, &mut setup).unwrap();
+
+        let mut monitor = load_runtime_state_at(&path).unwrap();
+        let acquired = update_runtime_state_at(&path, |state| {
+            acquire_lease(state, "CORE", Duration::from_secs(60), None, now)
+        })
+        .unwrap();
+        prune_expired_leases(&mut monitor, "CORE", now);
+        save_runtime_state_at(&path, &mut monitor).unwrap();
+
+        let saved = load_runtime_state_at(&path).unwrap();
+        assert_eq!(saved.shares["core"].leases, vec![acquired]);
+    }
+
+    #[test]
+    fn replay_auth_failure_suspends_automatic_mounts() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = replay_config(dir.path());
+        let mut state = RuntimeState::default();
+        state_entry_mut(&mut state, "CORE").needs_credentials = Some(Backend::Tb);
+
+        // Any mount attempt would call osascript, which the fixture lacks.
+        let guard = replay_fixture("auth_suspended.jsonl");
+        let status = reconcile_share(&cfg, &mut state, &cfg.shares[0], true, true, Utc::now());
+
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        assert_eq!(status.needs_credentials, Some(Backend::Tb));
+        assert_eq!(status.active_backend, None);
+        assert_eq!(status.desired_backend, Some(Backend::Tb));
+    }
+
+    #[test]
+    fn note_auth_failure_marks_only_auth_errors() {
+        let mut state = RuntimeState::default();
+        note_auth_failure(
+            &mut state,
+            "CORE",
+            Backend::Tb,
+            Some(MountFailureKind::HostUnreachable),
+        );
+        note_auth_failure(&mut state, "CORE", Backend::Fallback, None);
+        assert_eq!(state_entry_mut(&mut state, "CORE").needs_credentials, None);
+
+        note_auth_failure(
+            &mut state,
+            "CORE",
+            Backend::Fallback,
+            Some(MountFailureKind::AuthFailed),
+        );
+        assert_eq!(
+            state_entry_mut(&mut state, "CORE").needs_credentials,
+            Some(Backend::Fallback)
+        );
+    }
+
+    #[test]
+    fn resume_after_credentials_clears_auth_error() {
+        let mut state = RuntimeState::default();
+        set_share_error(
+            &mut state,
+            "CORE",
+            "CORE: initial mount failed: Authentication error".to_string(),
+            Some(MountFailureKind::AuthFailed),
+        );
+        note_auth_failure(
+            &mut state,
+            "CORE",
+            Backend::Tb,
+            Some(MountFailureKind::AuthFailed),
+        );
+
+        assert!(resume_after_credentials(&mut state, "core"));
+        let entry = state.shares.get("core").unwrap();
+        assert_eq!(entry.needs_credentials, None);
+        assert!(entry.last_error.is_none());
+        assert!(entry.last_error_kind.is_none());
+
+        assert!(!resume_after_credentials(&mut state, "core"));
+    }
+
     // --- path_eq ---
 
     #[test]
@@ -1968,4 +4250,300 @@ mod tests {
             Path::new("/Volumes/DATA")
         ));
     }
+
+    // --- Duplicate mounts ---
+
+    fn smb_mount(host: &str, share: &str, path: &str) -> mount::smb::SmbMount {
+        mount::smb::SmbMount {
+            host: host.to_string(),
+            share: share.to_string(),
+            user: None,
+            mount_path: PathBuf::from(path),
+        }
+    }
+
+    #[test]
+    fn is_suffixed_volume_matches_finder_names_only() {
+        assert!(is_suffixed_volume(Path::new("/Volumes/CORE-1"), "CORE"));
+        assert!(is_suffixed_volume(Path::new("/Volumes/core-12"), "CORE"));
+        assert!(!is_suffixed_volume(Path::new("/Volumes/CORE"), "CORE"));
+        assert!(!is_suffixed_volume(Path::new("/Volumes/CORE-old"), "CORE"));
+        assert!(!is_suffixed_volume(Path::new("/tmp/CORE-1"), "CORE"));
+        assert!(is_suffixed_volume(
+            Path::new("/Volumes/MY-SHARE-1"),
+            "MY-SHARE"
+        ));
+    }
+
+    #[test]
+    fn find_duplicate_mounts_skips_other_shares_own_mounts() {
+        let core = ShareConfig {
+            name: "CORE".to_string(),
+            thunderbolt_host: "10.10.10.1".to_string(),
+            fallback_host: "nas.local".to_string(),
+            share_name: "CORE".to_string(),
+            ..ShareConfig::default()
+        };
+        let other = ShareConfig {
+            name: "CORE-B".to_string(),
+            thunderbolt_host: "10.10.20.1".to_string(),
+            fallback_host: "backup.local".to_string(),
+            share_name: "CORE".to_string(),
+            mount_point: Some("/Volumes/CORE-3".to_string()),
+            ..ShareConfig::default()
+        };
+        let managed = vec![managed_mount_paths(&core), managed_mount_paths(&other)];
+        let mounts = vec![
+            smb_mount("10.10.10.1", "CORE", "/Volumes/CORE"),
+            smb_mount("nas.local", "CORE", "/tmp/elsewhere"),
+            smb_mount("10.10.20.1", "CORE", "/Volumes/CORE-3"),
+            smb_mount("unrelated.local", "CORE", "/Volumes/CORE-4"),
+            smb_mount("unrelated.local", "CORE", "/tmp/unrelated"),
+            smb_mount("10.10.10.1", "DATA", "/Volumes/DATA"),
+        ];
+
+        let (own, extras) = find_duplicate_mounts(&core, &managed[0], &managed, &mounts);
+
+        assert_eq!(own, Some(&mounts[0]));
+        let extra_paths: Vec<_> = extras.iter().map(|m| m.mount_path.clone()).collect();
+        assert_eq!(extra_paths, vec![PathBuf::from("/tmp/elsewhere")]);
+    }
+
+    #[test]
+    fn find_foreign_mounts_lists_share_copies_then_same_server() {
+        let core = ShareConfig {
+            name: "CORE".to_string(),
+            thunderbolt_host: "10.10.10.1".to_string(),
+            fallback_host: "nas.local".to_string(),
+            share_name: "CORE".to_string(),
+            ..ShareConfig::default()
+        };
+        let data = ShareConfig {
+            name: "DATA".to_string(),
+            thunderbolt_host: "10.10.10.1".to_string(),
+            fallback_host: "nas.local".to_string(),
+            share_name: "DATA".to_string(),
+            ..ShareConfig::default()
+        };
+        let managed = vec![managed_mount_paths(&core), managed_mount_paths(&data)];
+        let mounts = vec![
+            smb_mount("10.10.10.1", "CORE", "/Volumes/CORE"),
+            smb_mount("NAS.local", "CORE", "/Volumes/CORE-1"),
+            smb_mount("nas.local", "DATA", "/Volumes/DATA"),
+            smb_mount("nas.local", "PHOTOS", "/Volumes/PHOTOS"),
+            smb_mount("other.local", "MUSIC", "/Volumes/MUSIC"),
+        ];
+
+        let found = find_foreign_mounts(&core, &managed[0], &managed, &mounts);
+
+        let summary: Vec<_> = found
+            .iter()
+            .map(|m| (m.mount_point.as_str(), m.conflict))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("/Volumes/CORE-1", ForeignConflict::SameShare),
+                ("/Volumes/PHOTOS", ForeignConflict::SameServer),
+            ]
+        );
+        // CORE's mount at its own path is managed, not foreign to DATA.
+        let for_data: Vec<_> = find_foreign_mounts(&data, &managed[1], &managed, &mounts)
+            .into_iter()
+            .map(|m| (m.mount_point, m.conflict))
+            .collect();
+        assert_eq!(
+            for_data,
+            vec![
+                ("/Volumes/CORE-1".to_string(), ForeignConflict::SameServer),
+                ("/Volumes/PHOTOS".to_string(), ForeignConflict::SameServer),
+            ]
+        );
+    }
+
+    #[test]
+    fn replay_dedupe_unmounts_idle_duplicates_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = replay_config(dir.path());
+        let guard = replay_fixture("dedupe_core_1.jsonl");
+
+        let results = dedupe_mounts(&cfg, true);
+
+        assert_eq!(results.len(), 1);
+        let result = &results[0];
+        assert_eq!(result.share, "CORE");
+        assert_eq!(result.managed.as_deref(), Some("/Volumes/CORE"));
+        assert_eq!(result.duplicates.len(), 2);
+        assert_eq!(result.duplicates[0].mount_point, "/Volumes/CORE-1");
+        assert!(result.duplicates[0].unmounted);
+        assert_eq!(result.duplicates[1].mount_point, "/Volumes/CORE-2");
+        assert!(result.duplicates[1].busy);
+        assert!(!result.duplicates[1].unmounted);
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        assert_eq!(guard.unconsumed(), 0);
+    }
+
+    #[test]
+    fn replay_dedupe_dry_run_only_reports() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = replay_config(dir.path());
+        let guard = replay_fixture("dedupe_core_1.jsonl");
+
+        let results = dedupe_mounts(&cfg, false);
+
+        assert_eq!(results[0].duplicates.len(), 2);
+        assert!(
+            results[0]
+                .duplicates
+                .iter()
+                .all(|d| !d.unmounted && !d.busy)
+        );
+        // Only the mount table was read.
+        assert_eq!(guard.unconsumed(), 3);
+    }
+
+    #[test]
+    fn replay_dedupe_never_unmounts_other_servers_share() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cfg = replay_config(dir.path());
+        cfg.global.reclaim_foreign_mounts = true;
+        let guard = replay_fixture("dedupe_other_server.jsonl");
+
+        assert!(dedupe_mounts(&cfg, true).is_empty());
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+
+        let mounts = mount::smb::list_mounts();
+        let managed = vec![managed_mount_paths(&cfg.shares[0])];
+        let found = find_foreign_mounts(&cfg.shares[0], &managed[0], &managed, &mounts);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].mount_point, "/Volumes/CORE-1");
+        assert_eq!(found[0].host, "other-nas");
+        assert_eq!(found[0].conflict, ForeignConflict::SameShare);
+    }
+
+    // --- Adopting unmanaged mounts ---
+
+    #[test]
+    fn replay_unmanaged_mounts_skip_managed_share_copies() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = replay_config(dir.path());
+        let _guard = replay_fixture("adopt_mounts.jsonl");
+
+        let mounts = unmanaged_mounts(&cfg);
+
+        let points: Vec<_> = mounts.iter().map(|m| m.mount_point.as_str()).collect();
+        assert_eq!(points, vec!["/Volumes/PHOTOS", "/Volumes/PROJECTS"]);
+        assert_eq!(mounts[0].host, "nas.local");
+        assert_eq!(mounts[0].share, "PHOTOS");
+        assert_eq!(mounts[1].user.as_deref(), Some("CORP;jdoe"));
+    }
+
+    #[test]
+    fn suggest_alternate_host_uses_existing_favorite_pairing() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = replay_config(dir.path());
+
+        assert_eq!(
+            suggest_alternate_host(&cfg, "NAS.local"),
+            Some((Backend::Fallback, "10.10.10.1".to_string()))
+        );
+        assert_eq!(
+            suggest_alternate_host(&cfg, "10.10.10.1"),
+            Some((Backend::Tb, "nas.local".to_string()))
+        );
+        assert_eq!(suggest_alternate_host(&cfg, "files.corp.example"), None);
+    }
+
+    #[test]
+    fn replay_adopt_mount_takes_over_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cfg = replay_config(dir.path());
+        let mut state = RuntimeState::default();
+        let guard = replay_fixture("adopt_mounts.jsonl");
+        let mounts = unmanaged_mounts(&cfg);
+
+        let share = adopt_mount(
+            &mut cfg,
+            &mut state,
+            &mounts[0],
+            "Photos",
+            Backend::Fallback,
+            "10.10.10.1",
+        )
+        .unwrap();
+
+        assert_eq!(share.thunderbolt_host, "10.10.10.1");
+        assert_eq!(share.fallback_host, "nas.local");
+        assert_eq!(share.share_name, "PHOTOS");
+        assert!(share.guest);
+        assert!(config::find_share(&cfg, "photos").is_some());
+        let stable = dir.path().join("Photos");
+        assert_eq!(
+            fs::read_link(&stable).unwrap(),
+            PathBuf::from("/Volumes/PHOTOS")
+        );
+        let entry = state.shares.get("photos").unwrap();
+        assert_eq!(entry.active_backend, Some(Backend::Fallback));
+        assert!(entry.last_switch_at.is_some());
+        // Nothing was mounted or unmounted.
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+    }
+
+    #[test]
+    fn replay_adopt_mount_splits_domain_and_requires_alternate() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cfg = replay_config(dir.path());
+        let mut state = RuntimeState::default();
+        let _guard = replay_fixture("adopt_mounts.jsonl");
+        let mounts = unmanaged_mounts(&cfg);
+
+        assert!(
+            adopt_mount(
+                &mut cfg,
+                &mut state,
+                &mounts[1],
+                "PROJECTS",
+                Backend::Tb,
+                " "
+            )
+            .is_err()
+        );
+        assert_eq!(cfg.shares.len(), 1);
+
+        let share = adopt_mount(
+            &mut cfg,
+            &mut state,
+            &mounts[1],
+            "PROJECTS",
+            Backend::Tb,
+            "files-wifi.corp.example",
+        )
+        .unwrap();
+        assert_eq!(share.username, "jdoe");
+        assert_eq!(share.domain.as_deref(), Some("CORP"));
+        assert!(!share.guest);
+        assert_eq!(share.thunderbolt_host, "files.corp.example");
+    }
+
+    // --- Open files ---
+
+    #[test]
+    fn replay_share_open_files_falls_back_to_lsof() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = replay_config(dir.path());
+        let guard = replay_fixture("open_files_core.jsonl");
+
+        let files = share_open_files(&cfg, "core").unwrap();
+
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].command, "Final ");
+        assert_eq!(files[0].pid, 4211);
+        assert_eq!(
+            files[0].path,
+            "/Volumes/CORE/projects/edit.fcpbundle/Original Media/A001.mov"
+        );
+        assert_eq!(files[1].access, open_files::FileAccess::ReadWrite);
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        assert!(share_open_files(&cfg, "missing").is_err());
+    }
 }
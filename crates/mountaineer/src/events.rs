@@ -0,0 +1,71 @@
+//! Share events raised by reconciliation and switches, fanned out to the
+//! configured sinks (webhooks and desktop notifications).
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::engine::ShareStatus;
+use crate::{notify, webhooks};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShareEventKind {
+    /// The active backend changed (manual, drained or failback).
+    Switch,
+    /// The active backend went offline and the other took over.
+    Failover,
+    /// Thunderbolt is back while on fallback; waiting for the user.
+    RecoveryPending,
+    /// Neither backend is reachable, so there is nothing to fail over to.
+    BothDown,
+    /// A new error was recorded for the share.
+    Error,
+}
+
+impl ShareEventKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Switch => "switch",
+            Self::Failover => "failover",
+            Self::RecoveryPending => "recovery-pending",
+            Self::BothDown => "both-down",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// One event with the share's status at the time, flattened so the JSON
+/// carries every `ShareStatus` field next to `event` and `at`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareEvent {
+    pub event: ShareEventKind,
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub status: ShareStatus,
+}
+
+/// Hand `kinds` for `status` to every sink. Never blocks on delivery.
+pub fn publish(config: &Config, kinds: &[ShareEventKind], status: &ShareStatus) {
+    let now = Utc::now();
+    for &kind in kinds {
+        log::info!("{}: event {}", status.name, kind.label());
+        let event = ShareEvent {
+            event: kind,
+            at: now,
+            status: status.clone(),
+        };
+        webhooks::publish(config, &event);
+        notify::publish(config, &event);
+    }
+}
+
+/// [`publish`] `kind` for `share`, taking its status from `statuses`.
+pub fn publish_for(config: &Config, kind: ShareEventKind, statuses: &[ShareStatus], share: &str) {
+    if let Some(status) = statuses
+        .iter()
+        .find(|status| status.name.eq_ignore_ascii_case(share))
+    {
+        publish(config, &[kind], status);
+    }
+}
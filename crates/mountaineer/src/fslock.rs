@@ -0,0 +1,65 @@
+//! Advisory `flock(2)` locks shared by every Mountaineer process.
+//!
+//! Locks belong to the open file, not the process: two `File`s for the same
+//! path conflict even inside one process, and the kernel drops the lock when
+//! the file is closed or the process dies.
+
+use anyhow::{Context, Result};
+use nix::libc;
+use std::fs::{File, OpenOptions};
+use std::io::ErrorKind;
+use std::os::fd::AsRawFd;
+use std::path::Path;
+
+/// Exclusive lock on a lock file, held until dropped.
+#[derive(Debug)]
+pub struct FileLock {
+    _file: File,
+}
+
+/// Open (creating if needed) `path` for reading and writing, without
+/// truncating it.
+pub fn open(path: &Path) -> Result<File> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed creating {}", parent.display()))?;
+    }
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .with_context(|| format!("failed opening {}", path.display()))
+}
+
+/// Take an exclusive lock on `path`, waiting for other holders.
+pub fn lock_exclusive(path: &Path) -> Result<FileLock> {
+    let file = open(path)?;
+    loop {
+        // SAFETY: the descriptor stays open for the duration of the call.
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+            return Ok(FileLock { _file: file });
+        }
+        let err = std::io::Error::last_os_error();
+        if err.kind() != ErrorKind::Interrupted {
+            return Err(err).with_context(|| format!("failed locking {}", path.display()));
+        }
+    }
+}
+
+/// Non-blocking `flock` with `operation` (`LOCK_EX` or `LOCK_SH`).
+/// `Ok(false)` when another open file holds a conflicting lock.
+pub fn try_lock(file: &File, operation: libc::c_int) -> std::io::Result<bool> {
+    // SAFETY: the descriptor stays open for the duration of the call.
+    let rc = unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) };
+    if rc == 0 {
+        return Ok(true);
+    }
+    let err = std::io::Error::last_os_error();
+    if err.kind() == ErrorKind::WouldBlock {
+        Ok(false)
+    } else {
+        Err(err)
+    }
+}
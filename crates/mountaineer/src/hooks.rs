@@ -0,0 +1,299 @@
+//! User hook commands run on share lifecycle events.
+//!
+//! Commands come from `[hooks]` in config.toml, overridden per share by
+//! `[shares.hooks]`. Each runs through `/bin/sh -c` with the event described
+//! in `MOUNTAINEER_*` environment variables and is killed, with anything it
+//! started, after its timeout. Only `pre-switch` can change what happens: a
+//! non-zero exit vetoes the switch. Other failures are logged.
+
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::config::{Backend, Config, HooksConfig, ShareConfig};
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a failed hook's stderr is awaited once the deadline has passed.
+const STDERR_GRACE: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    PreSwitch,
+    PreUnmount,
+    PostMount,
+    Failover,
+    Failback,
+    SwitchBlocked,
+    BothBackendsDown,
+    SwitchFailed,
+}
+
+impl HookEvent {
+    /// Value of `MOUNTAINEER_EVENT`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PreSwitch => "pre-switch",
+            Self::PreUnmount => "pre-unmount",
+            Self::PostMount => "post-mount",
+            Self::Failover => "failover",
+            Self::Failback => "failback",
+            Self::SwitchBlocked => "switch-blocked",
+            Self::BothBackendsDown => "both-backends-down",
+            Self::SwitchFailed => "switch-failed",
+        }
+    }
+
+    fn command(self, hooks: &HooksConfig) -> Option<&str> {
+        match self {
+            Self::PreSwitch => hooks.pre_switch.as_deref(),
+            Self::PreUnmount => hooks.pre_unmount.as_deref(),
+            Self::PostMount => hooks.post_mount.as_deref(),
+            Self::Failover => hooks.failover.as_deref(),
+            Self::Failback => hooks.failback.as_deref(),
+            Self::SwitchBlocked => hooks.switch_blocked.as_deref(),
+            Self::BothBackendsDown => hooks.both_backends_down.as_deref(),
+            Self::SwitchFailed => hooks.switch_failed.as_deref(),
+        }
+        .filter(|command| !command.trim().is_empty())
+    }
+}
+
+/// What the event is about, passed to the hook as environment variables.
+#[derive(Debug, Clone, Copy)]
+pub struct HookContext<'a> {
+    pub from: Option<Backend>,
+    pub to: Option<Backend>,
+    pub mount_point: &'a Path,
+    pub reason: &'a str,
+}
+
+/// Run `share`'s hook for `event`, if one is configured. `Err` describes a
+/// hook that could not start, exited non-zero or timed out.
+pub fn run(
+    config: &Config,
+    share: &ShareConfig,
+    event: HookEvent,
+    context: &HookContext,
+) -> Result<(), String> {
+    let Some(command) = event
+        .command(&share.hooks)
+        .or_else(|| event.command(&config.hooks))
+    else {
+        return Ok(());
+    };
+    let timeout = share
+        .hooks
+        .timeout_secs
+        .or(config.hooks.timeout_secs)
+        .map_or(DEFAULT_TIMEOUT, Duration::from_secs);
+
+    log::info!("{}: running {} hook: {}", share.name, event.name(), command);
+    let result = run_command(command, &environment(share, event, context), timeout);
+    match &result {
+        Ok(()) => log::info!("{}: {} hook done", share.name, event.name()),
+        Err(e) => log::warn!("{}: {} hook failed: {}", share.name, event.name(), e),
+    }
+    result
+}
+
+fn environment(
+    share: &ShareConfig,
+    event: HookEvent,
+    context: &HookContext,
+) -> Vec<(&'static str, String)> {
+    let backend = |b: Option<Backend>| b.map(Backend::short_label).unwrap_or("").to_string();
+    vec![
+        ("MOUNTAINEER_EVENT", event.name().to_string()),
+        ("MOUNTAINEER_SHARE", share.name.clone()),
+        ("MOUNTAINEER_FROM", backend(context.from)),
+        ("MOUNTAINEER_TO", backend(context.to)),
+        (
+            "MOUNTAINEER_MOUNT_POINT",
+            context.mount_point.display().to_string(),
+        ),
+        ("MOUNTAINEER_REASON", context.reason.to_string()),
+    ]
+}
+
+fn run_command(
+    command: &str,
+    env: &[(&'static str, String)],
+    timeout: Duration,
+) -> Result<(), String> {
+    use std::os::unix::process::CommandExt;
+
+    let mut child = Command::new("/bin/sh")
+        .args(["-c", command])
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        // Own process group, so a timeout kills whatever the hook started.
+        .process_group(0)
+        .spawn()
+        .map_err(|e| format!("could not start: {}", e))?;
+
+    // Anything the hook leaves running in the background keeps stderr open,
+    // so the reader is never waited on past the deadline.
+    let mut stderr = child.stderr.take();
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut text = String::new();
+        if let Some(stderr) = stderr.as_mut() {
+            let _ = stderr.read_to_string(&mut text);
+        }
+        let _ = sender.send(text);
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if Instant::now() >= deadline => break None,
+            Ok(None) => std::thread::sleep(Duration::from_millis(20)),
+            Err(e) => return Err(format!("wait failed: {}", e)),
+        }
+    };
+    let Some(status) = status else {
+        // SAFETY: plain syscall; the group is the hook's, led by a child not
+        // yet reaped.
+        unsafe { nix::libc::killpg(child.id() as nix::libc::pid_t, nix::libc::SIGKILL) };
+        let _ = child.wait();
+        return Err(format!("timed out after {}s", timeout.as_secs()));
+    };
+
+    if status.success() {
+        return Ok(());
+    }
+    let stderr = receiver
+        .recv_timeout(
+            deadline
+                .saturating_duration_since(Instant::now())
+                .max(STDERR_GRACE),
+        )
+        .unwrap_or_default();
+    let detail = stderr.trim();
+    let code = status
+        .code()
+        .map_or("killed by a signal".to_string(), |code| {
+            format!("exited with {}", code)
+        });
+    if detail.is_empty() {
+        Err(code)
+    } else {
+        Err(format!("{}: {}", code, detail))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with(hooks: HooksConfig, share_hooks: HooksConfig) -> (Config, ShareConfig) {
+        let share = ShareConfig {
+            name: "CORE".to_string(),
+            hooks: share_hooks,
+            ..ShareConfig::default()
+        };
+        let config = Config {
+            shares: vec![share.clone()],
+            hooks,
+            ..Config::default()
+        };
+        (config, share)
+    }
+
+    fn context(reason: &str) -> HookContext<'_> {
+        HookContext {
+            from: Some(Backend::Fallback),
+            to: Some(Backend::Tb),
+            mount_point: Path::new("/Volumes/CORE"),
+            reason,
+        }
+    }
+
+    #[test]
+    fn hook_sees_event_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("env");
+        let (config, share) = config_with(
+            HooksConfig {
+                post_mount: Some(format!(
+                    "echo \"$MOUNTAINEER_EVENT $MOUNTAINEER_SHARE $MOUNTAINEER_FROM \
+                     $MOUNTAINEER_TO $MOUNTAINEER_MOUNT_POINT $MOUNTAINEER_REASON\" > '{}'",
+                    out.display()
+                )),
+                ..HooksConfig::default()
+            },
+            HooksConfig::default(),
+        );
+        run(&config, &share, HookEvent::PostMount, &context("failback")).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap().trim(),
+            "post-mount CORE fallback tb /Volumes/CORE failback"
+        );
+    }
+
+    #[test]
+    fn share_hook_overrides_global_and_unset_events_do_nothing() {
+        let (config, share) = config_with(
+            HooksConfig {
+                pre_switch: Some("exit 0".to_string()),
+                ..HooksConfig::default()
+            },
+            HooksConfig {
+                pre_switch: Some("echo 'sync still running' >&2; exit 4".to_string()),
+                ..HooksConfig::default()
+            },
+        );
+        let err = run(&config, &share, HookEvent::PreSwitch, &context("manual")).unwrap_err();
+        assert_eq!(err, "exited with 4: sync still running");
+        assert!(run(&config, &share, HookEvent::Failover, &context("failover")).is_ok());
+    }
+
+    #[test]
+    fn hook_is_killed_after_timeout() {
+        let (config, share) = config_with(
+            HooksConfig {
+                pre_unmount: Some("sleep 30".to_string()),
+                timeout_secs: Some(1),
+                ..HooksConfig::default()
+            },
+            HooksConfig::default(),
+        );
+        let started = Instant::now();
+        let err = run(&config, &share, HookEvent::PreUnmount, &context("manual")).unwrap_err();
+        assert_eq!(err, "timed out after 1s");
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn hook_that_leaves_a_background_job_returns_when_it_exits() {
+        let (config, share) = config_with(
+            HooksConfig {
+                post_mount: Some("sleep 30 & exit 0".to_string()),
+                ..HooksConfig::default()
+            },
+            HooksConfig::default(),
+        );
+        let started = Instant::now();
+        run(&config, &share, HookEvent::PostMount, &context("failback")).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let (config, share) = config_with(
+            HooksConfig {
+                pre_switch: Some("sleep 30 & echo 'sync busy' >&2; exit 3".to_string()),
+                timeout_secs: Some(1),
+                ..HooksConfig::default()
+            },
+            HooksConfig::default(),
+        );
+        let started = Instant::now();
+        let err = run(&config, &share, HookEvent::PreSwitch, &context("manual")).unwrap_err();
+        assert!(err.starts_with("exited with 3"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,253 @@
+//! Switch intent journal.
+//!
+//! A switch unmounts one backend and mounts the other at the same path, and
+//! the caller saves `state.json` only once it is over. A process that dies in
+//! between leaves the share unmounted while state still names the old
+//! backend. Before each step, `switch_backend_single_mount` records the share,
+//! both backends and the phase it is entering in
+//! `~/.mountaineer/switch-journal.json`, written and synced at once. The next
+//! owner of reconciliation finds the record and finishes or rolls back the
+//! switch (`engine::recover_interrupted_switches`).
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use crate::config::Backend;
+use crate::fslock;
+
+/// The step a switch was about to take, or `Switched` once it mounted the
+/// new backend but before state.json was saved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SwitchPhase {
+    Unmounting,
+    Mounting,
+    RollingBack,
+    Switched,
+}
+
+impl SwitchPhase {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Unmounting => "unmounting",
+            Self::Mounting => "mounting",
+            Self::RollingBack => "rolling-back",
+            Self::Switched => "switched",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwitchIntent {
+    pub share: String,
+    pub from: Backend,
+    pub to: Backend,
+    pub phase: SwitchPhase,
+    /// Process carrying out the switch.
+    pub pid: u32,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl SwitchIntent {
+    pub fn new(share: &str, from: Backend, to: Backend, phase: SwitchPhase) -> Self {
+        Self {
+            share: share.to_string(),
+            from,
+            to,
+            phase,
+            pid: std::process::id(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    /// The backend recovery leaves the share on. Before the new backend was
+    /// being mounted nothing is lost by going back; from then on the switch
+    /// is finished.
+    pub fn recovery_target(&self) -> Backend {
+        match self.phase {
+            SwitchPhase::Unmounting | SwitchPhase::RollingBack => self.from,
+            SwitchPhase::Mounting | SwitchPhase::Switched => self.to,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Journal {
+    /// One intent per share, by lowercase name.
+    #[serde(default)]
+    intents: BTreeMap<String, SwitchIntent>,
+}
+
+/// Record `intent` in the journal at `path`, replacing any earlier one for
+/// its share. A failure is logged, not returned: a journal that cannot be
+/// written must not stop a failover.
+pub fn record(path: &Path, intent: &SwitchIntent) {
+    if let Err(e) = record_at(path, intent) {
+        log::warn!("{}: cannot write switch journal: {:#}", intent.share, e);
+    }
+}
+
+/// Drop the intent for `share` from the journal at `path`.
+pub fn clear(path: &Path, share: &str) {
+    if let Err(e) = clear_at(path, share) {
+        log::warn!("{}: cannot clear switch journal: {:#}", share, e);
+    }
+}
+
+/// Drop the `Switched` intents of this process: its state, just saved, now
+/// names the new backends.
+pub fn clear_switched(path: &Path) {
+    let result = edit(path, |journal| {
+        let pid = std::process::id();
+        journal
+            .intents
+            .retain(|_, intent| !(intent.phase == SwitchPhase::Switched && intent.pid == pid));
+    });
+    if let Err(e) = result {
+        log::warn!("cannot clear switch journal: {:#}", e);
+    }
+}
+
+pub fn record_at(path: &Path, intent: &SwitchIntent) -> Result<()> {
+    edit(path, |journal| {
+        journal
+            .intents
+            .insert(intent.share.to_ascii_lowercase(), intent.clone());
+    })
+}
+
+pub fn clear_at(path: &Path, share: &str) -> Result<()> {
+    edit(path, |journal| {
+        journal.intents.remove(&share.to_ascii_lowercase());
+    })
+}
+
+/// Every intent recorded at `path`.
+pub fn intents_at(path: &Path) -> Result<Vec<SwitchIntent>> {
+    Ok(read(path)?.intents.into_values().collect())
+}
+
+fn read(path: &Path) -> Result<Journal> {
+    match fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text)
+            .with_context(|| format!("failed parsing switch journal {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Journal::default()),
+        Err(e) => {
+            Err(e).with_context(|| format!("failed reading switch journal {}", path.display()))
+        }
+    }
+}
+
+/// Read, change and write the journal under its lock. The write is synced
+/// before returning, since the next step may be the one that never ends.
+fn edit(path: &Path, change: impl FnOnce(&mut Journal)) -> Result<()> {
+    let _lock = fslock::lock_exclusive(&path.with_extension("lock"))?;
+    let mut journal = read(path)?;
+    let before = journal.intents.clone();
+    change(&mut journal);
+    if journal.intents == before {
+        return Ok(());
+    }
+
+    let tmp_path = path.with_extension("json.tmp");
+    let mut file = fs::File::create(&tmp_path)
+        .with_context(|| format!("failed writing {}", tmp_path.display()))?;
+    file.write_all(serde_json::to_string_pretty(&journal)?.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("failed renaming switch journal to {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovery_rolls_back_before_mounting_and_finishes_after() {
+        let intent = |phase| SwitchIntent::new("CORE", Backend::Fallback, Backend::Tb, phase);
+        assert_eq!(
+            intent(SwitchPhase::Unmounting).recovery_target(),
+            Backend::Fallback
+        );
+        assert_eq!(intent(SwitchPhase::Mounting).recovery_target(), Backend::Tb);
+        assert_eq!(
+            intent(SwitchPhase::RollingBack).recovery_target(),
+            Backend::Fallback
+        );
+        assert_eq!(intent(SwitchPhase::Switched).recovery_target(), Backend::Tb);
+    }
+
+    #[test]
+    fn intents_are_kept_per_share_until_cleared() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("switch-journal.json");
+        assert!(intents_at(&path).unwrap().is_empty());
+
+        let unmounting = SwitchIntent::new(
+            "CORE",
+            Backend::Tb,
+            Backend::Fallback,
+            SwitchPhase::Unmounting,
+        );
+        let mounting = SwitchIntent {
+            phase: SwitchPhase::Mounting,
+            ..unmounting.clone()
+        };
+        let other = SwitchIntent::new(
+            "DATA",
+            Backend::Fallback,
+            Backend::Tb,
+            SwitchPhase::Mounting,
+        );
+        record_at(&path, &unmounting).unwrap();
+        record_at(&path, &mounting).unwrap();
+        record_at(&path, &other).unwrap();
+        assert_eq!(
+            intents_at(&path).unwrap(),
+            vec![mounting.clone(), other.clone()]
+        );
+
+        clear_at(&path, "core").unwrap();
+        assert_eq!(intents_at(&path).unwrap(), vec![other]);
+    }
+
+    #[test]
+    fn clear_switched_keeps_unfinished_and_foreign_intents() {
+        let switched = SwitchIntent::new(
+            "CORE",
+            Backend::Tb,
+            Backend::Fallback,
+            SwitchPhase::Switched,
+        );
+        let unfinished = SwitchIntent::new(
+            "DATA",
+            Backend::Tb,
+            Backend::Fallback,
+            SwitchPhase::Mounting,
+        );
+        let foreign = SwitchIntent {
+            share: "MEDIA".to_string(),
+            pid: std::process::id() + 1,
+            ..switched.clone()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("switch-journal.json");
+        for intent in [&switched, &unfinished, &foreign] {
+            record(&path, intent);
+        }
+
+        clear_switched(&path);
+        let shares: Vec<String> = intents_at(&path)
+            .unwrap()
+            .into_iter()
+            .map(|i| i.share)
+            .collect();
+        assert_eq!(shares, vec!["DATA".to_string(), "MEDIA".to_string()]);
+    }
+}
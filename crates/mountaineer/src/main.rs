@@ -3,20 +3,43 @@
 use anyhow::{Result, anyhow};
 use clap::Parser;
 
+mod child;
 mod cli;
 mod config;
+mod config_watch;
+mod control;
+mod credentials;
+#[cfg(target_os = "macos")]
 mod dialogs;
 mod discovery;
 mod engine;
+mod events;
+mod fslock;
+#[cfg(target_os = "macos")]
 mod gui;
+mod hooks;
+mod journal;
 mod launchd;
 mod logging;
+mod migrate;
 mod mount;
 mod network;
+mod notify;
+mod open_files;
+mod ownership;
+mod runner;
+#[cfg(target_os = "macos")]
 mod tray;
+mod webhooks;
 
-use cli::{AliasCommand, Cli, Command, ConfigCommand, FavoritesCommand, MultiShareTarget};
-use config::{AliasConfig, Backend, Config, ShareConfig};
+use cli::{
+    AliasCommand, Cli, Command, ConfigCommand, CredentialsCommand, FavoritesCommand, LeaseCommand,
+    MultiShareTarget, WaitState,
+};
+use config::{AliasConfig, Backend, Config, CredentialStore, RemovedSharePolicy, ShareConfig};
+use config_watch::ConfigWatcher;
+use credentials::CredentialProvider;
+use events::ShareEventKind;
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
@@ -30,10 +53,15 @@ fn main() -> Result<()> {
     }
 
     match cli.command {
+        #[cfg(target_os = "macos")]
         None => {
             gui::run();
             Ok(())
         }
+        #[cfg(not(target_os = "macos"))]
+        None => Err(anyhow!(
+            "the menu bar app needs macOS; run a subcommand instead"
+        )),
         Some(command) => run_cli(command),
     }
 }
@@ -52,14 +80,27 @@ fn run_cli(command: Command) -> Result<()> {
             log::info!("cli: status --all={} --json={}", all, json);
             cmd_status(all, json)
         }
-        Command::Switch { share, to, force } => {
+        Command::Switch {
+            share,
+            to,
+            force,
+            drain,
+            cancel,
+        } => {
             log::info!(
-                "cli: switch --share={} --to={} --force={}",
+                "cli: switch --share={} --to={:?} --force={} --drain={:?} --cancel={}",
                 share,
-                to.short_label(),
-                force
+                to.map(Backend::short_label),
+                force,
+                drain,
+                cancel
             );
-            cmd_switch(&share, to, force)
+            match (to, drain) {
+                _ if cancel => cmd_switch_cancel(&share),
+                (Some(to), Some(drain)) => cmd_switch_drained(&share, to, drain),
+                (Some(to), None) => cmd_switch(&share, to, force),
+                (None, _) => Err(anyhow!("--to is required unless --cancel is given")),
+            }
         }
         Command::Verify { target, json } => {
             log::info!(
@@ -78,6 +119,14 @@ fn run_cli(command: Command) -> Result<()> {
             log::info!("cli: unmount --all={} --force={}", all, force);
             cmd_unmount(all, force)
         }
+        Command::Reload => {
+            log::info!("cli: reload");
+            cmd_reload()
+        }
+        Command::Dedupe { dry_run, json } => {
+            log::info!("cli: dedupe --dry-run={} --json={}", dry_run, json);
+            cmd_dedupe(dry_run, json)
+        }
         Command::Folders {
             share,
             subpath,
@@ -103,6 +152,56 @@ fn run_cli(command: Command) -> Result<()> {
             log::info!("cli: config command");
             cmd_config(command)
         }
+        Command::Credentials { command } => {
+            log::info!("cli: credentials command");
+            cmd_credentials(command)
+        }
+        Command::OpenFiles { share, json } => {
+            log::info!("cli: open-files --share={} --json={}", share, json);
+            cmd_open_files(&share, json)
+        }
+        Command::Lease { command } => {
+            log::info!("cli: lease command");
+            cmd_lease(command)
+        }
+        Command::Run {
+            share,
+            backend,
+            timeout,
+            command,
+        } => {
+            log::info!(
+                "cli: run --share={} --backend={:?} --timeout={:?} -- {:?}",
+                share,
+                backend.map(Backend::short_label),
+                timeout,
+                command
+            );
+            cmd_run(&share, backend, timeout, &command)
+        }
+        Command::Wait {
+            share,
+            all,
+            backend,
+            state,
+            timeout,
+            observe,
+        } => {
+            log::info!(
+                "cli: wait --share={:?} --all={} --backend={:?} --state={:?} --timeout={:?} --observe={}",
+                share,
+                all,
+                backend.map(Backend::short_label),
+                state,
+                timeout,
+                observe
+            );
+            cmd_wait(share.as_deref(), backend, state, timeout, observe)
+        }
+        Command::Reauth { share } => {
+            log::info!("cli: reauth --share={}", share);
+            cmd_reauth(&share)
+        }
         Command::Install => {
             log::info!("cli: install");
             cmd_install()
@@ -122,9 +221,25 @@ fn cmd_reconcile(all: bool) -> Result<()> {
     let cfg = config::load()?;
     ensure_has_shares(&cfg)?;
 
+    let _lock = match ownership::claim("reconcile --all")? {
+        ownership::Claim::Acquired(lock) => lock,
+        // Let the owner reconcile instead of fighting it.
+        ownership::Claim::Held(owner) => {
+            return match control::request::<Vec<engine::ShareStatus>>(&control::Call::Reload) {
+                Some(statuses) => {
+                    print_status_table(&statuses?);
+                    Ok(())
+                }
+                None => Err(ownership::refusal("reconcile --all", owner.as_ref())),
+            };
+        }
+    };
     let mut state = engine::load_runtime_state().unwrap_or_default();
+    for recovered in engine::recover_interrupted_switches(&cfg, &mut state) {
+        println!("{}", recovered.describe());
+    }
     let statuses = engine::reconcile_all(&cfg, &mut state);
-    engine::save_runtime_state(&state)?;
+    engine::save_runtime_state(&mut state)?;
 
     print_status_table(&statuses);
     Ok(())
@@ -133,6 +248,7 @@ fn cmd_reconcile(all: bool) -> Result<()> {
 fn cmd_monitor(interval: Option<u64>) -> Result<()> {
     let initial_cfg = config::load()?;
     ensure_has_shares(&initial_cfg)?;
+    let _lock = ownership::claim_or_refuse("monitor")?;
 
     let interval_secs = interval
         .or(Some(initial_cfg.global.check_interval_secs))
@@ -145,117 +261,366 @@ fn cmd_monitor(interval: Option<u64>) -> Result<()> {
         interval_secs
     );
 
+    // Network changes and control socket requests both wake the loop.
+    let (wake_tx, wake_rx) = std::sync::mpsc::channel();
+
     // Start SCDynamicStore network change monitor (spec 11)
-    let network_rx = network::monitor::start();
-    log::info!("Network change monitor started for cmd_monitor");
+    #[cfg(target_os = "macos")]
+    {
+        let network_rx = network::monitor::start();
+        let network_wake = wake_tx.clone();
+        std::thread::spawn(move || {
+            for event in network_rx {
+                if network_wake.send(MonitorWake::Network(event)).is_err() {
+                    return;
+                }
+            }
+            log::warn!("Network monitor channel disconnected, falling back to timer-only");
+        });
+        log::info!("Network change monitor started for cmd_monitor");
+    }
+    // Retry webhook deliveries left over from earlier runs.
+    webhooks::start_sender();
+    // Edits to config.toml are applied as soon as they are saved.
+    let mut watcher = ConfigWatcher::open();
+    let config_wake = wake_tx.clone();
+    if let Err(e) = config_watch::watch(config::config_path(), move || {
+        let _ = config_wake.send(MonitorWake::Config);
+    }) {
+        log::warn!("config watch unavailable: {:#}", e);
+    }
+    // CLI commands run here instead of racing this loop over state.json.
+    if let Err(e) = control::serve_default(move |incoming| {
+        let _ = wake_tx.send(MonitorWake::Control(incoming));
+    }) {
+        log::warn!("control socket unavailable: {:#}", e);
+    }
 
     let mut state = engine::load_runtime_state().unwrap_or_default();
+    for recovered in engine::recover_interrupted_switches(&initial_cfg, &mut state) {
+        println!("{}", recovered.describe());
+    }
+    engine::save_runtime_state(&mut state)?;
     loop {
-        // Hot-reload config each cycle per spec 11
-        let cfg = config::load().unwrap_or(initial_cfg.clone());
+        if let Err(e) = engine::refresh_runtime_state(&mut state) {
+            log::warn!("monitor: cannot read runtime state: {:#}", e);
+        }
+        // Apply config.toml edits (spec 11); an invalid one keeps the last good config.
+        for message in watcher.update(&mut state) {
+            println!("config: {}", message);
+        }
+        let cfg = watcher.config().clone();
+        let switched: Vec<String> = notify::take_actions()
+            .into_iter()
+            .filter(|action| switch_from_notification(&cfg, &mut state, action))
+            .map(|action| action.share)
+            .collect();
         let statuses = engine::reconcile_all(&cfg, &mut state);
+        for share in &switched {
+            events::publish_for(&cfg, ShareEventKind::Switch, &statuses, share);
+        }
         print_status_table(&statuses);
-        engine::save_runtime_state(&state)?;
+        engine::save_runtime_state(&mut state)?;
 
         // Wait for either: timer expiry OR network change event (spec 11).
         // On network event, debounce 500ms then immediately reconcile (spec 11).
-        match network_rx.recv_timeout(std::time::Duration::from_secs(interval_secs)) {
-            Ok(event) => {
-                log::info!("Network change detected: {:?}", event.changed_keys);
-                // Debounce: drain any further events arriving within 500ms (spec 11)
-                let debounce = std::time::Duration::from_millis(500);
-                while network_rx.recv_timeout(debounce).is_ok() {}
-                log::info!("Network debounce complete, triggering immediate reconcile");
-            }
-            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
-                // Normal timer-based reconcile — continue loop
-            }
-            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
-                log::warn!("Network monitor channel disconnected, falling back to timer-only");
-                std::thread::sleep(std::time::Duration::from_secs(interval_secs));
+        // Control requests are answered as they arrive.
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(interval_secs);
+        loop {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            match wake_rx.recv_timeout(remaining) {
+                Ok(MonitorWake::Control(incoming)) => {
+                    answer_control(&mut watcher, &mut state, incoming);
+                }
+                #[cfg(target_os = "macos")]
+                Ok(MonitorWake::Network(event)) => {
+                    log::info!("Network change detected: {:?}", event.changed_keys);
+                    // Debounce: drain any further events arriving within 500ms (spec 11)
+                    let debounce = std::time::Duration::from_millis(500);
+                    while let Ok(wake) = wake_rx.recv_timeout(debounce) {
+                        if let MonitorWake::Control(incoming) = wake {
+                            answer_control(&mut watcher, &mut state, incoming);
+                        }
+                    }
+                    log::info!("Network debounce complete, triggering immediate reconcile");
+                    break;
+                }
+                Ok(MonitorWake::Config) => {
+                    log::info!("config.toml changed, triggering immediate reconcile");
+                    break;
+                }
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                    // Normal timer-based reconcile — continue loop
+                    break;
+                }
+                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+                    std::thread::sleep(remaining);
+                    break;
+                }
             }
         }
     }
 }
 
+/// Run a control request with the config `monitor` is using. `reload` first
+/// applies edits to config.toml the watcher has not picked up yet.
+fn answer_control(
+    watcher: &mut ConfigWatcher,
+    state: &mut engine::RuntimeState,
+    incoming: control::Incoming,
+) {
+    if *incoming.call() == control::Call::Reload {
+        for message in watcher.update(state) {
+            println!("config: {}", message);
+        }
+    }
+    let result = control::execute(watcher.config(), incoming.call(), state);
+    incoming.respond(result);
+}
+
+/// What ends `monitor`'s wait between passes early.
+enum MonitorWake {
+    #[cfg(target_os = "macos")]
+    Network(network::monitor::NetworkChangeEvent),
+    Control(control::Incoming),
+    Config,
+}
+
+/// Perform a "Switch now" picked on a notification. Open files still block
+/// it; `true` when the share switched.
+fn switch_from_notification(
+    cfg: &Config,
+    state: &mut engine::RuntimeState,
+    action: &notify::SwitchNow,
+) -> bool {
+    let Some(share) = config::find_share(cfg, &action.share) else {
+        return false;
+    };
+    let Some(from) = state
+        .shares
+        .get(&share.name.to_ascii_lowercase())
+        .and_then(|entry| entry.active_backend)
+    else {
+        return false;
+    };
+    if from == action.to {
+        return false;
+    }
+    match engine::switch_backend_single_mount(
+        cfg,
+        state,
+        share,
+        from,
+        action.to,
+        false,
+        engine::SwitchReason::Manual,
+    ) {
+        engine::SwitchResult::Success => {
+            println!("{}: switched to {}", share.name, action.to.short_label());
+            true
+        }
+        engine::SwitchResult::BusyOpenFiles => {
+            eprintln!(
+                "{}: not switching to {}: files are still open",
+                share.name,
+                action.to.short_label()
+            );
+            false
+        }
+        other => {
+            eprintln!(
+                "{}: switch to {} failed: {:?}",
+                share.name,
+                action.to.short_label(),
+                other
+            );
+            false
+        }
+    }
+}
+
 fn cmd_status(all: bool, json: bool) -> Result<()> {
     if !all {
         return Err(anyhow!("status currently requires --all"));
     }
 
-    let cfg = config::load()?;
-    let mut state = engine::load_runtime_state().unwrap_or_default();
-    let statuses = engine::verify_all(&cfg, &mut state);
-    engine::save_runtime_state(&state)?;
+    let output = match control::request(&control::Call::Status) {
+        Some(output) => output?,
+        None => {
+            let cfg = config::load()?;
+            let mut state = engine::load_runtime_state().unwrap_or_default();
+            let statuses = engine::verify_all(&cfg, &mut state);
+            engine::save_runtime_state(&mut state)?;
+            engine::StatusOutput {
+                lsof_recheck: cfg.global.lsof_recheck,
+                shares: statuses,
+                reconciler: ownership::owner(),
+            }
+        }
+    };
 
     if json {
-        let output = engine::StatusOutput {
-            lsof_recheck: cfg.global.lsof_recheck,
-            shares: statuses,
-        };
         println!("{}", serde_json::to_string_pretty(&output)?);
     } else {
-        print_status_table(&statuses);
+        print_status_table(&output.shares);
+        match &output.reconciler {
+            Some(owner) => println!("reconciler: {}", owner.describe()),
+            None => println!("reconciler: none (nothing is keeping shares mounted)"),
+        }
+        print_webhook_backlog();
     }
     Ok(())
 }
 
+/// Note webhook deliveries still waiting for their endpoint.
+fn print_webhook_backlog() {
+    let Ok(queued) = webhooks::pending(&config::webhook_outbox_path()) else {
+        return;
+    };
+    if let Some(last) = queued.last() {
+        println!(
+            "webhooks: {} event(s) waiting for delivery (last error: {})",
+            queued.len(),
+            last.last_error.as_deref().unwrap_or("not sent yet")
+        );
+    }
+}
+
 fn cmd_switch(share_name: &str, to: Backend, force: bool) -> Result<()> {
+    let call = control::Call::Switch {
+        share: share_name.to_string(),
+        to,
+        force,
+    };
+    let reply: control::SwitchReply = match control::request(&call) {
+        Some(reply) => reply?,
+        None => {
+            let cfg = config::load()?;
+            ensure_has_shares(&cfg)?;
+            let _lock = ownership::claim_or_refuse("switch")?;
+            let mut state = engine::load_runtime_state().unwrap_or_default();
+            control::switch_share(&cfg, &mut state, share_name, to, force)?
+        }
+    };
+
+    if reply.already {
+        println!("{} is already on {}", share_name, to.short_label());
+    } else {
+        print_status_table(&reply.statuses);
+    }
+    Ok(())
+}
+
+/// `switch --drain`: register a pending switch, then keep trying it until the
+/// mount is idle, the deadline passes, or someone cancels it.
+fn cmd_switch_drained(share_name: &str, to: Backend, drain: std::time::Duration) -> Result<()> {
     let cfg = config::load()?;
     ensure_has_shares(&cfg)?;
-
     let share = config::find_share(&cfg, share_name)
         .ok_or_else(|| anyhow!("share '{}' is not configured", share_name))?
         .clone();
 
-    let mut state = engine::load_runtime_state().unwrap_or_default();
-
-    let from = state
-        .shares
-        .get(&share_name.to_ascii_lowercase())
-        .and_then(|e| e.active_backend)
-        .ok_or_else(|| {
-            anyhow!(
-                "share '{}' has no active backend to switch from",
-                share_name
-            )
-        })?;
+    let pending = engine::update_runtime_state(|state| {
+        engine::request_drained_switch(state, &share.name, to, drain, chrono::Utc::now())
+    })?;
+    println!(
+        "Waiting for open files on {} to close before switching to {} (until {}).",
+        share.name,
+        to.short_label(),
+        pending
+            .deadline
+            .with_timezone(&chrono::Local)
+            .format("%H:%M:%S")
+    );
+    println!(
+        "Cancel with: mountaineer switch --share {} --cancel",
+        share.name
+    );
 
-    if from == to {
-        println!("{} is already on {}", share_name, to.short_label());
-        return Ok(());
-    }
+    // With another reconciler running, it carries out the switch and this
+    // only watches for the outcome.
+    let lock = match ownership::claim("switch --drain")? {
+        ownership::Claim::Acquired(lock) => Some(lock),
+        ownership::Claim::Held(_) => None,
+    };
+    let poll = std::time::Duration::from_secs(cfg.global.check_interval_secs.clamp(1, 5));
+    loop {
+        // Reload each pass: the tray or another CLI may have cancelled it or
+        // already carried it out.
+        let mut state = engine::load_runtime_state().unwrap_or_default();
+        let entry = state.shares.get(&share.name.to_ascii_lowercase());
+        if entry.and_then(|e| e.pending_switch.as_ref()) != Some(&pending) {
+            if entry.and_then(|e| e.active_backend) == Some(to) {
+                println!("{} is on {}", share.name, to.short_label());
+                return Ok(());
+            }
+            return Err(anyhow!(
+                "drained switch of '{}' was cancelled or replaced",
+                share.name
+            ));
+        }
 
-    match engine::switch_backend_single_mount(&cfg, &mut state, &share, from, to, force) {
-        engine::SwitchResult::Success => {
-            engine::save_runtime_state(&state)?;
-            let statuses = engine::verify_all(&cfg, &mut state);
-            print_status_table(&statuses);
-            Ok(())
+        if lock.is_none() {
+            std::thread::sleep(poll);
+            continue;
         }
-        engine::SwitchResult::BusyOpenFiles => Err(anyhow!(
-            "cannot switch '{}': open files detected. Close files and retry, or use --force",
-            share_name
-        )),
-        engine::SwitchResult::UnmountFailed(e) => Err(anyhow!(
-            "cannot switch '{}': unmount failed: {}",
-            share_name,
-            e
-        )),
-        engine::SwitchResult::MountFailed { error, rolled_back } => {
-            if rolled_back {
-                engine::save_runtime_state(&state)?;
+        let outcome = engine::process_pending_switch(&cfg, &mut state, &share, chrono::Utc::now());
+        engine::save_runtime_state(&mut state)?;
+        match outcome {
+            Some(engine::DrainOutcome::Waiting) => std::thread::sleep(poll),
+            Some(engine::DrainOutcome::Switched) => {
+                let statuses =
+                    engine::verify_selected(&cfg, &mut state, std::slice::from_ref(&share.name))?;
+                engine::save_runtime_state(&mut state)?;
+                print_status_table(&statuses);
+                events::publish_for(&cfg, ShareEventKind::Switch, &statuses, &share.name);
+                return Ok(());
+            }
+            Some(engine::DrainOutcome::AlreadyOnTarget) | None => {
+                println!("{} is on {}", share.name, to.short_label());
+                return Ok(());
+            }
+            Some(engine::DrainOutcome::Expired) => {
+                return Err(anyhow!(
+                    "cannot switch '{}': files still open after {}s. Close files and retry, or use --force",
+                    share.name,
+                    drain.as_secs()
+                ));
+            }
+            Some(engine::DrainOutcome::Failed(result)) => {
+                let reason = match result {
+                    engine::SwitchResult::UnmountFailed(e) => format!("unmount failed: {}", e),
+                    engine::SwitchResult::Vetoed(e) => format!("vetoed by pre-switch hook: {}", e),
+                    engine::SwitchResult::MountFailed {
+                        error, rolled_back, ..
+                    } => format!("mount failed: {} (rolled back: {})", error, rolled_back),
+                    engine::SwitchResult::Success | engine::SwitchResult::BusyOpenFiles => {
+                        "open files detected".to_string()
+                    }
+                };
+                return Err(anyhow!("cannot switch '{}': {}", share.name, reason));
             }
-            Err(anyhow!(
-                "cannot switch '{}': mount failed: {} (rolled back: {})",
-                share_name,
-                error,
-                rolled_back
-            ))
         }
     }
 }
 
+fn cmd_switch_cancel(share_name: &str) -> Result<()> {
+    let cfg = config::load()?;
+    let share = config::find_share(&cfg, share_name)
+        .ok_or_else(|| anyhow!("share '{}' is not configured", share_name))?;
+    match engine::update_runtime_state(|state| engine::cancel_pending_switch(state, &share.name))? {
+        Some(pending) => {
+            println!(
+                "Cancelled pending switch of {} to {}",
+                share.name,
+                pending.to.short_label()
+            );
+        }
+        None => println!("{} has no pending switch", share.name),
+    }
+    Ok(())
+}
+
 fn cmd_verify(target: MultiShareTarget, json: bool) -> Result<()> {
     let cfg = config::load()?;
     ensure_has_shares(&cfg)?;
@@ -272,6 +637,7 @@ fn cmd_verify(target: MultiShareTarget, json: bool) -> Result<()> {
         let output = engine::StatusOutput {
             lsof_recheck: cfg.global.lsof_recheck,
             shares: statuses,
+            reconciler: None,
         };
         println!("{}", serde_json::to_string_pretty(&output)?);
     } else {
@@ -288,11 +654,18 @@ fn cmd_mount(all: bool) -> Result<()> {
     let cfg = config::load()?;
     ensure_has_shares(&cfg)?;
 
-    let mut state = engine::load_runtime_state().unwrap_or_default();
     // Use mount_all (not reconcile_all) so already-mounted shares are left
     // untouched — no failover or recovery is triggered. Per spec 08.
-    let statuses = engine::mount_all(&cfg, &mut state);
-    engine::save_runtime_state(&state)?;
+    let statuses = match control::request(&control::Call::Mount) {
+        Some(statuses) => statuses?,
+        None => {
+            let _lock = ownership::claim_or_refuse("mount --all")?;
+            let mut state = engine::load_runtime_state().unwrap_or_default();
+            let statuses = engine::mount_all(&cfg, &mut state);
+            engine::save_runtime_state(&mut state)?;
+            statuses
+        }
+    };
     print_status_table(&statuses);
     Ok(())
 }
@@ -305,9 +678,17 @@ fn cmd_unmount(all: bool, force: bool) -> Result<()> {
     let cfg = config::load()?;
     ensure_has_shares(&cfg)?;
 
-    let mut state = engine::load_runtime_state().unwrap_or_default();
-    let results = engine::unmount_all(&cfg, &mut state, force);
-    engine::save_runtime_state(&state)?;
+    let results: Vec<engine::UnmountResult> =
+        match control::request(&control::Call::Unmount { force }) {
+            Some(results) => results?,
+            None => {
+                let _lock = ownership::claim_or_refuse("unmount --all")?;
+                let mut state = engine::load_runtime_state().unwrap_or_default();
+                let results = engine::unmount_all(&cfg, &mut state, force);
+                engine::save_runtime_state(&mut state)?;
+                results
+            }
+        };
 
     println!(
         "{:<16} {:<10} {:<8} {:<8} {:<8} MESSAGE",
@@ -327,6 +708,382 @@ fn cmd_unmount(all: bool, force: bool) -> Result<()> {
     Ok(())
 }
 
+/// Ask the running agent to re-read config.toml and reconcile now.
+fn cmd_reload() -> Result<()> {
+    match control::request::<Vec<engine::ShareStatus>>(&control::Call::Reload) {
+        Some(statuses) => {
+            print_status_table(&statuses?);
+            Ok(())
+        }
+        None => {
+            println!("No agent is running; config.toml is read by each command.");
+            Ok(())
+        }
+    }
+}
+
+fn cmd_dedupe(dry_run: bool, json: bool) -> Result<()> {
+    let cfg = config::load()?;
+    ensure_has_shares(&cfg)?;
+
+    let results = if dry_run {
+        engine::dedupe_mounts(&cfg, false)
+    } else {
+        match control::request(&control::Call::Dedupe) {
+            Some(reply) => reply?,
+            None => {
+                let _lock = ownership::claim_or_refuse("dedupe")?;
+                engine::dedupe_mounts(&cfg, true)
+            }
+        }
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+        return Ok(());
+    }
+    if results.is_empty() {
+        println!("No duplicate mounts found.");
+        return Ok(());
+    }
+
+    println!(
+        "{:<16} {:<28} {:<28} {:<8} {:<8} MESSAGE",
+        "SHARE", "MANAGED", "DUPLICATE", "BUSY", "OK"
+    );
+    for result in results {
+        let managed = result.managed.unwrap_or_else(|| "-".to_string());
+        for item in result.duplicates {
+            println!(
+                "{:<16} {:<28} {:<28} {:<8} {:<8} {}",
+                result.share,
+                managed,
+                item.mount_point,
+                yes_no(item.busy),
+                yes_no(item.unmounted),
+                item.message.unwrap_or_default()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn cmd_open_files(share: &str, json: bool) -> Result<()> {
+    let cfg = config::load()?;
+    let files = engine::share_open_files(&cfg, share)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&files)?);
+        return Ok(());
+    }
+    if files.is_empty() {
+        println!("No open files on {}.", share);
+        return Ok(());
+    }
+
+    println!(
+        "{:<8} {:<20} {:<12} {:<6} PATH",
+        "PID", "COMMAND", "USER", "MODE"
+    );
+    for file in &files {
+        println!(
+            "{:<8} {:<20} {:<12} {:<6} {}",
+            file.pid,
+            file.command,
+            file.user,
+            file.access.label(),
+            file.path
+        );
+    }
+
+    let apps: Vec<String> = open_files::by_process(&files)
+        .iter()
+        .map(|process| format!("{} ({})", process.command.trim(), process.pid))
+        .collect();
+    println!("\nClose before switching: {}", apps.join(", "));
+    Ok(())
+}
+
+fn cmd_lease(command: LeaseCommand) -> Result<()> {
+    match command {
+        LeaseCommand::Acquire {
+            share,
+            ttl,
+            holder,
+            json,
+        } => {
+            let cfg = config::load()?;
+            let share = config::find_share(&cfg, &share)
+                .ok_or_else(|| anyhow!("share '{}' is not configured", share))?;
+            let lease = engine::update_runtime_state(|state| {
+                engine::acquire_lease(state, &share.name, ttl, holder, chrono::Utc::now())
+            })?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&lease)?);
+            } else {
+                // Token alone on stdout so scripts can capture it.
+                println!("{}", lease.token);
+            }
+            Ok(())
+        }
+        LeaseCommand::Release { token } => {
+            let (share, _) =
+                engine::update_runtime_state(|state| engine::release_lease(state, &token))?
+                    .ok_or_else(|| anyhow!("no lease with token '{}'", token))?;
+            println!("Released lease {} on {}", token, share);
+            Ok(())
+        }
+        LeaseCommand::List { share, json } => {
+            let cfg = config::load()?;
+            let shares: Vec<&ShareConfig> = match &share {
+                Some(name) => vec![
+                    config::find_share(&cfg, name)
+                        .ok_or_else(|| anyhow!("share '{}' is not configured", name))?,
+                ],
+                None => cfg.shares.iter().collect(),
+            };
+            let state = engine::load_runtime_state().unwrap_or_default();
+            let now = chrono::Utc::now();
+            let leases: Vec<(String, engine::Lease)> = shares
+                .iter()
+                .flat_map(|share| {
+                    engine::live_leases(&state, &share.name, now)
+                        .into_iter()
+                        .map(|lease| (share.name.clone(), lease))
+                })
+                .collect();
+
+            if json {
+                let rows: Vec<serde_json::Value> = leases
+                    .iter()
+                    .map(|(share, lease)| {
+                        let mut row = serde_json::to_value(lease).unwrap_or_default();
+                        row["share"] = serde_json::Value::from(share.as_str());
+                        row
+                    })
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&rows)?);
+            } else if leases.is_empty() {
+                println!("No live leases.");
+            } else {
+                println!("{:<12} {:<24} {:<10} HOLDER", "SHARE", "TOKEN", "EXPIRES");
+                for (share, lease) in &leases {
+                    println!(
+                        "{:<12} {:<24} {:<10} {}",
+                        share,
+                        lease.token,
+                        lease
+                            .expires_at
+                            .with_timezone(&chrono::Local)
+                            .format("%H:%M:%S"),
+                        lease.holder.as_deref().unwrap_or("-")
+                    );
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// `run` renews its lease this often and for this long, so the lease of a
+/// killed `run` lapses within a minute.
+const RUN_LEASE_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+const RUN_LEASE_RENEW: std::time::Duration = std::time::Duration::from_secs(20);
+
+fn cmd_run(
+    share_name: &str,
+    backend: Option<Backend>,
+    timeout: std::time::Duration,
+    command: &[String],
+) -> Result<()> {
+    let cfg = config::load()?;
+    let share = config::find_share(&cfg, share_name)
+        .ok_or_else(|| anyhow!("share '{}' is not configured", share_name))?
+        .clone();
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| anyhow!("no command given after --"))?;
+
+    let not_ready = || {
+        anyhow!(
+            "{} not ready{} after {}s",
+            share.name,
+            backend
+                .map(|b| format!(" on {}", b.short_label()))
+                .unwrap_or_default(),
+            timeout.as_secs()
+        )
+    };
+    let deadline = std::time::Instant::now() + timeout;
+    let lease = loop {
+        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+        let ready = wait_for_shares(
+            &cfg,
+            std::slice::from_ref(&share.name),
+            true,
+            remaining,
+            |status| status.is_ready_on(backend),
+        )?;
+        if ready.is_none() {
+            return Err(not_ready());
+        }
+
+        let lease = engine::update_runtime_state(|state| {
+            engine::acquire_lease(
+                state,
+                &share.name,
+                RUN_LEASE_TTL,
+                Some(format!("run: {} (pid {})", program, std::process::id())),
+                chrono::Utc::now(),
+            )
+        })?;
+        // A switch that started before the lease was taken is not stopped by
+        // it, so check again now that no new one can start.
+        let mut state = engine::load_runtime_state().unwrap_or_default();
+        let statuses =
+            engine::verify_selected(&cfg, &mut state, std::slice::from_ref(&share.name))?;
+        if statuses.iter().all(|status| status.is_ready_on(backend)) {
+            break lease;
+        }
+        engine::update_runtime_state(|state| engine::release_lease(state, &lease.token))?;
+        log::info!(
+            "run: {} changed before the lease was taken, waiting again",
+            share.name
+        );
+        if std::time::Instant::now() >= deadline {
+            return Err(not_ready());
+        }
+    };
+
+    let mut renewed_at = std::time::Instant::now();
+    let status =
+        child::run_forwarding_signals(std::process::Command::new(program).args(args), || {
+            if renewed_at.elapsed() < RUN_LEASE_RENEW {
+                return;
+            }
+            renewed_at = std::time::Instant::now();
+            let renewed = engine::update_runtime_state(|state| {
+                engine::renew_lease(state, &lease.token, RUN_LEASE_TTL, chrono::Utc::now())
+            });
+            if !matches!(renewed, Ok(true)) {
+                log::warn!("run: lease {} was released or expired", lease.token);
+            }
+        });
+
+    engine::update_runtime_state(|state| engine::release_lease(state, &lease.token))?;
+
+    let status = status.map_err(|e| anyhow!("failed to run '{}': {}", program, e))?;
+    match child::exit_code(status) {
+        0 => Ok(()),
+        code => std::process::exit(code),
+    }
+}
+
+/// `wait` exit codes besides 0 (reached) and 1 (other errors). 124 matches
+/// timeout(1).
+const WAIT_EXIT_TIMEOUT: i32 = 124;
+const WAIT_EXIT_UNKNOWN_SHARE: i32 = 3;
+
+/// `share` is `None` for `--all`.
+fn cmd_wait(
+    share: Option<&str>,
+    backend: Option<Backend>,
+    state: WaitState,
+    timeout: std::time::Duration,
+    observe: bool,
+) -> Result<()> {
+    let cfg = config::load()?;
+    if state == WaitState::Unmounted && backend.is_some() {
+        return Err(anyhow!("--backend only applies to --state ready"));
+    }
+    let names: Vec<String> = match share {
+        Some(name) => match config::find_share(&cfg, name) {
+            Some(share) => vec![share.name.clone()],
+            None => {
+                eprintln!("error: share '{}' is not configured", name);
+                std::process::exit(WAIT_EXIT_UNKNOWN_SHARE);
+            }
+        },
+        None => {
+            ensure_has_shares(&cfg)?;
+            cfg.shares.iter().map(|share| share.name.clone()).collect()
+        }
+    };
+
+    // Reconciling would remount a share we are waiting to see unmounted.
+    let drive = !observe && state == WaitState::Ready;
+    let reached = wait_for_shares(&cfg, &names, drive, timeout, |status| match state {
+        WaitState::Ready => status.is_ready_on(backend),
+        WaitState::Unmounted => status.is_unmounted(),
+    })?;
+    match reached {
+        Some(statuses) => {
+            print_status_table(&statuses);
+            Ok(())
+        }
+        None => {
+            eprintln!(
+                "error: timed out after {}s waiting for {} to be {}",
+                timeout.as_secs(),
+                names.join(", "),
+                match state {
+                    WaitState::Ready => "ready",
+                    WaitState::Unmounted => "unmounted",
+                }
+            );
+            std::process::exit(WAIT_EXIT_TIMEOUT);
+        }
+    }
+}
+
+/// Poll `names` until every status satisfies `reached`, reconciling them
+/// when `drive` is set and only verifying otherwise. `None` if `timeout`
+/// passes first.
+fn wait_for_shares(
+    cfg: &Config,
+    names: &[String],
+    drive: bool,
+    timeout: std::time::Duration,
+    reached: impl Fn(&engine::ShareStatus) -> bool,
+) -> Result<Option<Vec<engine::ShareStatus>>> {
+    let deadline = std::time::Instant::now() + timeout;
+    let poll = std::time::Duration::from_secs(cfg.global.check_interval_secs.clamp(1, 5));
+    // Leave reconciling to the owner when there is one.
+    let lock = if drive {
+        match ownership::claim("wait")? {
+            ownership::Claim::Acquired(lock) => Some(lock),
+            ownership::Claim::Held(owner) => {
+                log::info!(
+                    "wait: observing, reconciliation is owned by {}",
+                    owner
+                        .map(|o| o.describe())
+                        .unwrap_or_else(|| "another process".into())
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let drive = lock.is_some();
+    loop {
+        let mut state = engine::load_runtime_state().unwrap_or_default();
+        let statuses = if drive {
+            engine::reconcile_selected(cfg, &mut state, names)?
+        } else {
+            engine::verify_selected(cfg, &mut state, names)?
+        };
+        engine::save_runtime_state(&mut state)?;
+        if statuses.iter().all(&reached) {
+            return Ok(Some(statuses));
+        }
+        let now = std::time::Instant::now();
+        if now >= deadline {
+            return Ok(None);
+        }
+        std::thread::sleep(poll.min(deadline - now));
+    }
+}
+
 fn cmd_folders(share: &str, subpath: Option<&str>, json: bool) -> Result<()> {
     let cfg = config::load()?;
     let entries = engine::list_folders(&cfg, share, subpath)?;
@@ -427,18 +1184,33 @@ fn cmd_favorites(command: FavoritesCommand) -> Result<()> {
                 thunderbolt_host: tb_host,
                 fallback_host,
                 share_name: remote_share.unwrap_or_else(|| share.clone()),
+                ..ShareConfig::default()
             };
 
             engine::add_share(&mut cfg, share_cfg)?;
             config::save(&cfg)?;
             println!("Added favorite '{}'.", share);
 
+            // A running reconciler sees the new share in config.toml and
+            // mounts it itself.
+            let _lock = match ownership::claim("favorites add")? {
+                ownership::Claim::Acquired(lock) => lock,
+                ownership::Claim::Held(owner) => {
+                    println!(
+                        "{} will mount it.",
+                        owner
+                            .map(|owner| owner.describe())
+                            .unwrap_or_else(|| "The running agent".to_string())
+                    );
+                    return Ok(());
+                }
+            };
             // Attempt immediate mount — non-fatal if it fails, since the monitor
             // loop will retry. Config and symlink are already persisted.
             let mut state = engine::load_runtime_state().unwrap_or_default();
             match engine::reconcile_selected(&cfg, &mut state, std::slice::from_ref(&share)) {
                 Ok(statuses) => {
-                    engine::save_runtime_state(&state)?;
+                    engine::save_runtime_state(&mut state)?;
                     for status in &statuses {
                         if let Some(err) = &status.last_error {
                             eprintln!("warning: initial mount for '{}' failed: {}", share, err);
@@ -451,6 +1223,13 @@ fn cmd_favorites(command: FavoritesCommand) -> Result<()> {
             }
             Ok(())
         }
+        FavoritesCommand::Adopt {
+            mount,
+            share,
+            tb_host,
+            fallback_host,
+            json,
+        } => cmd_favorites_adopt(mount, share, tb_host, fallback_host, json),
         FavoritesCommand::Remove { share, cleanup } => {
             let mut cfg = config::load()?;
             let removed = engine::remove_share(&mut cfg, &share)
@@ -458,10 +1237,21 @@ fn cmd_favorites(command: FavoritesCommand) -> Result<()> {
             config::save(&cfg)?;
 
             if cleanup {
-                let mut state = engine::load_runtime_state().unwrap_or_default();
-                let (affected_aliases, unmount_results) =
-                    engine::cleanup_removed_share(&cfg, &mut state, &removed.name)?;
-                engine::save_runtime_state(&state)?;
+                let call = control::Call::Cleanup {
+                    share: Box::new(removed.clone()),
+                };
+                let (affected_aliases, item): (usize, engine::UnmountResult) =
+                    match control::request(&call) {
+                        Some(reply) => reply?,
+                        None => {
+                            let _lock = ownership::claim_or_refuse("favorites remove --cleanup")?;
+                            let mut state = engine::load_runtime_state().unwrap_or_default();
+                            let cleaned =
+                                engine::cleanup_removed_share(&cfg, &mut state, &removed)?;
+                            engine::save_runtime_state(&mut state)?;
+                            cleaned
+                        }
+                    };
 
                 println!("Removed '{}' from favorites with cleanup.", removed.name);
                 if affected_aliases > 0 {
@@ -470,17 +1260,15 @@ fn cmd_favorites(command: FavoritesCommand) -> Result<()> {
                         affected_aliases
                     );
                 }
-                for item in unmount_results {
-                    println!(
-                        "cleanup {} {}: attempted={} busy={} unmounted={} {}",
-                        item.share,
-                        item.backend.short_label(),
-                        item.attempted,
-                        item.busy,
-                        item.unmounted,
-                        item.message.unwrap_or_default()
-                    );
-                }
+                println!(
+                    "cleanup {} {}: attempted={} busy={} unmounted={} {}",
+                    item.share,
+                    item.backend.short_label(),
+                    item.attempted,
+                    item.busy,
+                    item.unmounted,
+                    item.message.unwrap_or_default()
+                );
             } else {
                 // P11.1: Report dependent aliases on non-cleanup remove (spec 06 AC 7)
                 let affected_aliases: Vec<String> = cfg
@@ -521,8 +1309,8 @@ fn cmd_favorites(command: FavoritesCommand) -> Result<()> {
                         "{:<16} {:<16} {:<24} {:<24} {}",
                         share.name,
                         share.username,
-                        share.thunderbolt_host,
-                        share.fallback_host,
+                        network::host::display(&share.thunderbolt_host),
+                        network::host::display(&share.fallback_host),
                         share.share_name
                     );
                 }
@@ -532,6 +1320,118 @@ fn cmd_favorites(command: FavoritesCommand) -> Result<()> {
     }
 }
 
+fn cmd_favorites_adopt(
+    mount: Option<String>,
+    name: Option<String>,
+    tb_host: Option<String>,
+    fallback_host: Option<String>,
+    json: bool,
+) -> Result<()> {
+    let mut cfg = config::load()?;
+    let candidates = engine::unmanaged_mounts(&cfg);
+
+    let Some(mount_point) = mount else {
+        if json {
+            println!("{}", serde_json::to_string_pretty(&candidates)?);
+        } else if candidates.is_empty() {
+            println!("No unmanaged SMB mounts found.");
+        } else {
+            println!("{:<28} {:<24} {:<16} USER", "MOUNT POINT", "HOST", "SHARE");
+            for item in &candidates {
+                println!(
+                    "{:<28} {:<24} {:<16} {}",
+                    item.mount_point,
+                    item.host,
+                    item.share,
+                    item.user.as_deref().unwrap_or("-")
+                );
+            }
+        }
+        return Ok(());
+    };
+
+    let candidate = candidates
+        .iter()
+        .find(|item| std::path::Path::new(&item.mount_point) == std::path::Path::new(&mount_point))
+        .ok_or_else(|| {
+            anyhow!(
+                "{} is not an unmanaged SMB mount; run `mountaineer favorites adopt` to list them",
+                mount_point
+            )
+        })?;
+
+    let (mounted_as, alternate) = match (tb_host, fallback_host) {
+        (Some(tb), None) => (Backend::Fallback, tb),
+        (None, Some(fb)) => (Backend::Tb, fb),
+        _ => match engine::suggest_alternate_host(&cfg, &candidate.host) {
+            Some((role, alternate)) => {
+                println!(
+                    "Using {} as the {} host (from an existing favorite on {}).",
+                    alternate,
+                    match role {
+                        Backend::Tb => "fallback",
+                        Backend::Fallback => "Thunderbolt",
+                    },
+                    candidate.host
+                );
+                (role, alternate)
+            }
+            None => prompt_alternate_host(&candidate.host)?,
+        },
+    };
+
+    let name = name.unwrap_or_else(|| candidate.share.clone());
+    // State first: a running reconciler that sees the share in config.toml
+    // before it knows the share is mounted would mount it a second time.
+    let share = engine::update_runtime_state(|state| {
+        engine::adopt_mount(&mut cfg, state, candidate, &name, mounted_as, &alternate)
+    })??;
+    config::save(&cfg)?;
+
+    println!(
+        "Adopted {} as favorite '{}' (TB {}, fallback {}, active {}).",
+        candidate.mount_point,
+        share.name,
+        share.thunderbolt_host,
+        share.fallback_host,
+        mounted_as.short_label()
+    );
+    Ok(())
+}
+
+/// Ask which role `host` plays and for the other host, when neither a flag
+/// nor an existing favorite says.
+fn prompt_alternate_host(host: &str) -> Result<(Backend, String)> {
+    use std::io::{BufRead, IsTerminal, Write};
+
+    if !std::io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "cannot tell the alternate host for {}; pass --tb-host or --fallback-host",
+            host
+        ));
+    }
+    let read_line = |prompt: &str| -> Result<String> {
+        eprint!("{}", prompt);
+        std::io::stderr().flush()?;
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line)?;
+        Ok(line.trim().to_string())
+    };
+
+    let answer = read_line(&format!("Is {} the Thunderbolt host? [y/N] ", host))?;
+    let mounted_as = if answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes") {
+        Backend::Tb
+    } else {
+        Backend::Fallback
+    };
+    let other = match mounted_as {
+        Backend::Tb => "Fallback",
+        Backend::Fallback => "Thunderbolt",
+    };
+    let alternate = read_line(&format!("{} host: ", other))?;
+    Ok((mounted_as, alternate))
+}
+
 fn cmd_config(command: ConfigCommand) -> Result<()> {
     match command {
         ConfigCommand::Set { key, value } => {
@@ -575,9 +1475,46 @@ fn cmd_config(command: ConfigCommand) -> Result<()> {
                     cfg.global.connect_timeout_ms = ms;
                     println!("connect-timeout = {}ms", ms);
                 }
+                "reclaim-foreign-mounts" => {
+                    cfg.global.reclaim_foreign_mounts = parse_on_off(&value)?;
+                    println!(
+                        "reclaim-foreign-mounts = {}",
+                        if cfg.global.reclaim_foreign_mounts {
+                            "on"
+                        } else {
+                            "off"
+                        }
+                    );
+                }
+                "credential-store" => {
+                    cfg.global.credential_store = match value.as_str() {
+                        "keychain" => CredentialStore::Keychain,
+                        "file" => CredentialStore::File,
+                        _ => {
+                            return Err(anyhow!(
+                                "invalid value '{}': expected keychain or file",
+                                value
+                            ));
+                        }
+                    };
+                    println!("credential-store = {}", cfg.global.credential_store.label());
+                }
+                "on-share-removed" => {
+                    cfg.global.on_share_removed = match value.as_str() {
+                        "keep" => RemovedSharePolicy::Keep,
+                        "cleanup" => RemovedSharePolicy::Cleanup,
+                        _ => {
+                            return Err(anyhow!(
+                                "invalid value '{}': expected keep or cleanup",
+                                value
+                            ));
+                        }
+                    };
+                    println!("on-share-removed = {}", cfg.global.on_share_removed.label());
+                }
                 _ => {
                     return Err(anyhow!(
-                        "unknown config key '{}'. valid keys: lsof-recheck, auto-failback, check-interval, connect-timeout",
+                        "unknown config key '{}'. valid keys: lsof-recheck, auto-failback, check-interval, connect-timeout, reclaim-foreign-mounts, credential-store, on-share-removed",
                         key
                     ));
                 }
@@ -596,6 +1533,12 @@ fn cmd_config(command: ConfigCommand) -> Result<()> {
             );
             println!("connect_timeout_ms = {}", cfg.global.connect_timeout_ms);
             println!("lsof_recheck = {}", cfg.global.lsof_recheck);
+            println!(
+                "reclaim_foreign_mounts = {}",
+                cfg.global.reclaim_foreign_mounts
+            );
+            println!("credential_store = {}", cfg.global.credential_store.label());
+            println!("on_share_removed = {}", cfg.global.on_share_removed.label());
             Ok(())
         }
     }
@@ -609,6 +1552,177 @@ fn parse_on_off(value: &str) -> Result<bool> {
     }
 }
 
+fn cmd_reauth(share_name: &str) -> Result<()> {
+    let cfg = config::load()?;
+    let share = config::find_share(&cfg, share_name)
+        .ok_or_else(|| anyhow!("share '{}' is not configured", share_name))?
+        .clone();
+
+    let password = prompt_password(&format!(
+        "Password for {} on {} (leave empty to retry the saved one): ",
+        share.username, share.name
+    ))?;
+    if !password.is_empty() {
+        let store = open_credential_store(&cfg)?;
+        for host in credentials::share_hosts(&share) {
+            store.set(host, &share.username, &password)?;
+        }
+    }
+
+    let call = control::Call::Reauth {
+        share: share.name.clone(),
+    };
+    let reply: control::ReauthReply = match control::request(&call) {
+        Some(reply) => reply?,
+        None => {
+            let _lock = ownership::claim_or_refuse("reauth")?;
+            let mut state = engine::load_runtime_state().unwrap_or_default();
+            let reply = control::reauth_share(&cfg, &mut state, &share.name)?;
+            engine::save_runtime_state(&mut state)?;
+            reply
+        }
+    };
+    if !reply.was_suspended {
+        println!(
+            "{} was not waiting for credentials; retried mount",
+            share.name
+        );
+    }
+    let statuses = reply.statuses;
+    print_status_table(&statuses);
+
+    if statuses.iter().any(|s| s.needs_credentials.is_some()) {
+        return Err(anyhow!(
+            "'{}' still rejects the credentials; automatic mounts remain suspended",
+            share.name
+        ));
+    }
+    Ok(())
+}
+
+fn cmd_credentials(command: CredentialsCommand) -> Result<()> {
+    let cfg = config::load()?;
+    let share_name = match &command {
+        CredentialsCommand::Set { share }
+        | CredentialsCommand::Test { share }
+        | CredentialsCommand::Remove { share } => share.clone(),
+    };
+    let share = config::find_share(&cfg, &share_name)
+        .ok_or_else(|| anyhow!("share '{}' is not configured", share_name))?
+        .clone();
+    let store = open_credential_store(&cfg)?;
+
+    match command {
+        CredentialsCommand::Set { .. } => {
+            let password = prompt_password(&format!(
+                "Password for {} on {}: ",
+                share.username, share.name
+            ))?;
+            if password.is_empty() {
+                return Err(anyhow!("password must not be empty"));
+            }
+            for host in credentials::share_hosts(&share) {
+                store.set(host, &share.username, &password)?;
+                println!(
+                    "saved {}@{} in {} store",
+                    share.username,
+                    host,
+                    store.name()
+                );
+            }
+
+            let mut state = engine::load_runtime_state().unwrap_or_default();
+            if engine::resume_after_credentials(&mut state, &share.name) {
+                engine::save_runtime_state(&mut state)?;
+                println!("{}: automatic mounts resumed", share.name);
+            }
+            Ok(())
+        }
+        CredentialsCommand::Test { .. } => {
+            if share.guest {
+                println!("{}: guest share, no credentials to test", share.name);
+                return Ok(());
+            }
+            let timeout = std::time::Duration::from_secs(10);
+            let options = engine::smb_options(&share);
+            let hosts = credentials::share_hosts(&share);
+            let mut failed = 0;
+            for host in &hosts {
+                let result = match store.get(host, &share.username)? {
+                    None => Err("no stored password".to_string()),
+                    Some(password) => {
+                        discovery::verify_credentials(host, &options, &password, timeout)
+                            .map_err(|e| format!("{}: {}", e.kind().label(), e))
+                    }
+                };
+                match result {
+                    Ok(()) => println!("{}@{}: ok", share.username, host),
+                    Err(reason) => {
+                        failed += 1;
+                        println!("{}@{}: {}", share.username, host, reason);
+                    }
+                }
+            }
+            if failed > 0 {
+                return Err(anyhow!(
+                    "{} of {} server(s) failed the credential check",
+                    failed,
+                    hosts.len()
+                ));
+            }
+            Ok(())
+        }
+        CredentialsCommand::Remove { .. } => {
+            for host in credentials::share_hosts(&share) {
+                if store.remove(host, &share.username)? {
+                    println!("removed {}@{}", share.username, host);
+                } else {
+                    println!("{}@{}: nothing stored", share.username, host);
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Open the configured credential store, prompting for the file store's
+/// passphrase when it is not in the environment and a terminal is attached.
+fn open_credential_store(cfg: &Config) -> Result<Box<dyn CredentialProvider>> {
+    use std::io::IsTerminal;
+
+    let passphrase = if cfg.global.credential_store == CredentialStore::File
+        && std::env::var_os(credentials::PASSPHRASE_ENV).is_none()
+        && std::io::stdin().is_terminal()
+    {
+        Some(prompt_password("Credential file passphrase: ")?)
+    } else {
+        None
+    };
+    credentials::provider(&cfg.global, passphrase)
+}
+
+/// Read a password from the terminal without echo, or a line from stdin
+/// when it is not a terminal.
+fn prompt_password(prompt: &str) -> Result<String> {
+    use std::io::{BufRead, IsTerminal, Write};
+
+    let stdin = std::io::stdin();
+    let interactive = stdin.is_terminal();
+    if interactive {
+        eprint!("{}", prompt);
+        std::io::stderr().flush()?;
+        let _ = std::process::Command::new("stty").arg("-echo").status();
+    }
+    let mut line = String::new();
+    let read = stdin.lock().read_line(&mut line);
+    if interactive {
+        let _ = std::process::Command::new("stty").arg("echo").status();
+        eprintln!();
+    }
+    read?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
 fn cmd_install() -> Result<()> {
     if launchd::is_installed() {
         println!("LaunchAgent already exists. Reinstalling...");
@@ -689,6 +1803,47 @@ fn print_status_table(statuses: &[engine::ShareStatus]) {
         if let Some(error) = &status.last_error {
             println!("  ! {}", error);
         }
+        if let (Some(kind), Some(hint)) = (status.last_error_kind, &status.last_error_hint) {
+            println!("    {}: {}", kind.label(), hint);
+        }
+        if let Some(backend) = status.needs_credentials {
+            println!(
+                "  ! {} rejected the credentials; mounts suspended. Run: mountaineer reauth --share {}",
+                backend.short_label(),
+                status.name
+            );
+        }
+        if let Some(pending) = &status.pending_switch {
+            println!(
+                "  ~ switching to {} once files close (gives up at {})",
+                pending.to.short_label(),
+                pending
+                    .deadline
+                    .with_timezone(&chrono::Local)
+                    .format("%H:%M:%S")
+            );
+        }
+        for lease in &status.leases {
+            println!(
+                "  ~ leased by {} until {} ({})",
+                lease.holder.as_deref().unwrap_or("unnamed holder"),
+                lease
+                    .expires_at
+                    .with_timezone(&chrono::Local)
+                    .format("%H:%M:%S"),
+                lease.token
+            );
+        }
+        for foreign in &status.foreign_mounts {
+            let conflict = match foreign.conflict {
+                engine::ForeignConflict::SameShare => "same share",
+                engine::ForeignConflict::SameServer => "same server",
+            };
+            println!(
+                "  ! foreign mount ({}): //{}/{} at {}",
+                conflict, foreign.host, foreign.share, foreign.mount_point
+            );
+        }
     }
 }
 
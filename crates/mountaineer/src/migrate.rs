@@ -0,0 +1,336 @@
+//! Schema versions of `config.toml` and `state.json`, and the migrations
+//! between them.
+//!
+//! Both files carry a top-level `version`. Files written before versioning
+//! have none and count as version 0. Loading an older file runs each step
+//! after its version on the parsed document, before it is deserialized, so a
+//! step can rename or move keys the current structs no longer know. The
+//! loader then writes the upgraded file back, after copying the original to
+//! `<file>.v<N>.bak` ([`backup`]); `config.toml` keeps its comments where it
+//! can ([`upgraded_config_text`]). A file newer than this build is refused
+//! rather than loaded and saved over without the fields it does not know.
+//!
+//! To change a schema, bump its version and append a step that turns a
+//! document of the previous version into the new one, with a fixture of the
+//! previous version under `fixtures/migrate`.
+
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_VERSION: u32 = 1;
+pub const STATE_VERSION: u32 = 1;
+
+type StateDocument = serde_json::Map<String, serde_json::Value>;
+
+/// Turns a document of version `to - 1` into version `to`.
+struct Step<D: 'static> {
+    to: u32,
+    summary: &'static str,
+    apply: fn(&mut D),
+}
+
+const CONFIG_STEPS: &[Step<toml::Table>] = &[Step {
+    to: 1,
+    summary: "add the version key",
+    apply: unchanged,
+}];
+
+const STATE_STEPS: &[Step<StateDocument>] = &[Step {
+    to: 1,
+    summary: "add the version key",
+    apply: unchanged,
+}];
+
+/// Version 1 is the schema as it stood when versions were introduced; every
+/// earlier change only added fields with defaults.
+fn unchanged<D>(_: &mut D) {}
+
+/// Upgrade a parsed `config.toml` to [`CONFIG_VERSION`]. Returns the version
+/// it had.
+pub fn upgrade_config(doc: &mut toml::Table) -> Result<u32> {
+    let found = match doc.get("version") {
+        None => 0,
+        Some(toml::Value::Integer(n)) => version_number(*n)?,
+        Some(other) => bail!("version must be a whole number, not {}", other),
+    };
+    run_steps(doc, found, CONFIG_VERSION, CONFIG_STEPS, "config.toml")?;
+    doc.insert("version".to_string(), i64::from(CONFIG_VERSION).into());
+    Ok(found)
+}
+
+/// Upgrade a parsed `state.json` to [`STATE_VERSION`]. Returns the version it
+/// had.
+pub fn upgrade_state(doc: &mut StateDocument) -> Result<u32> {
+    let found = match doc.get("version") {
+        None => 0,
+        Some(serde_json::Value::Number(n)) if n.is_i64() => version_number(n.as_i64().unwrap())?,
+        Some(other) => bail!("version must be a whole number, not {}", other),
+    };
+    run_steps(doc, found, STATE_VERSION, STATE_STEPS, "state.json")?;
+    doc.insert("version".to_string(), STATE_VERSION.into());
+    Ok(found)
+}
+
+/// The text to write back for `original` once upgraded to `doc`. When the
+/// steps changed nothing but the version, that is `original` with its
+/// `version` line set, keeping the user's comments and key order; otherwise
+/// `doc` reformatted.
+pub fn upgraded_config_text(original: &str, doc: &toml::Table) -> Result<String> {
+    let text = with_config_version(original, CONFIG_VERSION);
+    match toml::from_str::<toml::Table>(&text) {
+        Ok(edited) if &edited == doc => Ok(text),
+        _ => Ok(toml::to_string_pretty(doc)?),
+    }
+}
+
+/// `original` with its top-level `version` line replaced by `version`, or
+/// one added as the first line if it has none.
+fn with_config_version(original: &str, version: u32) -> String {
+    let line = format!("version = {}", version);
+    let mut text = String::with_capacity(original.len() + line.len() + 1);
+    let mut in_root = true;
+    let mut replaced = false;
+    for current in original.split_inclusive('\n') {
+        let trimmed = current.trim_start();
+        in_root &= !trimmed.starts_with('[');
+        let is_version = trimmed
+            .strip_prefix("version")
+            .is_some_and(|rest| rest.trim_start().starts_with('='));
+        if in_root && !replaced && is_version {
+            text.push_str(&line);
+            if current.ends_with('\n') {
+                text.push('\n');
+            }
+            replaced = true;
+        } else {
+            text.push_str(current);
+        }
+    }
+    if replaced {
+        text
+    } else {
+        format!("{}\n{}", line, original)
+    }
+}
+
+fn version_number(n: i64) -> Result<u32> {
+    u32::try_from(n).map_err(|_| anyhow::anyhow!("version {} is not valid", n))
+}
+
+fn run_steps<D>(
+    doc: &mut D,
+    found: u32,
+    current: u32,
+    steps: &[Step<D>],
+    file: &str,
+) -> Result<()> {
+    if found > current {
+        bail!(
+            "{} is version {}, but this mountaineer only understands up to version {}; update mountaineer",
+            file,
+            found,
+            current
+        );
+    }
+    for step in steps.iter().filter(|step| step.to > found) {
+        (step.apply)(doc);
+        log::info!("migrate: {} to version {}: {}", file, step.to, step.summary);
+    }
+    Ok(())
+}
+
+/// Copy `path` to `<path>.v<version>.bak` before it is upgraded in place. An
+/// existing backup is kept: it is the older copy.
+pub fn backup(path: &Path, version: u32) -> Result<PathBuf> {
+    let mut name = path
+        .file_name()
+        .with_context(|| format!("{} has no file name", path.display()))?
+        .to_os_string();
+    name.push(format!(".v{}.bak", version));
+    let backup = path.with_file_name(name);
+    if !backup.exists() {
+        fs::copy(path, &backup)
+            .with_context(|| format!("failed writing backup {}", backup.display()))?;
+    }
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{self, Backend, RemovedSharePolicy};
+    use crate::engine;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures/migrate")
+            .join(name)
+    }
+
+    /// Copy a fixture into a temporary directory as `file_name`.
+    fn install_fixture(name: &str, file_name: &str) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(file_name);
+        fs::copy(fixture(name), &path).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn steps_lead_to_the_current_versions() {
+        assert_eq!(
+            CONFIG_STEPS.iter().map(|s| s.to).collect::<Vec<_>>(),
+            (1..=CONFIG_VERSION).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            STATE_STEPS.iter().map(|s| s.to).collect::<Vec<_>>(),
+            (1..=STATE_VERSION).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn every_config_version_loads_and_is_upgraded_in_place() {
+        for (name, version) in [
+            ("config-v0-baseline.toml", 0),
+            ("config-v0.toml", 0),
+            ("config-v1.toml", 1),
+        ] {
+            let (_dir, path) = install_fixture(name, "config.toml");
+            let original = fs::read_to_string(&path).unwrap();
+
+            let cfg = config::load_at(&path).unwrap_or_else(|e| panic!("{}: {:#}", name, e));
+            assert_eq!(cfg.version, CONFIG_VERSION, "{}", name);
+            assert_eq!(cfg.shares[0].name, "CORE", "{}", name);
+            assert_eq!(cfg.shares[0].thunderbolt_host, "10.10.10.1", "{}", name);
+            assert_eq!(cfg.aliases[0].target_subpath, "dev/projects", "{}", name);
+
+            let upgraded = fs::read_to_string(&path).unwrap();
+            let backup = path.with_file_name(format!("config.toml.v{}.bak", version));
+            if version < CONFIG_VERSION {
+                // Only the version line is added; comments and layout stay.
+                assert_eq!(upgraded, format!("version = 1\n{}", original), "{}", name);
+                assert_eq!(fs::read_to_string(&backup).unwrap(), original, "{}", name);
+            } else {
+                assert_eq!(upgraded, original, "{}", name);
+                assert!(!backup.exists(), "{}", name);
+            }
+
+            // Loading again changes nothing.
+            config::load_at(&path).unwrap();
+            assert_eq!(fs::read_to_string(&path).unwrap(), upgraded, "{}", name);
+        }
+    }
+
+    #[test]
+    fn upgraded_config_text_sets_an_existing_version_line() {
+        let original = "# mine\nversion = 0 \n\n[global]\nversion = \"x\"\n";
+        let mut doc: toml::Table = toml::from_str(original).unwrap();
+        upgrade_config(&mut doc).unwrap();
+        assert_eq!(
+            upgraded_config_text(original, &doc).unwrap(),
+            "# mine\nversion = 1\n\n[global]\nversion = \"x\"\n"
+        );
+    }
+
+    #[test]
+    fn later_config_fields_survive_the_upgrade() {
+        let (_dir, path) = install_fixture("config-v0.toml", "config.toml");
+        config::load_at(&path).unwrap();
+        let cfg = config::load_at(&path).unwrap();
+
+        let core = &cfg.shares[0];
+        assert_eq!(core.domain.as_deref(), Some("STUDIO"));
+        assert_eq!(core.port, Some(4450));
+        assert_eq!(core.mount_options, vec!["nobrowse".to_string()]);
+        assert_eq!(core.mount_point.as_deref(), Some("~/.mountaineer/mnt/CORE"));
+        assert_eq!(core.hooks.post_mount.as_deref(), Some("~/bin/reindex"));
+        assert!(cfg.shares[1].guest);
+        assert_eq!(cfg.global.credential_store, config::CredentialStore::File);
+        assert!(cfg.global.reclaim_foreign_mounts);
+        assert_eq!(cfg.global.on_share_removed, RemovedSharePolicy::Cleanup);
+        assert_eq!(cfg.hooks.timeout_secs, Some(5));
+        assert_eq!(cfg.webhooks.len(), 1);
+        assert!(!cfg.notifications.failover);
+    }
+
+    #[test]
+    fn every_state_version_loads_and_is_upgraded_in_place() {
+        for (name, version) in [
+            ("state-v0-baseline.json", 0),
+            ("state-v0.json", 0),
+            ("state-v1.json", 1),
+        ] {
+            let (_dir, path) = install_fixture(name, "state.json");
+            let original = fs::read_to_string(&path).unwrap();
+
+            let state = engine::load_runtime_state_at(&path)
+                .unwrap_or_else(|e| panic!("{}: {:#}", name, e));
+            assert_eq!(state.version, STATE_VERSION, "{}", name);
+            let core = &state.shares["core"];
+            assert_eq!(core.active_backend, Some(Backend::Fallback), "{}", name);
+            assert!(core.tb_recovery_pending, "{}", name);
+
+            let upgraded = fs::read_to_string(&path).unwrap();
+            let backup = path.with_file_name(format!("state.json.v{}.bak", version));
+            if version < STATE_VERSION {
+                assert!(
+                    upgraded.contains("\"version\": 1"),
+                    "{}:\n{}",
+                    name,
+                    upgraded
+                );
+                assert_eq!(fs::read_to_string(&backup).unwrap(), original, "{}", name);
+                // Rewriting the same entries is not a change to them.
+                let reloaded = engine::load_runtime_state_at(&path).unwrap();
+                assert_eq!(reloaded.shares, state.shares, "{}", name);
+            } else {
+                assert_eq!(upgraded, original, "{}", name);
+                assert!(!backup.exists(), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn later_state_fields_survive_the_upgrade() {
+        let (_dir, path) = install_fixture("state-v0.json", "state.json");
+        let state = engine::load_runtime_state_at(&path).unwrap();
+        let core = &state.shares["core"];
+        assert_eq!(core.revision, 7);
+        assert_eq!(core.needs_credentials, Some(Backend::Tb));
+        assert_eq!(core.leases[0].holder.as_deref(), Some("render farm"));
+        assert_eq!(
+            core.pending_switch.as_ref().map(|p| p.to),
+            Some(Backend::Tb)
+        );
+    }
+
+    #[test]
+    fn files_from_a_newer_version_are_refused_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        let text = format!("version = {}\n", CONFIG_VERSION + 1);
+        fs::write(&config_path, &text).unwrap();
+        let err = format!("{:#}", config::load_at(&config_path).unwrap_err());
+        assert!(err.contains("only understands up to version 1"), "{}", err);
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), text);
+
+        let state_path = dir.path().join("state.json");
+        let text = format!("{{\"version\": {}, \"shares\": {{}}}}", STATE_VERSION + 1);
+        fs::write(&state_path, &text).unwrap();
+        assert!(engine::load_runtime_state_at(&state_path).is_err());
+        assert_eq!(fs::read_to_string(&state_path).unwrap(), text);
+
+        let backups = fs::read_dir(dir.path())
+            .unwrap()
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .unwrap()
+                    .path()
+                    .to_string_lossy()
+                    .ends_with(".bak")
+            })
+            .count();
+        assert_eq!(backups, 0);
+    }
+}
@@ -1,9 +1,16 @@
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 use std::time::{Duration, Instant};
 
+use crate::network::host as net_host;
+use crate::runner;
+
+/// Kill a mount that sends a password if the server has not answered by
+/// then, so a hung server cannot stall the reconcile loop.
+const PASSWORD_MOUNT_TIMEOUT: Duration = Duration::from_secs(60);
+
 #[derive(Debug)]
 pub enum MountError {
     CreateMountPoint {
@@ -13,6 +20,7 @@ pub enum MountError {
     MountFailed {
         stderr: String,
         exit_code: Option<i32>,
+        kind: MountFailureKind,
     },
     UnmountFailed {
         stderr: String,
@@ -34,7 +42,9 @@ impl fmt::Display for MountError {
                     source
                 )
             }
-            MountError::MountFailed { stderr, exit_code } => {
+            MountError::MountFailed {
+                stderr, exit_code, ..
+            } => {
                 let code = exit_code.map_or_else(|| "?".to_string(), |code| code.to_string());
                 write!(f, "mount failed (exit {}): {}", code, stderr)
             }
@@ -46,6 +56,149 @@ impl fmt::Display for MountError {
     }
 }
 
+impl MountError {
+    pub(crate) fn mount_failed(stderr: String, exit_code: Option<i32>) -> Self {
+        let kind = MountFailureKind::classify(&stderr);
+        MountError::MountFailed {
+            stderr,
+            exit_code,
+            kind,
+        }
+    }
+
+    /// Category of the failure, used for remediation hints and retry policy.
+    pub fn kind(&self) -> MountFailureKind {
+        match self {
+            MountError::MountFailed { kind, .. } => *kind,
+            MountError::CreateMountPoint { source, .. } => {
+                MountFailureKind::classify(&source.to_string())
+            }
+            MountError::UnmountFailed { .. } | MountError::CommandSpawn { .. } => {
+                MountFailureKind::Unknown
+            }
+        }
+    }
+}
+
+/// Category of a failed mount, parsed from osascript / mount_smbfs stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MountFailureKind {
+    AuthFailed,
+    HostUnreachable,
+    ShareNotFound,
+    PermissionDenied,
+    /// The share is already mounted at a different path (e.g. `/Volumes/CORE-1`).
+    AlreadyMounted,
+    /// Finder reported -5014 / "File exists" for a volume it already mounted.
+    /// Non-fatal: the existing mount is adopted on the next cycle.
+    FinderCollision,
+    Timeout,
+    Unknown,
+}
+
+impl MountFailureKind {
+    /// Classify combined osascript / mount_smbfs error output.
+    ///
+    /// The first matching category wins, so the more specific Finder collision
+    /// and authentication patterns are checked before the generic ones.
+    pub fn classify(message: &str) -> Self {
+        let lower = message.to_ascii_lowercase();
+        let has = |needles: &[&str]| needles.iter().any(|n| lower.contains(n));
+
+        if lower.contains("file exists")
+            && has(&[
+                "no detectable share path",
+                "an error of type -5014",
+                "execution error",
+            ])
+        {
+            MountFailureKind::FinderCollision
+        } else if has(&[
+            "authentication error",
+            "authentication failed",
+            "logon failure",
+            "status_logon_failure",
+            "bad password",
+            "password is incorrect",
+        ]) {
+            MountFailureKind::AuthFailed
+        } else if has(&[
+            "bad network name",
+            "status_bad_network_name",
+            "share does not exist",
+            "share not found",
+            "server rejected the connection: no such file or directory",
+        ]) {
+            MountFailureKind::ShareNotFound
+        } else if has(&["timed out", "timeout"]) {
+            MountFailureKind::Timeout
+        } else if has(&[
+            "no route to host",
+            "host is down",
+            "network is unreachable",
+            "connection refused",
+            "server connection failed",
+            "could not connect",
+            "unknown host",
+            "nodename nor servname",
+        ]) {
+            MountFailureKind::HostUnreachable
+        } else if has(&[
+            "permission denied",
+            "operation not permitted",
+            "access denied",
+        ]) {
+            MountFailureKind::PermissionDenied
+        } else if has(&["file exists", "already mounted", "resource busy"]) {
+            MountFailureKind::AlreadyMounted
+        } else {
+            MountFailureKind::Unknown
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MountFailureKind::AuthFailed => "authentication failed",
+            MountFailureKind::HostUnreachable => "host unreachable",
+            MountFailureKind::ShareNotFound => "share not found",
+            MountFailureKind::PermissionDenied => "permission denied",
+            MountFailureKind::AlreadyMounted => "already mounted elsewhere",
+            MountFailureKind::FinderCollision => "Finder mount collision",
+            MountFailureKind::Timeout => "timed out",
+            MountFailureKind::Unknown => "mount failed",
+        }
+    }
+
+    /// One-line remediation hint shown next to the error in status and the tray.
+    pub fn hint(self) -> &'static str {
+        match self {
+            MountFailureKind::AuthFailed => {
+                "check the username and the saved password for this server"
+            }
+            MountFailureKind::HostUnreachable => {
+                "check the cable or network and that the server is on and sharing over SMB"
+            }
+            MountFailureKind::ShareNotFound => {
+                "check that share_name in config.toml matches the name exported by the server"
+            }
+            MountFailureKind::PermissionDenied => {
+                "the account lacks access to the share or mount point; check share permissions"
+            }
+            MountFailureKind::AlreadyMounted => {
+                "unmount the stray copy of this share (e.g. /Volumes/<SHARE>-1) and retry"
+            }
+            MountFailureKind::FinderCollision => {
+                "Finder already mounted this volume; it will be adopted on the next check"
+            }
+            MountFailureKind::Timeout => {
+                "the server did not answer in time; it may be asleep or overloaded"
+            }
+            MountFailureKind::Unknown => "see ~/Library/Logs/mountaineer.log for details",
+        }
+    }
+}
+
 impl std::error::Error for MountError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
@@ -56,101 +209,234 @@ impl std::error::Error for MountError {
     }
 }
 
+/// How to connect to a share beyond its host and name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SmbOptions {
+    pub username: String,
+    /// AD / workgroup domain, sent as `DOMAIN;user`.
+    pub domain: Option<String>,
+    /// Non-standard SMB port; `None` uses 445.
+    pub port: Option<u16>,
+    /// Guest login: no username or password is sent.
+    pub guest: bool,
+    /// `mount_smbfs -o` options. Finder cannot apply these, so shares that
+    /// set any are mounted with `mount_smbfs` directly.
+    pub mount_options: Vec<String>,
+    /// The mount point is a directory Mountaineer owns (a per-share
+    /// `mount_point`) rather than `/Volumes/<share>`. Finder always mounts under
+    /// `/Volumes`, so it is skipped, and a copy mounted elsewhere is reported
+    /// instead of adopted.
+    pub own_mount_point: bool,
+}
+
+/// Mount `//user@host/share` at `mount_point`.
+///
+/// With `password`, credentials are sent to osascript and `mount_smbfs` on
+/// stdin so they never appear in argv; without it, Finder and `mount_smbfs`
+/// fall back to the Keychain. Guest shares never send a password.
 pub fn mount_share(
     host: &str,
     share: &str,
-    username: &str,
+    options: &SmbOptions,
     mount_point: &Path,
+    password: Option<&str>,
 ) -> Result<(), MountError> {
     if let Some(existing_mount) = find_existing_mount_for_share(host, share) {
-        adopt_existing_mount(mount_point, &existing_mount)?;
+        adopt_existing_mount(mount_point, &existing_mount, options.own_mount_point)?;
         return Ok(());
     }
 
+    let password = password.filter(|_| !options.guest);
+
     // Prefer Finder-backed AppleScript mount for less disruptive UX.
     // If it fails or doesn't yield a detectable mount entry, fall back to mount_smbfs.
-    let osascript_error = match try_osascript_mount(host, share, username) {
-        Ok(()) => {
-            if let Some(existing_mount) =
-                wait_for_existing_mount_for_share(host, share, Duration::from_secs(2))
-            {
-                adopt_existing_mount(mount_point, &existing_mount)?;
-                return Ok(());
+    let osascript_error = if options.mount_options.is_empty() && !options.own_mount_point {
+        match try_osascript_mount(host, share, options, password) {
+            Ok(()) => {
+                if let Some(existing_mount) =
+                    wait_for_existing_mount_for_share(host, share, Duration::from_secs(2))
+                {
+                    adopt_existing_mount(mount_point, &existing_mount, false)?;
+                    return Ok(());
+                }
+                Some("osascript mount returned success but no detectable share path".to_string())
             }
-            Some("osascript mount returned success but no detectable share path".to_string())
+            Err(err) => Some(format!("osascript mount failed: {}", err)),
         }
-        Err(err) => Some(format!("osascript mount failed: {}", err)),
+    } else {
+        None
     };
 
     ensure_mount_point_dir(mount_point)?;
 
-    let url = build_smb_url(host, share, username);
-    let output = Command::new("mount_smbfs")
-        .arg(&url)
-        .arg(mount_point)
-        .output()
-        .map_err(|source| MountError::CommandSpawn {
-            command: "mount_smbfs".to_string(),
-            source,
-        })?;
+    let args = mount_smbfs_args(host, share, options, mount_point);
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let output = match password {
+        // run_with_input_timeout detaches mount_smbfs from the tty, so its
+        // password prompt reads stdin.
+        Some(password) => runner::run_with_input_timeout(
+            "mount_smbfs",
+            &args,
+            &format!("{}\n", password),
+            PASSWORD_MOUNT_TIMEOUT,
+        ),
+        None => runner::run("mount_smbfs", &args),
+    }
+    .map_err(|source| MountError::CommandSpawn {
+        command: "mount_smbfs".to_string(),
+        source,
+    })?;
 
-    if output.status.success() {
+    if output.success() {
         return Ok(());
     }
 
-    let original_stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-    let original_exit = output.status.code();
+    let original_stderr = output.stderr.trim().to_string();
+    let original_exit = output.code;
 
     // If Finder mounted the share elsewhere, adopt that mount path.
     if let Some(existing_mount) = find_existing_mount_for_share(host, share) {
-        adopt_existing_mount(mount_point, &existing_mount)?;
+        adopt_existing_mount(mount_point, &existing_mount, options.own_mount_point)?;
         return Ok(());
     }
 
-    let mut combined = osascript_error.unwrap_or_else(|| "osascript mount failed".to_string());
-    combined.push_str("; mount_smbfs fallback failed: ");
+    let mut combined = match osascript_error {
+        Some(err) => format!("{}; mount_smbfs fallback failed: ", err),
+        None => "mount_smbfs failed: ".to_string(),
+    };
     combined.push_str(&original_stderr);
-    Err(MountError::MountFailed {
-        stderr: combined,
-        exit_code: original_exit,
+    Err(MountError::mount_failed(combined, original_exit))
+}
+
+/// `//[DOMAIN;]user@host[:port]/share`, with each component percent-encoded.
+///
+/// Used as-is for `mount_smbfs` and behind `smb:` for Finder.
+fn build_smb_url(host: &str, share: &str, options: &SmbOptions) -> String {
+    format!("{}/{}", build_server_url(host, options), url_escape(share))
+}
+
+/// `//[DOMAIN;]user@host[:port]`: the server part of [`build_smb_url`], as
+/// `smbutil view` takes it.
+pub fn build_server_url(host: &str, options: &SmbOptions) -> String {
+    let authority = net_host::url_authority(host, options.port);
+    match user_info(options) {
+        Some(user) => format!("//{}@{}", user, authority),
+        None => format!("//{}", authority),
+    }
+}
+
+/// The userinfo part of the URL: `guest:` for guest logins, otherwise the
+/// escaped `DOMAIN;user`, or nothing when there is no username.
+fn user_info(options: &SmbOptions) -> Option<String> {
+    if options.guest {
+        return Some("guest:".to_string());
+    }
+    let username = options.username.trim();
+    if username.is_empty() {
+        return None;
+    }
+    Some(match options.domain.as_deref().map(str::trim) {
+        Some(domain) if !domain.is_empty() => {
+            format!("{};{}", url_escape(domain), url_escape(username))
+        }
+        _ => url_escape(username),
     })
 }
 
-fn build_smb_url(host: &str, share: &str, username: &str) -> String {
-    if username.trim().is_empty() {
-        format!("//{}/{}", host, share)
-    } else {
-        format!("//{}@{}/{}", username, host, share)
+/// The account name as Finder's `as user name` expects it (unescaped).
+fn login_name(options: &SmbOptions) -> String {
+    match options.domain.as_deref().map(str::trim) {
+        Some(domain) if !domain.is_empty() => format!("{};{}", domain, options.username.trim()),
+        _ => options.username.trim().to_string(),
+    }
+}
+
+fn mount_smbfs_args(
+    host: &str,
+    share: &str,
+    options: &SmbOptions,
+    mount_point: &Path,
+) -> Vec<String> {
+    let mut args = Vec::new();
+    if options.guest {
+        // -N: do not prompt for a password.
+        args.push("-N".to_string());
+    }
+    if !options.mount_options.is_empty() {
+        args.push("-o".to_string());
+        args.push(options.mount_options.join(","));
     }
+    args.push(build_smb_url(host, share, options));
+    args.push(mount_point.to_string_lossy().into_owned());
+    args
+}
+
+/// Percent-encode everything but RFC 3986 unreserved characters.
+fn url_escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
+/// Decode `%XX` sequences, as `mount` prints share names with special characters.
+fn url_unescape(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(hex) = value.get(i + 1..i + 3)
+            && let Ok(byte) = u8::from_str_radix(hex, 16)
+        {
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
 }
 
 pub fn is_mount_alive(mount_point: &Path) -> bool {
-    let (tx, rx) = std::sync::mpsc::channel();
-    let path = mount_point.to_path_buf();
+    runner::alive(mount_point, || {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let path = mount_point.to_path_buf();
 
-    std::thread::spawn(move || {
-        let _ = tx.send(std::fs::metadata(&path).is_ok());
-    });
+        std::thread::spawn(move || {
+            let _ = tx.send(std::fs::metadata(&path).is_ok());
+        });
 
-    rx.recv_timeout(std::time::Duration::from_secs(2))
-        .unwrap_or(false)
+        rx.recv_timeout(std::time::Duration::from_secs(2))
+            .unwrap_or(false)
+    })
 }
 
 pub fn is_mounted(mount_point: &Path) -> bool {
-    let output = match Command::new("mount").args(["-t", "smbfs"]).output() {
+    let output = match runner::run("mount", &["-t", "smbfs"]) {
         Ok(output) => output,
         Err(_) => return false,
     };
 
-    if !output.status.success() {
+    if !output.success() {
         return false;
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stdout = output.stdout;
     let adopted_target = resolve_symlink_target(mount_point);
 
     stdout.lines().any(|line| {
-        let Some((_host, _share, current_mount)) = parse_mount_smb_line(line) else {
+        let Some(SmbMount {
+            mount_path: current_mount,
+            ..
+        }) = parse_mount_smb_line(line)
+        else {
             return false;
         };
 
@@ -171,45 +457,37 @@ pub fn unmount_graceful(mount_point: &Path) -> Result<(), MountError> {
 
 fn unmount_impl(mount_point: &Path, force: bool) -> Result<(), MountError> {
     let unmount_target = resolve_symlink_target(mount_point).unwrap_or_else(|| mount_point.into());
+    let target = unmount_target.to_string_lossy();
 
     let diskutil = if force {
-        Command::new("diskutil")
-            .args(["unmount", "force"])
-            .arg(&unmount_target)
-            .output()
+        runner::run("diskutil", &["unmount", "force", &target])
     } else {
-        Command::new("diskutil")
-            .arg("unmount")
-            .arg(&unmount_target)
-            .output()
+        runner::run("diskutil", &["unmount", &target])
     }
     .map_err(|source| MountError::CommandSpawn {
         command: "diskutil".to_string(),
         source,
     })?;
 
-    if diskutil.status.success() {
+    if diskutil.success() {
         return Ok(());
     }
 
-    let diskutil_err = String::from_utf8_lossy(&diskutil.stderr).trim().to_string();
+    let diskutil_err = diskutil.stderr.trim().to_string();
     let umount = if force {
-        Command::new("umount")
-            .arg("-f")
-            .arg(&unmount_target)
-            .output()
+        runner::run("umount", &["-f", &target])
     } else {
-        Command::new("umount").arg(&unmount_target).output()
+        runner::run("umount", &[&target])
     }
     .map_err(|source| MountError::CommandSpawn {
         command: "umount".to_string(),
         source,
     })?;
 
-    if umount.status.success() {
+    if umount.success() {
         Ok(())
     } else {
-        let umount_err = String::from_utf8_lossy(&umount.stderr).trim().to_string();
+        let umount_err = umount.stderr.trim().to_string();
         let mode = if force { "force" } else { "graceful" };
         Err(MountError::UnmountFailed {
             stderr: format!(
@@ -231,30 +509,50 @@ fn ensure_mount_point_dir(mount_point: &Path) -> Result<(), MountError> {
     })
 }
 
-fn try_osascript_mount(host: &str, share: &str, username: &str) -> Result<(), String> {
-    let smb_url = if username.trim().is_empty() {
-        format!("smb://{}/{}", host, share)
-    } else {
-        format!("smb://{}@{}/{}", username, host, share)
-    };
-    let script = format!(
-        r#"tell application "Finder"
+fn try_osascript_mount(
+    host: &str,
+    share: &str,
+    options: &SmbOptions,
+    password: Option<&str>,
+) -> Result<(), String> {
+    let smb_url = applescript_escape(&format!("smb:{}", build_smb_url(host, share, options)));
+
+    let output = match password {
+        None => {
+            let script = format!(
+                r#"tell application "Finder"
 mount volume "{}"
 end tell"#,
-        smb_url
-    );
-
-    let output = Command::new("osascript")
-        .args(["-e", &script])
-        .output()
-        .map_err(|err| format!("failed to run osascript: {}", err))?;
-    if output.status.success() {
+                smb_url
+            );
+            runner::run("osascript", &["-e", &script])
+        }
+        Some(password) => {
+            // The script carries the password, so feed it on stdin ("-").
+            let script = format!(
+                r#"tell application "Finder"
+mount volume "{}" as user name "{}" with password "{}"
+end tell"#,
+                smb_url,
+                applescript_escape(&login_name(options)),
+                applescript_escape(password)
+            );
+            runner::run_with_input_timeout("osascript", &["-"], &script, PASSWORD_MOUNT_TIMEOUT)
+        }
+    }
+    .map_err(|err| format!("failed to run osascript: {}", err))?;
+    if output.success() {
         Ok(())
     } else {
-        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+        Err(output.stderr.trim().to_string())
     }
 }
 
+/// Escape `\` and `"` for use inside an AppleScript string literal.
+fn applescript_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
 fn wait_for_existing_mount_for_share(
     host: &str,
     share: &str,
@@ -272,10 +570,35 @@ fn wait_for_existing_mount_for_share(
     }
 }
 
-fn adopt_existing_mount(mount_point: &Path, existing_mount: &Path) -> Result<(), MountError> {
+/// Take over a mount made elsewhere (e.g. by Finder) as `mount_point`,
+/// without unmounting it.
+pub fn adopt_mount_point(mount_point: &Path, existing_mount: &Path) -> Result<(), MountError> {
+    adopt_existing_mount(mount_point, existing_mount, false)
+}
+
+/// Point `mount_point` at a mount of the same share found in the mount table.
+///
+/// An owned mount point is never replaced with a symlink: a copy mounted
+/// anywhere else is reported as `AlreadyMounted` so the stray can be removed.
+fn adopt_existing_mount(
+    mount_point: &Path,
+    existing_mount: &Path,
+    owned: bool,
+) -> Result<(), MountError> {
     if paths_match(mount_point, existing_mount) {
         return Ok(());
     }
+    if owned {
+        return Err(MountError::MountFailed {
+            stderr: format!(
+                "share is already mounted at {}, not at its mount_point {}",
+                existing_mount.display(),
+                mount_point.display()
+            ),
+            exit_code: None,
+            kind: MountFailureKind::AlreadyMounted,
+        });
+    }
 
     if let Some(parent) = mount_point.parent() {
         fs::create_dir_all(parent).map_err(|source| MountError::CreateMountPoint {
@@ -291,81 +614,112 @@ fn adopt_existing_mount(mount_point: &Path, existing_mount: &Path) -> Result<(),
             {
                 return Ok(());
             }
-            fs::remove_file(mount_point).map_err(|err| MountError::MountFailed {
-                stderr: format!(
-                    "failed clearing stale mountpoint symlink {}: {}",
-                    mount_point.display(),
-                    err
-                ),
-                exit_code: None,
+            fs::remove_file(mount_point).map_err(|err| {
+                MountError::mount_failed(
+                    format!(
+                        "failed clearing stale mountpoint symlink {}: {}",
+                        mount_point.display(),
+                        err
+                    ),
+                    None,
+                )
             })?;
         } else if meta.file_type().is_dir() {
             if paths_match(mount_point, existing_mount) {
                 return Ok(());
             }
-            fs::remove_dir(mount_point).map_err(|err| MountError::MountFailed {
-                stderr: format!(
-                    "failed clearing mountpoint directory {} before adopt: {}",
-                    mount_point.display(),
-                    err
-                ),
-                exit_code: None,
+            fs::remove_dir(mount_point).map_err(|err| {
+                MountError::mount_failed(
+                    format!(
+                        "failed clearing mountpoint directory {} before adopt: {}",
+                        mount_point.display(),
+                        err
+                    ),
+                    None,
+                )
             })?;
         } else {
-            fs::remove_file(mount_point).map_err(|err| MountError::MountFailed {
-                stderr: format!(
-                    "failed clearing mountpoint file {} before adopt: {}",
-                    mount_point.display(),
-                    err
-                ),
-                exit_code: None,
+            fs::remove_file(mount_point).map_err(|err| {
+                MountError::mount_failed(
+                    format!(
+                        "failed clearing mountpoint file {} before adopt: {}",
+                        mount_point.display(),
+                        err
+                    ),
+                    None,
+                )
             })?;
         }
     }
 
     std::os::unix::fs::symlink(existing_mount, mount_point).map_err(|err| {
-        MountError::MountFailed {
-            stderr: format!(
+        MountError::mount_failed(
+            format!(
                 "failed adopting existing mount {} -> {}: {}",
                 mount_point.display(),
                 existing_mount.display(),
                 err
             ),
-            exit_code: None,
-        }
+            None,
+        )
     })?;
 
     Ok(())
 }
 
-fn find_existing_mount_for_share(host: &str, share: &str) -> Option<PathBuf> {
-    let output = Command::new("mount").args(["-t", "smbfs"]).output().ok()?;
-    if !output.status.success() {
-        return None;
-    }
+/// One `smbfs` entry from the mount table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmbMount {
+    /// Server host in canonical form (see `network::host::display`).
+    pub host: String,
+    /// Remote share name, percent-decoded.
+    pub share: String,
+    /// Account from the URL (`DOMAIN;user` when a domain was used), if any.
+    pub user: Option<String>,
+    pub mount_path: PathBuf,
+}
 
-    String::from_utf8_lossy(&output.stdout)
+/// All SMB mounts currently in the mount table, whoever made them.
+pub fn list_mounts() -> Vec<SmbMount> {
+    let output = match runner::run("mount", &["-t", "smbfs"]) {
+        Ok(output) if output.success() => output,
+        _ => return Vec::new(),
+    };
+    output
+        .stdout
         .lines()
         .filter_map(parse_mount_smb_line)
-        .find(|(current_host, current_share, _)| {
-            current_host.eq_ignore_ascii_case(host) && current_share.eq_ignore_ascii_case(share)
-        })
-        .map(|(_, _, mount_path)| mount_path)
+        .collect()
 }
 
-fn parse_mount_smb_line(line: &str) -> Option<(String, String, PathBuf)> {
+fn find_existing_mount_for_share(host: &str, share: &str) -> Option<PathBuf> {
+    list_mounts()
+        .into_iter()
+        .find(|mount| net_host::same(&mount.host, host) && mount.share.eq_ignore_ascii_case(share))
+        .map(|mount| mount.mount_path)
+}
+
+fn parse_mount_smb_line(line: &str) -> Option<SmbMount> {
     let (left, right) = line.split_once(" on ")?;
     let (mount_path, _flags) = right.split_once(" (")?;
 
     let smb = left.strip_prefix("//")?;
-    let smb = smb.split_once('@').map_or(smb, |(_, rest)| rest);
-    let (host, share) = smb.split_once('/')?;
+    let (user, smb) = match smb.split_once('@') {
+        Some((user, rest)) => {
+            // Drop any `:password` part; `guest:` leaves just the account.
+            let user = user.split_once(':').map_or(user, |(name, _)| name);
+            (Some(url_unescape(user)).filter(|u| !u.is_empty()), rest)
+        }
+        None => (None, smb),
+    };
+    let (authority, share) = smb.split_once('/')?;
 
-    Some((
-        host.to_string(),
-        share.to_string(),
-        PathBuf::from(mount_path),
-    ))
+    Some(SmbMount {
+        host: net_host::display(net_host::strip_port(authority)),
+        share: url_unescape(share),
+        user,
+        mount_path: PathBuf::from(mount_path),
+    })
 }
 
 fn resolve_symlink_target(path: &Path) -> Option<PathBuf> {
@@ -390,23 +744,232 @@ fn paths_match(a: &Path, b: &Path) -> bool {
 mod tests {
     use super::*;
 
+    fn user(username: &str) -> SmbOptions {
+        SmbOptions {
+            username: username.to_string(),
+            ..SmbOptions::default()
+        }
+    }
+
     #[test]
     fn build_url_with_user() {
         assert_eq!(
-            build_smb_url("server.local", "CORE", "user"),
+            build_smb_url("server.local", "CORE", &user("user")),
             "//user@server.local/CORE"
         );
     }
 
     #[test]
     fn build_url_without_user() {
-        assert_eq!(build_smb_url("10.10.10.1", "CORE", ""), "//10.10.10.1/CORE");
+        assert_eq!(
+            build_smb_url("10.10.10.1", "CORE", &user("")),
+            "//10.10.10.1/CORE"
+        );
+    }
+
+    #[test]
+    fn build_url_with_domain_and_port() {
+        let options = SmbOptions {
+            domain: Some("CORP".to_string()),
+            port: Some(1445),
+            ..user("alice")
+        };
+        assert_eq!(
+            build_smb_url("nas.local", "CORE", &options),
+            "//CORP;alice@nas.local:1445/CORE"
+        );
+    }
+
+    #[test]
+    fn build_server_url_matches_mount_url() {
+        let options = SmbOptions {
+            domain: Some("CORP".to_string()),
+            port: Some(1445),
+            ..user("j.doe@corp")
+        };
+        assert_eq!(
+            build_server_url("fe80::1%en0", &options),
+            "//CORP;j.doe%40corp@[fe80::1%25en0]:1445"
+        );
+        assert!(
+            build_smb_url("fe80::1%en0", "CORE", &options)
+                .starts_with(&build_server_url("fe80::1%en0", &options))
+        );
+    }
+
+    #[test]
+    fn build_url_ignores_blank_domain() {
+        let options = SmbOptions {
+            domain: Some("  ".to_string()),
+            ..user("alice")
+        };
+        assert_eq!(
+            build_smb_url("nas.local", "CORE", &options),
+            "//alice@nas.local/CORE"
+        );
+    }
+
+    #[test]
+    fn build_url_guest_ignores_username_and_domain() {
+        let options = SmbOptions {
+            guest: true,
+            domain: Some("CORP".to_string()),
+            port: Some(139),
+            ..user("alice")
+        };
+        assert_eq!(
+            build_smb_url("nas.local", "Public", &options),
+            "//guest:@nas.local:139/Public"
+        );
+    }
+
+    #[test]
+    fn build_url_escapes_user_domain_and_share() {
+        let options = SmbOptions {
+            domain: Some("R&D;EU".to_string()),
+            ..user("j.doe@corp")
+        };
+        assert_eq!(
+            build_smb_url("nas.local", "Team Files/#1", &options),
+            "//R%26D%3BEU;j.doe%40corp@nas.local/Team%20Files%2F%231"
+        );
+    }
+
+    #[test]
+    fn url_escape_roundtrips_through_unescape() {
+        for value in ["CORE", "Team Files", "a%b", "100%", "Ünïcode", "x;y@z:w"] {
+            assert_eq!(url_unescape(&url_escape(value)), value);
+        }
+        assert_eq!(url_unescape("bad%zzescape%"), "bad%zzescape%");
+    }
+
+    #[test]
+    fn mount_smbfs_args_for_every_option_combination() {
+        let mount_point = Path::new("/Volumes/CORE");
+        for guest in [false, true] {
+            for domain in [None, Some("CORP")] {
+                for port in [None, Some(1445u16)] {
+                    for mount_options in [vec![], vec!["nobrowse"], vec!["soft", "rdonly"]] {
+                        let options = SmbOptions {
+                            username: "alice".to_string(),
+                            domain: domain.map(str::to_string),
+                            port,
+                            guest,
+                            mount_options: mount_options.iter().map(|o| o.to_string()).collect(),
+                            ..SmbOptions::default()
+                        };
+
+                        let mut expected: Vec<String> = Vec::new();
+                        if guest {
+                            expected.push("-N".to_string());
+                        }
+                        if !mount_options.is_empty() {
+                            expected.push("-o".to_string());
+                            expected.push(mount_options.join(","));
+                        }
+                        let user = match (guest, domain) {
+                            (true, _) => "guest:".to_string(),
+                            (false, Some(domain)) => format!("{};alice", domain),
+                            (false, None) => "alice".to_string(),
+                        };
+                        let authority = match port {
+                            Some(port) => format!("nas.local:{}", port),
+                            None => "nas.local".to_string(),
+                        };
+                        expected.push(format!("//{}@{}/CORE", user, authority));
+                        expected.push("/Volumes/CORE".to_string());
+
+                        assert_eq!(
+                            mount_smbfs_args("nas.local", "CORE", &options, mount_point),
+                            expected,
+                            "{:?}",
+                            options
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn login_name_includes_domain() {
+        assert_eq!(login_name(&user("alice")), "alice");
+        let options = SmbOptions {
+            domain: Some("CORP".to_string()),
+            ..user("alice")
+        };
+        assert_eq!(login_name(&options), "CORP;alice");
+    }
+
+    #[test]
+    fn build_url_brackets_ipv6_and_encodes_zone() {
+        assert_eq!(
+            build_smb_url("fe80::1%bridge0", "CORE", &user("admin")),
+            "//admin@[fe80::1%25bridge0]/CORE"
+        );
+        let options = SmbOptions {
+            port: Some(1445),
+            ..user("admin")
+        };
+        assert_eq!(
+            build_smb_url("[2001:db8::1]", "CORE", &options),
+            "//admin@[2001:db8::1]:1445/CORE"
+        );
+    }
+
+    #[test]
+    fn parse_mount_smb_line_normalizes_ipv6_and_port() {
+        for line in [
+            "//admin@[fe80::1%25bridge0]/CORE on /Volumes/CORE (smbfs, nodev)",
+            "//admin@fe80::1%bridge0/CORE on /Volumes/CORE (smbfs, nodev)",
+            "//admin@[FE80::1%bridge0]:445/CORE on /Volumes/CORE (smbfs, nodev)",
+        ] {
+            let SmbMount { host, share, .. } = parse_mount_smb_line(line).unwrap();
+            assert_eq!(host, "fe80::1%bridge0", "{}", line);
+            assert_eq!(share, "CORE");
+        }
+        let SmbMount { host, .. } =
+            parse_mount_smb_line("//a@nas.local:1445/CORE on /Volumes/CORE (smbfs)").unwrap();
+        assert_eq!(host, "nas.local");
+    }
+
+    #[test]
+    fn parse_mount_smb_line_reads_user() {
+        let user = |line: &str| parse_mount_smb_line(line).unwrap().user;
+        assert_eq!(
+            user("//CORP;j%20doe@nas.local/CORE on /Volumes/CORE (smbfs)").as_deref(),
+            Some("CORP;j doe")
+        );
+        assert_eq!(
+            user("//GUEST:@nas.local/Public on /Volumes/Public (smbfs)").as_deref(),
+            Some("GUEST")
+        );
+        assert_eq!(user("//nas.local/CORE on /Volumes/CORE (smbfs)"), None);
+    }
+
+    #[test]
+    fn parse_mount_smb_line_decodes_share_name() {
+        let line = "//alice@nas.local/Team%20Files on /Volumes/Team Files (smbfs, nodev)";
+        let SmbMount {
+            host,
+            share,
+            mount_path,
+            ..
+        } = parse_mount_smb_line(line).unwrap();
+        assert_eq!(host, "nas.local");
+        assert_eq!(share, "Team Files");
+        assert_eq!(mount_path, PathBuf::from("/Volumes/Team Files"));
     }
 
     #[test]
     fn parse_mount_smb_line_with_user_prefix() {
         let line = "//user@10.10.10.1/CORE on /Volumes/CORE (smbfs, nodev)";
-        let (host, share, mount_path) = parse_mount_smb_line(line).unwrap();
+        let SmbMount {
+            host,
+            share,
+            mount_path,
+            ..
+        } = parse_mount_smb_line(line).unwrap();
         assert_eq!(host, "10.10.10.1");
         assert_eq!(share, "CORE");
         assert_eq!(mount_path, PathBuf::from("/Volumes/CORE"));
@@ -415,9 +978,279 @@ mod tests {
     #[test]
     fn parse_mount_smb_line_without_user_prefix() {
         let line = "//macmini.local/VAULT-R1 on /tmp/vault (smbfs, nodev)";
-        let (host, share, mount_path) = parse_mount_smb_line(line).unwrap();
+        let SmbMount {
+            host,
+            share,
+            mount_path,
+            ..
+        } = parse_mount_smb_line(line).unwrap();
         assert_eq!(host, "macmini.local");
         assert_eq!(share, "VAULT-R1");
         assert_eq!(mount_path, PathBuf::from("/tmp/vault"));
     }
+
+    #[test]
+    fn classify_auth_failure() {
+        let msg = "mount_smbfs failed (exit 77): mount_smbfs: server rejected the connection: Authentication error";
+        assert_eq!(
+            MountFailureKind::classify(msg),
+            MountFailureKind::AuthFailed
+        );
+    }
+
+    #[test]
+    fn classify_share_not_found() {
+        let msg = "mount_smbfs failed (exit 68): STATUS_BAD_NETWORK_NAME";
+        assert_eq!(
+            MountFailureKind::classify(msg),
+            MountFailureKind::ShareNotFound
+        );
+    }
+
+    #[test]
+    fn classify_host_unreachable() {
+        let msg = "mount_smbfs failed (exit 64): server connection failed: No route to host";
+        assert_eq!(
+            MountFailureKind::classify(msg),
+            MountFailureKind::HostUnreachable
+        );
+    }
+
+    #[test]
+    fn classify_timeout() {
+        let msg = "mount_smbfs failed: Operation timed out";
+        assert_eq!(MountFailureKind::classify(msg), MountFailureKind::Timeout);
+    }
+
+    #[test]
+    fn classify_permission_denied() {
+        let msg = "mount_smbfs failed (exit 13): Permission denied";
+        assert_eq!(
+            MountFailureKind::classify(msg),
+            MountFailureKind::PermissionDenied
+        );
+    }
+
+    #[test]
+    fn classify_already_mounted_without_finder_context() {
+        assert_eq!(
+            MountFailureKind::classify("mount_smbfs: File exists"),
+            MountFailureKind::AlreadyMounted
+        );
+    }
+
+    #[test]
+    fn classify_finder_collision_wins_over_already_mounted() {
+        let msg = "//u@macmini.local/CORE: File exists; osascript fallback mounted no detectable share path";
+        assert_eq!(
+            MountFailureKind::classify(msg),
+            MountFailureKind::FinderCollision
+        );
+    }
+
+    #[test]
+    fn classify_unknown_for_unrecognised_output() {
+        assert_eq!(
+            MountFailureKind::classify("something odd"),
+            MountFailureKind::Unknown
+        );
+        assert_eq!(MountFailureKind::classify(""), MountFailureKind::Unknown);
+    }
+
+    #[test]
+    fn mount_failed_error_carries_kind() {
+        let err = MountError::mount_failed("Authentication error".to_string(), Some(77));
+        assert_eq!(err.kind(), MountFailureKind::AuthFailed);
+        assert_eq!(
+            MountError::UnmountFailed {
+                stderr: "busy".to_string()
+            }
+            .kind(),
+            MountFailureKind::Unknown
+        );
+    }
+
+    #[test]
+    fn failure_kind_serializes_snake_case() {
+        let json = serde_json::to_string(&MountFailureKind::AuthFailed).unwrap();
+        assert_eq!(json, "\"auth_failed\"");
+    }
+
+    #[test]
+    fn applescript_escape_quotes_and_backslashes() {
+        assert_eq!(applescript_escape(r#"pa"ss\word"#), r#"pa\"ss\\word"#);
+    }
+
+    #[test]
+    fn mount_with_password_keeps_it_out_of_argv() {
+        use crate::runner::{CommandOutput, Interaction};
+
+        let command = |program: &str, args: &[&str], stdout: &str| Interaction::Command {
+            program: program.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            output: Some(CommandOutput {
+                code: Some(0),
+                stdout: stdout.to_string(),
+                stderr: String::new(),
+            }),
+            error: None,
+        };
+        // Replay only matches these exact argv lists, so a password passed as
+        // an argument would show up as a miss.
+        let guard = runner::replay::install(vec![
+            command("mount", &["-t", "smbfs"], ""),
+            command("osascript", &["-"], ""),
+            command(
+                "mount",
+                &["-t", "smbfs"],
+                "//admin@nas.local/CORE on /Volumes/CORE (smbfs, nodev)\n",
+            ),
+        ]);
+
+        mount_share(
+            "nas.local",
+            "CORE",
+            &user("admin"),
+            Path::new("/Volumes/CORE"),
+            Some("s3cret"),
+        )
+        .unwrap();
+
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        assert_eq!(guard.unconsumed(), 0);
+    }
+
+    fn ok(program: &str, args: &[&str], stdout: &str) -> runner::Interaction {
+        runner::Interaction::Command {
+            program: program.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            output: Some(runner::CommandOutput {
+                code: Some(0),
+                stdout: stdout.to_string(),
+                stderr: String::new(),
+            }),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn osascript_url_carries_domain_port_and_escaping() {
+        let options = SmbOptions {
+            domain: Some("CORP".to_string()),
+            port: Some(1445),
+            ..user("j doe")
+        };
+        let script = "tell application \"Finder\"\nmount volume \"smb://CORP;j%20doe@nas.local:1445/Team%20Files\"\nend tell";
+        let guard = runner::replay::install(vec![ok("osascript", &["-e", script], "")]);
+
+        try_osascript_mount("nas.local", "Team Files", &options, None).unwrap();
+
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+    }
+
+    #[test]
+    fn mount_options_skip_finder_and_guest_sends_no_password() {
+        let options = SmbOptions {
+            guest: true,
+            mount_options: vec!["nobrowse".to_string(), "rdonly".to_string()],
+            ..user("admin")
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let mount_point = dir.path().join("Public");
+        let mount_point_arg = mount_point.to_string_lossy().into_owned();
+        // No osascript entry: Finder cannot apply -o options, so calling it
+        // would be recorded as a miss.
+        let guard = runner::replay::install(vec![
+            ok("mount", &["-t", "smbfs"], ""),
+            ok(
+                "mount_smbfs",
+                &[
+                    "-N",
+                    "-o",
+                    "nobrowse,rdonly",
+                    "//guest:@nas.local/Public",
+                    &mount_point_arg,
+                ],
+                "",
+            ),
+        ]);
+
+        mount_share(
+            "nas.local",
+            "Public",
+            &options,
+            &mount_point,
+            Some("ignored"),
+        )
+        .unwrap();
+
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        assert_eq!(guard.unconsumed(), 0);
+    }
+
+    #[test]
+    fn owned_mount_point_is_created_and_mounted_without_finder() {
+        let dir = tempfile::tempdir().unwrap();
+        let mount_point = dir.path().join("mnt").join("CORE");
+        let mount_point_arg = mount_point.to_string_lossy().into_owned();
+        let options = SmbOptions {
+            own_mount_point: true,
+            ..user("admin")
+        };
+        let guard = runner::replay::install(vec![
+            ok("mount", &["-t", "smbfs"], ""),
+            ok(
+                "mount_smbfs",
+                &["//admin@nas.local/CORE", &mount_point_arg],
+                "",
+            ),
+        ]);
+
+        mount_share("nas.local", "CORE", &options, &mount_point, None).unwrap();
+
+        assert!(mount_point.is_dir());
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        assert_eq!(guard.unconsumed(), 0);
+    }
+
+    #[test]
+    fn owned_mount_point_reports_copy_mounted_elsewhere() {
+        let dir = tempfile::tempdir().unwrap();
+        let mount_point = dir.path().join("CORE");
+        let options = SmbOptions {
+            own_mount_point: true,
+            ..user("admin")
+        };
+        let _guard = runner::replay::install(vec![ok(
+            "mount",
+            &["-t", "smbfs"],
+            "//admin@nas.local/CORE on /Volumes/CORE-1 (smbfs, nodev)\n",
+        )]);
+
+        let err = mount_share("nas.local", "CORE", &options, &mount_point, None).unwrap_err();
+
+        assert_eq!(err.kind(), MountFailureKind::AlreadyMounted);
+        assert!(err.to_string().contains("/Volumes/CORE-1"));
+        assert!(fs::symlink_metadata(&mount_point).is_err());
+    }
+
+    #[test]
+    fn owned_mount_point_accepts_existing_mount_there() {
+        let dir = tempfile::tempdir().unwrap();
+        let mount_point = dir.path().join("CORE");
+        let options = SmbOptions {
+            own_mount_point: true,
+            ..user("admin")
+        };
+        let listing = format!(
+            "//admin@nas.local/CORE on {} (smbfs, nodev)\n",
+            mount_point.display()
+        );
+        let guard = runner::replay::install(vec![ok("mount", &["-t", "smbfs"], &listing)]);
+
+        mount_share("nas.local", "CORE", &options, &mount_point, None).unwrap();
+
+        assert!(guard.misses().is_empty(), "misses: {:?}", guard.misses());
+        assert_eq!(guard.unconsumed(), 0);
+    }
 }
@@ -0,0 +1,252 @@
+//! SMB server host strings as written in config.toml.
+//!
+//! A host is a DNS name, an IPv4 address, or an IPv6 literal. IPv6 may carry a
+//! zone (`fe80::1%bridge0`, the Thunderbolt bridge's link-local address) and
+//! may be bracketed (`[fe80::1%bridge0]`). Ports are configured separately, so
+//! a host never carries one.
+
+use std::net::{Ipv6Addr, SocketAddr, SocketAddrV6, ToSocketAddrs};
+
+/// An IPv6 literal split into address and optional zone (interface name or
+/// numeric scope id).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ipv6Host {
+    pub addr: Ipv6Addr,
+    pub zone: Option<String>,
+}
+
+/// Parse `host` as an IPv6 literal, bracketed or not. `%25` (the URL form of
+/// the zone separator) is accepted as well as `%`.
+pub fn parse_ipv6(host: &str) -> Option<Ipv6Host> {
+    let host = unbracket(host);
+    let (addr, zone) = match host.split_once('%') {
+        Some((addr, zone)) => {
+            // `%25` is the URL form; a bare numeric zone like `%251` stays as is.
+            let zone = zone
+                .strip_prefix("25")
+                .filter(|z| !z.is_empty() && !z.bytes().all(|b| b.is_ascii_digit()))
+                .unwrap_or(zone);
+            (addr, Some(zone))
+        }
+        None => (host, None),
+    };
+    if zone.is_some_and(|z| z.is_empty() || z.contains(['%', '/', ']'])) {
+        return None;
+    }
+    Some(Ipv6Host {
+        addr: addr.parse().ok()?,
+        zone: zone.map(str::to_string),
+    })
+}
+
+/// Reject strings that are neither a name, IPv4 nor a valid IPv6 literal,
+/// such as `nas.local:445` or a malformed address.
+pub fn validate(host: &str) -> Result<(), String> {
+    let trimmed = host.trim();
+    if trimmed.starts_with('[') || trimmed.contains(':') || trimmed.contains('%') {
+        if parse_ipv6(trimmed).is_none() {
+            return Err(format!(
+                "'{}' is not a valid IPv6 address (set `port` separately instead of host:port)",
+                host
+            ));
+        }
+    } else if trimmed.contains(['/', '@', ' ']) {
+        return Err(format!("'{}' is not a valid host name", host));
+    }
+    Ok(())
+}
+
+/// Canonical, unbracketed form for status output and comparisons:
+/// `fe80::1%bridge0`, `10.0.0.1`, `nas.local`.
+pub fn display(host: &str) -> String {
+    match parse_ipv6(host) {
+        Some(v6) => match v6.zone {
+            Some(zone) => format!("{}%{}", v6.addr, zone),
+            None => v6.addr.to_string(),
+        },
+        None => unbracket(host).to_string(),
+    }
+}
+
+/// Whether two host strings name the same server, ignoring case, brackets and
+/// IPv6 spelling (`FE80:0::1` vs `fe80::1`).
+pub fn same(a: &str, b: &str) -> bool {
+    display(a).eq_ignore_ascii_case(&display(b))
+}
+
+/// `host:port` for probes and logs, bracketing IPv6 (`[fe80::1%bridge0]:445`).
+pub fn socket_addr_string(host: &str, port: u16) -> String {
+    if parse_ipv6(host).is_some() {
+        format!("[{}]:{}", display(host), port)
+    } else {
+        format!("{}:{}", unbracket(host), port)
+    }
+}
+
+/// URL authority (RFC 3986/6874): IPv6 is bracketed and its zone separator is
+/// written as `%25`.
+pub fn url_authority(host: &str, port: Option<u16>) -> String {
+    let host = match parse_ipv6(host) {
+        Some(Ipv6Host { addr, zone: None }) => format!("[{}]", addr),
+        Some(Ipv6Host {
+            addr,
+            zone: Some(zone),
+        }) => format!("[{}%25{}]", addr, zone),
+        None => unbracket(host).to_string(),
+    };
+    match port {
+        Some(port) => format!("{}:{}", host, port),
+        None => host,
+    }
+}
+
+/// Socket addresses to try for `host`. Zoned IPv6 literals resolve their
+/// interface name to a scope id; names go through the system resolver.
+pub fn resolve(host: &str, port: u16) -> Vec<SocketAddr> {
+    if let Some(v6) = parse_ipv6(host) {
+        let scope_id = match v6.zone.as_deref() {
+            None => 0,
+            Some(zone) => match scope_id(zone) {
+                Some(id) => id,
+                None => return Vec::new(),
+            },
+        };
+        return vec![SocketAddr::V6(SocketAddrV6::new(
+            v6.addr, port, 0, scope_id,
+        ))];
+    }
+    (unbracket(host), port)
+        .to_socket_addrs()
+        .map(Iterator::collect)
+        .unwrap_or_default()
+}
+
+/// Drop a trailing `:port` from a URL authority (`nas.local:1445`,
+/// `[fe80::1]:445`), leaving bare IPv6 literals untouched.
+pub fn strip_port(authority: &str) -> &str {
+    if let Some(end) = authority.rfind(']') {
+        return &authority[..=end];
+    }
+    match authority.split_once(':') {
+        Some((host, port)) if !port.contains(':') => host,
+        _ => authority,
+    }
+}
+
+fn scope_id(zone: &str) -> Option<u32> {
+    if let Ok(id) = zone.parse() {
+        return Some(id);
+    }
+    nix::net::if_::if_nametoindex(zone).ok()
+}
+
+fn unbracket(host: &str) -> &str {
+    let host = host.trim();
+    host.strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ipv6_accepts_bracketed_zoned_and_url_forms() {
+        let expected = Ipv6Host {
+            addr: "fe80::1".parse().unwrap(),
+            zone: Some("bridge0".to_string()),
+        };
+        for host in [
+            "fe80::1%bridge0",
+            "[fe80::1%bridge0]",
+            "[fe80::1%25bridge0]",
+            " FE80:0::1%bridge0 ",
+        ] {
+            assert_eq!(parse_ipv6(host), Some(expected.clone()), "{}", host);
+        }
+        assert_eq!(parse_ipv6("2001:db8::1").unwrap().zone, None);
+        assert_eq!(parse_ipv6("10.0.0.1"), None);
+        assert_eq!(parse_ipv6("nas.local"), None);
+        assert_eq!(parse_ipv6("fe80::1%"), None);
+        assert_eq!(
+            parse_ipv6("fe80::1%251").unwrap().zone.as_deref(),
+            Some("251")
+        );
+    }
+
+    #[test]
+    fn validate_rejects_host_with_port_and_bad_literals() {
+        for host in ["nas.local", "10.0.0.1", "fe80::1%bridge0", "[2001:db8::1]"] {
+            assert!(validate(host).is_ok(), "{}", host);
+        }
+        for host in [
+            "nas.local:445",
+            "10.0.0.1:445",
+            "[fe80::1",
+            "fe80::zz",
+            "a/b",
+        ] {
+            assert!(validate(host).is_err(), "{}", host);
+        }
+    }
+
+    #[test]
+    fn display_is_canonical_and_unbracketed() {
+        assert_eq!(display("[FE80:0::1%25bridge0]"), "fe80::1%bridge0");
+        assert_eq!(display("[2001:DB8::1]"), "2001:db8::1");
+        assert_eq!(display("nas.local"), "nas.local");
+        assert!(same("[fe80::1%bridge0]", "FE80::1%bridge0"));
+        assert!(same("NAS.local", "nas.local"));
+        assert!(!same("fe80::1%bridge0", "fe80::1%en0"));
+    }
+
+    #[test]
+    fn socket_addr_string_brackets_ipv6() {
+        assert_eq!(socket_addr_string("10.0.0.1", 445), "10.0.0.1:445");
+        assert_eq!(
+            socket_addr_string("fe80::1%bridge0", 445),
+            "[fe80::1%bridge0]:445"
+        );
+        assert_eq!(
+            socket_addr_string("[2001:db8::1]", 1445),
+            "[2001:db8::1]:1445"
+        );
+    }
+
+    #[test]
+    fn url_authority_encodes_zone_and_port() {
+        assert_eq!(url_authority("nas.local", None), "nas.local");
+        assert_eq!(url_authority("nas.local", Some(1445)), "nas.local:1445");
+        assert_eq!(url_authority("2001:db8::1", None), "[2001:db8::1]");
+        assert_eq!(
+            url_authority("[fe80::1%bridge0]", Some(445)),
+            "[fe80::1%25bridge0]:445"
+        );
+    }
+
+    #[test]
+    fn strip_port_handles_names_and_ipv6() {
+        assert_eq!(strip_port("nas.local:1445"), "nas.local");
+        assert_eq!(strip_port("nas.local"), "nas.local");
+        assert_eq!(strip_port("[fe80::1%25bridge0]:445"), "[fe80::1%25bridge0]");
+        assert_eq!(strip_port("fe80::1%bridge0"), "fe80::1%bridge0");
+    }
+
+    #[test]
+    fn resolve_uses_numeric_scope_and_literals() {
+        assert_eq!(
+            resolve("fe80::1%4", 445),
+            vec!["[fe80::1%4]:445".parse::<SocketAddr>().unwrap()]
+        );
+        assert_eq!(
+            resolve("[2001:db8::1]", 445),
+            vec!["[2001:db8::1]:445".parse::<SocketAddr>().unwrap()]
+        );
+        assert_eq!(
+            resolve("10.0.0.1", 445),
+            vec!["10.0.0.1:445".parse::<SocketAddr>().unwrap()]
+        );
+        assert!(resolve("fe80::1%no-such-interface0", 445).is_empty());
+    }
+}
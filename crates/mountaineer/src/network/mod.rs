@@ -1,3 +1,6 @@
+pub mod host;
+#[cfg(target_os = "macos")]
 #[allow(dead_code)]
 pub mod interface;
+#[cfg(target_os = "macos")]
 pub mod monitor;
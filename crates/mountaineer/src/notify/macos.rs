@@ -0,0 +1,273 @@
+//! Notification Center delivery.
+//!
+//! [`UserNotifier`] uses the User Notifications framework, which only works
+//! from the app bundle: it registers the "Switch now" action and a delegate
+//! that turns clicks into [`super::queue_action`]. [`ScriptNotifier`] covers
+//! `mountaineer monitor` run from a terminal, through `osascript display
+//! notification`, without actions.
+
+use objc::declare::ClassDecl;
+use objc::runtime::{BOOL, NO, Object, Sel};
+use objc::{class, msg_send, sel, sel_impl};
+use std::ffi::{CStr, c_void};
+use std::os::raw::{c_int, c_ulong};
+use std::sync::OnceLock;
+
+use super::{Notification, Notifier, SWITCH_NOW_ACTION, SwitchNow};
+use crate::config::Backend;
+use crate::runner;
+
+const RECOVERY_CATEGORY: &str = "tb-recovery";
+/// UNAuthorizationOptionSound | UNAuthorizationOptionAlert
+const AUTHORIZATION_OPTIONS: u64 = (1 << 1) | (1 << 2);
+
+#[link(name = "UserNotifications", kind = "framework")]
+unsafe extern "C" {}
+
+unsafe extern "C" {
+    static _NSConcreteGlobalBlock: c_void;
+}
+
+// --- Blocks ---
+//
+// The framework takes and hands out Objective-C blocks. Only two shapes are
+// needed, so they are laid out by hand instead of pulling in a block crate.
+
+const BLOCK_IS_GLOBAL: c_int = 1 << 28;
+
+#[repr(C)]
+struct BlockDescriptor {
+    reserved: c_ulong,
+    size: c_ulong,
+}
+
+#[repr(C)]
+struct GlobalBlock<F> {
+    isa: *const c_void,
+    flags: c_int,
+    reserved: c_int,
+    invoke: F,
+    descriptor: *const BlockDescriptor,
+}
+
+unsafe impl<F> Sync for GlobalBlock<F> {}
+
+type AuthorizationInvoke = unsafe extern "C" fn(*const c_void, BOOL, *mut Object);
+
+static AUTHORIZATION_DESCRIPTOR: BlockDescriptor = BlockDescriptor {
+    reserved: 0,
+    size: std::mem::size_of::<GlobalBlock<AuthorizationInvoke>>() as c_ulong,
+};
+
+/// `^(BOOL granted, NSError *error)` passed to `requestAuthorization`.
+static AUTHORIZATION_HANDLER: GlobalBlock<AuthorizationInvoke> = GlobalBlock {
+    isa: &raw const _NSConcreteGlobalBlock,
+    flags: BLOCK_IS_GLOBAL,
+    reserved: 0,
+    invoke: authorization_done,
+    descriptor: &AUTHORIZATION_DESCRIPTOR,
+};
+
+unsafe extern "C" fn authorization_done(_block: *const c_void, granted: BOOL, _error: *mut Object) {
+    if granted == NO {
+        log::warn!("Notifications are turned off for Mountaineer in System Settings");
+    }
+}
+
+/// Header shared by every block; enough to call a `^(void)` block.
+#[repr(C)]
+struct BlockHeader {
+    isa: *const c_void,
+    flags: c_int,
+    reserved: c_int,
+    invoke: unsafe extern "C" fn(*mut BlockHeader),
+}
+
+unsafe fn call_void_block(block: *mut c_void) {
+    if block.is_null() {
+        return;
+    }
+    let header = block as *mut BlockHeader;
+    unsafe { ((*header).invoke)(header) };
+}
+
+// --- ObjC helpers ---
+
+/// An autoreleased `NSString`; callers run inside an autorelease pool.
+unsafe fn nsstring(s: &str) -> *mut Object {
+    let ns: *mut Object = msg_send![class!(NSString), alloc];
+    let ns: *mut Object = msg_send![ns, initWithBytes:s.as_ptr() length:s.len() encoding:4u64];
+    msg_send![ns, autorelease]
+}
+
+unsafe fn rust_string(ns: *mut Object) -> Option<String> {
+    if ns.is_null() {
+        return None;
+    }
+    let utf8: *const i8 = msg_send![ns, UTF8String];
+    if utf8.is_null() {
+        return None;
+    }
+    Some(
+        unsafe { CStr::from_ptr(utf8) }
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+unsafe fn notification_center() -> *mut Object {
+    msg_send![class!(UNUserNotificationCenter), currentNotificationCenter]
+}
+
+/// `userNotificationCenter:didReceiveNotificationResponse:withCompletionHandler:`
+extern "C" fn did_receive_response(
+    _this: &Object,
+    _cmd: Sel,
+    _center: *mut Object,
+    response: *mut Object,
+    completion: *mut c_void,
+) {
+    unsafe {
+        let pool: *mut Object = msg_send![class!(NSAutoreleasePool), new];
+        let action: *mut Object = msg_send![response, actionIdentifier];
+        if rust_string(action).as_deref() == Some(SWITCH_NOW_ACTION) {
+            let notification: *mut Object = msg_send![response, notification];
+            let request: *mut Object = msg_send![notification, request];
+            let content: *mut Object = msg_send![request, content];
+            let info: *mut Object = msg_send![content, userInfo];
+            let share: *mut Object = msg_send![info, objectForKey: nsstring("share")];
+            let to: *mut Object = msg_send![info, objectForKey: nsstring("to")];
+            let to = match rust_string(to).as_deref() {
+                Some("tb") => Some(Backend::Tb),
+                Some("fallback") => Some(Backend::Fallback),
+                _ => None,
+            };
+            if let (Some(share), Some(to)) = (rust_string(share), to) {
+                super::queue_action(SwitchNow { share, to });
+            }
+        }
+        let _: () = msg_send![pool, drain];
+        call_void_block(completion);
+    }
+}
+
+/// Register the recovery category and delegate, and ask for permission.
+/// `false` when not running from the app bundle.
+fn setup() -> bool {
+    static READY: OnceLock<bool> = OnceLock::new();
+    *READY.get_or_init(|| unsafe {
+        let bundle: *mut Object = msg_send![class!(NSBundle), mainBundle];
+        let identifier: *mut Object = msg_send![bundle, bundleIdentifier];
+        if identifier.is_null() {
+            return false;
+        }
+        let pool: *mut Object = msg_send![class!(NSAutoreleasePool), new];
+        let center = notification_center();
+
+        let mut decl = ClassDecl::new("MountaineerNotificationDelegate", class!(NSObject))
+            .expect("notification delegate class registered twice");
+        decl.add_method(
+            sel!(userNotificationCenter:didReceiveNotificationResponse:withCompletionHandler:),
+            did_receive_response
+                as extern "C" fn(&Object, Sel, *mut Object, *mut Object, *mut c_void),
+        );
+        let delegate: *mut Object = msg_send![decl.register(), new];
+        // The center holds its delegate weakly; this one is never released.
+        let _: () = msg_send![center, setDelegate: delegate];
+
+        let action: *mut Object = msg_send![class!(UNNotificationAction),
+            actionWithIdentifier: nsstring(SWITCH_NOW_ACTION)
+            title: nsstring("Switch now")
+            options: 0u64];
+        let actions: *mut Object = msg_send![class!(NSArray), arrayWithObject: action];
+        let no_intents: *mut Object = msg_send![class!(NSArray), array];
+        let category: *mut Object = msg_send![class!(UNNotificationCategory),
+            categoryWithIdentifier: nsstring(RECOVERY_CATEGORY)
+            actions: actions
+            intentIdentifiers: no_intents
+            options: 0u64];
+        let categories: *mut Object = msg_send![class!(NSSet), setWithObject: category];
+        let _: () = msg_send![center, setNotificationCategories: categories];
+
+        let _: () = msg_send![center,
+            requestAuthorizationWithOptions: AUTHORIZATION_OPTIONS
+            completionHandler: &AUTHORIZATION_HANDLER as *const GlobalBlock<AuthorizationInvoke>];
+        let _: () = msg_send![pool, drain];
+        true
+    })
+}
+
+/// Notification Center through `UNUserNotificationCenter`.
+pub struct UserNotifier;
+
+impl UserNotifier {
+    /// `None` outside the app bundle, where the framework refuses to work.
+    pub fn new() -> Option<Self> {
+        setup().then_some(Self)
+    }
+}
+
+impl Notifier for UserNotifier {
+    fn notify(&self, notification: &Notification) -> Result<(), String> {
+        unsafe {
+            let pool: *mut Object = msg_send![class!(NSAutoreleasePool), new];
+
+            let content: *mut Object = msg_send![class!(UNMutableNotificationContent), new];
+            let content: *mut Object = msg_send![content, autorelease];
+            let _: () = msg_send![content, setTitle: nsstring(&notification.title)];
+            let _: () = msg_send![content, setBody: nsstring(&notification.body)];
+            let sound: *mut Object = msg_send![class!(UNNotificationSound), defaultSound];
+            let _: () = msg_send![content, setSound: sound];
+            if let Some(action) = &notification.action {
+                let _: () = msg_send![content, setCategoryIdentifier: nsstring(RECOVERY_CATEGORY)];
+                let info: *mut Object = msg_send![class!(NSMutableDictionary), dictionary];
+                let _: () =
+                    msg_send![info, setObject: nsstring(&action.share) forKey: nsstring("share")];
+                let _: () = msg_send![info,
+                    setObject: nsstring(action.to.short_label())
+                    forKey: nsstring("to")];
+                let _: () = msg_send![content, setUserInfo: info];
+            }
+
+            // One identifier per share and event, so a repeat replaces the
+            // notification still on screen.
+            let identifier = format!("{}-{}", notification.share, notification.event.label());
+            let no_trigger: *mut Object = std::ptr::null_mut();
+            let request: *mut Object = msg_send![class!(UNNotificationRequest),
+                requestWithIdentifier: nsstring(&identifier)
+                content: content
+                trigger: no_trigger];
+            let no_handler: *mut c_void = std::ptr::null_mut();
+            let _: () = msg_send![notification_center(),
+                addNotificationRequest: request
+                withCompletionHandler: no_handler];
+
+            let _: () = msg_send![pool, drain];
+        }
+        Ok(())
+    }
+}
+
+/// `osascript display notification`, for runs outside the app bundle.
+pub struct ScriptNotifier;
+
+impl Notifier for ScriptNotifier {
+    fn notify(&self, notification: &Notification) -> Result<(), String> {
+        let script = format!(
+            "display notification {} with title {}",
+            applescript_string(&notification.body),
+            applescript_string(&notification.title)
+        );
+        let output =
+            runner::run("osascript", &["-e", &script]).map_err(|e| format!("osascript: {}", e))?;
+        if output.success() {
+            Ok(())
+        } else {
+            Err(output.stderr.trim().to_string())
+        }
+    }
+}
+
+fn applescript_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
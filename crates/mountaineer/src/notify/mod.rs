@@ -0,0 +1,517 @@
+//! Desktop notifications for share events the user should act on.
+//!
+//! Every published share event passes through here. `failover`,
+//! `recovery-pending`, `both-down` and (opt-in) `error` notify, each behind
+//! its flag in `[notifications]` and at most once per share and event within
+//! `min_interval_secs`. Notifications go to Notification Center, or to
+//! `notifications.command` when one is set. A recovery notification carries
+//! a "Switch now" action; clicking it queues a [`SwitchNow`] that the tray or
+//! `monitor` loop picks up with [`take_actions`].
+
+#[cfg(target_os = "macos")]
+mod macos;
+
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::collections::HashMap;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use crate::config::{Backend, Config};
+use crate::engine::{RecoveryHold, ShareStatus};
+use crate::events::{ShareEvent, ShareEventKind};
+
+/// Identifier of the "Switch now" action, also what a notification command
+/// prints on stdout when the user picked it.
+pub const SWITCH_NOW_ACTION: &str = "switch-now";
+
+/// Something that can put a notification in front of the user.
+pub trait Notifier: Send + Sync {
+    fn notify(&self, notification: &Notification) -> Result<(), String>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub share: String,
+    pub event: ShareEventKind,
+    pub title: String,
+    pub body: String,
+    pub action: Option<SwitchNow>,
+}
+
+/// The user asked to switch `share` to `to` from a notification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwitchNow {
+    pub share: String,
+    pub to: Backend,
+}
+
+/// Remembers when each share last notified for each event.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    last_shown: HashMap<(String, ShareEventKind), DateTime<Utc>>,
+}
+
+impl RateLimiter {
+    /// Whether a notification for `share` and `event` may be shown at `now`;
+    /// records it when it may.
+    pub fn allow(
+        &mut self,
+        share: &str,
+        event: ShareEventKind,
+        min_interval: ChronoDuration,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let key = (share.to_ascii_lowercase(), event);
+        if let Some(last) = self.last_shown.get(&key)
+            && now - *last < min_interval
+        {
+            return false;
+        }
+        self.last_shown.insert(key, now);
+        true
+    }
+}
+
+static LIMITER: Mutex<Option<RateLimiter>> = Mutex::new(None);
+static ACTIONS: Mutex<Vec<SwitchNow>> = Mutex::new(Vec::new());
+
+/// Show a notification for `event` if config wants it and it is not rate
+/// limited. Delivery runs on its own thread and never blocks the caller.
+pub fn publish(config: &Config, event: &ShareEvent) {
+    let Some(notification) = due(config, event) else {
+        return;
+    };
+    let Some(notifier) = notifier(config) else {
+        return;
+    };
+    std::thread::spawn(move || show(notifier.as_ref(), &notification));
+}
+
+/// The notification `event` should show now, if config wants one and it is
+/// not rate limited.
+fn due(config: &Config, event: &ShareEvent) -> Option<Notification> {
+    if !config.notifications.wants(event.event) {
+        return None;
+    }
+    let notification = notification_for(event.event, &event.status)?;
+    let min_interval = ChronoDuration::seconds(config.notifications.min_interval_secs as i64);
+    let allowed = LIMITER
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(RateLimiter::default)
+        .allow(
+            &notification.share,
+            notification.event,
+            min_interval,
+            event.at,
+        );
+    if !allowed {
+        log::debug!(
+            "{}: {} notification rate limited",
+            notification.share,
+            notification.event.label()
+        );
+        return None;
+    }
+    Some(notification)
+}
+
+fn show(notifier: &dyn Notifier, notification: &Notification) {
+    if let Err(e) = notifier.notify(notification) {
+        log::warn!(
+            "{}: could not show {} notification: {}",
+            notification.share,
+            notification.event.label(),
+            e
+        );
+    }
+}
+
+/// Register with Notification Center up front, so a click on a notification
+/// left by an earlier run reaches this process. Called by the tray at start.
+#[cfg(target_os = "macos")]
+pub fn init() {
+    let _ = macos::UserNotifier::new();
+}
+
+/// `notifications.command` when set; otherwise Notification Center, or
+/// `osascript` when not running from the app bundle. `None` off macOS without
+/// a command.
+fn notifier(config: &Config) -> Option<Box<dyn Notifier>> {
+    if let Some(command) = config
+        .notifications
+        .command
+        .as_deref()
+        .filter(|command| !command.trim().is_empty())
+    {
+        return Some(Box::new(CommandNotifier {
+            command: command.to_string(),
+        }));
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let notifier: Box<dyn Notifier> = match macos::UserNotifier::new() {
+            Some(notifier) => Box::new(notifier),
+            None => Box::new(macos::ScriptNotifier),
+        };
+        Some(notifier)
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        None
+    }
+}
+
+/// Title, body and action for `event` on the share described by `status`.
+pub fn notification_for(event: ShareEventKind, status: &ShareStatus) -> Option<Notification> {
+    let share = status.name.clone();
+    let (title, body, action) = match event {
+        ShareEventKind::RecoveryPending => (
+            format!("Thunderbolt is back for {}", share),
+            match status.recovery_hold {
+                Some(RecoveryHold::Busy) => {
+                    "Files are still open on the fallback mount. Switch once they are closed."
+                }
+                Some(RecoveryHold::Vetoed) => "The pre-switch hook refused the switch back.",
+                Some(RecoveryHold::Manual) | None => "Switch back when you are ready.",
+            }
+            .to_string(),
+            Some(SwitchNow {
+                share: share.clone(),
+                to: Backend::Tb,
+            }),
+        ),
+        ShareEventKind::Failover => {
+            let to = status.active_backend?;
+            let (from_host, to_host) = match to {
+                Backend::Tb => (&status.fallback.host, &status.tb.host),
+                Backend::Fallback => (&status.tb.host, &status.fallback.host),
+            };
+            (
+                format!("{} failed over to {}", share, to.short_label()),
+                format!(
+                    "{} stopped answering; now mounted from {}.",
+                    from_host, to_host
+                ),
+                None,
+            )
+        }
+        ShareEventKind::BothDown => (
+            format!("{} is offline", share),
+            format!(
+                "Neither {} nor {} is reachable.",
+                status.tb.host, status.fallback.host
+            ),
+            None,
+        ),
+        ShareEventKind::Error => (
+            format!("{} needs attention", share),
+            status.last_error.clone()?,
+            None,
+        ),
+        ShareEventKind::Switch => return None,
+    };
+    Some(Notification {
+        share,
+        event,
+        title,
+        body,
+        action,
+    })
+}
+
+/// Queue an action the user picked on a notification.
+pub fn queue_action(action: SwitchNow) {
+    log::info!(
+        "{}: switch to {} requested from a notification",
+        action.share,
+        action.to.short_label()
+    );
+    ACTIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(action);
+}
+
+/// Actions picked since the last call, oldest first.
+pub fn take_actions() -> Vec<SwitchNow> {
+    std::mem::take(&mut *ACTIONS.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Runs a shell command with the notification in `MOUNTAINEER_*` variables,
+/// e.g. `terminal-notifier -title "$MOUNTAINEER_TITLE" -message
+/// "$MOUNTAINEER_BODY" -execute "$MOUNTAINEER_ACTION_COMMAND"` or
+/// `notify-send -w -A switch-now="$MOUNTAINEER_ACTION_LABEL" ...`. A command
+/// that prints `switch-now` performs the action.
+pub struct CommandNotifier {
+    pub command: String,
+}
+
+impl Notifier for CommandNotifier {
+    fn notify(&self, notification: &Notification) -> Result<(), String> {
+        let output = Command::new("/bin/sh")
+            .args(["-c", &self.command])
+            .envs(command_environment(notification))
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| format!("could not start: {}", e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!(
+                "exited with {}: {}",
+                output.status.code().unwrap_or(-1),
+                stderr.trim()
+            ));
+        }
+        if let Some(action) = &notification.action
+            && String::from_utf8_lossy(&output.stdout).trim() == SWITCH_NOW_ACTION
+        {
+            queue_action(action.clone());
+        }
+        Ok(())
+    }
+}
+
+fn command_environment(notification: &Notification) -> Vec<(&'static str, String)> {
+    let (action, label, command) = match &notification.action {
+        Some(action) => (
+            SWITCH_NOW_ACTION,
+            "Switch now",
+            format!(
+                "{} switch --share {} --to {}",
+                shell_quote(&current_exe()),
+                shell_quote(&action.share),
+                action.to.short_label()
+            ),
+        ),
+        None => ("", "", String::new()),
+    };
+    vec![
+        ("MOUNTAINEER_EVENT", notification.event.label().to_string()),
+        ("MOUNTAINEER_SHARE", notification.share.clone()),
+        ("MOUNTAINEER_TITLE", notification.title.clone()),
+        ("MOUNTAINEER_BODY", notification.body.clone()),
+        ("MOUNTAINEER_ACTION", action.to_string()),
+        ("MOUNTAINEER_ACTION_LABEL", label.to_string()),
+        ("MOUNTAINEER_ACTION_COMMAND", command),
+    ]
+}
+
+fn current_exe() -> String {
+    std::env::current_exe()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| "mountaineer".to_string())
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::BackendStatus;
+
+    fn status(name: &str, active: Option<Backend>) -> ShareStatus {
+        let backend = |host: &str| BackendStatus {
+            host: host.to_string(),
+            mount_point: format!("/Volumes/{}", name),
+            reachable: true,
+            mounted: false,
+            alive: false,
+            ready: false,
+            last_error: None,
+            last_error_kind: None,
+        };
+        ShareStatus {
+            name: name.to_string(),
+            stable_path: format!("/Users/me/Shares/{}", name),
+            active_backend: active,
+            desired_backend: active,
+            tb_recovery_pending: false,
+            recovery_hold: None,
+            tb: backend("10.10.10.1"),
+            fallback: backend("nas.local"),
+            last_switch_at: None,
+            last_error: None,
+            last_error_kind: None,
+            last_error_hint: None,
+            needs_credentials: None,
+            tb_reachable_since: None,
+            tb_healthy_since: None,
+            foreign_mounts: Vec::new(),
+            pending_switch: None,
+            leases: Vec::new(),
+        }
+    }
+
+    fn event(kind: ShareEventKind, status: ShareStatus, at: DateTime<Utc>) -> ShareEvent {
+        ShareEvent {
+            event: kind,
+            at,
+            status,
+        }
+    }
+
+    #[test]
+    fn recovery_notification_offers_switch_now() {
+        let note = notification_for(
+            ShareEventKind::RecoveryPending,
+            &status("CORE", Some(Backend::Fallback)),
+        )
+        .unwrap();
+        assert_eq!(note.title, "Thunderbolt is back for CORE");
+        assert_eq!(
+            note.action,
+            Some(SwitchNow {
+                share: "CORE".to_string(),
+                to: Backend::Tb,
+            })
+        );
+
+        let failover = notification_for(
+            ShareEventKind::Failover,
+            &status("CORE", Some(Backend::Fallback)),
+        )
+        .unwrap();
+        assert_eq!(failover.title, "CORE failed over to fallback");
+        assert_eq!(
+            failover.body,
+            "10.10.10.1 stopped answering; now mounted from nas.local."
+        );
+        assert!(failover.action.is_none());
+        assert!(notification_for(ShareEventKind::Switch, &status("CORE", None)).is_none());
+    }
+
+    #[test]
+    fn rate_limiter_is_per_share_and_event() {
+        let mut limiter = RateLimiter::default();
+        let now = Utc::now();
+        let interval = ChronoDuration::seconds(300);
+        assert!(limiter.allow("CORE", ShareEventKind::Failover, interval, now));
+        assert!(!limiter.allow(
+            "core",
+            ShareEventKind::Failover,
+            interval,
+            now + ChronoDuration::seconds(299)
+        ));
+        assert!(limiter.allow("CORE", ShareEventKind::BothDown, interval, now));
+        assert!(limiter.allow("DATA", ShareEventKind::Failover, interval, now));
+        assert!(limiter.allow(
+            "CORE",
+            ShareEventKind::Failover,
+            interval,
+            now + ChronoDuration::seconds(300)
+        ));
+    }
+
+    /// Keeps what it is asked to show.
+    #[derive(Default)]
+    struct RecordingNotifier {
+        shown: Mutex<Vec<Notification>>,
+    }
+
+    impl Notifier for RecordingNotifier {
+        fn notify(&self, notification: &Notification) -> Result<(), String> {
+            self.shown.lock().unwrap().push(notification.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn publish_respects_flags_and_rate_limit() {
+        let mut config = Config::default();
+        config.notifications.failover = false;
+        let share = format!("NOTIFY-{:?}", std::thread::current().id());
+        let at = Utc::now();
+        let notifier = RecordingNotifier::default();
+
+        let fallback = status(&share, Some(Backend::Fallback));
+        let events = [
+            event(ShareEventKind::Failover, fallback.clone(), at),
+            event(ShareEventKind::RecoveryPending, fallback.clone(), at),
+            event(
+                ShareEventKind::RecoveryPending,
+                fallback,
+                at + ChronoDuration::seconds(10),
+            ),
+        ];
+        for event in &events {
+            if let Some(notification) = due(&config, event) {
+                show(&notifier, &notification);
+            }
+        }
+
+        let shown = notifier.shown.into_inner().unwrap();
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown[0].event, ShareEventKind::RecoveryPending);
+    }
+
+    #[test]
+    fn recovery_pending_body_names_what_it_waits_for() {
+        let body = |hold| {
+            let mut status = status("CORE", Some(Backend::Fallback));
+            status.tb_recovery_pending = true;
+            status.recovery_hold = hold;
+            notification_for(ShareEventKind::RecoveryPending, &status)
+                .unwrap()
+                .body
+        };
+        assert_eq!(
+            body(Some(RecoveryHold::Manual)),
+            "Switch back when you are ready."
+        );
+        assert!(body(Some(RecoveryHold::Busy)).starts_with("Files are still open"));
+        assert!(body(Some(RecoveryHold::Vetoed)).contains("pre-switch hook"));
+    }
+
+    #[test]
+    fn command_notifier_exports_notification_and_queues_picked_action() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("env");
+        let notifier = CommandNotifier {
+            command: format!(
+                "echo \"$MOUNTAINEER_EVENT|$MOUNTAINEER_TITLE|$MOUNTAINEER_ACTION_LABEL\" > '{}'; \
+                 echo \"$MOUNTAINEER_ACTION\"",
+                out.display()
+            ),
+        };
+        let share = format!("ACT-{:?}", std::thread::current().id());
+        let note =
+            notification_for(ShareEventKind::RecoveryPending, &status(&share, None)).unwrap();
+        notifier.notify(&note).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap().trim(),
+            format!(
+                "recovery-pending|Thunderbolt is back for {}|Switch now",
+                share
+            )
+        );
+        assert!(take_actions().contains(&SwitchNow {
+            share,
+            to: Backend::Tb,
+        }));
+    }
+
+    #[test]
+    fn action_command_quotes_share_name() {
+        let note = Notification {
+            share: "it's".to_string(),
+            event: ShareEventKind::RecoveryPending,
+            title: String::new(),
+            body: String::new(),
+            action: Some(SwitchNow {
+                share: "it's".to_string(),
+                to: Backend::Tb,
+            }),
+        };
+        let env = command_environment(&note);
+        let command = &env
+            .iter()
+            .find(|(key, _)| *key == "MOUNTAINEER_ACTION_COMMAND")
+            .unwrap()
+            .1;
+        assert!(command.ends_with(r" switch --share 'it'\''s' --to tb"));
+    }
+}
@@ -0,0 +1,749 @@
+//! Open files under a mount point, attributed to the processes holding them.
+//!
+//! Open descriptors are read directly from the kernel (libproc on macOS,
+//! `/proc/*/fd` on Linux) and filtered by mount-point prefix. `lsof +D` walks
+//! the whole share instead, which takes tens of seconds on a large NAS, so it
+//! is only the fallback when the native scan is unavailable or runs over
+//! [`SCAN_BUDGET`].
+//!
+//! Inside a [`cycle`], one scan of every process serves all shares.
+
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::runner;
+
+/// Longest a native scan of all processes may take before falling back to
+/// `lsof`.
+pub const SCAN_BUDGET: Duration = Duration::from_millis(500);
+
+/// How a process has a file open, from the lsof FD column (`3r`, `4w`, `5u`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileAccess {
+    Read,
+    Write,
+    ReadWrite,
+    /// Not a numbered descriptor: working directory, mapped text, etc.
+    Other,
+}
+
+impl FileAccess {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Read => "r",
+            Self::Write => "w",
+            Self::ReadWrite => "rw",
+            Self::Other => "-",
+        }
+    }
+}
+
+/// One open handle under a mount point.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenFile {
+    pub pid: u32,
+    pub command: String,
+    pub user: String,
+    /// Raw FD column: a descriptor with its mode (`42r`) or a kind (`cwd`, `txt`).
+    pub fd: String,
+    pub access: FileAccess,
+    pub path: String,
+}
+
+/// Processes holding files open, in order of first appearance.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProcessFiles {
+    pub pid: u32,
+    pub command: String,
+    pub user: String,
+    pub paths: Vec<String>,
+}
+
+/// Open files under `path`. Empty if nothing is open or neither the native
+/// scan nor lsof could tell.
+pub fn list(path: &Path) -> Vec<OpenFile> {
+    if let Some(files) = runner::open_files(path, || native(path)) {
+        if !files.is_empty() {
+            log::info!("{} open handle(s) on {}", files.len(), path.display());
+        }
+        return files;
+    }
+    list_lsof(path)
+}
+
+/// Shares one native scan among all [`list`] calls on this thread until the
+/// returned guard is dropped. Nested calls reuse the outer cycle.
+///
+/// Files opened after the scan are not seen until the next cycle, so keep
+/// cycles to a single reconcile pass.
+pub fn cycle() -> Cycle {
+    CYCLE.with(|state| {
+        let mut state = state.borrow_mut();
+        if state.is_some() {
+            return Cycle { owner: false };
+        }
+        *state = Some(CycleScan::Pending);
+        Cycle { owner: true }
+    })
+}
+
+/// Guard returned by [`cycle`].
+pub struct Cycle {
+    owner: bool,
+}
+
+impl Drop for Cycle {
+    fn drop(&mut self) {
+        if self.owner {
+            CYCLE.with(|state| state.borrow_mut().take());
+        }
+    }
+}
+
+enum CycleScan {
+    Pending,
+    /// `None` when the scan was unavailable or over budget.
+    Done(Option<Rc<Vec<OpenFile>>>),
+}
+
+thread_local! {
+    static CYCLE: RefCell<Option<CycleScan>> = const { RefCell::new(None) };
+}
+
+/// Open files under `path` from a native scan, reusing the cycle's scan.
+fn native(path: &Path) -> Option<Vec<OpenFile>> {
+    let scan = CYCLE.with(|state| {
+        let mut state = state.borrow_mut();
+        match state.as_mut() {
+            None => timed_scan().map(Rc::new),
+            Some(CycleScan::Done(scan)) => scan.clone(),
+            Some(pending) => {
+                let scan = timed_scan().map(Rc::new);
+                *pending = CycleScan::Done(scan.clone());
+                scan
+            }
+        }
+    })?;
+
+    // The kernel reports resolved paths; adopted mounts sit behind a symlink.
+    let root = resolve(path);
+    Some(
+        scan.iter()
+            .filter(|file| Path::new(&file.path).starts_with(&root))
+            .cloned()
+            .collect(),
+    )
+}
+
+/// `path` with symlinks resolved, or as given when that fails or runs over
+/// [`SCAN_BUDGET`], as it does on a dead mount.
+fn resolve(path: &Path) -> PathBuf {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let owned = path.to_path_buf();
+    std::thread::spawn(move || {
+        let _ = tx.send(owned.canonicalize());
+    });
+    match rx.recv_timeout(SCAN_BUDGET) {
+        Ok(Ok(resolved)) => resolved,
+        Ok(Err(_)) => path.to_path_buf(),
+        Err(_) => {
+            log::warn!(
+                "{} did not resolve within {}ms; matching open files against it as given",
+                path.display(),
+                SCAN_BUDGET.as_millis()
+            );
+            path.to_path_buf()
+        }
+    }
+}
+
+fn timed_scan() -> Option<Vec<OpenFile>> {
+    let started = Instant::now();
+    let scan = platform::scan(started + SCAN_BUDGET);
+    match &scan {
+        Some(files) => log::debug!(
+            "open-file scan: {} handle(s) in {}ms",
+            files.len(),
+            started.elapsed().as_millis()
+        ),
+        None => log::warn!(
+            "open-file scan unavailable or over {}ms budget; falling back to lsof",
+            SCAN_BUDGET.as_millis()
+        ),
+    }
+    scan
+}
+
+fn list_lsof(path: &Path) -> Vec<OpenFile> {
+    match runner::run("lsof", &["+D", &path.to_string_lossy()]) {
+        Ok(output) => {
+            let files = parse_lsof(&output.stdout);
+            if !files.is_empty() {
+                log::info!("lsof: {} open handle(s) on {}", files.len(), path.display());
+            }
+            files
+        }
+        Err(e) => {
+            log::warn!("lsof check failed on {}: {}", path.display(), e);
+            Vec::new()
+        }
+    }
+}
+
+/// lsof-style FD label: descriptor number plus `r`, `w` or `u`.
+fn fd_label(fd: i32, access: FileAccess) -> String {
+    let mode = match access {
+        FileAccess::Read => "r",
+        FileAccess::Write => "w",
+        FileAccess::ReadWrite => "u",
+        FileAccess::Other => "",
+    };
+    format!("{}{}", fd, mode)
+}
+
+/// Access mode from open flags' low bits: kernel `FREAD`/`FWRITE` on macOS
+/// (1, 2, 3), `O_RDONLY`/`O_WRONLY`/`O_RDWR` on Linux (0, 1, 2).
+fn access_from_bits(read: bool, write: bool) -> FileAccess {
+    match (read, write) {
+        (true, true) => FileAccess::ReadWrite,
+        (true, false) => FileAccess::Read,
+        (false, true) => FileAccess::Write,
+        (false, false) => FileAccess::Other,
+    }
+}
+
+/// Login name for `uid`, cached per scan.
+fn user_name(cache: &mut std::collections::HashMap<u32, String>, uid: u32) -> String {
+    cache
+        .entry(uid)
+        .or_insert_with(|| {
+            nix::unistd::User::from_uid(nix::unistd::Uid::from_raw(uid))
+                .ok()
+                .flatten()
+                .map(|user| user.name)
+                .unwrap_or_else(|| uid.to_string())
+        })
+        .clone()
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{FileAccess, OpenFile, access_from_bits, fd_label, user_name};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::os::unix::fs::MetadataExt;
+    use std::time::Instant;
+
+    /// Every descriptor and working directory under `/proc` this user can
+    /// read. `None` if `/proc` is missing or `deadline` passes.
+    pub(super) fn scan(deadline: Instant) -> Option<Vec<OpenFile>> {
+        let mut users = HashMap::new();
+        let mut files = Vec::new();
+        for entry in fs::read_dir("/proc").ok()?.flatten() {
+            if Instant::now() >= deadline {
+                return None;
+            }
+            let Some(pid) = entry.file_name().to_str().and_then(|n| n.parse().ok()) else {
+                continue;
+            };
+            let dir = entry.path();
+            // Other users' processes are unreadable, as with lsof.
+            let Ok(fds) = fs::read_dir(dir.join("fd")) else {
+                continue;
+            };
+            let command = fs::read_to_string(dir.join("comm"))
+                .map(|comm| comm.trim_end().to_string())
+                .unwrap_or_default();
+            let user = entry
+                .metadata()
+                .map(|meta| user_name(&mut users, meta.uid()))
+                .unwrap_or_default();
+            let mut push = |fd: String, access, path: String| {
+                files.push(OpenFile {
+                    pid,
+                    command: command.clone(),
+                    user: user.clone(),
+                    fd,
+                    access,
+                    path,
+                })
+            };
+
+            if let Ok(cwd) = fs::read_link(dir.join("cwd")) {
+                push(
+                    "cwd".to_string(),
+                    FileAccess::Other,
+                    cwd.display().to_string(),
+                );
+            }
+            for fd in fds.flatten() {
+                let Ok(target) = fs::read_link(fd.path()) else {
+                    continue;
+                };
+                // Sockets and pipes read as `socket:[123]`.
+                if !target.is_absolute() {
+                    continue;
+                }
+                let Some(num) = fd.file_name().to_str().and_then(|n| n.parse::<i32>().ok()) else {
+                    continue;
+                };
+                let access = fdinfo_access(&dir.join("fdinfo").join(num.to_string()));
+                push(fd_label(num, access), access, target.display().to_string());
+            }
+        }
+        Some(files)
+    }
+
+    fn fdinfo_access(path: &std::path::Path) -> FileAccess {
+        let flags = fs::read_to_string(path).ok().and_then(|info| {
+            info.lines()
+                .find_map(|line| line.strip_prefix("flags:"))
+                .and_then(|flags| u32::from_str_radix(flags.trim(), 8).ok())
+        });
+        match flags.map(|flags| flags & 0o3) {
+            Some(0) => access_from_bits(true, false),
+            Some(1) => access_from_bits(false, true),
+            Some(2) => access_from_bits(true, true),
+            _ => FileAccess::Other,
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{FileAccess, OpenFile, access_from_bits, fd_label, user_name};
+    use nix::libc::{self, c_int, c_void};
+    use std::collections::HashMap;
+    use std::mem::{size_of, zeroed};
+    use std::time::Instant;
+
+    /// `<sys/proc_info.h>` flavor for `proc_pidfdinfo`, not exported by libc.
+    const PROC_PIDFDVNODEPATHINFO: c_int = 2;
+    /// Kernel open flags (`FREAD`/`FWRITE`), not `O_*`.
+    const FREAD: u32 = 0x1;
+    const FWRITE: u32 = 0x2;
+
+    /// Layout of `struct proc_fileinfo`; only the open flags are read.
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct ProcFileInfo {
+        fi_openflags: u32,
+        fi_status: u32,
+        fi_offset: i64,
+        fi_type: i32,
+        fi_guardflags: u32,
+    }
+
+    #[repr(C)]
+    struct VnodeFdInfoWithPath {
+        pfi: ProcFileInfo,
+        pvip: libc::vnode_info_path,
+    }
+
+    /// Every vnode descriptor and working directory libproc lets this user
+    /// see. `None` if listing processes fails or `deadline` passes.
+    pub(super) fn scan(deadline: Instant) -> Option<Vec<OpenFile>> {
+        let mut users = HashMap::new();
+        let mut files = Vec::new();
+        for pid in all_pids()? {
+            if Instant::now() >= deadline {
+                return None;
+            }
+            // Other users' processes are unreadable, as with lsof.
+            let Some(bsd) = bsd_info(pid) else {
+                continue;
+            };
+            let fds = list_fds(pid);
+            let command = c_string(&bsd.pbi_comm);
+            let user = user_name(&mut users, bsd.pbi_uid);
+            let mut push = |fd: String, access, path: String| {
+                files.push(OpenFile {
+                    pid: pid as u32,
+                    command: command.clone(),
+                    user: user.clone(),
+                    fd,
+                    access,
+                    path,
+                })
+            };
+
+            if let Some(cwd) = cwd(pid) {
+                push("cwd".to_string(), FileAccess::Other, cwd);
+            }
+            for fd in fds {
+                if fd.proc_fdtype != libc::PROX_FDTYPE_VNODE as u32 {
+                    continue;
+                }
+                let Some((flags, path)) = vnode_path(pid, fd.proc_fd) else {
+                    continue;
+                };
+                let access = access_from_bits(flags & FREAD != 0, flags & FWRITE != 0);
+                push(fd_label(fd.proc_fd, access), access, path);
+            }
+        }
+        Some(files)
+    }
+
+    fn all_pids() -> Option<Vec<c_int>> {
+        // SAFETY: a null buffer of size 0 only asks for the count.
+        let count = unsafe { libc::proc_listallpids(std::ptr::null_mut(), 0) };
+        if count <= 0 {
+            return None;
+        }
+        // Room for processes started between the two calls.
+        let mut pids: Vec<c_int> = vec![0; count as usize + 64];
+        let bytes = (pids.len() * size_of::<c_int>()) as c_int;
+        // SAFETY: `pids` is `bytes` long and outlives the call.
+        let count = unsafe { libc::proc_listallpids(pids.as_mut_ptr().cast::<c_void>(), bytes) };
+        if count <= 0 {
+            return None;
+        }
+        pids.truncate(count as usize);
+        Some(pids)
+    }
+
+    fn bsd_info(pid: c_int) -> Option<libc::proc_bsdinfo> {
+        // SAFETY: a plain C struct; all zeroes is a valid value to be overwritten.
+        let mut info: libc::proc_bsdinfo = unsafe { zeroed() };
+        let size = size_of::<libc::proc_bsdinfo>() as c_int;
+        // SAFETY: `info` is `size` bytes and outlives the call.
+        let got = unsafe {
+            libc::proc_pidinfo(
+                pid,
+                libc::PROC_PIDTBSDINFO,
+                0,
+                (&mut info as *mut libc::proc_bsdinfo).cast::<c_void>(),
+                size,
+            )
+        };
+        (got == size).then_some(info)
+    }
+
+    fn list_fds(pid: c_int) -> Vec<libc::proc_fdinfo> {
+        // SAFETY: a null buffer of size 0 only asks for the size needed.
+        let bytes =
+            unsafe { libc::proc_pidinfo(pid, libc::PROC_PIDLISTFDS, 0, std::ptr::null_mut(), 0) };
+        if bytes <= 0 {
+            return Vec::new();
+        }
+        let capacity = bytes as usize / size_of::<libc::proc_fdinfo>() + 16;
+        let mut fds: Vec<libc::proc_fdinfo> = Vec::with_capacity(capacity);
+        // SAFETY: the buffer has room for `capacity` entries and outlives the call.
+        let bytes = unsafe {
+            libc::proc_pidinfo(
+                pid,
+                libc::PROC_PIDLISTFDS,
+                0,
+                fds.as_mut_ptr().cast::<c_void>(),
+                (capacity * size_of::<libc::proc_fdinfo>()) as c_int,
+            )
+        };
+        if bytes <= 0 {
+            return Vec::new();
+        }
+        // SAFETY: the kernel wrote `bytes` (at most the buffer size) of
+        // initialized entries.
+        unsafe { fds.set_len(bytes as usize / size_of::<libc::proc_fdinfo>()) };
+        fds
+    }
+
+    fn vnode_path(pid: c_int, fd: i32) -> Option<(u32, String)> {
+        // SAFETY: a plain C struct; all zeroes is a valid value to be overwritten.
+        let mut info: VnodeFdInfoWithPath = unsafe { zeroed() };
+        let size = size_of::<VnodeFdInfoWithPath>() as c_int;
+        // SAFETY: `info` is `size` bytes and outlives the call.
+        let got = unsafe {
+            libc::proc_pidfdinfo(
+                pid,
+                fd,
+                PROC_PIDFDVNODEPATHINFO,
+                (&mut info as *mut VnodeFdInfoWithPath).cast::<c_void>(),
+                size,
+            )
+        };
+        if got != size {
+            return None;
+        }
+        let path = path_from(&info.pvip)?;
+        Some((info.pfi.fi_openflags, path))
+    }
+
+    fn cwd(pid: c_int) -> Option<String> {
+        // SAFETY: a plain C struct; all zeroes is a valid value to be overwritten.
+        let mut info: libc::proc_vnodepathinfo = unsafe { zeroed() };
+        let size = size_of::<libc::proc_vnodepathinfo>() as c_int;
+        // SAFETY: `info` is `size` bytes and outlives the call.
+        let got = unsafe {
+            libc::proc_pidinfo(
+                pid,
+                libc::PROC_PIDVNODEPATHINFO,
+                0,
+                (&mut info as *mut libc::proc_vnodepathinfo).cast::<c_void>(),
+                size,
+            )
+        };
+        if got != size {
+            return None;
+        }
+        path_from(&info.pvi_cdir)
+    }
+
+    fn path_from(vip: &libc::vnode_info_path) -> Option<String> {
+        // `vip_path` is a MAXPATHLEN C string split into 32-byte rows.
+        let path = c_string(vip.vip_path.as_flattened());
+        (!path.is_empty()).then_some(path)
+    }
+
+    /// A fixed-size C string field; not necessarily NUL-terminated.
+    fn c_string(chars: &[libc::c_char]) -> String {
+        let bytes: Vec<u8> = chars
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as u8)
+            .collect();
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod platform {
+    use super::OpenFile;
+    use std::time::Instant;
+
+    pub(super) fn scan(_deadline: Instant) -> Option<Vec<OpenFile>> {
+        None
+    }
+}
+
+/// Group open files by the process holding them.
+pub fn by_process(files: &[OpenFile]) -> Vec<ProcessFiles> {
+    let mut groups: Vec<ProcessFiles> = Vec::new();
+    for file in files {
+        match groups.iter_mut().find(|group| group.pid == file.pid) {
+            Some(group) => {
+                if !group.paths.contains(&file.path) {
+                    group.paths.push(file.path.clone());
+                }
+            }
+            None => groups.push(ProcessFiles {
+                pid: file.pid,
+                command: file.command.clone(),
+                user: file.user.clone(),
+                paths: vec![file.path.clone()],
+            }),
+        }
+    }
+    groups
+}
+
+/// Parse lsof's default table. NAME may contain spaces, so it is taken as
+/// the rest of the line from its leading `/`: `+D` only reports absolute
+/// paths, and the columns before NAME never contain a slash (lsof escapes
+/// COMMAND, e.g. `Final\x20`).
+pub(crate) fn parse_lsof(stdout: &str) -> Vec<OpenFile> {
+    stdout
+        .lines()
+        .filter(|line| !line.starts_with("COMMAND"))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let command = unescape_command(fields.next()?);
+            let pid = fields.next()?.parse().ok()?;
+            let user = fields.next()?.to_string();
+            let fd = fields.next()?.to_string();
+            let path = &line[line.find(" /")? + 1..];
+            Some(OpenFile {
+                pid,
+                command,
+                user,
+                access: parse_access(&fd),
+                fd,
+                path: path.trim_end().to_string(),
+            })
+        })
+        .collect()
+}
+
+fn parse_access(fd: &str) -> FileAccess {
+    let digits = fd.bytes().take_while(u8::is_ascii_digit).count();
+    if digits == 0 {
+        return FileAccess::Other;
+    }
+    match fd[digits..].chars().next() {
+        Some('r') => FileAccess::Read,
+        Some('w') => FileAccess::Write,
+        Some('u') => FileAccess::ReadWrite,
+        _ => FileAccess::Other,
+    }
+}
+
+/// Undo lsof's `\xNN` escaping of COMMAND (`Final\x20` for "Final Cut").
+fn unescape_command(raw: &str) -> String {
+    let bytes = raw.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes[i] == b'\\' && bytes.get(i + 1) == Some(&b'x');
+        let byte = raw
+            .get(i + 2..i + 4)
+            .filter(|_| escaped)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match byte {
+            Some(byte) => {
+                out.push(byte);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FCP: &str = "COMMAND     PID  USER   FD   TYPE DEVICE SIZE/OFF     NODE NAME\n\
+Final\\x20  4211 admin   42r   REG   52,7 81920000 12345678 /Volumes/CORE/projects/edit.fcpbundle/Original Media/A001.mov\n\
+Final\\x20  4211 admin   43u   REG   52,7    16384 12345679 /Volumes/CORE/projects/edit.fcpbundle/CurrentVersion.fcpevent\n\
+zsh         801 admin  cwd    DIR   52,7      384       2 /Volumes/CORE\n";
+
+    #[test]
+    fn parse_lsof_attributes_files_to_processes() {
+        let files = parse_lsof(FCP);
+
+        assert_eq!(files.len(), 3);
+        assert_eq!(
+            files[0],
+            OpenFile {
+                pid: 4211,
+                command: "Final ".to_string(),
+                user: "admin".to_string(),
+                fd: "42r".to_string(),
+                access: FileAccess::Read,
+                path: "/Volumes/CORE/projects/edit.fcpbundle/Original Media/A001.mov".to_string(),
+            }
+        );
+        assert_eq!(files[1].access, FileAccess::ReadWrite);
+        assert_eq!(files[2].command, "zsh");
+        assert_eq!(files[2].access, FileAccess::Other);
+        assert_eq!(files[2].path, "/Volumes/CORE");
+    }
+
+    #[test]
+    fn parse_lsof_handles_empty_and_garbage() {
+        assert!(parse_lsof("").is_empty());
+        assert!(parse_lsof("lsof: WARNING: can't stat()\n").is_empty());
+        let header_only = "COMMAND PID USER FD TYPE DEVICE SIZE/OFF NODE NAME\n";
+        assert!(parse_lsof(header_only).is_empty());
+    }
+
+    #[test]
+    fn parse_access_reads_mode_suffix() {
+        assert_eq!(parse_access("3r"), FileAccess::Read);
+        assert_eq!(parse_access("12w"), FileAccess::Write);
+        assert_eq!(parse_access("7uW"), FileAccess::ReadWrite);
+        assert_eq!(parse_access("txt"), FileAccess::Other);
+        assert_eq!(parse_access("4"), FileAccess::Other);
+    }
+
+    #[test]
+    fn by_process_groups_and_dedups_paths() {
+        let groups = by_process(&parse_lsof(FCP));
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].pid, 4211);
+        assert_eq!(groups[0].paths.len(), 2);
+        assert_eq!(groups[1].command, "zsh");
+    }
+
+    #[test]
+    fn fd_label_matches_lsof() {
+        assert_eq!(fd_label(42, FileAccess::Read), "42r");
+        assert_eq!(fd_label(7, FileAccess::ReadWrite), "7u");
+        assert_eq!(fd_label(3, FileAccess::Other), "3");
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn platform_scan_reports_access_of_own_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("render.mov");
+        let _writer = std::fs::File::create(&path).unwrap();
+        let _reader = std::fs::File::open(&path).unwrap();
+        let _both = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+
+        let files = platform::scan(Instant::now() + Duration::from_secs(10)).expect("native scan");
+
+        let resolved = path.canonicalize().unwrap();
+        let mut access: Vec<_> = files
+            .iter()
+            .filter(|f| f.pid == std::process::id() && Path::new(&f.path) == resolved)
+            .map(|f| f.access.label())
+            .collect();
+        access.sort();
+        assert_eq!(access, ["r", "rw", "w"], "{:?}", files);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn native_scan_finds_own_open_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clip.mov");
+        let _file = std::fs::File::create(&path).unwrap();
+
+        let files = native(dir.path()).expect("native scan");
+
+        let ours: Vec<_> = files
+            .iter()
+            .filter(|f| f.pid == std::process::id())
+            .collect();
+        assert_eq!(ours.len(), 1, "{:?}", files);
+        assert_eq!(Path::new(&ours[0].path), path.canonicalize().unwrap());
+        assert_eq!(ours[0].access, FileAccess::Write);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn cycle_reuses_one_scan_until_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let mine = |files: Vec<OpenFile>| {
+            files
+                .into_iter()
+                .filter(|f| f.pid == std::process::id())
+                .count()
+        };
+
+        {
+            let _cycle = cycle();
+            let nested = cycle();
+            assert_eq!(mine(native(dir.path()).unwrap()), 0);
+            let _file = std::fs::File::create(dir.path().join("late.txt")).unwrap();
+            // Opened after this cycle's scan.
+            assert_eq!(mine(native(dir.path()).unwrap()), 0);
+            drop(nested);
+            assert_eq!(mine(native(dir.path()).unwrap()), 0);
+        }
+
+        let _file = std::fs::File::open(dir.path().join("late.txt")).unwrap();
+        assert_eq!(mine(native(dir.path()).unwrap()), 1);
+    }
+
+    #[test]
+    fn unescape_command_decodes_hex_escapes() {
+        assert_eq!(unescape_command("Final\\x20Cut"), "Final Cut");
+        assert_eq!(unescape_command("plain"), "plain");
+        assert_eq!(unescape_command("bad\\xZZ"), "bad\\xZZ");
+        assert_eq!(unescape_command("Caf\\xc3\\xa9"), "Café");
+    }
+}
@@ -0,0 +1,226 @@
+//! Reconciler ownership: only one process mounts, unmounts and fails over.
+//!
+//! The tray, `monitor` and a scheduled `reconcile --all` would otherwise
+//! undo each other's work. Whoever reconciles holds an exclusive `flock` on
+//! `~/.mountaineer/reconciler.lock` and writes its PID and role into it. The
+//! kernel drops the lock when the process exits, however it exits, so a
+//! leftover file from a crashed owner never blocks anyone; its contents are
+//! only trusted while the lock is held.
+
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Local, Utc};
+use nix::libc;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::config;
+use crate::fslock::{self, try_lock};
+
+/// A probe from [`owner_at`] holds a shared lock for a moment; retry that
+/// long before concluding someone else owns reconciliation.
+const CLAIM_ATTEMPTS: u32 = 3;
+const CLAIM_RETRY: Duration = Duration::from_millis(50);
+
+/// The process that owns reconciliation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Owner {
+    pub pid: u32,
+    /// What is reconciling: `tray`, `monitor`, `reconcile --all`, ...
+    pub role: String,
+    pub since: DateTime<Utc>,
+}
+
+impl Owner {
+    pub fn describe(&self) -> String {
+        format!(
+            "{} (pid {}, since {})",
+            self.role,
+            self.pid,
+            self.since.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+        )
+    }
+}
+
+/// Held while this process owns reconciliation. Dropping it gives it up.
+#[derive(Debug)]
+pub struct ReconcilerLock {
+    file: File,
+}
+
+impl Drop for ReconcilerLock {
+    fn drop(&mut self) {
+        // Closing the file releases the lock; clear the owner first so the
+        // file does not name a process that no longer owns anything.
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// Outcome of [`claim_at`].
+#[derive(Debug)]
+pub enum Claim {
+    Acquired(ReconcilerLock),
+    /// Another process owns reconciliation. `None` if it has not written
+    /// its details yet.
+    Held(Option<Owner>),
+}
+
+/// Try to take ownership of reconciliation at `path` for `role`, without
+/// waiting for the current owner.
+pub fn claim_at(path: &Path, role: &str) -> Result<Claim> {
+    let mut file = fslock::open(path)?;
+
+    let mut attempt = 1;
+    while !try_lock(&file, libc::LOCK_EX)
+        .with_context(|| format!("failed locking {}", path.display()))?
+    {
+        if attempt == CLAIM_ATTEMPTS {
+            return Ok(Claim::Held(read_owner(&mut file)));
+        }
+        attempt += 1;
+        std::thread::sleep(CLAIM_RETRY);
+    }
+
+    let owner = Owner {
+        pid: std::process::id(),
+        role: role.to_string(),
+        since: Utc::now(),
+    };
+    file.set_len(0)?;
+    file.rewind()?;
+    file.write_all(serde_json::to_string(&owner)?.as_bytes())
+        .with_context(|| format!("failed writing {}", path.display()))?;
+    file.sync_all()?;
+    log::info!("ownership: {} owns reconciliation", owner.describe());
+    Ok(Claim::Acquired(ReconcilerLock { file }))
+}
+
+/// [`claim_at`] on the default lock file.
+pub fn claim(role: &str) -> Result<Claim> {
+    claim_at(&config::reconciler_lock_path(), role)
+}
+
+/// Take ownership for `role`, or fail naming the process that has it.
+pub fn claim_or_refuse(role: &str) -> Result<ReconcilerLock> {
+    match claim(role)? {
+        Claim::Acquired(lock) => Ok(lock),
+        Claim::Held(owner) => Err(refusal(role, owner.as_ref())),
+    }
+}
+
+/// The error for `role` finding reconciliation owned by `owner`.
+pub fn refusal(role: &str, owner: Option<&Owner>) -> anyhow::Error {
+    anyhow!(
+        "cannot run {}: reconciliation is owned by {}\nhint: stop it first, or leave it running and use `mountaineer reload` to make it reconcile now",
+        role,
+        owner
+            .map(Owner::describe)
+            .unwrap_or_else(|| "another mountaineer process".to_string())
+    )
+}
+
+/// Who owns reconciliation at `path`, if anyone.
+pub fn owner_at(path: &Path) -> Option<Owner> {
+    let mut file = File::open(path).ok()?;
+    match try_lock(&file, libc::LOCK_SH) {
+        // Nobody holds it; closing the file drops our shared lock.
+        Ok(true) => None,
+        Ok(false) => read_owner(&mut file),
+        Err(e) => {
+            log::warn!("ownership: cannot probe {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// [`owner_at`] on the default lock file.
+pub fn owner() -> Option<Owner> {
+    owner_at(&config::reconciler_lock_path())
+}
+
+fn read_owner(file: &mut File) -> Option<Owner> {
+    let mut contents = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut contents).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_claim_sees_the_first_owner() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reconciler.lock");
+
+        let Claim::Acquired(_lock) = claim_at(&path, "monitor").unwrap() else {
+            panic!("first claim should succeed");
+        };
+        match claim_at(&path, "reconcile --all").unwrap() {
+            Claim::Held(Some(owner)) => {
+                assert_eq!(owner.pid, std::process::id());
+                assert_eq!(owner.role, "monitor");
+            }
+            other => panic!("expected the lock to be held, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn owner_is_reported_only_while_held() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reconciler.lock");
+        assert_eq!(owner_at(&path), None);
+
+        let lock = match claim_at(&path, "tray").unwrap() {
+            Claim::Acquired(lock) => lock,
+            Claim::Held(owner) => panic!("unexpected owner {:?}", owner),
+        };
+        assert_eq!(owner_at(&path).map(|o| o.role), Some("tray".to_string()));
+
+        drop(lock);
+        assert_eq!(owner_at(&path), None);
+        assert!(matches!(
+            claim_at(&path, "monitor").unwrap(),
+            Claim::Acquired(_)
+        ));
+    }
+
+    #[test]
+    fn stale_owner_details_are_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reconciler.lock");
+        // Left behind by an owner that was killed before it could clear it.
+        std::fs::write(
+            &path,
+            r#"{"pid":1,"role":"monitor","since":"2026-01-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(owner_at(&path), None);
+        assert!(matches!(
+            claim_at(&path, "tray").unwrap(),
+            Claim::Acquired(_)
+        ));
+    }
+
+    #[test]
+    fn refusal_names_the_owner() {
+        let owner = Owner {
+            pid: 4242,
+            role: "tray".to_string(),
+            since: Utc::now(),
+        };
+        let message = refusal("monitor", Some(&owner)).to_string();
+        assert!(
+            message.starts_with("cannot run monitor: reconciliation is owned by tray (pid 4242")
+        );
+        assert!(
+            refusal("monitor", None)
+                .to_string()
+                .contains("another mountaineer process")
+        );
+    }
+}
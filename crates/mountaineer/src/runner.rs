@@ -0,0 +1,652 @@
+//! Every external command, probe and scan the engine depends on, recorded to
+//! `MOUNTAINEER_RECORD` when set and replayed from fixtures in tests.
+
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, OnceLock, mpsc};
+use std::time::{Duration, Instant};
+
+use crate::open_files::OpenFile;
+
+/// Environment variable naming the fixture file that record mode appends to.
+pub const RECORD_ENV: &str = "MOUNTAINEER_RECORD";
+
+/// Recorded in place of output that must not reach a fixture.
+pub const REDACTED: &str = "[redacted]";
+
+/// How long to wait for a command's output after it exits.
+const OUTPUT_GRACE: Duration = Duration::from_millis(500);
+
+/// Captured result of an external command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandOutput {
+    /// Exit code, or `None` when the process was killed by a signal.
+    pub code: Option<i32>,
+    #[serde(default)]
+    pub stdout: String,
+    #[serde(default)]
+    pub stderr: String,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+/// One recorded interaction, stored as a JSON line in fixture files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Interaction {
+    Command {
+        program: String,
+        args: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        output: Option<CommandOutput>,
+        /// Spawn/wait failure message when the command could not produce output.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    Probe {
+        addr: String,
+        reachable: bool,
+    },
+    Alive {
+        path: String,
+        alive: bool,
+    },
+    OpenFiles {
+        path: String,
+        /// `None` when the native scan was unavailable or over budget.
+        #[serde(default)]
+        files: Option<Vec<OpenFile>>,
+    },
+}
+
+#[cfg(test)]
+impl Interaction {
+    fn key(&self) -> String {
+        match self {
+            Interaction::Command { program, args, .. } => command_key(program, args),
+            Interaction::Probe { addr, .. } => format!("probe {}", addr),
+            Interaction::Alive { path, .. } => format!("alive {}", path),
+            Interaction::OpenFiles { path, .. } => format!("open_files {}", path),
+        }
+    }
+}
+
+#[cfg(test)]
+fn command_key(program: &str, args: &[String]) -> String {
+    let mut key = format!("command {}", program);
+    for arg in args {
+        key.push('\u{1f}');
+        key.push_str(arg);
+    }
+    key
+}
+
+/// Run `program` with `args` to completion.
+pub fn run(program: &str, args: &[&str]) -> io::Result<CommandOutput> {
+    run_recorded(program, args, false, || run_live(program, args))
+}
+
+/// [`run`] for a command that prints a secret, such as a stored password.
+/// Its stdout is recorded as [`REDACTED`].
+pub fn run_secret(program: &str, args: &[&str]) -> io::Result<CommandOutput> {
+    run_recorded(program, args, true, || run_live(program, args))
+}
+
+fn run_live(program: &str, args: &[&str]) -> io::Result<CommandOutput> {
+    let output = Command::new(program).args(args).output()?;
+    Ok(CommandOutput {
+        code: output.status.code(),
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    })
+}
+
+/// Run `program` with `args`, writing `input` to its stdin.
+///
+/// Used for secrets that must not appear in argv. Only the command and its
+/// output are recorded; `input` is never written to a fixture. The child
+/// runs without a controlling terminal, so password prompts read `input`
+/// instead of the user's tty.
+pub fn run_with_input(program: &str, args: &[&str], input: &str) -> io::Result<CommandOutput> {
+    run_recorded(program, args, false, || {
+        spawn_and_wait(program, args, Some(input), None)
+    })
+}
+
+/// [`run_with_input`] with the kill-after-`timeout` behavior of [`run_with_timeout`].
+pub fn run_with_input_timeout(
+    program: &str,
+    args: &[&str],
+    input: &str,
+    timeout: Duration,
+) -> io::Result<CommandOutput> {
+    run_recorded(program, args, false, || {
+        spawn_and_wait(program, args, Some(input), Some(timeout))
+    })
+}
+
+/// Run `program` with `args`, killing it if it has not exited within `timeout`.
+///
+/// External commands such as `smbutil view` can hang indefinitely on a dead
+/// server, so callers that run inside the reconcile loop should prefer this.
+pub fn run_with_timeout(
+    program: &str,
+    args: &[&str],
+    timeout: Duration,
+) -> io::Result<CommandOutput> {
+    run_recorded(program, args, false, || {
+        spawn_and_wait(program, args, None, Some(timeout))
+    })
+}
+
+fn spawn_and_wait(
+    program: &str,
+    args: &[&str],
+    input: Option<&str>,
+    timeout: Option<Duration>,
+) -> io::Result<CommandOutput> {
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if input.is_some() {
+        // readpassphrase(3), behind the prompts of mount_smbfs and smbutil,
+        // reads /dev/tty whenever there is a controlling terminal and ignores
+        // stdin. A new session has none.
+        // SAFETY: setsid is async-signal-safe and touches no parent memory.
+        unsafe {
+            command.pre_exec(|| {
+                if nix::libc::setsid() < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+    let mut child = command.spawn()?;
+
+    // Both pipes are drained while the child runs: one that fills a pipe
+    // buffer would otherwise block until it is killed or forever.
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        // A child that exits without reading stdin closes the pipe; that is
+        // reported through its exit status, not as a write error.
+        let _ = stdin.write_all(input.as_bytes());
+    }
+
+    let deadline = timeout.map(|timeout| (Instant::now() + timeout, timeout));
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => {
+                if let Some((deadline, timeout)) = deadline
+                    && Instant::now() >= deadline
+                {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("{} timed out after {}ms", program, timeout.as_millis()),
+                    ));
+                }
+                std::thread::sleep(Duration::from_millis(25));
+            }
+            Err(e) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(e);
+            }
+        }
+    };
+
+    // Anything the command left running in the background keeps its pipes
+    // open, so the readers are only waited on briefly once it has exited.
+    let collect = |receiver: mpsc::Receiver<Vec<u8>>| {
+        let bytes = receiver.recv_timeout(OUTPUT_GRACE).unwrap_or_default();
+        String::from_utf8_lossy(&bytes).into_owned()
+    };
+    Ok(CommandOutput {
+        code: status.code(),
+        stdout: collect(stdout),
+        stderr: collect(stderr),
+    })
+}
+
+/// Read `pipe` to the end on its own thread.
+fn drain(pipe: Option<impl Read + Send + 'static>) -> mpsc::Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        let _ = sender.send(bytes);
+    });
+    receiver
+}
+
+/// Record or replay a reachability probe. `live` performs the real check.
+pub fn probe(addr: &str, live: impl FnOnce() -> bool) -> bool {
+    #[cfg(test)]
+    if replay::is_active() {
+        return matches!(
+            replay::lookup(&format!("probe {}", addr)),
+            Some(Interaction::Probe {
+                reachable: true,
+                ..
+            })
+        );
+    }
+
+    let reachable = live();
+    record(&Interaction::Probe {
+        addr: addr.to_string(),
+        reachable,
+    });
+    reachable
+}
+
+/// Record or replay a mount liveness check. `live` performs the real check.
+pub fn alive(path: &Path, live: impl FnOnce() -> bool) -> bool {
+    let path = path.display().to_string();
+
+    #[cfg(test)]
+    if replay::is_active() {
+        return matches!(
+            replay::lookup(&format!("alive {}", path)),
+            Some(Interaction::Alive { alive: true, .. })
+        );
+    }
+
+    let alive = live();
+    record(&Interaction::Alive { path, alive });
+    alive
+}
+
+/// Record or replay a native open-file scan under `path`. `live` performs
+/// the scan.
+pub fn open_files(
+    path: &Path,
+    live: impl FnOnce() -> Option<Vec<OpenFile>>,
+) -> Option<Vec<OpenFile>> {
+    let path = path.display().to_string();
+
+    #[cfg(test)]
+    if replay::is_active() {
+        return match replay::lookup(&format!("open_files {}", path)) {
+            Some(Interaction::OpenFiles { files, .. }) => files,
+            _ => None,
+        };
+    }
+
+    let files = live();
+    record(&Interaction::OpenFiles {
+        path,
+        files: files.clone(),
+    });
+    files
+}
+
+fn run_recorded(
+    program: &str,
+    args: &[&str],
+    redact_stdout: bool,
+    live: impl FnOnce() -> io::Result<CommandOutput>,
+) -> io::Result<CommandOutput> {
+    let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+
+    #[cfg(test)]
+    if replay::is_active() {
+        return match replay::lookup(&command_key(program, &args)) {
+            Some(Interaction::Command {
+                output: Some(output),
+                ..
+            }) => Ok(output),
+            Some(Interaction::Command { error, .. }) => Err(io::Error::other(
+                error.unwrap_or_else(|| "recorded command failed".to_string()),
+            )),
+            _ => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no recorded interaction for {} {:?}", program, args),
+            )),
+        };
+    }
+
+    let result = live();
+    record(&command_interaction(program, args, &result, redact_stdout));
+    result
+}
+
+fn command_interaction(
+    program: &str,
+    args: Vec<String>,
+    result: &io::Result<CommandOutput>,
+    redact_stdout: bool,
+) -> Interaction {
+    let (output, error) = match result {
+        Ok(output) if redact_stdout => (
+            Some(CommandOutput {
+                stdout: REDACTED.to_string(),
+                ..output.clone()
+            }),
+            None,
+        ),
+        Ok(output) => (Some(output.clone()), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    Interaction::Command {
+        program: program.to_string(),
+        args,
+        output,
+        error,
+    }
+}
+
+fn recorder() -> Option<&'static Mutex<std::fs::File>> {
+    static RECORDER: OnceLock<Option<Mutex<std::fs::File>>> = OnceLock::new();
+    RECORDER
+        .get_or_init(|| {
+            let path = std::env::var_os(RECORD_ENV)?;
+            match OpenOptions::new().create(true).append(true).open(&path) {
+                Ok(file) => {
+                    log::info!(
+                        "runner: recording external interactions to {}",
+                        Path::new(&path).display()
+                    );
+                    Some(Mutex::new(file))
+                }
+                Err(e) => {
+                    log::warn!(
+                        "runner: cannot open record file {}: {}",
+                        Path::new(&path).display(),
+                        e
+                    );
+                    None
+                }
+            }
+        })
+        .as_ref()
+}
+
+fn record(interaction: &Interaction) {
+    let Some(recorder) = recorder() else {
+        return;
+    };
+    let line = match serde_json::to_string(interaction) {
+        Ok(line) => line,
+        Err(e) => {
+            log::warn!("runner: failed to serialize interaction: {}", e);
+            return;
+        }
+    };
+    let mut file = recorder.lock().unwrap_or_else(|e| e.into_inner());
+    if let Err(e) = writeln!(file, "{}", line) {
+        log::warn!("runner: failed to record interaction: {}", e);
+    }
+}
+
+/// Parse a fixture: one JSON [`Interaction`] per line. Blank lines and lines
+/// starting with `#` are ignored so captured sessions can be annotated.
+#[cfg(test)]
+pub fn parse_fixture(text: &str) -> anyhow::Result<Vec<Interaction>> {
+    use anyhow::Context;
+
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let trimmed = line.trim();
+            !trimmed.is_empty() && !trimmed.starts_with('#')
+        })
+        .map(|(idx, line)| {
+            serde_json::from_str(line).with_context(|| format!("fixture line {}", idx + 1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub mod replay {
+    //! Thread-local replay of recorded fixtures. Calls with the same key take
+    //! entries in order; the last one keeps answering once they run out.
+
+    use super::Interaction;
+    use std::cell::RefCell;
+    use std::collections::{HashMap, VecDeque};
+
+    struct Session {
+        pending: HashMap<String, VecDeque<Interaction>>,
+        last: HashMap<String, Interaction>,
+        misses: Vec<String>,
+    }
+
+    thread_local! {
+        static SESSION: RefCell<Option<Session>> = const { RefCell::new(None) };
+    }
+
+    /// Active replay on the current thread; uninstalled when dropped.
+    pub struct Guard {
+        _private: (),
+    }
+
+    impl Guard {
+        /// Number of recorded interactions that were never consumed.
+        pub fn unconsumed(&self) -> usize {
+            SESSION.with(|s| {
+                s.borrow()
+                    .as_ref()
+                    .map(|session| session.pending.values().map(VecDeque::len).sum())
+                    .unwrap_or(0)
+            })
+        }
+
+        /// Keys that were requested but had no recorded interaction.
+        pub fn misses(&self) -> Vec<String> {
+            SESSION.with(|s| {
+                s.borrow()
+                    .as_ref()
+                    .map(|session| session.misses.clone())
+                    .unwrap_or_default()
+            })
+        }
+    }
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            SESSION.with(|s| s.borrow_mut().take());
+        }
+    }
+
+    /// Install `interactions` as the replay source for the current thread.
+    pub fn install(interactions: Vec<Interaction>) -> Guard {
+        let mut pending: HashMap<String, VecDeque<Interaction>> = HashMap::new();
+        for interaction in interactions {
+            pending
+                .entry(interaction.key())
+                .or_default()
+                .push_back(interaction);
+        }
+        SESSION.with(|s| {
+            *s.borrow_mut() = Some(Session {
+                pending,
+                last: HashMap::new(),
+                misses: Vec::new(),
+            })
+        });
+        Guard { _private: () }
+    }
+
+    pub(super) fn is_active() -> bool {
+        SESSION.with(|s| s.borrow().is_some())
+    }
+
+    pub(super) fn lookup(key: &str) -> Option<Interaction> {
+        SESSION.with(|s| {
+            let mut borrowed = s.borrow_mut();
+            let session = borrowed.as_mut()?;
+            if let Some(next) = session.pending.get_mut(key).and_then(VecDeque::pop_front) {
+                session.last.insert(key.to_string(), next.clone());
+                return Some(next);
+            }
+            let last = session.last.get(key).cloned();
+            if last.is_none() {
+                log::warn!("replay: no recorded interaction for {:?}", key);
+                session.misses.push(key.to_string());
+            }
+            last
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interaction_json_roundtrip() {
+        let interaction = Interaction::Command {
+            program: "mount".to_string(),
+            args: vec!["-t".to_string(), "smbfs".to_string()],
+            output: Some(CommandOutput {
+                code: Some(0),
+                stdout: "//u@10.10.10.1/CORE on /Volumes/CORE (smbfs)\n".to_string(),
+                stderr: String::new(),
+            }),
+            error: None,
+        };
+        let line = serde_json::to_string(&interaction).unwrap();
+        assert!(line.contains("\"kind\":\"command\""));
+        assert!(!line.contains("\"error\""));
+        let parsed = parse_fixture(&line).unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].key(), interaction.key());
+    }
+
+    #[test]
+    fn parse_fixture_skips_comments_and_blank_lines() {
+        let text = r#"
+# TB cable pulled
+{"kind":"probe","addr":"10.10.10.1:445","reachable":false}
+
+{"kind":"alive","path":"/Volumes/CORE","alive":true}
+"#;
+        let parsed = parse_fixture(text).unwrap();
+        assert_eq!(parsed.len(), 2);
+    }
+
+    #[test]
+    fn parse_fixture_reports_bad_line() {
+        let err = parse_fixture("{\"kind\":\"probe\"}").unwrap_err();
+        assert!(err.to_string().contains("fixture line 1"));
+    }
+
+    #[test]
+    fn replay_consumes_in_order_then_repeats_last() {
+        let fixture = r#"
+{"kind":"command","program":"mount","args":["-t","smbfs"],"output":{"code":0,"stdout":"first"}}
+{"kind":"command","program":"mount","args":["-t","smbfs"],"output":{"code":0,"stdout":"second"}}
+"#;
+        let guard = replay::install(parse_fixture(fixture).unwrap());
+        assert_eq!(run("mount", &["-t", "smbfs"]).unwrap().stdout, "first");
+        assert_eq!(run("mount", &["-t", "smbfs"]).unwrap().stdout, "second");
+        assert_eq!(run("mount", &["-t", "smbfs"]).unwrap().stdout, "second");
+        assert_eq!(guard.unconsumed(), 0);
+        assert!(guard.misses().is_empty());
+    }
+
+    #[test]
+    fn replay_unknown_command_is_an_error() {
+        let guard = replay::install(Vec::new());
+        let err = run("diskutil", &["unmount", "/Volumes/CORE"]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(guard.misses().len(), 1);
+    }
+
+    #[test]
+    fn replay_recorded_spawn_error() {
+        let fixture =
+            r#"{"kind":"command","program":"smbutil","args":["view"],"error":"timed out"}"#;
+        let _guard = replay::install(parse_fixture(fixture).unwrap());
+        let err = run("smbutil", &["view"]).unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+    }
+
+    #[test]
+    fn replay_answers_probe_and_alive_without_calling_live() {
+        let fixture = r#"
+{"kind":"probe","addr":"10.10.10.1:445","reachable":true}
+{"kind":"alive","path":"/Volumes/CORE","alive":false}
+"#;
+        let _guard = replay::install(parse_fixture(fixture).unwrap());
+        assert!(probe("10.10.10.1:445", || panic!("live probe called")));
+        assert!(!alive(Path::new("/Volumes/CORE"), || panic!(
+            "live check called"
+        )));
+    }
+
+    #[test]
+    fn secret_stdout_is_redacted_when_recorded() {
+        let result = Ok(CommandOutput {
+            code: Some(0),
+            stdout: "s3cret\n".to_string(),
+            stderr: String::new(),
+        });
+        let line = serde_json::to_string(&command_interaction(
+            "security",
+            vec!["find-internet-password".to_string()],
+            &result,
+            true,
+        ))
+        .unwrap();
+        assert!(!line.contains("s3cret"));
+        assert!(line.contains(REDACTED));
+    }
+
+    #[test]
+    fn input_reaches_prompts_even_with_a_terminal() {
+        // Reads /dev/tty when it can, as readpassphrase does, so this passes
+        // only if the child has no controlling terminal, wherever tests run.
+        let script =
+            "if (: </dev/tty) 2>/dev/null; then echo tty; else read p; echo \"stdin:$p\"; fi";
+        let output = run_with_input("sh", &["-c", script], "hunter2\n").unwrap();
+        assert_eq!(output.stdout, "stdin:hunter2\n");
+    }
+
+    #[test]
+    fn output_larger_than_a_pipe_buffer_does_not_block() {
+        let output = run_with_timeout(
+            "sh",
+            &[
+                "-c",
+                "head -c 1048576 /dev/zero; head -c 1048576 /dev/zero >&2",
+            ],
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        assert!(output.success());
+        assert_eq!(output.stdout.len(), 1 << 20);
+        assert_eq!(output.stderr.len(), 1 << 20);
+    }
+
+    #[test]
+    fn guard_drop_uninstalls_replay() {
+        {
+            let _guard = replay::install(Vec::new());
+            assert!(replay::is_active());
+        }
+        assert!(!replay::is_active());
+    }
+}
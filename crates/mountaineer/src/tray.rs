@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
@@ -7,26 +7,46 @@ use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu};
 use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
 
 use crate::config::{self, AliasConfig, Backend, ShareConfig};
+use crate::config_watch::{self, ConfigWatcher};
+use crate::control;
+use crate::credentials;
 use crate::dialogs;
 use crate::engine::{self, RuntimeState, ShareStatus, SwitchResult};
+use crate::events::{self, ShareEventKind};
 use crate::logging;
 use crate::network;
+use crate::notify;
+use crate::open_files::{self, OpenFile};
+use crate::ownership;
+use crate::webhooks;
+
+/// How long "Switch to X When Files Close" waits before giving up.
+const TRAY_DRAIN_TIMEOUT: Duration = Duration::from_secs(30 * 60);
 
 /// Shared state for the tray menu, updated by the background reconciliation loop.
 struct TrayState {
     statuses: Vec<ShareStatus>,
     runtime_state: RuntimeState,
-    /// Shares that returned BusyOpenFiles on last switch attempt (spec 14).
+    /// Shares that returned BusyOpenFiles on last switch attempt (spec 14),
+    /// with the files that blocked it.
     /// Cleared on successful switch or next reconcile cycle.
-    busy_shares: HashSet<String>,
+    busy_shares: HashMap<String, Vec<OpenFile>>,
     /// In-progress operation message shown at the top of the menu (spec 14/17).
     /// Set before an operation, cleared after completion.
     in_progress: Option<String>,
+    /// Another process owns reconciliation, so the tray only shows status.
+    reconciled_by: Option<ownership::Owner>,
+    /// This tray holds the reconciler lock. While it does not, menu actions
+    /// that mount, unmount or change runtime state are sent to the owner.
+    reconciling: bool,
+    /// Why the saved config.toml is not in use; the last good one is.
+    config_error: Option<String>,
 }
 
 pub fn install() {
     // Load initial state
-    let cfg = config::load().unwrap_or_default();
+    let watcher = ConfigWatcher::open();
+    let cfg = watcher.config().clone();
     let mut runtime_state = engine::load_runtime_state().unwrap_or_default();
     let statuses = engine::verify_all(&cfg, &mut runtime_state);
     let initial_health = compute_health(&statuses);
@@ -34,10 +54,17 @@ pub fn install() {
     let state = Arc::new(Mutex::new(TrayState {
         statuses,
         runtime_state,
-        busy_shares: HashSet::new(),
+        busy_shares: HashMap::new(),
         in_progress: None,
+        reconciled_by: None,
+        reconciling: false,
+        config_error: watcher.error().map(str::to_string),
     }));
 
+    // Retry webhook deliveries left over from earlier runs.
+    webhooks::start_sender();
+    notify::init();
+
     let menu = build_dynamic_menu(&state);
     let tray = TrayIconBuilder::new()
         .with_menu(Box::new(menu))
@@ -74,6 +101,19 @@ pub fn install() {
         })
         .expect("failed to spawn tray network bridge thread");
 
+    // Saving config.toml wakes the reconcile loop the same way.
+    let config_changed = Arc::new(AtomicBool::new(false));
+    let config_changed_writer = Arc::clone(&config_changed);
+    if let Err(e) = config_watch::watch(config::config_path(), move || {
+        config_changed_writer.store(true, Ordering::Release);
+    }) {
+        log::warn!("Tray: config watch unavailable: {:#}", e);
+    }
+
+    // CLI commands sent over the control socket run on the reconcile thread,
+    // between passes, so they never race it over runtime state.
+    let (control_tx, control_rx) = std::sync::mpsc::channel::<control::Incoming>();
+
     // Dirty flag: set by reconcile thread when TrayState is updated,
     // read by main thread to trigger menu rebuild.
     let state_dirty = Arc::new(AtomicBool::new(false));
@@ -85,24 +125,76 @@ pub fn install() {
     std::thread::Builder::new()
         .name("tray-reconcile".into())
         .spawn(move || {
+            // Held while the tray owns reconciliation. Until it does (say,
+            // `monitor` is running) the tray only verifies, and tries again
+            // every pass so it takes over once the owner exits.
+            let mut lock: Option<ownership::ReconcilerLock> = None;
+            let mut watcher = watcher;
             loop {
-                // Load config and reconcile
-                let cfg = config::load().unwrap_or_default();
-                let check_interval = cfg.global.check_interval_secs;
+                let mut reconciled_by = None;
+                let mut just_claimed = false;
+                if lock.is_none() {
+                    match ownership::claim("tray") {
+                        Ok(ownership::Claim::Acquired(acquired)) => {
+                            lock = Some(acquired);
+                            just_claimed = true;
+                            let control_tx = control_tx.clone();
+                            if let Err(e) = control::serve_default(move |incoming| {
+                                let _ = control_tx.send(incoming);
+                            }) {
+                                log::warn!("Tray: control socket unavailable: {:#}", e);
+                            }
+                        }
+                        Ok(ownership::Claim::Held(owner)) => reconciled_by = owner,
+                        Err(e) => log::warn!("Tray: cannot claim reconciliation: {:#}", e),
+                    }
+                }
 
-                {
+                let cfg = {
                     let mut guard = state_for_reconcile.lock().unwrap();
-                    guard.statuses = engine::reconcile_all(&cfg, &mut guard.runtime_state);
-                    let _ = engine::save_runtime_state(&guard.runtime_state);
+                    let cfg = if lock.is_some() {
+                        if let Err(e) = engine::refresh_runtime_state(&mut guard.runtime_state) {
+                            log::warn!("Tray: cannot read runtime state: {:#}", e);
+                        }
+                        // Apply config.toml edits; an invalid one keeps the last good config.
+                        for message in watcher.update(&mut guard.runtime_state) {
+                            log::info!("Tray: config: {}", message);
+                        }
+                        let cfg = watcher.config().clone();
+                        if just_claimed {
+                            engine::recover_interrupted_switches(&cfg, &mut guard.runtime_state);
+                        }
+                        guard.statuses = engine::reconcile_all(&cfg, &mut guard.runtime_state);
+                        let _ = engine::save_runtime_state(&mut guard.runtime_state);
+                        cfg
+                    } else {
+                        // The owner applies config changes to the shares;
+                        // the watcher keeps them until this tray is one.
+                        watcher.reload();
+                        let cfg = watcher.config().clone();
+                        // The owner's state file is the truth; never save over it.
+                        if let Ok(on_disk) = engine::load_runtime_state() {
+                            guard.runtime_state = on_disk;
+                        }
+                        guard.statuses = engine::verify_all(&cfg, &mut guard.runtime_state);
+                        cfg
+                    };
+                    guard.reconciled_by = reconciled_by;
+                    guard.reconciling = lock.is_some();
+                    guard.config_error = watcher.error().map(str::to_string);
                     // Clear busy_shares — reconcile may have resolved the open-files condition
                     guard.busy_shares.clear();
-                }
+                    cfg
+                };
+                let check_interval = cfg.global.check_interval_secs;
 
                 // Signal main thread to rebuild menu
                 dirty_writer.store(true, Ordering::Release);
 
                 // Poll at 500ms granularity so network events trigger reconcile within
                 // ~500ms instead of waiting the full check_interval (spec 11).
+                // Control requests are answered while waiting; the menu is
+                // refreshed by the pass that follows each one that changed state.
                 let poll_ms = 500u64;
                 let total_wait_ms = check_interval * 1000;
                 let mut waited_ms = 0u64;
@@ -111,7 +203,30 @@ pub fn install() {
                         log::info!("Tray: network change flag set, triggering immediate reconcile");
                         break;
                     }
-                    std::thread::sleep(Duration::from_millis(poll_ms));
+                    if config_changed.swap(false, Ordering::AcqRel) {
+                        log::info!("Tray: config.toml changed, triggering immediate reconcile");
+                        break;
+                    }
+                    if let Ok(incoming) = control_rx.recv_timeout(Duration::from_millis(poll_ms)) {
+                        let changes_state = *incoming.call() != control::Call::Status;
+                        let result = {
+                            let mut guard = state_for_reconcile.lock().unwrap();
+                            if *incoming.call() == control::Call::Reload {
+                                for message in watcher.update(&mut guard.runtime_state) {
+                                    log::info!("Tray: config: {}", message);
+                                }
+                            }
+                            control::execute(
+                                watcher.config(),
+                                incoming.call(),
+                                &mut guard.runtime_state,
+                            )
+                        };
+                        incoming.respond(result);
+                        if changes_state {
+                            break;
+                        }
+                    }
                     waited_ms += poll_ms;
                 }
             }
@@ -145,7 +260,13 @@ fn run_main_loop(state: Arc<Mutex<TrayState>>, tray: TrayIcon, state_dirty: Arc<
             }
         }
 
-        // 3. If background reconcile updated state, rebuild menu on main thread
+        // 3. "Switch now" clicked on a notification: same path as the menu item
+        for action in notify::take_actions() {
+            let id = format!("switch-{}-{}", action.share, action.to.short_label());
+            handle_menu_event(&id, &state, &tray);
+        }
+
+        // 4. If background reconcile updated state, rebuild menu on main thread
         if state_dirty.swap(false, Ordering::AcqRel) {
             let health = {
                 let guard = state.lock().unwrap();
@@ -221,6 +342,14 @@ fn handle_menu_event(id: &str, state: &Arc<Mutex<TrayState>>, tray: &TrayIcon) {
             );
             rebuild_menu(state, tray);
         }
+        "toggle-reclaim-foreign-mounts" => {
+            toggle_config_bool(
+                "reclaim_foreign_mounts",
+                |g| g.reclaim_foreign_mounts,
+                |g, v| g.reclaim_foreign_mounts = v,
+            );
+            rebuild_menu(state, tray);
+        }
         "add-favorite" => {
             handle_add_favorite(state, tray);
         }
@@ -235,6 +364,21 @@ fn handle_menu_event(id: &str, state: &Arc<Mutex<TrayState>>, tray: &TrayIcon) {
                 handle_remove_favorite(share_name, state, tray);
             }
         }
+        _ if id.starts_with("adopt-mount-") => {
+            if let Some(mount_point) = id.strip_prefix("adopt-mount-") {
+                handle_adopt_mount(mount_point, state, tray);
+            }
+        }
+        _ if id.starts_with("reclaim-foreign-") => {
+            if let Some(share_name) = id.strip_prefix("reclaim-foreign-") {
+                handle_reclaim_foreign(share_name, state, tray);
+            }
+        }
+        _ if id.starts_with("reauth-") => {
+            if let Some(share_name) = id.strip_prefix("reauth-") {
+                handle_reauth(share_name, state, tray);
+            }
+        }
         _ if id.starts_with("remove-alias-") => {
             if let Some(alias_name) = id.strip_prefix("remove-alias-") {
                 handle_remove_alias(alias_name, state, tray);
@@ -261,6 +405,24 @@ fn handle_menu_event(id: &str, state: &Arc<Mutex<TrayState>>, tray: &TrayIcon) {
                 }
             }
         }
+        _ if id.starts_with("drain-switch-") => {
+            // Parse: drain-switch-{share}-{backend}
+            if let Some(rest) = id.strip_prefix("drain-switch-") {
+                if let Some((share_name, backend_str)) = rest.rsplit_once('-') {
+                    let to = match backend_str {
+                        "tb" => Backend::Tb,
+                        "fallback" | "fb" => Backend::Fallback,
+                        _ => return,
+                    };
+                    handle_drain_switch(share_name, to, state, tray);
+                }
+            }
+        }
+        _ if id.starts_with("cancel-pending-") => {
+            if let Some(share_name) = id.strip_prefix("cancel-pending-") {
+                handle_cancel_pending(share_name, state, tray);
+            }
+        }
         _ if id.starts_with("switch-") => {
             // Parse: switch-{share}-{backend}
             let parts: Vec<&str> = id
@@ -327,9 +489,25 @@ fn handle_switch_with_force(
         Some(b) => b,
         None => {
             log::warn!("{}: no current backend, will do initial mount", share_name);
+            if !guard.reconciling {
+                drop(guard);
+                let progress = format!("Mounting {}...", share_name);
+                let reply = request_owner(
+                    &control::Call::Reload,
+                    "Mount Failed",
+                    progress,
+                    state,
+                    tray,
+                );
+                if let Some(statuses) = reply {
+                    state.lock().unwrap().statuses = statuses;
+                }
+                rebuild_menu_and_icon(state, tray);
+                return;
+            }
             // No active backend — trigger a reconcile to do initial mount through the engine
             guard.statuses = engine::reconcile_all(&cfg, &mut guard.runtime_state);
-            let _ = engine::save_runtime_state(&guard.runtime_state);
+            let _ = engine::save_runtime_state(&mut guard.runtime_state);
             drop(guard);
             let new_menu = build_dynamic_menu(state);
             tray.set_menu(Some(Box::new(new_menu)));
@@ -342,11 +520,11 @@ fn handle_switch_with_force(
     // P10.1: Before switching, check for open files and show warning with file count
     // (spec 14 AC 2). Only check when not forcing — force bypasses open-file checks.
     if !force {
-        let mount_point = config::volume_mount_path(&share.share_name);
-        let open_count = engine::open_handle_count(&mount_point);
-        if open_count > 0 {
+        let mount_point = config::share_mount_path(&share);
+        let files = open_files::list(&mount_point);
+        if !files.is_empty() {
             drop(guard);
-            let proceed = dialogs::show_open_files_warning(share_name, open_count, to);
+            let proceed = dialogs::show_open_files_warning(share_name, &files, to);
             if !proceed {
                 return; // User cancelled
             }
@@ -355,12 +533,29 @@ fn handle_switch_with_force(
         }
     }
 
+    let progress = format!("Switching {} to {}...", share_name, to.short_label());
+    if !guard.reconciling {
+        drop(guard);
+        let call = control::Call::Switch {
+            share: share.name.clone(),
+            to,
+            force,
+        };
+        let reply: Option<control::SwitchReply> =
+            request_owner(&call, "Switch Failed", progress, state, tray);
+        if let Some(reply) = reply {
+            let mut guard = state.lock().unwrap();
+            guard.busy_shares.remove(&share_key);
+            if !reply.already {
+                guard.statuses = reply.statuses;
+            }
+        }
+        rebuild_menu_and_icon(state, tray);
+        return;
+    }
+
     // Show in-progress indicator in the menu (spec 14/17)
-    guard.in_progress = Some(format!(
-        "Switching {} to {}...",
-        share_name,
-        to.short_label()
-    ));
+    guard.in_progress = Some(progress);
     drop(guard);
     rebuild_menu(state, tray);
 
@@ -373,6 +568,7 @@ fn handle_switch_with_force(
         from,
         to,
         force,
+        engine::SwitchReason::Manual,
     );
 
     // Clear in-progress indicator
@@ -387,12 +583,13 @@ fn handle_switch_with_force(
                 to.short_label()
             );
             guard.busy_shares.remove(&share_key);
-            let _ = engine::save_runtime_state(&guard.runtime_state);
+            let _ = engine::save_runtime_state(&mut guard.runtime_state);
 
             // Refresh statuses
             drop(guard);
             let mut guard = state.lock().unwrap();
             guard.statuses = engine::verify_all(&cfg, &mut guard.runtime_state);
+            events::publish_for(&cfg, ShareEventKind::Switch, &guard.statuses, share_name);
 
             // Update menu and icon
             let health = compute_health(&guard.statuses);
@@ -408,10 +605,25 @@ fn handle_switch_with_force(
                 force
             );
             // Track this share as busy so the menu can show "Force Switch" (spec 14)
-            guard.busy_shares.insert(share_key);
+            let files = open_files::list(&config::share_mount_path(&share));
+            guard.busy_shares.insert(share_key, files);
             drop(guard);
             rebuild_menu(state, tray);
         }
+        SwitchResult::Vetoed(e) => {
+            log::warn!("{}: switch vetoed by pre-switch hook: {}", share_name, e);
+            drop(guard);
+            dialogs::show_error_dialog(
+                "Switch Cancelled by Hook",
+                &format!(
+                    "The pre-switch hook for '{}' refused the switch to {}:\n\n{}",
+                    share_name,
+                    to.short_label(),
+                    e
+                ),
+            );
+            rebuild_menu(state, tray);
+        }
         // P10.2: Show error dialogs on switch failure (spec 14 AC 5)
         SwitchResult::UnmountFailed(e) => {
             log::error!("{}: unmount failed: {}", share_name, e);
@@ -427,7 +639,11 @@ fn handle_switch_with_force(
             );
             rebuild_menu(state, tray);
         }
-        SwitchResult::MountFailed { error, rolled_back } => {
+        SwitchResult::MountFailed {
+            error,
+            rolled_back,
+            kind,
+        } => {
             log::error!(
                 "{}: mount failed: {} (rolled back: {})",
                 share_name,
@@ -443,10 +659,11 @@ fn handle_switch_with_force(
             dialogs::show_error_dialog(
                 "Switch Failed",
                 &format!(
-                    "Failed to mount '{}' via {}:\n\n{}{}",
+                    "Failed to mount '{}' via {}:\n\n{}\n\n{}{}",
                     share_name,
                     to.short_label(),
                     error,
+                    kind.hint(),
                     rollback_msg
                 ),
             );
@@ -465,6 +682,16 @@ fn handle_mount_all(state: &Arc<Mutex<TrayState>>, tray: &TrayIcon) {
         }
     };
 
+    if reconciled_elsewhere(state) {
+        let progress = "Mounting all shares...".to_string();
+        let reply = request_owner(&control::Call::Mount, "Mount Failed", progress, state, tray);
+        if let Some(statuses) = reply {
+            state.lock().unwrap().statuses = statuses;
+        }
+        rebuild_menu_and_icon(state, tray);
+        return;
+    }
+
     {
         let mut guard = state.lock().unwrap();
         guard.in_progress = Some("Mounting all shares...".to_string());
@@ -473,7 +700,7 @@ fn handle_mount_all(state: &Arc<Mutex<TrayState>>, tray: &TrayIcon) {
 
     let mut guard = state.lock().unwrap();
     guard.statuses = engine::mount_all(&cfg, &mut guard.runtime_state);
-    let _ = engine::save_runtime_state(&guard.runtime_state);
+    let _ = engine::save_runtime_state(&mut guard.runtime_state);
     guard.in_progress = None;
 
     let health = compute_health(&guard.statuses);
@@ -496,16 +723,32 @@ fn handle_unmount_all(state: &Arc<Mutex<TrayState>>, tray: &TrayIcon) {
         }
     };
 
-    {
-        let mut guard = state.lock().unwrap();
-        guard.in_progress = Some("Unmounting all shares...".to_string());
-    }
-    rebuild_menu(state, tray);
+    let results: Vec<engine::UnmountResult> = if reconciled_elsewhere(state) {
+        let call = control::Call::Unmount { force: false };
+        let progress = "Unmounting all shares...".to_string();
+        match request_owner(&call, "Unmount Failed", progress, state, tray) {
+            Some(results) => results,
+            None => return,
+        }
+    } else {
+        {
+            let mut guard = state.lock().unwrap();
+            guard.in_progress = Some("Unmounting all shares...".to_string());
+        }
+        rebuild_menu(state, tray);
 
+        let mut guard = state.lock().unwrap();
+        let results = engine::unmount_all(&cfg, &mut guard.runtime_state, false);
+        let _ = engine::save_runtime_state(&mut guard.runtime_state);
+        guard.in_progress = None;
+        results
+    };
     let mut guard = state.lock().unwrap();
-    let results = engine::unmount_all(&cfg, &mut guard.runtime_state, false);
-    let _ = engine::save_runtime_state(&guard.runtime_state);
-    guard.in_progress = None;
+    if !guard.reconciling
+        && let Ok(on_disk) = engine::load_runtime_state()
+    {
+        guard.runtime_state = on_disk;
+    }
 
     // P10.4: Collect busy share names and unmounted count for summary dialog (spec 17 AC 3)
     let mut busy_names: Vec<String> = Vec::new();
@@ -721,6 +964,7 @@ fn handle_add_favorite(state: &Arc<Mutex<TrayState>>, tray: &TrayIcon) {
             .map(|s| s.trim().to_string())
             .filter(|s| !s.is_empty())
             .unwrap_or_else(|| share_name.clone()),
+        ..ShareConfig::default()
     };
 
     // Load config, add share, save — same code path as CLI favorites add
@@ -746,6 +990,12 @@ fn handle_add_favorite(state: &Arc<Mutex<TrayState>>, tray: &TrayIcon) {
 
     log::info!("Tray: added favorite '{}'", share_name);
 
+    // The owner's config watcher picks the new share up and mounts it.
+    if reconciled_elsewhere(state) {
+        rebuild_menu(state, tray);
+        return;
+    }
+
     // Show in-progress indicator and trigger immediate reconcile to mount
     {
         let mut guard = state.lock().unwrap();
@@ -756,7 +1006,7 @@ fn handle_add_favorite(state: &Arc<Mutex<TrayState>>, tray: &TrayIcon) {
     {
         let mut guard = state.lock().unwrap();
         guard.statuses = engine::reconcile_all(&cfg, &mut guard.runtime_state);
-        let _ = engine::save_runtime_state(&guard.runtime_state);
+        let _ = engine::save_runtime_state(&mut guard.runtime_state);
         guard.in_progress = None;
     }
 
@@ -770,6 +1020,314 @@ fn handle_add_favorite(state: &Arc<Mutex<TrayState>>, tray: &TrayIcon) {
     let _ = tray.set_icon(Some(make_icon_for_health(health)));
 }
 
+/// Handle "Adopt Existing Mount" for an SMB mount Mountaineer does not manage.
+/// Takes it over as a new favorite without unmounting it.
+fn handle_adopt_mount(mount_point: &str, state: &Arc<Mutex<TrayState>>, tray: &TrayIcon) {
+    let mut cfg = match config::load() {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to load config for adopt-mount: {}", e);
+            dialogs::show_error_dialog("Error", &format!("Failed to load config: {}", e));
+            return;
+        }
+    };
+    let Some(mount) = engine::unmanaged_mounts(&cfg)
+        .into_iter()
+        .find(|m| m.mount_point == mount_point)
+    else {
+        dialogs::show_error_dialog(
+            "Cannot Adopt Mount",
+            &format!(
+                "{} is no longer mounted or is already managed.",
+                mount_point
+            ),
+        );
+        rebuild_menu(state, tray);
+        return;
+    };
+
+    let description = format!(
+        "//{}/{} is mounted at {}. Mountaineer will manage it as a favorite without unmounting it.",
+        mount.host, mount.share, mount.mount_point
+    );
+    let suggestion = engine::suggest_alternate_host(&cfg, &mount.host);
+    let Some(input) = dialogs::show_adopt_mount_dialog(&description, &mount.share, suggestion)
+    else {
+        return;
+    };
+    if input.share_name.trim().is_empty() {
+        dialogs::show_error_dialog("Missing Fields", "A favorite name is required.");
+        return;
+    }
+
+    {
+        let mut guard = state.lock().unwrap();
+        let adopt = |cfg: &mut config::Config, runtime_state: &mut RuntimeState| {
+            engine::adopt_mount(
+                cfg,
+                runtime_state,
+                &mount,
+                &input.share_name,
+                input.mounted_as,
+                &input.alternate_host,
+            )
+        };
+        // Not the owner: write state.json under its lock before config.toml
+        // names the share, so the owner never mounts it a second time.
+        let result = if guard.reconciling {
+            adopt(&mut cfg, &mut guard.runtime_state)
+        } else {
+            engine::update_runtime_state(|runtime_state| adopt(&mut cfg, runtime_state))
+                .and_then(|result| result)
+        };
+        if let Err(e) = result {
+            drop(guard);
+            dialogs::show_error_dialog("Cannot Adopt Mount", &format!("{:#}", e));
+            return;
+        }
+        if let Err(e) = config::save(&cfg) {
+            log::error!("Failed to save config after adopt-mount: {}", e);
+            drop(guard);
+            dialogs::show_error_dialog("Error", &format!("Failed to save config: {}", e));
+            return;
+        }
+        if guard.reconciling {
+            let _ = engine::save_runtime_state(&mut guard.runtime_state);
+        } else if let Ok(on_disk) = engine::load_runtime_state() {
+            guard.runtime_state = on_disk;
+        }
+        guard.statuses = engine::verify_all(&cfg, &mut guard.runtime_state);
+    }
+
+    log::info!(
+        "Tray: adopted {} as favorite '{}'",
+        mount.mount_point,
+        input.share_name.trim()
+    );
+    rebuild_menu(state, tray);
+}
+
+/// Handle "Reclaim Duplicate Mounts" for a share: unmount idle foreign copies
+/// of it, leaving busy ones in place.
+fn handle_reclaim_foreign(share_name: &str, state: &Arc<Mutex<TrayState>>, tray: &TrayIcon) {
+    let cfg = match config::load() {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to load config for reclaim: {}", e);
+            return;
+        }
+    };
+
+    let results = if reconciled_elsewhere(state) {
+        let progress = format!("Reclaiming duplicate mounts of {}...", share_name);
+        match request_owner(
+            &control::Call::Dedupe,
+            "Reclaim Failed",
+            progress,
+            state,
+            tray,
+        ) {
+            Some(results) => results,
+            None => return,
+        }
+    } else {
+        engine::dedupe_mounts(&cfg, true)
+    };
+    let left: Vec<String> = results
+        .into_iter()
+        .filter(|result| result.share.eq_ignore_ascii_case(share_name))
+        .flat_map(|result| result.duplicates)
+        .filter(|duplicate| !duplicate.unmounted)
+        .map(|duplicate| {
+            format!(
+                "{}: {}",
+                duplicate.mount_point,
+                duplicate.message.unwrap_or_default()
+            )
+        })
+        .collect();
+    log::info!("Tray: reclaimed duplicate mounts of '{}'", share_name);
+
+    {
+        let mut guard = state.lock().unwrap();
+        guard.statuses = engine::verify_all(&cfg, &mut guard.runtime_state);
+    }
+    rebuild_menu(state, tray);
+
+    if !left.is_empty() {
+        dialogs::show_error_dialog("Some Mounts Were Left in Place", &left.join("\n"));
+    }
+}
+
+/// Handle "Switch to X When Files Close": park the switch in runtime state so
+/// the reconcile loop runs it once the mount is idle (or drops it after
+/// `TRAY_DRAIN_TIMEOUT`).
+fn handle_drain_switch(
+    share_name: &str,
+    to: Backend,
+    state: &Arc<Mutex<TrayState>>,
+    tray: &TrayIcon,
+) {
+    let cfg = match config::load() {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to load config for drained switch: {}", e);
+            return;
+        }
+    };
+    log::info!(
+        "Tray: switching {} to {} once files close",
+        share_name,
+        to.short_label()
+    );
+    {
+        let mut guard = state.lock().unwrap();
+        let request = |runtime_state: &mut RuntimeState| {
+            engine::request_drained_switch(
+                runtime_state,
+                share_name,
+                to,
+                TRAY_DRAIN_TIMEOUT,
+                chrono::Utc::now(),
+            );
+        };
+        if guard.reconciling {
+            request(&mut guard.runtime_state);
+            let _ = engine::save_runtime_state(&mut guard.runtime_state);
+        } else {
+            // The owner's next pass picks the pending switch up from state.json.
+            if let Err(e) = engine::update_runtime_state(request) {
+                log::error!("Tray: cannot save drained switch: {:#}", e);
+            }
+            if let Ok(on_disk) = engine::load_runtime_state() {
+                guard.runtime_state = on_disk;
+            }
+        }
+        guard.busy_shares.remove(&share_name.to_ascii_lowercase());
+        guard.statuses = engine::verify_all(&cfg, &mut guard.runtime_state);
+    }
+    rebuild_menu(state, tray);
+}
+
+fn handle_cancel_pending(share_name: &str, state: &Arc<Mutex<TrayState>>, tray: &TrayIcon) {
+    let cfg = config::load().unwrap_or_default();
+    {
+        let mut guard = state.lock().unwrap();
+        if !guard.reconciling {
+            match engine::update_runtime_state(|runtime_state| {
+                engine::cancel_pending_switch(runtime_state, share_name)
+            }) {
+                Ok(Some(_)) => log::info!("Tray: cancelled pending switch of {}", share_name),
+                Ok(None) => {}
+                Err(e) => log::error!("Tray: cannot cancel pending switch: {:#}", e),
+            }
+            if let Ok(on_disk) = engine::load_runtime_state() {
+                guard.runtime_state = on_disk;
+            }
+        } else if engine::cancel_pending_switch(&mut guard.runtime_state, share_name).is_some() {
+            log::info!("Tray: cancelled pending switch of {}", share_name);
+            let _ = engine::save_runtime_state(&mut guard.runtime_state);
+        }
+        guard.statuses = engine::verify_all(&cfg, &mut guard.runtime_state);
+    }
+    rebuild_menu(state, tray);
+}
+
+/// Handle "Re-enter Password..." for a share whose credentials were rejected.
+/// Saves the new password in the credential store, clears the suspension and
+/// remounts.
+fn handle_reauth(share_name: &str, state: &Arc<Mutex<TrayState>>, tray: &TrayIcon) {
+    let cfg = match config::load() {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to load config for reauth: {}", e);
+            return;
+        }
+    };
+    let share = match config::find_share(&cfg, share_name) {
+        Some(s) => s.clone(),
+        None => {
+            log::error!("Share '{}' not found", share_name);
+            return;
+        }
+    };
+
+    let password = match dialogs::show_password_dialog(&share.name, &share.username) {
+        Some(p) => p,
+        None => return, // User cancelled
+    };
+
+    if !password.is_empty() {
+        let saved = credentials::provider(&cfg.global, None).and_then(|store| {
+            credentials::share_hosts(&share)
+                .into_iter()
+                .try_for_each(|host| store.set(host, &share.username, &password))
+        });
+        if let Err(e) = saved {
+            log::error!("{}: {}", share.name, e);
+            dialogs::show_error_dialog("Cannot Save Password", &e.to_string());
+            return;
+        }
+    }
+
+    let still_rejected = if reconciled_elsewhere(state) {
+        let call = control::Call::Reauth {
+            share: share.name.clone(),
+        };
+        let progress = format!("Mounting {}...", share.name);
+        let reply: control::ReauthReply =
+            match request_owner(&call, "Cannot Mount", progress, state, tray) {
+                Some(reply) => reply,
+                None => return,
+            };
+        let mut guard = state.lock().unwrap();
+        for status in reply.statuses {
+            if let Some(existing) = guard.statuses.iter_mut().find(|s| s.name == status.name) {
+                *existing = status;
+            }
+        }
+        guard
+            .statuses
+            .iter()
+            .any(|s| s.name == share.name && s.needs_credentials.is_some())
+    } else {
+        {
+            let mut guard = state.lock().unwrap();
+            engine::resume_after_credentials(&mut guard.runtime_state, &share.name);
+            guard.in_progress = Some(format!("Mounting {}...", share.name));
+        }
+        rebuild_menu(state, tray);
+
+        let mut guard = state.lock().unwrap();
+        guard.statuses = engine::reconcile_all(&cfg, &mut guard.runtime_state);
+        let _ = engine::save_runtime_state(&mut guard.runtime_state);
+        guard.in_progress = None;
+        guard
+            .statuses
+            .iter()
+            .any(|s| s.name == share.name && s.needs_credentials.is_some())
+    };
+
+    let health = {
+        let guard = state.lock().unwrap();
+        compute_health(&guard.statuses)
+    };
+    let new_menu = build_dynamic_menu(state);
+    tray.set_menu(Some(Box::new(new_menu)));
+    let _ = tray.set_icon(Some(make_icon_for_health(health)));
+
+    if still_rejected {
+        dialogs::show_error_dialog(
+            "Authentication Failed",
+            &format!(
+                "The server still rejects the credentials for '{}'. \
+                 Automatic mounts remain paused.",
+                share.name
+            ),
+        );
+    }
+}
+
 /// Handle "Remove Favorite" tray action (spec 15).
 /// Shows a confirmation dialog with cleanup option and alias impact reporting.
 fn handle_remove_favorite(share_name: &str, state: &Arc<Mutex<TrayState>>, tray: &TrayIcon) {
@@ -815,16 +1373,33 @@ fn handle_remove_favorite(share_name: &str, state: &Arc<Mutex<TrayState>>, tray:
     }
 
     // Cleanup if requested (unmount + remove symlink)
-    if choice.cleanup {
+    if choice.cleanup && reconciled_elsewhere(state) {
+        let call = control::Call::Cleanup {
+            share: Box::new(removed.clone()),
+        };
+        let progress = format!("Unmounting {}...", removed.name);
+        let reply: Option<(usize, engine::UnmountResult)> =
+            request_owner(&call, "Cleanup Failed", progress, state, tray);
+        if let Some((alias_count, unmount_result)) = reply {
+            log::info!(
+                "Tray: removed '{}' with cleanup by the owner ({} aliases affected, unmounted={} busy={})",
+                removed.name,
+                alias_count,
+                unmount_result.unmounted,
+                unmount_result.busy
+            );
+        }
+    } else if choice.cleanup {
         let mut guard = state.lock().unwrap();
-        match engine::cleanup_removed_share(&cfg, &mut guard.runtime_state, &removed.name) {
-            Ok((alias_count, unmount_results)) => {
-                let _ = engine::save_runtime_state(&guard.runtime_state);
+        match engine::cleanup_removed_share(&cfg, &mut guard.runtime_state, &removed) {
+            Ok((alias_count, unmount_result)) => {
+                let _ = engine::save_runtime_state(&mut guard.runtime_state);
                 log::info!(
-                    "Tray: removed '{}' with cleanup ({} aliases affected, {} unmount ops)",
+                    "Tray: removed '{}' with cleanup ({} aliases affected, unmounted={} busy={})",
                     removed.name,
                     alias_count,
-                    unmount_results.len()
+                    unmount_result.unmounted,
+                    unmount_result.busy
                 );
             }
             Err(e) => {
@@ -864,6 +1439,25 @@ fn build_dynamic_menu(state: &Arc<Mutex<TrayState>>) -> Menu {
         let progress_item = MenuItem::with_id("in-progress", msg, false, None);
         let _ = menu.append(&progress_item);
     }
+    if let Some(owner) = &guard.reconciled_by {
+        let owner_item = MenuItem::with_id(
+            "reconciled-by",
+            format!("Kept mounted by {}", owner.describe()),
+            false,
+            None,
+        );
+        let _ = menu.append(&owner_item);
+    }
+    if let Some(error) = &guard.config_error {
+        let first_line = error.lines().next().unwrap_or(error);
+        let error_item = MenuItem::with_id(
+            "config-error",
+            format!("⚠ config.toml not applied: {}", first_line),
+            false,
+            None,
+        );
+        let _ = menu.append(&error_item);
+    }
 
     let _ = menu.append(&PredefinedMenuItem::separator());
     let has_pending = guard
@@ -890,11 +1484,21 @@ fn build_dynamic_menu(state: &Arc<Mutex<TrayState>>) -> Menu {
             .map(|e| e.tb_recovery_pending)
             .unwrap_or(false);
 
-        let label = if tb_pending {
+        let label = if status.needs_credentials.is_some() {
+            format!(
+                "{} {} [password needed] {}",
+                status_text, status.name, backend_label
+            )
+        } else if tb_pending {
             format!(
                 "{} {} [TB available!] {}",
                 status_text, status.name, backend_label
             )
+        } else if !status.foreign_mounts.is_empty() {
+            format!(
+                "{} {} [foreign mount] ({})",
+                status_text, status.name, backend_label
+            )
         } else {
             format!("{} {} ({})", status_text, status.name, backend_label)
         };
@@ -913,9 +1517,8 @@ fn build_dynamic_menu(state: &Arc<Mutex<TrayState>>) -> Menu {
             Backend::Fallback => status.fallback.reachable,
         };
 
-        let is_busy = guard
-            .busy_shares
-            .contains(&status.name.to_ascii_lowercase());
+        let busy_files = guard.busy_shares.get(&status.name.to_ascii_lowercase());
+        let is_busy = busy_files.is_some();
 
         if other_reachable {
             let switch_label = if tb_pending && other_backend == Backend::Tb {
@@ -941,6 +1544,22 @@ fn build_dynamic_menu(state: &Arc<Mutex<TrayState>>) -> Menu {
                 );
                 let _ = submenu.append(&busy_label);
 
+                // Which apps to close before switching
+                for process in open_files::by_process(busy_files.map_or(&[][..], Vec::as_slice)) {
+                    let process_item = MenuItem::with_id(
+                        format!("info-busy-{}-{}", status.name, process.pid),
+                        format!(
+                            "    {} (pid {}): {} file(s)",
+                            process.command.trim(),
+                            process.pid,
+                            process.paths.len()
+                        ),
+                        false,
+                        None,
+                    );
+                    let _ = submenu.append(&process_item);
+                }
+
                 let force_label = format!(
                     "Force Switch to {} (may lose data!)",
                     other_backend.short_label()
@@ -956,9 +1575,61 @@ fn build_dynamic_menu(state: &Arc<Mutex<TrayState>>) -> Menu {
                     None,
                 );
                 let _ = submenu.append(&force_item);
+
+                if status.pending_switch.is_none() {
+                    let drain_item = MenuItem::with_id(
+                        format!(
+                            "drain-switch-{}-{}",
+                            status.name,
+                            other_backend.short_label()
+                        ),
+                        format!("Switch to {} When Files Close", other_backend.short_label()),
+                        true,
+                        None,
+                    );
+                    let _ = submenu.append(&drain_item);
+                }
             }
         }
 
+        // Switch leases held by tools (`mountaineer lease`, `mountaineer run`)
+        for lease in &status.leases {
+            let lease_item = MenuItem::with_id(
+                format!("info-lease-{}-{}", status.name, lease.token),
+                format!(
+                    "🔒 Held by {} until {}",
+                    lease.holder.as_deref().unwrap_or("a lease"),
+                    lease
+                        .expires_at
+                        .with_timezone(&chrono::Local)
+                        .format("%H:%M")
+                ),
+                false,
+                None,
+            );
+            let _ = submenu.append(&lease_item);
+        }
+
+        if let Some(pending) = &status.pending_switch {
+            let pending_item = MenuItem::with_id(
+                format!("info-pending-{}", status.name),
+                format!(
+                    "⏳ Switching to {} once files close",
+                    pending.to.short_label()
+                ),
+                false,
+                None,
+            );
+            let _ = submenu.append(&pending_item);
+            let cancel_item = MenuItem::with_id(
+                format!("cancel-pending-{}", status.name),
+                "Cancel Pending Switch",
+                true,
+                None,
+            );
+            let _ = submenu.append(&cancel_item);
+        }
+
         // Show backend status
         let _ = submenu.append(&PredefinedMenuItem::separator());
 
@@ -999,6 +1670,56 @@ fn build_dynamic_menu(state: &Arc<Mutex<TrayState>>) -> Menu {
             let err_item =
                 MenuItem::with_id(format!("info-err-{}", status.name), &err_label, false, None);
             let _ = submenu.append(&err_item);
+            if let Some(hint) = &status.last_error_hint {
+                let hint_item = MenuItem::with_id(
+                    format!("info-hint-{}", status.name),
+                    format!("→ {}", hint),
+                    false,
+                    None,
+                );
+                let _ = submenu.append(&hint_item);
+            }
+        }
+
+        // Credentials rejected: automatic mounts are suspended until re-entry
+        if status.needs_credentials.is_some() {
+            let reauth_item = MenuItem::with_id(
+                format!("reauth-{}", status.name),
+                "Re-enter Password...",
+                true,
+                None,
+            );
+            let _ = submenu.append(&reauth_item);
+        }
+
+        // Mounts of this share or its servers made outside Mountaineer
+        if !status.foreign_mounts.is_empty() {
+            let _ = submenu.append(&PredefinedMenuItem::separator());
+            for foreign in &status.foreign_mounts {
+                let item = MenuItem::with_id(
+                    format!("info-foreign-{}-{}", status.name, foreign.mount_point),
+                    format!(
+                        "⚠ //{}/{} at {}",
+                        foreign.host, foreign.share, foreign.mount_point
+                    ),
+                    false,
+                    None,
+                );
+                let _ = submenu.append(&item);
+            }
+            if status
+                .foreign_mounts
+                .iter()
+                .any(|m| m.conflict == engine::ForeignConflict::SameShare)
+            {
+                let reclaim_item = MenuItem::with_id(
+                    format!("reclaim-foreign-{}", status.name),
+                    "Reclaim Duplicate Mounts",
+                    true,
+                    None,
+                );
+                let _ = submenu.append(&reclaim_item);
+            }
         }
 
         // Remove Favorite action (spec 15)
@@ -1028,6 +1749,25 @@ fn build_dynamic_menu(state: &Arc<Mutex<TrayState>>) -> Menu {
     let add_fav = MenuItem::with_id("add-favorite", "Add Favorite...", true, None);
     let _ = menu.append(&add_fav);
 
+    // Existing SMB mounts that could become favorites
+    {
+        let cfg = config::load().unwrap_or_default();
+        let candidates = engine::unmanaged_mounts(&cfg);
+        if !candidates.is_empty() {
+            let adopt_submenu = Submenu::new("Adopt Existing Mount", true);
+            for mount in &candidates {
+                let item = MenuItem::with_id(
+                    format!("adopt-mount-{}", mount.mount_point),
+                    format!("//{}/{} ({})", mount.host, mount.share, mount.mount_point),
+                    true,
+                    None,
+                );
+                let _ = adopt_submenu.append(&item);
+            }
+            let _ = menu.append(&adopt_submenu);
+        }
+    }
+
     // Alias management submenu (spec 16)
     {
         let cfg = config::load().unwrap_or_default();
@@ -1114,6 +1854,18 @@ fn build_dynamic_menu(state: &Arc<Mutex<TrayState>>) -> Menu {
     let lsof_toggle = MenuItem::with_id("toggle-lsof-recheck", &lsof_label, true, None);
     let _ = menu.append(&lsof_toggle);
 
+    let reclaim_label = format!(
+        "Reclaim Foreign Mounts [{}]",
+        if cfg.global.reclaim_foreign_mounts {
+            "on"
+        } else {
+            "off"
+        }
+    );
+    let reclaim_toggle =
+        MenuItem::with_id("toggle-reclaim-foreign-mounts", &reclaim_label, true, None);
+    let _ = menu.append(&reclaim_toggle);
+
     let _ = menu.append(&PredefinedMenuItem::separator());
 
     let quit = MenuItem::with_id("quit", "Quit Mountaineer", true, None);
@@ -1170,6 +1922,43 @@ fn rebuild_menu(state: &Arc<Mutex<TrayState>>, tray: &TrayIcon) {
     tray.set_menu(Some(Box::new(new_menu)));
 }
 
+fn rebuild_menu_and_icon(state: &Arc<Mutex<TrayState>>, tray: &TrayIcon) {
+    let health = compute_health(&state.lock().unwrap().statuses);
+    rebuild_menu(state, tray);
+    let _ = tray.set_icon(Some(make_icon_for_health(health)));
+}
+
+/// Whether another process owns reconciliation, so menu actions that mount,
+/// unmount or change runtime state must go to it instead of racing it.
+fn reconciled_elsewhere(state: &Arc<Mutex<TrayState>>) -> bool {
+    !state.lock().unwrap().reconciling
+}
+
+/// Run `call` in the process that owns reconciliation, showing `progress` in
+/// the menu meanwhile. `None` after an error dialog titled `title`.
+fn request_owner<T: serde::de::DeserializeOwned>(
+    call: &control::Call,
+    title: &str,
+    progress: String,
+    state: &Arc<Mutex<TrayState>>,
+    tray: &TrayIcon,
+) -> Option<T> {
+    state.lock().unwrap().in_progress = Some(progress);
+    rebuild_menu(state, tray);
+    let reply = control::request::<T>(call);
+    state.lock().unwrap().in_progress = None;
+    let message = match reply {
+        Some(Ok(reply)) => return Some(reply),
+        Some(Err(e)) => format!("{:#}", e),
+        None => "The process that keeps shares mounted did not answer. Try again in a moment."
+            .to_string(),
+    };
+    log::error!("Tray: {}: {}", title, message);
+    dialogs::show_error_dialog(title, &message);
+    rebuild_menu(state, tray);
+    None
+}
+
 /// Overall health state for the tray icon (spec 18).
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum HealthState {
@@ -1202,7 +1991,11 @@ fn compute_health(statuses: &[ShareStatus]) -> HealthState {
         } else {
             all_connected = false;
         }
-        if status.tb_recovery_pending || status.last_error.is_some() {
+        let duplicated = status
+            .foreign_mounts
+            .iter()
+            .any(|m| m.conflict == engine::ForeignConflict::SameShare);
+        if status.tb_recovery_pending || status.last_error.is_some() || duplicated {
             any_degraded = true;
         }
     }
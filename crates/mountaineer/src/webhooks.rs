@@ -0,0 +1,435 @@
+//! Webhook sink: share events POSTed as JSON to the `[[webhooks]]` endpoints.
+//!
+//! Events are never sent from the reconcile pass itself. `publish` appends
+//! one delivery per matching endpoint to an outbox file and wakes the sender
+//! thread of a daemon (monitor or tray), which POSTs with `curl` and retries
+//! failures with exponential backoff. Short-lived commands only queue. The
+//! outbox survives restarts, so an endpoint that is down while Mountaineer
+//! quits still gets the events once both are back.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::mpsc::{self, Sender};
+use std::time::Duration;
+
+use crate::config::{self, Config};
+use crate::events::ShareEvent;
+use crate::fslock;
+use crate::runner;
+
+/// How long one POST may take before it counts as failed.
+const POST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the sender looks for deliveries whose retry time has come.
+const SENDER_POLL: Duration = Duration::from_secs(5);
+/// Delay before the first retry; doubled for each further attempt.
+const RETRY_BASE_SECS: i64 = 5;
+const RETRY_MAX_SECS: i64 = 3600;
+/// Deliveries still failing after this many attempts are dropped.
+const MAX_ATTEMPTS: u32 = 10;
+
+/// One pending POST of `body` to `url`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub id: u64,
+    pub url: String,
+    pub body: String,
+    #[serde(default)]
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Outbox {
+    #[serde(default)]
+    next_id: u64,
+    #[serde(default)]
+    entries: Vec<OutboxEntry>,
+}
+
+static SENDER: OnceLock<Sender<()>> = OnceLock::new();
+
+/// Queue `event` for every webhook that wants it and wake the sender, if
+/// this process runs one.
+pub fn publish(config: &Config, event: &ShareEvent) {
+    let urls: Vec<&str> = config
+        .webhooks
+        .iter()
+        .filter(|hook| hook.events.is_empty() || hook.events.contains(&event.event))
+        .map(|hook| hook.url.as_str())
+        .collect();
+    if urls.is_empty() {
+        return;
+    }
+    let body = match serde_json::to_string(event) {
+        Ok(body) => body,
+        Err(e) => {
+            log::warn!("webhooks: could not encode event: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = enqueue(&config::webhook_outbox_path(), &urls, &body, Utc::now()) {
+        log::warn!("webhooks: could not queue event: {:#}", e);
+        return;
+    }
+    if let Some(sender) = SENDER.get() {
+        sender.send(()).ok();
+    }
+}
+
+/// Start the background sender once per process. Daemons call this at
+/// startup so deliveries left in the outbox by an earlier run are retried.
+pub fn start_sender() -> &'static Sender<()> {
+    SENDER.get_or_init(|| {
+        let (wake, woken) = mpsc::channel::<()>();
+        std::thread::spawn(move || {
+            let path = config::webhook_outbox_path();
+            loop {
+                if let Err(e) = deliver_due(&path, Utc::now()) {
+                    log::warn!("webhooks: {:#}", e);
+                }
+                match woken.recv_timeout(SENDER_POLL) {
+                    Ok(()) | Err(mpsc::RecvTimeoutError::Timeout) => {}
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                }
+            }
+        });
+        wake
+    })
+}
+
+/// Append one delivery of `body` per url to the outbox at `path`.
+pub fn enqueue(path: &Path, urls: &[&str], body: &str, now: DateTime<Utc>) -> Result<()> {
+    let _lock = fslock::lock_exclusive(&outbox_lock_path(path))?;
+    let mut outbox = load(path)?;
+    for url in urls {
+        outbox.next_id += 1;
+        outbox.entries.push(OutboxEntry {
+            id: outbox.next_id,
+            url: url.to_string(),
+            body: body.to_string(),
+            attempts: 0,
+            next_attempt_at: now,
+            last_error: None,
+        });
+    }
+    save(path, &outbox)
+}
+
+/// Deliveries still waiting in the outbox at `path`.
+pub fn pending(path: &Path) -> Result<Vec<OutboxEntry>> {
+    let _lock = fslock::lock_exclusive(&outbox_lock_path(path))?;
+    Ok(load(path)?.entries)
+}
+
+/// POST every delivery due at `now`, oldest first. Delivered entries leave
+/// the outbox; failed ones are rescheduled, or dropped after
+/// [`MAX_ATTEMPTS`]. The outbox lock is not held while posting, so `publish`
+/// from the reconcile loop never waits on a slow endpoint. A separate sender
+/// lock keeps a second daemon from posting the same entries meanwhile.
+pub fn deliver_due(path: &Path, now: DateTime<Utc>) -> Result<()> {
+    let sender = fslock::open(&path.with_extension("send.lock"))?;
+    if !fslock::try_lock(&sender, nix::libc::LOCK_EX)
+        .with_context(|| format!("failed locking the sender of {}", path.display()))?
+    {
+        return Ok(());
+    }
+    let due: Vec<OutboxEntry> = {
+        let _lock = fslock::lock_exclusive(&outbox_lock_path(path))?;
+        load(path)?
+            .entries
+            .into_iter()
+            .filter(|entry| entry.next_attempt_at <= now)
+            .collect()
+    };
+    if due.is_empty() {
+        return Ok(());
+    }
+
+    let results: Vec<(u64, Result<(), String>)> = due
+        .iter()
+        .map(|entry| (entry.id, post(&entry.url, &entry.body)))
+        .collect();
+
+    let _lock = fslock::lock_exclusive(&outbox_lock_path(path))?;
+    let mut outbox = load(path)?;
+    for (id, result) in results {
+        let Some(index) = outbox.entries.iter().position(|entry| entry.id == id) else {
+            continue;
+        };
+        match result {
+            Ok(()) => {
+                let entry = outbox.entries.remove(index);
+                log::info!("webhooks: delivered event to {}", entry.url);
+            }
+            Err(e) => {
+                let entry = &mut outbox.entries[index];
+                entry.attempts += 1;
+                if entry.attempts >= MAX_ATTEMPTS {
+                    log::warn!(
+                        "webhooks: giving up on {} after {} attempts: {}",
+                        entry.url,
+                        entry.attempts,
+                        e
+                    );
+                    outbox.entries.remove(index);
+                    continue;
+                }
+                let delay = (RETRY_BASE_SECS << (entry.attempts - 1)).min(RETRY_MAX_SECS);
+                log::warn!(
+                    "webhooks: POST to {} failed ({}), retrying in {}s",
+                    entry.url,
+                    e,
+                    delay
+                );
+                entry.next_attempt_at = now + ChronoDuration::seconds(delay);
+                entry.last_error = Some(e);
+            }
+        }
+    }
+    save(path, &outbox)
+}
+
+/// POST `body` as JSON to `url`; only a 2xx answer counts as delivered.
+///
+/// Webhook URLs often carry a token, so the URL and body reach `curl` as a
+/// config file on stdin: neither shows in `ps` nor in recorded fixtures.
+fn post(url: &str, body: &str) -> Result<(), String> {
+    let max_time = POST_TIMEOUT.as_secs().to_string();
+    let curl_config = format!(
+        "url = \"{}\"\ndata-raw = \"{}\"\n",
+        curl_quote(url),
+        curl_quote(body)
+    );
+    let output = runner::run_with_input_timeout(
+        "curl",
+        &[
+            "-sS",
+            "-X",
+            "POST",
+            "-H",
+            "Content-Type: application/json",
+            "-o",
+            "/dev/null",
+            "-w",
+            "%{http_code}",
+            "--max-time",
+            &max_time,
+            "--config",
+            "-",
+        ],
+        &curl_config,
+        POST_TIMEOUT + Duration::from_secs(5),
+    )
+    .map_err(|e| format!("could not run curl: {}", e))?;
+    let status = output.stdout.trim();
+    if status.starts_with('2') && status.len() == 3 {
+        return Ok(());
+    }
+    if !output.success() {
+        return Err(output.stderr.trim().to_string());
+    }
+    Err(format!("HTTP {}", status))
+}
+
+/// Escape `value` for a double-quoted string in a curl config file.
+fn curl_quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted
+}
+
+fn outbox_lock_path(path: &Path) -> PathBuf {
+    path.with_extension("lock")
+}
+
+fn load(path: &Path) -> Result<Outbox> {
+    if !path.exists() {
+        return Ok(Outbox::default());
+    }
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("failed reading webhook outbox {}", path.display()))?;
+    serde_json::from_str(&text)
+        .with_context(|| format!("failed parsing webhook outbox {}", path.display()))
+}
+
+fn save(path: &Path, outbox: &Outbox) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed creating {}", parent.display()))?;
+    }
+    let tmp_path: PathBuf = path.with_extension("json.tmp");
+    fs::write(&tmp_path, serde_json::to_string_pretty(outbox)?)
+        .with_context(|| format!("failed writing {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path).with_context(|| {
+        format!(
+            "failed renaming {} to {}",
+            tmp_path.display(),
+            path.display()
+        )
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// Accept one request per status in `statuses`, answering with it, and
+    /// return the request bodies.
+    fn serve(statuses: Vec<u16>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/events", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push(String::from_utf8(body).unwrap());
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                )
+                .unwrap();
+            }
+            bodies
+        });
+        (url, handle)
+    }
+
+    fn curl_available() -> bool {
+        std::process::Command::new("curl")
+            .arg("--version")
+            .output()
+            .is_ok()
+    }
+
+    #[test]
+    fn failed_delivery_is_retried_after_backoff() {
+        if !curl_available() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("outbox.json");
+        let (url, server) = serve(vec![500, 200]);
+        let now = Utc::now();
+        enqueue(&path, &[&url], r#"{"event":"failover"}"#, now).unwrap();
+
+        deliver_due(&path, now).unwrap();
+        let queued = pending(&path).unwrap();
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].attempts, 1);
+        assert_eq!(queued[0].last_error.as_deref(), Some("HTTP 500"));
+        assert_eq!(queued[0].next_attempt_at, now + ChronoDuration::seconds(5));
+
+        // Not due yet: nothing is sent.
+        deliver_due(&path, now + ChronoDuration::seconds(1)).unwrap();
+        assert_eq!(pending(&path).unwrap().len(), 1);
+
+        deliver_due(&path, now + ChronoDuration::seconds(5)).unwrap();
+        assert!(pending(&path).unwrap().is_empty());
+        assert_eq!(
+            server.join().unwrap(),
+            vec![r#"{"event":"failover"}"#, r#"{"event":"failover"}"#]
+        );
+    }
+
+    #[test]
+    fn outbox_persists_until_delivered() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("outbox.json");
+        let now = Utc::now();
+        enqueue(
+            &path,
+            &["http://a.example/", "http://b.example/"],
+            "{}",
+            now,
+        )
+        .unwrap();
+        enqueue(&path, &["http://a.example/"], "{}", now).unwrap();
+        let queued = pending(&path).unwrap();
+        assert_eq!(
+            queued.iter().map(|entry| entry.id).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(queued[1].url, "http://b.example/");
+        assert!(queued.iter().all(|entry| entry.next_attempt_at == now));
+    }
+
+    #[test]
+    fn only_one_process_delivers_at_a_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("outbox.json");
+        let now = Utc::now();
+        enqueue(&path, &["http://127.0.0.1:9/"], "{}", now).unwrap();
+
+        let _other = fslock::lock_exclusive(&path.with_extension("send.lock")).unwrap();
+        deliver_due(&path, now).unwrap();
+        let queued = pending(&path).unwrap();
+        assert_eq!(queued[0].attempts, 0);
+        assert!(queued[0].last_error.is_none());
+    }
+
+    #[test]
+    fn curl_config_strings_are_escaped() {
+        assert_eq!(curl_quote(r#"{"note":"a\b"}"#), r#"{\"note\":\"a\\b\"}"#);
+        assert_eq!(curl_quote("a\nb"), "a\\nb");
+    }
+
+    #[test]
+    fn unreachable_endpoint_is_dropped_after_max_attempts() {
+        if !curl_available() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("outbox.json");
+        // Bound and dropped, so nothing listens on the port.
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let url = format!("http://127.0.0.1:{}/", port);
+        let mut now = Utc::now();
+        enqueue(&path, &[&url], "{}", now).unwrap();
+        for _ in 0..MAX_ATTEMPTS {
+            assert_eq!(pending(&path).unwrap().len(), 1);
+            deliver_due(&path, now).unwrap();
+            now += ChronoDuration::seconds(RETRY_MAX_SECS);
+        }
+        assert!(pending(&path).unwrap().is_empty());
+    }
+}